@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use fraction::Fraction;
+
+use crate::rtp::{parse_rtp_event, RtpPacket};
+
+/// Gathers the payload sizes and timestamp deltas of the RTP streams of a capture whose codec
+/// is known, to write the features telling the codec apart, see [`Calibrator::to_yaml`].
+#[derive(Clone, Debug, Default)]
+pub struct Calibrator {
+    /// last sequence number and timestamp of every SSRC
+    last: HashMap<u32, (u16, u32)>,
+    /// packets seen with every payload size and delta time
+    stats: HashMap<(u16, u32), u64>,
+    packets: u64,
+}
+
+impl Calibrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet. Like [`CodecDetector::on_pkt`](crate::CodecDetector::on_pkt), packets
+    /// of static payload types, telephone events and keep-alives are left out, and the delta
+    /// time is only known for packets following the previous one of their stream.
+    pub fn on_pkt<P: RtpPacket>(&mut self, pkt: &P) {
+        if pkt.payload().is_empty()
+            || !pkt.payload_type().is_dynamic()
+            || parse_rtp_event(pkt.payload()).is_ok()
+        {
+            return;
+        }
+        self.packets += 1;
+        let last = self.last.insert(pkt.ssrc(), (pkt.seq(), pkt.ts()));
+        if let Some((seq, ts)) = last {
+            if pkt.seq().wrapping_sub(seq) == 1 {
+                let delta_time = pkt.ts().wrapping_sub(ts);
+                *self
+                    .stats
+                    .entry((pkt.payload().len() as u16, delta_time))
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// Payload size, delta time and packet count of the features seen on at least `min_share`
+    /// of the packets, most seen first
+    pub fn features(&self, min_share: f64) -> Vec<(u16, u32, u64)> {
+        let mut features = self
+            .stats
+            .iter()
+            .filter(|(_, cnt)| **cnt as f64 >= self.packets as f64 * min_share)
+            .map(|(&(size, delta), &cnt)| (size, delta, cnt))
+            .collect::<Vec<_>>();
+        features.sort_by_key(|&(size, delta, cnt)| (std::cmp::Reverse(cnt), size, delta));
+        features
+    }
+
+    /// Features seen on at least 1% of the packets, in the format of
+    /// [`DEFAULT_FEATURES`](crate::DEFAULT_FEATURES), ready to be added to a feature file.
+    /// Payloads less than half as large as the most seen ones are marked as SID frames.
+    pub fn to_yaml(&self, name: &str, sample_rate: u32) -> String {
+        let features = self.features(0.01);
+        let mut yaml = format!("# {}, {} packets\n", name, self.packets);
+        let speech_size = features.first().map_or(0, |f| f.0);
+        for (size, delta, cnt) in features {
+            let kind = if size * 2 < speech_size { "SID, " } else { "" };
+            let _ = writeln!(
+                yaml,
+                "# {}{} bytes every {} ticks ({} ticks per byte), {:.1}% of the packets",
+                kind,
+                size,
+                delta,
+                Fraction::new(delta, size),
+                cnt as f64 * 100.0 / self.packets as f64
+            );
+            let _ = writeln!(
+                yaml,
+                "- name: {}\n  sampleRate: {}\n  payloadSize: {}\n  deltaTime: {}",
+                name, sample_rate, size, delta
+            );
+        }
+        yaml
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rtp::{parse_rtp, PayloadType};
+    use crate::CodecDetector;
+
+    use super::*;
+
+    fn rtp(seq: u16, ts: u32, payload_len: usize) -> Vec<u8> {
+        let mut data = vec![0x80, 0x61];
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&ts.to_be_bytes());
+        data.extend_from_slice(&0x1234u32.to_be_bytes());
+        data.resize(12 + payload_len, 0x5a);
+        data
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let mut calibrator = Calibrator::new();
+        let mut ts = 0;
+        for seq in 0..200u16 {
+            // talk, then comfort noise every 8 frames
+            let (len, delta) = if seq < 150 { (33, 320) } else { (6, 2560) };
+            ts += delta;
+            calibrator.on_pkt(&parse_rtp(&rtp(seq, ts, len)).unwrap());
+        }
+
+        let features = calibrator.features(0.01);
+        // the first packet has no delta time
+        assert_eq!(features, vec![(33, 320, 149), (6, 2560, 50)]);
+        assert_eq!(calibrator.features(0.5).len(), 1);
+
+        let yaml = calibrator.to_yaml("evs", 16000);
+        assert!(yaml.starts_with("# evs, 200 packets\n"));
+        assert!(yaml.contains("# SID, 6 bytes every 2560 ticks (1280/3 ticks per byte)"));
+
+        // rows the detector reads back
+        let mut detector = CodecDetector::new();
+        detector.get_features_from_yaml_str(&yaml).unwrap();
+        let mut ts = 0;
+        for seq in 0..50u16 {
+            ts += 320;
+            detector.on_pkt(&parse_rtp(&rtp(seq, ts, 33)).unwrap());
+        }
+        let result = detector.get_result();
+        assert_eq!(result[&PayloadType::Dynamic(97)].name.as_str(), "evs");
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::{self, Display};
+
+use combine::error::UnexpectedParse;
+use rtp_core::ParseError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors of the codec detector and of the RTP parsers it ships
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The RTP header of a packet is invalid
+    Rtp(ParseError),
+    /// A payload isn't of the kind its parser expects, e.g. not a RTP event
+    Payload(&'static str),
+    /// A codec features file couldn't be read
+    Io(std::io::Error),
+    /// A codec features file isn't valid YAML of [`Codec`](crate::Codec) features
+    Features(serde_yaml::Error),
+}
+
+impl Error {
+    /// Stable name of the kind of error, for reports and metrics
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Rtp(_) => "rtp",
+            Self::Payload(_) => "payload",
+            Self::Io(_) => "io",
+            Self::Features(_) => "features",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rtp(e) => e.fmt(f),
+            Self::Payload(msg) => f.write_str(msg),
+            Self::Io(e) => write!(f, "Failed to read codec features: {e}"),
+            Self::Features(e) => write!(f, "Invalid codec features: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Rtp(e) => Some(e),
+            Self::Payload(_) => None,
+            Self::Io(e) => Some(e),
+            Self::Features(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Self::Rtp(e)
+    }
+}
+
+impl From<UnexpectedParse> for Error {
+    fn from(e: UnexpectedParse) -> Self {
+        Self::Payload(match e {
+            UnexpectedParse::Eoi => "Payload too short",
+            UnexpectedParse::Unexpected => "Unexpected payload",
+        })
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Self::Features(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::rtp::{parse_rtp, parse_rtp_event};
+    use crate::CodecDetector;
+
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let e = parse_rtp(&[0x80; 4]).unwrap_err();
+        assert!(matches!(e, Error::Rtp(ParseError::Truncated)));
+        assert_eq!(e.code(), "rtp");
+        assert!(std::error::Error::source(&e).is_some());
+
+        let e = parse_rtp_event(&[0x01, 0x80]).unwrap_err();
+        assert_eq!(e.to_string(), "Payload too short");
+        let e = parse_rtp_event(&[0x01, 0x80, 0x00, 0xa0, 0x00]).unwrap_err();
+        assert_eq!(e.to_string(), "Payload type is not RTP Event");
+
+        let mut detector = CodecDetector::new();
+        let e = detector.get_features_from_yaml_str("[").unwrap_err();
+        assert_eq!(e.code(), "features");
+    }
+}
@@ -1,19 +1,25 @@
 //! Original algorithm: Fast RTP Detection and Codecs Classification in Internet Traffic(2014)
 
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::{BufReader, Seek};
+use std::fs::read_to_string;
 use std::path::Path;
 use std::sync::Arc;
 
-use anyhow::Result;
 use fraction::Fraction;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
+mod calibrate;
+mod error;
 pub mod rtp;
+pub use calibrate::Calibrator;
+pub use error::{Error, Result};
 use rtp::{parse_rtp_event, PayloadType, RtpPacket};
 
+/// Features of the codecs known out of the box, in the format read by
+/// [`CodecDetector::get_features_from_yaml`]
+pub const DEFAULT_FEATURES: &str = include_str!("../codec.yaml");
+
 #[derive(Clone, Debug, Deserialize, Serialize, Eq, Hash, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Codec {
@@ -71,6 +77,7 @@ pub struct CodecDetector {
     last_ts: HashMap<u32, u32>,
     pub max_uniq_payload_size_num: usize,
     payload_size_stat: HashMap<PayloadType, HashSet<usize>>,
+    keep_alive_cnt: u64,
 }
 
 impl CodecDetector {
@@ -80,6 +87,15 @@ impl CodecDetector {
             ..Default::default()
         }
     }
+
+    /// Create a detector knowing the features of [`DEFAULT_FEATURES`]
+    pub fn with_default_features() -> Self {
+        let mut detector = Self::new();
+        detector
+            .get_features_from_yaml_str(DEFAULT_FEATURES)
+            .expect("builtin codec features are valid");
+        detector
+    }
 }
 
 impl CodecDetector {
@@ -129,7 +145,22 @@ impl CodecDetector {
         }
     }
 
+    /// Count a keep-alive packet, see [`rtp::is_keep_alive`]. Keep-alives carry no codec
+    /// features and are kept out of the delta time of their stream.
+    pub fn on_keep_alive(&mut self) {
+        self.keep_alive_cnt += 1;
+    }
+
+    /// Keep-alive packets seen so far
+    pub fn keep_alives(&self) -> u64 {
+        self.keep_alive_cnt
+    }
+
     pub fn on_pkt<P: RtpPacket>(&mut self, pkt: &P) {
+        if pkt.payload().is_empty() {
+            self.on_keep_alive();
+            return;
+        }
         if parse_rtp_event(pkt.payload()).is_ok() || !pkt.payload_type().is_dynamic() {
             // Filter out all RTP event pkts and non dynamic codec pkts
             return;
@@ -143,7 +174,7 @@ impl CodecDetector {
             Some(cnt) => *cnt += 1,
         };
 
-        if (pkt.seq() - self.last_seq(pkt)) != 1 {
+        if pkt.seq().wrapping_sub(self.last_seq(pkt)) != 1 {
             self.last_seq.insert(pkt.ssrc(), pkt.seq());
             self.last_ts.insert(pkt.ssrc(), pkt.ts());
             return;
@@ -173,17 +204,35 @@ impl CodecDetector {
                             stat.insert(codec.clone(), 1);
                             self.codec_stat.insert(pkt.payload_type(), stat);
                         }
-                        Some(stat) => {
-                            if let Some(stat) = stat.get_mut(codec) {
-                                *stat += 1;
-                            }
-                        }
+                        Some(stat) => *stat.entry(codec.clone()).or_insert(0) += 1,
                     }
                 }
             }
         }
     }
 
+    /// Add the statistics of `next`, a detector with the same features fed with the packets
+    /// following those fed to this one, e.g. to detect the codecs of a capture scanned in
+    /// chunks. The delta time between the last packet of a stream here and its first one in
+    /// `next` is lost.
+    pub fn merge(&mut self, next: CodecDetector) {
+        for (pt, cnt) in next.pt_pkt_stat {
+            *self.pt_pkt_stat.entry(pt).or_insert(0) += cnt;
+        }
+        for (pt, stat) in next.codec_stat {
+            let codecs = self.codec_stat.entry(pt).or_default();
+            for (codec, cnt) in stat {
+                *codecs.entry(codec).or_insert(0) += cnt;
+            }
+        }
+        for (pt, lens) in next.payload_size_stat {
+            self.payload_size_stat.entry(pt).or_default().extend(lens);
+        }
+        self.last_seq.extend(next.last_seq);
+        self.last_ts.extend(next.last_ts);
+        self.keep_alive_cnt += next.keep_alive_cnt;
+    }
+
     pub fn on_pkts<'a, I, P: RtpPacket + 'a>(&mut self, pkts: I)
     where
         I: IntoIterator<Item = &'a P>,
@@ -193,15 +242,35 @@ impl CodecDetector {
         }
     }
 
+    /// Most likely codec of every payload type, see [`CodecDetector::get_candidates`]
     pub fn get_result(&self) -> HashMap<PayloadType, Codec> {
+        self.get_candidates()
+            .into_iter()
+            .filter_map(|(pt, mut codecs)| Some((pt, codecs.drain(..).next()?)))
+            .collect()
+    }
+
+    /// Codecs matching most packets of every payload type, most matched first and codecs
+    /// matching as many packets in the order of their features. Several codecs share payload
+    /// sizes (e.g. EVS 13.2 and AMR-WB 12.65 bandwidth efficient), those are told apart by
+    /// their content only. A codec name appears once per sample rate it matched at.
+    pub fn get_candidates(&self) -> HashMap<PayloadType, Vec<Codec>> {
         let mut result = HashMap::new();
         for (pt, stat) in &self.codec_stat {
             let tot_cnt = self.pt_pkt_stat.get(pt).unwrap_or(&0);
-            for (codec, cnt) in stat {
-                if *cnt > (tot_cnt * 618 / 1000) {
-                    result.insert(*pt, codec.clone());
-                    break;
-                }
+            let mut codecs = stat
+                .iter()
+                .filter(|(_, cnt)| **cnt > (tot_cnt * 618 / 1000))
+                .collect::<Vec<_>>();
+            codecs.sort_by_key(|(codec, cnt)| {
+                (std::cmp::Reverse(**cnt), self.features.get_index_of(*codec))
+            });
+            let candidates = codecs
+                .into_iter()
+                .map(|(codec, _)| codec.clone())
+                .collect::<Vec<_>>();
+            if !candidates.is_empty() {
+                result.insert(*pt, candidates);
             }
         }
         result
@@ -212,10 +281,12 @@ impl CodecDetector {
     }
 
     pub fn get_features_from_yaml(&mut self, fpath: &Path) -> Result<()> {
-        let mut file = BufReader::new(File::open(fpath)?);
-        let codecs: Vec<Codec> = serde_yaml::from_reader(&mut file)?;
-        file.rewind()?;
-        let features: Vec<CodecFeature> = serde_yaml::from_reader(&mut file)?;
+        self.get_features_from_yaml_str(&read_to_string(fpath)?)
+    }
+
+    pub fn get_features_from_yaml_str(&mut self, yaml: &str) -> Result<()> {
+        let codecs: Vec<Codec> = serde_yaml::from_str(yaml)?;
+        let features: Vec<CodecFeature> = serde_yaml::from_str(yaml)?;
         for (codec, mut ft) in codecs.iter().zip(features) {
             ft.set_radio();
             self.add_feature(codec.clone(), ft);
@@ -1,7 +1,5 @@
 use std::fmt::Display;
-use std::ops::{Add, Sub};
 
-use anyhow::{anyhow, bail, Result};
 use combine::error::UnexpectedParse;
 use combine::parser::byte::num::be_u16;
 use combine::parser::byte::{byte, bytes};
@@ -10,194 +8,13 @@ use combine::parser::repeat::skip_many;
 use combine::{look_ahead, many1, Parser};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+pub use rtp_core::{
+    is_keep_alive, FrameMeta, ParseError, PayloadType, RawRtpPacket, RtpPacket, RtpPacketizer,
+    SeqNum,
+};
 use serde::Serialize;
 
-#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-#[repr(transparent)]
-pub struct SeqNum(pub u16);
-
-impl Add for SeqNum {
-    type Output = u16;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        let (seq, _) = self.0.overflowing_add(rhs.0);
-        seq
-    }
-}
-
-impl Sub for SeqNum {
-    type Output = u16;
-
-    fn sub(self, rhs: Self) -> Self::Output {
-        let (seq, _) = self.0.overflowing_sub(rhs.0);
-        seq
-    }
-}
-
-impl From<u16> for SeqNum {
-    fn from(x: u16) -> Self {
-        Self(x)
-    }
-}
-
-impl From<SeqNum> for u16 {
-    fn from(x: SeqNum) -> Self {
-        x.0
-    }
-}
-
-/// RTP payload type, range from 0~127
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
-#[repr(u8)]
-pub enum PayloadType {
-    #[default]
-    PCMU = 0,
-    CELP = 1,
-    G721 = 2,
-    GSM = 3,
-    G723 = 4,
-    DVI4_8000 = 5,
-    DVI4_16000 = 6,
-    LPC = 7,
-    PCMA = 8,
-    G722 = 9,
-    L16_44100_2 = 10,
-    L16_44100_1 = 11,
-    QCELP = 12,
-    CN = 13,
-    MPA = 14,
-    G728 = 15,
-    DVI4_11025 = 16,
-    DVI4_22050 = 17,
-    G729 = 18,
-    CELB = 25,
-    JPEG = 26,
-    NV = 28,
-    H261 = 31,
-    MPV = 32,
-    MP2T = 33,
-    H263 = 34,
-    Reserved(u8),
-    Dynamic(u8),
-    Unassigned(u8),
-}
-
-impl PayloadType {
-    pub fn to_u8(self) -> u8 {
-        match self {
-            Self::PCMU => 0,
-            Self::CELP => 1,
-            Self::G721 => 2,
-            Self::GSM => 3,
-            Self::G723 => 4,
-            Self::DVI4_8000 => 5,
-            Self::DVI4_16000 => 6,
-            Self::LPC => 7,
-            Self::PCMA => 8,
-            Self::G722 => 9,
-            Self::L16_44100_2 => 10,
-            Self::L16_44100_1 => 11,
-            Self::QCELP => 12,
-            Self::CN => 13,
-            Self::MPA => 14,
-            Self::G728 => 15,
-            Self::DVI4_11025 => 16,
-            Self::DVI4_22050 => 17,
-            Self::G729 => 18,
-            Self::CELB => 25,
-            Self::JPEG => 26,
-            Self::NV => 28,
-            Self::H261 => 31,
-            Self::MPV => 32,
-            Self::MP2T => 33,
-            Self::H263 => 34,
-            Self::Reserved(t) | Self::Dynamic(t) | Self::Unassigned(t) => t,
-        }
-    }
-
-    pub fn from_u8(t: u8) -> Self {
-        match t & 0x7f {
-            0 => Self::PCMU,
-            3 => Self::GSM,
-            4 => Self::G723,
-            5 => Self::DVI4_8000,
-            6 => Self::DVI4_16000,
-            7 => Self::LPC,
-            8 => Self::PCMA,
-            9 => Self::G722,
-            10 => Self::L16_44100_2,
-            11 => Self::L16_44100_1,
-            12 => Self::QCELP,
-            13 => Self::CN,
-            14 => Self::MPA,
-            15 => Self::G728,
-            16 => Self::DVI4_11025,
-            17 => Self::DVI4_22050,
-            18 => Self::G729,
-            25 => Self::CELB,
-            26 => Self::JPEG,
-            28 => Self::NV,
-            31 => Self::H261,
-            32 => Self::MPV,
-            33 => Self::MP2T,
-            34 => Self::H263,
-            t if t == 1 || t == 2 || t == 19 => Self::Reserved(t),
-            t if (72..=76).contains(&t) => Self::Reserved(t),
-            t if (96..=127).contains(&t) => Self::Dynamic(t),
-            t => Self::Unassigned(t),
-        }
-    }
-
-    pub fn is_dynamic(self) -> bool {
-        matches!(self, Self::Dynamic(_))
-    }
-}
-
-impl Display for PayloadType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            Self::PCMU => "PCMU",
-            Self::CELP => "CELP",
-            Self::G721 => "G721",
-            Self::GSM => "GSM",
-            Self::G723 => "G723",
-            Self::DVI4_8000 => "DVI4",
-            Self::DVI4_16000 => "DVI4",
-            Self::LPC => "LPC",
-            Self::PCMA => "PCMA",
-            Self::G722 => "G722",
-            Self::L16_44100_2 => "L16",
-            Self::L16_44100_1 => "L16",
-            Self::QCELP => "QCELP",
-            Self::CN => "CN",
-            Self::MPA => "MPA",
-            Self::G728 => "G728",
-            Self::DVI4_11025 => "DVI4",
-            Self::DVI4_22050 => "DVI4",
-            Self::G729 => "G729",
-            Self::CELB => "CelB",
-            Self::JPEG => "JPEG",
-            Self::NV => "NV",
-            Self::H261 => "H261",
-            Self::MPV => "MPV",
-            Self::MP2T => "MP2T",
-            Self::H263 => "H263",
-            Self::Dynamic(t) => return format!("DYNAMIC-{}", t).fmt(f),
-            Self::Reserved(t) => return format!("RESERVED-{}", t).fmt(f),
-            Self::Unassigned(t) => return format!("UNASSIGNED-{}", t).fmt(f),
-        }
-        .fmt(f)
-    }
-}
-
-impl Serialize for PayloadType {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(&self.to_string())
-    }
-}
+use crate::error::{Error, Result};
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Extension<'a> {
@@ -205,81 +22,14 @@ pub struct Extension<'a> {
     pub value: &'a [u8],
 }
 
-pub trait RtpPacket {
-    fn raw(&self) -> &[u8];
-    fn version(&self) -> u8 {
-        (self.raw()[0] & 0b1100_0000) >> 6
-    }
-
-    fn padding(&self) -> bool {
-        (self.raw()[0] & 0b0010_0000) == 0b0010_0000
-    }
-
-    fn extension(&self) -> bool {
-        (self.raw()[0] & 0b0001_0000) == 0b0001_0000
-    }
-
-    fn csi_cnt(&self) -> usize {
-        (self.raw()[0] & 0x0f) as usize
-    }
-
-    fn marked(&self) -> bool {
-        (self.raw()[1] & 0x80) == 0x80
-    }
-
-    fn payload_type(&self) -> PayloadType {
-        PayloadType::from_u8(self.raw()[1])
-    }
-
-    fn seq(&self) -> u16 {
-        match <&[u8; 2]>::try_from(&self.raw()[2..4]) {
-            Ok(seq) => u16::from_be_bytes(*seq),
-            Err(_) => unreachable!(),
-        }
-    }
-
-    fn ts(&self) -> u32 {
-        match <&[u8; 4]>::try_from(&self.raw()[4..8]) {
-            Ok(seq) => u32::from_be_bytes(*seq),
-            Err(_) => unreachable!(),
-        }
-    }
-
-    fn ssrc(&self) -> u32 {
-        match <&[u8; 4]>::try_from(&self.raw()[8..12]) {
-            Ok(seq) => u32::from_be_bytes(*seq),
-            Err(_) => unreachable!(),
-        }
-    }
-
-    fn payload(&self) -> &[u8] {
-        let mut buf = if !self.extension() {
-            &self.raw()[12..]
-        } else {
-            let mut offset = 12 + 2;
-            let ext_len = match <&[u8; 2]>::try_from(&self.raw()[offset..offset + 2]) {
-                Ok(seq) => u16::from_be_bytes(*seq) as usize,
-                Err(_) => unreachable!(),
-            } * 4;
-            offset += ext_len;
-            &self.raw()[offset..]
-        };
-
-        if self.padding() {
-            if let Some(padding_len) = buf.last() {
-                buf = &buf[0..(buf.len() - (*padding_len as usize))];
-            }
-        }
-
-        buf
-    }
-
+/// Header extensions of a RTP packet, which need a parser `rtp-core` doesn't carry
+pub trait RtpExtensions: RtpPacket {
     fn get_extensions(&self) -> Result<Option<Vec<()>>> {
         if !self.extension() {
             return Ok(None);
         }
 
-        match look_ahead(bytes(b"\xbe\xde")).parse(&self.raw()[12..]) {
+        match look_ahead(bytes(b"\xbe\xde")).parse(&self.raw()[12 + self.csi_cnt() * 4..]) {
             Ok((_, rem)) => {
                 // One byte header extensions
                 let (exts, _) = take(2)
@@ -324,7 +74,7 @@ pub trait RtpPacket {
                             }
                         })
                     })
-                    .parse(&self.raw()[12..])?;
+                    .parse(&self.raw()[12 + self.csi_cnt() * 4..])?;
                 exts
             }
             Err(UnexpectedParse::Eoi) => unreachable!(),
@@ -333,81 +83,110 @@ pub trait RtpPacket {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct RawRtpPacket<'a> {
-    raw: &'a [u8],
-}
+impl<P: RtpPacket + ?Sized> RtpExtensions for P {}
 
-impl<'a> RtpPacket for RawRtpPacket<'a> {
-    fn raw(&self) -> &[u8] {
-        self.raw
-    }
+/// Check the header of a RTP packet, which must carry a payload
+pub fn parse_rtp(data: &[u8]) -> Result<RawRtpPacket<'_>> {
+    Ok(rtp_core::parse_rtp(data)?)
 }
 
-impl<'a> RawRtpPacket<'a> {
-    pub fn new(raw: &'a [u8]) -> Self {
-        Self { raw }
-    }
+/// Kind of traffic sharing the media port told apart from RTP by [`NotRtpFilter`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NotRtp {
+    /// Empty, or not RTP version 2
+    Version,
+    /// STUN, e.g. ICE connectivity checks
+    Stun,
+    /// RTCP of a known RTP stream
+    Rtcp,
+    /// DTLS, e.g. the DTLS-SRTP handshake
+    Dtls,
+    /// ZRTP handshake
+    Zrtp,
+    /// Matched by a user supplied check
+    Custom,
 }
 
-pub fn parse_rtp(data: &[u8]) -> Result<RawRtpPacket> {
-    let (_hdr, mut rem) = take(12).parse(data)?;
+/// STUN magic cookie (RFC 5389), in bytes 4 to 7
+const STUN_MAGIC: [u8; 4] = [0x21, 0x12, 0xa4, 0x42];
+/// ZRTP magic cookie (RFC 6189), in bytes 4 to 7
+const ZRTP_MAGIC: [u8; 4] = [0x5a, 0x52, 0x54, 0x50];
+
+/// Heuristics telling RTP apart from other packets multiplexed on the media port (RFC 7983).
+///
+/// Every check can be turned off, e.g. `version` for RTP carried over an encapsulation which
+/// keeps a few leading bytes, and `custom` checks can be added for traffic only a given network
+/// carries.
+#[derive(Clone, Debug)]
+pub struct NotRtpFilter {
+    /// Reject packets whose first byte isn't RTP version 2
+    pub version: bool,
+    pub stun: bool,
+    /// Reject packets whose RTCP sender SSRC is a known RTP SSRC
+    pub rtcp: bool,
+    pub dtls: bool,
+    pub zrtp: bool,
+    /// Extra checks, a packet any of them matches isn't RTP
+    pub custom: Vec<fn(&[u8]) -> bool>,
+}
 
-    let pkt = RawRtpPacket { raw: data };
-    if pkt.extension() {
-        let (_exts, r) = take(2)
-            .and(be_u16())
-            .then(|(_magic, len)| take(len as usize * 4))
-            .parse(rem)?;
-        rem = r;
+impl Default for NotRtpFilter {
+    fn default() -> Self {
+        Self {
+            version: true,
+            stun: true,
+            rtcp: true,
+            dtls: true,
+            zrtp: true,
+            custom: vec![],
+        }
     }
+}
 
-    if pkt.padding() {
-        let len = match rem.last() {
-            None => bail!("Invalid RTP Packet: no payload avaliable"),
-            Some(l) => *l as usize,
+impl NotRtpFilter {
+    /// Which kind of non RTP packet `data` is, `None` if it may be RTP. `ssrcs` are the SSRCs
+    /// of known RTP streams, whose RTCP packets are recognized.
+    pub fn classify(&self, data: &[u8], ssrcs: &[u32]) -> Option<NotRtp> {
+        let Some(&first) = data.first() else {
+            return Some(NotRtp::Version);
         };
-        if len >= rem.len() {
-            bail!("Invalid RTP Packet: padding is longer than payload len");
-        } else {
-            rem = &rem[0..rem.len() - 1 - len];
+        let magic = data.get(4..8);
+
+        if self.zrtp && first == 0x10 && magic == Some(&ZRTP_MAGIC) {
+            return Some(NotRtp::Zrtp);
+        }
+        if self.dtls && (20..=63).contains(&first) {
+            return Some(NotRtp::Dtls);
+        }
+        if self.stun && first < 4 && magic == Some(&STUN_MAGIC) {
+            return Some(NotRtp::Stun);
+        }
+        if self.version && !(0x80..=0xbf).contains(&first) {
+            return Some(NotRtp::Version);
+        }
+        if self.rtcp {
+            if let Some(ssrc) = magic {
+                let ssrc = u32::from_be_bytes([ssrc[0], ssrc[1], ssrc[2], ssrc[3]]);
+                if ssrcs.contains(&ssrc) {
+                    return Some(NotRtp::Rtcp);
+                }
+            }
         }
+        if self.custom.iter().any(|check| check(data)) {
+            return Some(NotRtp::Custom);
+        }
+        None
     }
 
-    if rem.is_empty() {
-        bail!("Invalid RTP Packet: no payload avaliable");
+    /// Detect whether a packet is not a RTP packet
+    pub fn detect(&self, data: &[u8], ssrcs: &[u32]) -> bool {
+        self.classify(data, ssrcs).is_some()
     }
-
-    Ok(pkt)
 }
 
-/// Detect whether a packet is not a RTP packet
+/// Detect whether a packet is not a RTP packet, with the default [`NotRtpFilter`]
 pub fn detect_not_rtp(data: &[u8], ssrcs: &[u32]) -> bool {
-    if data.is_empty() {
-        return true;
-    }
-
-    if data[0] < 0x80 || data[0] > 0xbf {
-        return true;
-    }
-
-    if data.len() >= 8 && data[4..8] == [0x21, 0x12, 0xa4, 0x42] {
-        // skip STUN packets
-        return true;
-    }
-
-    if data.len() >= 8 {
-        // skip RTCP packets
-        let ssrc = ((data[4] as u32) << 24)
-            | ((data[5] as u32) << 16)
-            | ((data[6] as u32) << 8)
-            | (data[7] as u32);
-        if ssrcs.contains(&ssrc) {
-            return true;
-        }
-    }
-
-    false
+    NotRtpFilter::default().detect(data, ssrcs)
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, FromPrimitive, Hash, PartialEq)]
@@ -483,9 +262,9 @@ impl RtpEvent {
 /// Parse RTP event ID heuristically
 pub fn parse_rtp_event(data: &[u8]) -> Result<RtpEvent> {
     let (((event_id, flags), duration), rem) = take(1).and(take(1)).and(be_u16()).parse(data)?;
-    let event_id = EventCode::from_u8(event_id[0]).ok_or_else(|| anyhow!("Invalid RTP EventID"))?;
+    let event_id = EventCode::from_u8(event_id[0]).ok_or(Error::Payload("Invalid RTP EventID"))?;
     if !rem.is_empty() {
-        bail!("Payload type is not RTP Event");
+        return Err(Error::Payload("Payload type is not RTP Event"));
     }
     Ok(RtpEvent {
         event_id,
@@ -517,26 +296,120 @@ mod tests {
     }
 
     #[test]
-    fn test_seq_num() -> Result<()> {
-        let seq1 = SeqNum(1);
-        let seq2 = SeqNum(2);
-        assert_eq!(seq2 - seq1, 1);
-
-        let seq1 = SeqNum(2);
-        let seq2 = SeqNum(2);
-        assert_eq!(seq2 - seq1, 0);
-
-        let seq1 = SeqNum(3);
-        let seq2 = SeqNum(2);
-        assert_eq!(seq2 - seq1, 65535);
-
-        let seq1 = SeqNum(65535);
-        let seq2 = SeqNum(0);
-        assert_eq!(seq2 - seq1, 1);
-
-        let seq1 = SeqNum(0);
-        let seq2 = SeqNum(65535);
-        assert_eq!(seq2 - seq1, 65535);
+    fn test_parse_rtp_csrcs() -> Result<()> {
+        for cc in 1..=15u8 {
+            let mut data = vec![0x80 | cc, 0x60, 0x00, 0x02, 0x00, 0x00, 0x01, 0x40];
+            data.extend_from_slice(&0x1234u32.to_be_bytes());
+            for csrc in 0..cc as u32 {
+                data.extend_from_slice(&(0xc000 + csrc).to_be_bytes());
+            }
+            data.extend_from_slice(&[0xf0, 0x44, 0x01]);
+
+            let rtp = parse_rtp(&data)?;
+            assert_eq!(rtp.ssrc(), 0x1234);
+            assert_eq!(
+                rtp.csrcs(),
+                (0..cc as u32).map(|c| 0xc000 + c).collect::<Vec<_>>()
+            );
+            assert_eq!(rtp.payload(), &[0xf0, 0x44, 0x01]);
+
+            // with a one-byte header extension after the CSRCs
+            let mut ext = data.clone();
+            ext[0] |= 0x10;
+            let pos = 12 + cc as usize * 4;
+            ext.splice(pos..pos, [0xbe, 0xde, 0x00, 0x01, 0x10, 0xaa, 0x00, 0x00]);
+            let rtp = parse_rtp(&ext)?;
+            assert_eq!(rtp.csrcs().len(), cc as usize);
+            assert_eq!(rtp.payload(), &[0xf0, 0x44, 0x01]);
+
+            // CSRCs the packet is too short to hold
+            assert!(parse_rtp(&data[..12 + cc as usize * 4 - 1]).is_err());
+        }
         Ok(())
     }
+
+    #[test]
+    fn test_parse_rtp_padding() {
+        let hdr = [
+            0xa0, 0x60, 0x00, 0x02, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x12, 0x34,
+        ];
+        let packet = |payload: &[u8]| [&hdr[..], payload].concat();
+
+        let data = packet(&[0xf0, 0x44, 0x00, 0x02]);
+        let rtp = parse_rtp(&data).unwrap();
+        assert_eq!(rtp.payload(), &[0xf0, 0x44]);
+
+        // just the count octet
+        let data = packet(&[0xf0, 0x44, 0x01]);
+        assert_eq!(parse_rtp(&data).unwrap().payload(), &[0xf0, 0x44]);
+
+        // padding as long as the payload leaves nothing
+        let data = packet(&[0x00, 0x00, 0x03]);
+        assert!(parse_rtp(&data).is_err());
+        assert!(is_keep_alive(&data));
+        assert!(RawRtpPacket::new(&data).payload().is_empty());
+
+        // padding longer than the payload, or a zero count
+        for payload in [&[0xf0, 0x04][..], &[0xf0, 0x44, 0x00]] {
+            let data = packet(payload);
+            assert!(parse_rtp(&data).is_err());
+            assert!(!is_keep_alive(&data));
+            assert!(RawRtpPacket::new(&data).payload().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_not_rtp_filter() {
+        let rtp: &[u8] = &[
+            0x80, 0x60, 0x00, 0x02, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x12, 0x34, 0xf0,
+        ];
+        let stun: &[u8] = &[0x00, 0x01, 0x00, 0x00, 0x21, 0x12, 0xa4, 0x42];
+        let zrtp: &[u8] = &[0x10, 0x00, 0x00, 0x01, 0x5a, 0x52, 0x54, 0x50];
+        let dtls: &[u8] = &[0x16, 0xfe, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let rtcp: &[u8] = &[0x80, 0xc8, 0x00, 0x06, 0x00, 0x00, 0x12, 0x34];
+
+        let filter = NotRtpFilter::default();
+        assert_eq!(filter.classify(rtp, &[0x1234]), None);
+        assert_eq!(filter.classify(stun, &[]), Some(NotRtp::Stun));
+        assert_eq!(filter.classify(zrtp, &[]), Some(NotRtp::Zrtp));
+        assert_eq!(filter.classify(dtls, &[]), Some(NotRtp::Dtls));
+        assert_eq!(filter.classify(rtcp, &[0x1234]), Some(NotRtp::Rtcp));
+        assert_eq!(filter.classify(rtcp, &[]), None);
+        assert_eq!(filter.classify(&[], &[]), Some(NotRtp::Version));
+        assert_eq!(filter.classify(&[0x40, 0x00], &[]), Some(NotRtp::Version));
+
+        let filter = NotRtpFilter {
+            version: false,
+            dtls: false,
+            custom: vec![|data| data.starts_with(&[0xff])],
+            ..Default::default()
+        };
+        assert_eq!(filter.classify(dtls, &[]), None);
+        assert_eq!(filter.classify(&[0x40, 0x00], &[]), None);
+        assert_eq!(filter.classify(zrtp, &[]), Some(NotRtp::Zrtp));
+        assert_eq!(filter.classify(&[0xff, 0x00], &[]), Some(NotRtp::Custom));
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let hdr: &[u8] = &[
+            0x80, 0x60, 0x00, 0x02, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x12, 0x34,
+        ];
+        assert!(is_keep_alive(&[]));
+        assert!(is_keep_alive(&[0; 4]));
+        assert!(is_keep_alive(hdr));
+        assert!(parse_rtp(hdr).is_err());
+
+        // only padding
+        let mut padded = hdr.to_vec();
+        padded[0] |= 0x20;
+        padded.extend_from_slice(&[0, 0, 0, 4]);
+        assert!(is_keep_alive(&padded));
+
+        let mut pkt = hdr.to_vec();
+        pkt.push(0xf0);
+        assert!(!is_keep_alive(&pkt));
+        assert!(parse_rtp(&pkt).is_ok());
+        assert!(!is_keep_alive(b"not rtp"));
+    }
 }
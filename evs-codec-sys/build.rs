@@ -72,16 +72,20 @@ fn main() -> Result<()> {
             files.push(path);
         }
     }
-    cfg.files(files)
-        .flag("-pedantic")
-        .flag("-Wcast-qual")
-        .flag("-Wno-long-long")
-        .flag("-Wpointer-arith")
-        .flag("-Wstrict-prototypes")
-        .flag("-Wmissing-prototypes")
-        .flag("-Werror-implicit-function-declaration")
-        .includes(dirs)
-        .warnings(false);
+    cfg.files(files).includes(dirs).warnings(false);
+    if sys_builder::is_msvc() {
+        // the reference code uses the plain C string functions MSVC deprecates, the CRT
+        // (/MT or /MD) follows crt-static
+        cfg.define("_CRT_SECURE_NO_WARNINGS", None);
+    } else {
+        cfg.flag("-pedantic")
+            .flag("-Wcast-qual")
+            .flag("-Wno-long-long")
+            .flag("-Wpointer-arith")
+            .flag("-Wstrict-prototypes")
+            .flag("-Wmissing-prototypes")
+            .flag("-Werror-implicit-function-declaration");
+    }
 
     cfg.compile("evs");
     #[cfg(feature = "gen")]
@@ -1,12 +1,17 @@
 use anyhow::{anyhow, Result};
 
-use sys_builder::{find_lib, IgnoreMacros, Library};
+use sys_builder::{find_lib, Library};
+
+/// Oldest libg722_1 accepted when found by pkg-config
+const LIBG7221_MIN_VERSION: &str = "0.0.1";
 
 #[cfg(feature = "gen")]
-fn gen() -> Result<()> {
+fn gen(library: &Library) -> Result<()> {
     use std::env;
     use std::path::Path;
 
+    use sys_builder::IgnoreMacros;
+
     let ignored_macros = IgnoreMacros(
         vec![
             "FP_INFINITE",
@@ -19,9 +24,6 @@ fn gen() -> Result<()> {
         .collect(),
     );
 
-    let mut library = Library::new("g722_1".to_string(), "LIBG7221_ROOT".to_string());
-    find_lib(&mut library)
-        .map_err(|e| anyhow!("Failed to find {} library, {}", library.name, e))?;
     let out_dir = env::var("OUT_DIR")?;
     let out_path = Path::new(&out_dir).join("libg7221_sys.rs");
 
@@ -65,9 +67,13 @@ fn gen() -> Result<()> {
 }
 
 fn main() -> Result<()> {
+    let mut library = Library::new("g722_1".to_string(), "LIBG7221_ROOT".to_string());
+    library.version = Some(LIBG7221_MIN_VERSION.to_string());
+    find_lib(&mut library)
+        .map_err(|e| anyhow!("Failed to find {} library, {}", library.name, e))?;
+
     #[cfg(feature = "gen")]
-    gen()?;
-    cargo_emit::rustc_link_lib!("g722_1");
+    gen(&library)?;
 
     Ok(())
 }
@@ -1,32 +1,57 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+
+use sys_builder::{find_lib, Library};
+
+/// Oldest opencore-amr accepted when found by pkg-config
+const OPENCORE_AMR_MIN_VERSION: &str = "0.1.3";
+
+#[cfg(feature = "gen")]
+fn gen(libraries: &[Library]) -> Result<()> {
+    use std::env;
+    use std::path::Path;
+
+    let out_dir = env::var("OUT_DIR")?;
+    let out_path = Path::new(&out_dir).join("opencore_amr_sys.rs");
+
+    let mut bindings = bindgen::builder()
+        .default_macro_constant_type(bindgen::MacroTypeVariation::Signed)
+        .disable_nested_struct_naming()
+        .trust_clang_mangling(false)
+        .derive_default(true);
+
+    if let Ok(cpath_dir) = env::var("CPATH") {
+        bindings = bindings.clang_arg(format!("-I{}", cpath_dir))
+    }
+
+    bindings = bindings.clang_args(
+        libraries
+            .iter()
+            .flat_map(|library| &library.inc_paths)
+            .map(|p| format!("-I{}", p.display())),
+    );
+
+    bindings
+        .header("src/amrwb.h")
+        .layout_tests(false)
+        .generate()
+        .unwrap_or_else(|e| panic!("could not run bindgen on header src/amrwb.h, {}", e))
+        .write_to_file(&out_path)
+        .unwrap_or_else(|e| panic!("Could not write to {:?}, {}", out_path, e));
+    Ok(())
+}
 
 fn main() -> Result<()> {
-    #[cfg(feature = "gen")]
-    {
-        use std::env;
-        use std::path::Path;
-        let out_dir = env::var("OUT_DIR")?;
-        let out_path = Path::new(&out_dir).join("opencore_amr_sys.rs");
-
-        let cpath_dir = env::var("CPATH")?;
-        let bindings = bindgen::builder()
-            .default_macro_constant_type(bindgen::MacroTypeVariation::Signed)
-            .disable_nested_struct_naming()
-            .trust_clang_mangling(false)
-            .clang_arg(format!("-I{}", cpath_dir))
-            .derive_default(true);
-        let bindings = bindings.header("src/amrwb.h");
-
-        bindings
-            .layout_tests(false)
-            .generate()
-            .unwrap_or_else(|e| panic!("could not run bindgen on header src/amrwb.h, {}", e))
-            .write_to_file(&out_path)
-            .unwrap_or_else(|e| panic!("Could not write to {:?}, {}", out_path, e));
+    let mut libraries = vec![];
+    for name in ["opencore-amrnb", "opencore-amrwb"] {
+        let mut library = Library::new(name.to_string(), "OPENCORE_AMR_ROOT".to_string());
+        library.version = Some(OPENCORE_AMR_MIN_VERSION.to_string());
+        find_lib(&mut library)
+            .map_err(|e| anyhow!("Failed to find {} library, {}", library.name, e))?;
+        libraries.push(library);
     }
 
-    cargo_emit::rustc_link_lib!("opencore-amrnb");
-    cargo_emit::rustc_link_lib!("opencore-amrwb");
+    #[cfg(feature = "gen")]
+    gen(&libraries)?;
 
     Ok(())
 }
@@ -0,0 +1,696 @@
+//! RTP header parsing shared by the offline tools and embedded probes, and the metadata of
+//! the frames depacketized from RTP payloads, shared with the decoders.
+//!
+//! Only `core` and `alloc` are used, the `serde` feature serializes [`PayloadType`]. The
+//! `test-util` feature gives the random data generator of the tests of the workspace.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::{self, Display};
+use core::ops::{Add, Sub};
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+
+#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[repr(transparent)]
+pub struct SeqNum(pub u16);
+
+impl SeqNum {
+    /// Compare sequence numbers as serial numbers (RFC 1982), so that 0 follows 65535.
+    ///
+    /// Sequence numbers exactly half the space apart are undefined in RFC 1982, here the
+    /// numerically greater one is considered newer.
+    pub fn serial_cmp(&self, other: &Self) -> Ordering {
+        match (*self - *other) as i16 {
+            0 => Ordering::Equal,
+            i16::MIN => self.0.cmp(&other.0),
+            d if d > 0 => Ordering::Greater,
+            _ => Ordering::Less,
+        }
+    }
+}
+
+impl Add for SeqNum {
+    type Output = u16;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let (seq, _) = self.0.overflowing_add(rhs.0);
+        seq
+    }
+}
+
+impl Sub for SeqNum {
+    type Output = u16;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let (seq, _) = self.0.overflowing_sub(rhs.0);
+        seq
+    }
+}
+
+impl From<u16> for SeqNum {
+    fn from(x: u16) -> Self {
+        Self(x)
+    }
+}
+
+impl From<SeqNum> for u16 {
+    fn from(x: SeqNum) -> Self {
+        x.0
+    }
+}
+
+/// RTP payload type, range from 0~127
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum PayloadType {
+    #[default]
+    PCMU = 0,
+    CELP = 1,
+    G721 = 2,
+    GSM = 3,
+    G723 = 4,
+    DVI4_8000 = 5,
+    DVI4_16000 = 6,
+    LPC = 7,
+    PCMA = 8,
+    G722 = 9,
+    L16_44100_2 = 10,
+    L16_44100_1 = 11,
+    QCELP = 12,
+    CN = 13,
+    MPA = 14,
+    G728 = 15,
+    DVI4_11025 = 16,
+    DVI4_22050 = 17,
+    G729 = 18,
+    CELB = 25,
+    JPEG = 26,
+    NV = 28,
+    H261 = 31,
+    MPV = 32,
+    MP2T = 33,
+    H263 = 34,
+    Reserved(u8),
+    Dynamic(u8),
+    Unassigned(u8),
+}
+
+impl PayloadType {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::PCMU => 0,
+            Self::CELP => 1,
+            Self::G721 => 2,
+            Self::GSM => 3,
+            Self::G723 => 4,
+            Self::DVI4_8000 => 5,
+            Self::DVI4_16000 => 6,
+            Self::LPC => 7,
+            Self::PCMA => 8,
+            Self::G722 => 9,
+            Self::L16_44100_2 => 10,
+            Self::L16_44100_1 => 11,
+            Self::QCELP => 12,
+            Self::CN => 13,
+            Self::MPA => 14,
+            Self::G728 => 15,
+            Self::DVI4_11025 => 16,
+            Self::DVI4_22050 => 17,
+            Self::G729 => 18,
+            Self::CELB => 25,
+            Self::JPEG => 26,
+            Self::NV => 28,
+            Self::H261 => 31,
+            Self::MPV => 32,
+            Self::MP2T => 33,
+            Self::H263 => 34,
+            Self::Reserved(t) | Self::Dynamic(t) | Self::Unassigned(t) => t,
+        }
+    }
+
+    pub fn from_u8(t: u8) -> Self {
+        match t & 0x7f {
+            0 => Self::PCMU,
+            3 => Self::GSM,
+            4 => Self::G723,
+            5 => Self::DVI4_8000,
+            6 => Self::DVI4_16000,
+            7 => Self::LPC,
+            8 => Self::PCMA,
+            9 => Self::G722,
+            10 => Self::L16_44100_2,
+            11 => Self::L16_44100_1,
+            12 => Self::QCELP,
+            13 => Self::CN,
+            14 => Self::MPA,
+            15 => Self::G728,
+            16 => Self::DVI4_11025,
+            17 => Self::DVI4_22050,
+            18 => Self::G729,
+            25 => Self::CELB,
+            26 => Self::JPEG,
+            28 => Self::NV,
+            31 => Self::H261,
+            32 => Self::MPV,
+            33 => Self::MP2T,
+            34 => Self::H263,
+            t if t == 1 || t == 2 || t == 19 => Self::Reserved(t),
+            t if (72..=76).contains(&t) => Self::Reserved(t),
+            t if (96..=127).contains(&t) => Self::Dynamic(t),
+            t => Self::Unassigned(t),
+        }
+    }
+
+    pub fn is_dynamic(self) -> bool {
+        matches!(self, Self::Dynamic(_))
+    }
+}
+
+impl Display for PayloadType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::PCMU => "PCMU",
+            Self::CELP => "CELP",
+            Self::G721 => "G721",
+            Self::GSM => "GSM",
+            Self::G723 => "G723",
+            Self::DVI4_8000 => "DVI4",
+            Self::DVI4_16000 => "DVI4",
+            Self::LPC => "LPC",
+            Self::PCMA => "PCMA",
+            Self::G722 => "G722",
+            Self::L16_44100_2 => "L16",
+            Self::L16_44100_1 => "L16",
+            Self::QCELP => "QCELP",
+            Self::CN => "CN",
+            Self::MPA => "MPA",
+            Self::G728 => "G728",
+            Self::DVI4_11025 => "DVI4",
+            Self::DVI4_22050 => "DVI4",
+            Self::G729 => "G729",
+            Self::CELB => "CelB",
+            Self::JPEG => "JPEG",
+            Self::NV => "NV",
+            Self::H261 => "H261",
+            Self::MPV => "MPV",
+            Self::MP2T => "MP2T",
+            Self::H263 => "H263",
+            Self::Dynamic(t) => return format!("DYNAMIC-{}", t).fmt(f),
+            Self::Reserved(t) => return format!("RESERVED-{}", t).fmt(f),
+            Self::Unassigned(t) => return format!("UNASSIGNED-{}", t).fmt(f),
+        }
+        .fmt(f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PayloadType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+pub trait RtpPacket {
+    fn raw(&self) -> &[u8];
+    fn version(&self) -> u8 {
+        (self.raw()[0] & 0b1100_0000) >> 6
+    }
+
+    fn padding(&self) -> bool {
+        (self.raw()[0] & 0b0010_0000) == 0b0010_0000
+    }
+
+    fn extension(&self) -> bool {
+        (self.raw()[0] & 0b0001_0000) == 0b0001_0000
+    }
+
+    fn csi_cnt(&self) -> usize {
+        (self.raw()[0] & 0x0f) as usize
+    }
+
+    /// Contributing sources listed after the fixed header
+    fn csrcs(&self) -> Vec<u32> {
+        self.raw()[12..12 + self.csi_cnt() * 4]
+            .chunks_exact(4)
+            .map(|csrc| u32::from_be_bytes([csrc[0], csrc[1], csrc[2], csrc[3]]))
+            .collect()
+    }
+
+    fn marked(&self) -> bool {
+        (self.raw()[1] & 0x80) == 0x80
+    }
+
+    fn payload_type(&self) -> PayloadType {
+        PayloadType::from_u8(self.raw()[1])
+    }
+
+    fn seq(&self) -> u16 {
+        match <&[u8; 2]>::try_from(&self.raw()[2..4]) {
+            Ok(seq) => u16::from_be_bytes(*seq),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    fn ts(&self) -> u32 {
+        match <&[u8; 4]>::try_from(&self.raw()[4..8]) {
+            Ok(seq) => u32::from_be_bytes(*seq),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    fn ssrc(&self) -> u32 {
+        match <&[u8; 4]>::try_from(&self.raw()[8..12]) {
+            Ok(seq) => u32::from_be_bytes(*seq),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    fn payload(&self) -> &[u8] {
+        let offset = 12 + self.csi_cnt() * 4;
+        let mut buf = if !self.extension() {
+            &self.raw()[offset..]
+        } else {
+            let ext_len = match <&[u8; 2]>::try_from(&self.raw()[offset + 2..offset + 4]) {
+                Ok(seq) => u16::from_be_bytes(*seq) as usize,
+                Err(_) => unreachable!(),
+            } * 4;
+            &self.raw()[offset + 4 + ext_len..]
+        };
+
+        if self.padding() {
+            // parse_rtp rejects malformed padding, unchecked packets get an empty payload
+            buf = strip_padding(buf).unwrap_or_default();
+        }
+
+        buf
+    }
+
+    /// Data of the header extension element `id`, in the one-byte or two-byte format of
+    /// RFC 8285, `None` if absent or malformed
+    fn extension_element(&self, id: u8) -> Option<&[u8]> {
+        if !self.extension() || id == 0 {
+            return None;
+        }
+        let offset = 12 + self.csi_cnt() * 4;
+        let hdr = self.raw().get(offset..offset + 4)?;
+        let profile = u16::from_be_bytes([hdr[0], hdr[1]]);
+        let len = u16::from_be_bytes([hdr[2], hdr[3]]) as usize * 4;
+        let mut elems = self.raw().get(offset + 4..offset + 4 + len)?;
+        let one_byte = match profile {
+            0xbede => true,
+            p if p & 0xfff0 == 0x1000 => false,
+            _ => return None,
+        };
+
+        while let Some((&first, rem)) = elems.split_first() {
+            // padding between elements
+            if first == 0 {
+                elems = rem;
+                continue;
+            }
+            let (elem_id, elem_len, rem) = if one_byte {
+                // id 15 stops the parsing of the extension
+                if first >> 4 == 15 {
+                    return None;
+                }
+                (first >> 4, (first & 0x0f) as usize + 1, rem)
+            } else {
+                let (&len, rem) = rem.split_first()?;
+                (first, len as usize, rem)
+            };
+            let data = rem.get(..elem_len)?;
+            if elem_id == id {
+                return Some(data);
+            }
+            elems = &rem[elem_len..];
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RawRtpPacket<'a> {
+    raw: &'a [u8],
+}
+
+impl<'a> RtpPacket for RawRtpPacket<'a> {
+    fn raw(&self) -> &[u8] {
+        self.raw
+    }
+}
+
+impl<'a> RawRtpPacket<'a> {
+    pub fn new(raw: &'a [u8]) -> Self {
+        Self { raw }
+    }
+}
+
+/// Why a packet isn't a valid RTP packet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Shorter than its header, CSRC list and header extension
+    Truncated,
+    /// Padding longer than the payload, or a zero padding count
+    Padding,
+    /// Nothing left once the header and padding are removed, e.g. a keep-alive
+    NoPayload,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => "Invalid RTP Packet: shorter than its header",
+            Self::Padding => "Invalid RTP Packet: padding is longer than payload len",
+            Self::NoPayload => "Invalid RTP Packet: no payload avaliable, keep-alive",
+        }
+        .fmt(f)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// Check the header of a RTP packet, which must carry a payload
+pub fn parse_rtp(data: &[u8]) -> Result<RawRtpPacket<'_>, ParseError> {
+    if rtp_payload(data)?.is_empty() {
+        return Err(ParseError::NoPayload);
+    }
+    Ok(RawRtpPacket { raw: data })
+}
+
+/// Payload of a RTP packet, empty for keep-alive packets
+pub fn rtp_payload(data: &[u8]) -> Result<&[u8], ParseError> {
+    if data.len() < 12 {
+        return Err(ParseError::Truncated);
+    }
+    let pkt = RawRtpPacket { raw: data };
+    let mut rem = data
+        .get(12 + pkt.csi_cnt() * 4..)
+        .ok_or(ParseError::Truncated)?;
+    if pkt.extension() {
+        let len = rem.get(2..4).ok_or(ParseError::Truncated)?;
+        let len = u16::from_be_bytes([len[0], len[1]]) as usize * 4;
+        rem = rem.get(4 + len..).ok_or(ParseError::Truncated)?;
+    }
+
+    if pkt.padding() {
+        rem = strip_padding(rem).ok_or(ParseError::Padding)?;
+    }
+
+    Ok(rem)
+}
+
+/// Remove the padding at the end of a payload. The last octet counts the padding octets,
+/// itself included (RFC 3550 section 5.1), so `None` if it is 0 or exceeds the payload.
+fn strip_padding(payload: &[u8]) -> Option<&[u8]> {
+    let len = *payload.last()? as usize;
+    if len == 0 {
+        return None;
+    }
+    payload.get(..payload.len().checked_sub(len)?)
+}
+
+/// Longest datagram taken for a keep-alive rather than a truncated packet
+const KEEP_ALIVE_MAX_LEN: usize = 4;
+
+/// Detect whether a packet is a keep-alive: a RTP header without payload (RFC 6263), or the
+/// few bytes some gateways send to keep NAT bindings open
+pub fn is_keep_alive(data: &[u8]) -> bool {
+    data.len() <= KEEP_ALIVE_MAX_LEN
+        || (data[0] & 0xc0 == 0x80 && rtp_payload(data).is_ok_and(|p| p.is_empty()))
+}
+
+/// Builds the RTP packets of a stream: a packet every `ptime` milliseconds, with consecutive
+/// sequence numbers and timestamps advancing by the samples of a packet
+#[derive(Clone, Copy, Debug)]
+pub struct RtpPacketizer {
+    ssrc: u32,
+    payload_type: u8,
+    seq: u16,
+    ts: u32,
+    samples_per_packet: u32,
+    first: bool,
+}
+
+impl RtpPacketizer {
+    /// Packetizer of a stream with a `clock_rate` Hz RTP clock, starting at sequence number and
+    /// timestamp 0
+    pub fn new(ssrc: u32, payload_type: u8, clock_rate: u32, ptime: u32) -> Self {
+        Self {
+            ssrc,
+            payload_type: payload_type & 0x7f,
+            seq: 0,
+            ts: 0,
+            samples_per_packet: clock_rate / 1000 * ptime,
+            first: true,
+        }
+    }
+
+    /// Start from another sequence number and timestamp, random ones in real streams
+    pub fn with_start(mut self, seq: u16, ts: u32) -> Self {
+        self.seq = seq;
+        self.ts = ts;
+        self
+    }
+
+    /// RTP clock ticks of a packet
+    pub fn samples_per_packet(&self) -> u32 {
+        self.samples_per_packet
+    }
+
+    /// Header of the next packet followed by `payload`, the first packet has the marker bit
+    pub fn packetize(&mut self, payload: &[u8]) -> Vec<u8> {
+        let mut pkt = Vec::with_capacity(12 + payload.len());
+        pkt.push(0x80);
+        pkt.push(self.payload_type | if self.first { 0x80 } else { 0 });
+        pkt.extend_from_slice(&self.seq.to_be_bytes());
+        pkt.extend_from_slice(&self.ts.to_be_bytes());
+        pkt.extend_from_slice(&self.ssrc.to_be_bytes());
+        pkt.extend_from_slice(payload);
+
+        self.first = false;
+        self.seq = self.seq.wrapping_add(1);
+        self.ts = self.ts.wrapping_add(self.samples_per_packet);
+        pkt
+    }
+}
+
+/// What the payload format tells of a frame, attached by the depacketizer so that reports and
+/// decoders don't parse it again.
+///
+/// It travels to the decoder at the end of the frame data, see [`FrameMeta::append`] and
+/// [`FrameMeta::split`]. Frames from elsewhere may end with the same bytes, so decoders only
+/// split the frames of tracks whose codec extra data is [`FrameMeta::MAGIC`], see
+/// [`FrameMeta::attached`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameMeta {
+    /// Frame type of the payload format, e.g. the FT field of an AMR ToC
+    pub frame_type: u8,
+    /// Bitrate in bits per second of the codec mode, `None` if the frame type has none
+    pub bitrate: Option<u32>,
+    /// Silence descriptor frame of comfort noise
+    pub sid: bool,
+    /// Bad frame indication: the frame was damaged, e.g. its quality bit cleared, or lost
+    pub bfi: bool,
+    /// Duration in RTP timestamp units, `None` if the payload format doesn't tell
+    pub duration: Option<u32>,
+}
+
+impl FrameMeta {
+    /// Bytes appended to a frame: frame type, flags, bitrate, duration and [`Self::MAGIC`]
+    pub const LEN: usize = 14;
+    /// End of the frames carrying a [`FrameMeta`], and codec extra data of their track
+    pub const MAGIC: [u8; 4] = *b"FMD1";
+
+    const SID: u8 = 0x01;
+    const BFI: u8 = 0x02;
+    const BITRATE: u8 = 0x04;
+    const DURATION: u8 = 0x08;
+
+    /// Append the metadata to the data of its frame
+    pub fn append(&self, data: &mut Vec<u8>) {
+        let flags = (self.sid as u8 * Self::SID)
+            | (self.bfi as u8 * Self::BFI)
+            | (self.bitrate.is_some() as u8 * Self::BITRATE)
+            | (self.duration.is_some() as u8 * Self::DURATION);
+        data.push(self.frame_type);
+        data.push(flags);
+        data.extend_from_slice(&self.bitrate.unwrap_or_default().to_be_bytes());
+        data.extend_from_slice(&self.duration.unwrap_or_default().to_be_bytes());
+        data.extend_from_slice(&Self::MAGIC);
+    }
+
+    /// Whether the codec extra data of a track tells that its frames carry a [`FrameMeta`]
+    pub fn attached(extra_data: Option<&[u8]>) -> bool {
+        extra_data == Some(&Self::MAGIC[..])
+    }
+
+    /// Split data into the frame and the metadata appended to it, `None` if there is none
+    pub fn split(data: &[u8]) -> (&[u8], Option<Self>) {
+        let Some(at) = data.len().checked_sub(Self::LEN) else {
+            return (data, None);
+        };
+        let (frame, meta) = data.split_at(at);
+        let flags = meta[1];
+        let known = Self::SID | Self::BFI | Self::BITRATE | Self::DURATION;
+        if meta[10..] != Self::MAGIC || flags & !known != 0 {
+            return (data, None);
+        }
+        let word = |i: usize| u32::from_be_bytes([meta[i], meta[i + 1], meta[i + 2], meta[i + 3]]);
+        let meta = Self {
+            frame_type: meta[0],
+            bitrate: (flags & Self::BITRATE != 0).then(|| word(2)),
+            sid: flags & Self::SID != 0,
+            bfi: flags & Self::BFI != 0,
+            duration: (flags & Self::DURATION != 0).then(|| word(6)),
+        };
+        (frame, Some(meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn test_seq_num() {
+        let seq1 = SeqNum(1);
+        let seq2 = SeqNum(2);
+        assert_eq!(seq2 - seq1, 1);
+
+        let seq1 = SeqNum(2);
+        let seq2 = SeqNum(2);
+        assert_eq!(seq2 - seq1, 0);
+
+        let seq1 = SeqNum(3);
+        let seq2 = SeqNum(2);
+        assert_eq!(seq2 - seq1, 65535);
+
+        let seq1 = SeqNum(65535);
+        let seq2 = SeqNum(0);
+        assert_eq!(seq2 - seq1, 1);
+
+        let seq1 = SeqNum(0);
+        let seq2 = SeqNum(65535);
+        assert_eq!(seq2 - seq1, 65535);
+    }
+
+    #[test]
+    fn test_seq_num_serial_cmp() {
+        assert_eq!(SeqNum(1).serial_cmp(&SeqNum(2)), Ordering::Less);
+        assert_eq!(SeqNum(2).serial_cmp(&SeqNum(2)), Ordering::Equal);
+        assert_eq!(SeqNum(0).serial_cmp(&SeqNum(65535)), Ordering::Greater);
+        assert_eq!(SeqNum(65535).serial_cmp(&SeqNum(0)), Ordering::Less);
+        assert_eq!(SeqNum(10).serial_cmp(&SeqNum(65530)), Ordering::Greater);
+        assert_eq!(SeqNum(32767).serial_cmp(&SeqNum(0)), Ordering::Greater);
+        assert_eq!(SeqNum(32769).serial_cmp(&SeqNum(0)), Ordering::Less);
+        assert_eq!(SeqNum(32768).serial_cmp(&SeqNum(0)), Ordering::Greater);
+        assert_eq!(SeqNum(0).serial_cmp(&SeqNum(32768)), Ordering::Less);
+    }
+    #[test]
+    fn test_rtp_payload() {
+        let hdr = [
+            0x80, 0x60, 0x00, 0x02, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x12, 0x34,
+        ];
+        assert_eq!(rtp_payload(&hdr[..11]), Err(ParseError::Truncated));
+        assert_eq!(rtp_payload(&hdr), Ok(&[][..]));
+        assert_eq!(parse_rtp(&hdr).unwrap_err(), ParseError::NoPayload);
+
+        // one CSRC announced, none present
+        let mut data = hdr.to_vec();
+        data[0] |= 0x01;
+        assert_eq!(rtp_payload(&data), Err(ParseError::Truncated));
+        data.extend_from_slice(&[0, 0, 0, 1, 0xf0]);
+        let rtp = parse_rtp(&data).unwrap();
+        assert_eq!(rtp.csrcs(), [1]);
+        assert_eq!(rtp.payload(), &[0xf0]);
+    }
+
+    #[test]
+    fn test_extension_element() {
+        let mut data = [
+            0x90, 0x60, 0x00, 0x02, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x12, 0x34,
+        ]
+        .to_vec();
+        // one-byte elements: id 1 "a1", padding, id 3 one byte
+        data.extend_from_slice(&[0xbe, 0xde, 0x00, 0x02, 0x11, b'a', b'1', 0x00, 0x30, 0x07]);
+        data.extend_from_slice(&[0x00, 0x00, 0xf0]);
+        let rtp = parse_rtp(&data).unwrap();
+        assert_eq!(rtp.extension_element(1), Some(&b"a1"[..]));
+        assert_eq!(rtp.extension_element(3), Some(&[0x07][..]));
+        assert_eq!(rtp.extension_element(2), None);
+        assert_eq!(rtp.payload(), &[0xf0]);
+
+        // two-byte elements: id 1 "mic"
+        data.truncate(12);
+        data.extend_from_slice(&[0x10, 0x00, 0x00, 0x02, 0x01, 0x03, b'm', b'i', b'c', 0x00]);
+        data.extend_from_slice(&[0x00, 0x00, 0xf0]);
+        let rtp = parse_rtp(&data).unwrap();
+        assert_eq!(rtp.extension_element(1), Some(&b"mic"[..]));
+
+        // element overrunning the extension
+        data[17] = 0x10;
+        assert_eq!(parse_rtp(&data).unwrap().extension_element(1), None);
+    }
+
+    #[test]
+    fn test_packetizer() {
+        let mut packetizer = RtpPacketizer::new(0x1234, 8, 8000, 20).with_start(0xffff, 100);
+        assert_eq!(packetizer.samples_per_packet(), 160);
+
+        let first = packetizer.packetize(&[0xd5; 160]);
+        let rtp = parse_rtp(&first).unwrap();
+        assert!(rtp.marked());
+        assert_eq!(rtp.payload_type(), PayloadType::PCMA);
+        assert_eq!((rtp.seq(), rtp.ts(), rtp.ssrc()), (0xffff, 100, 0x1234));
+        assert_eq!(rtp.payload().len(), 160);
+
+        let second = packetizer.packetize(&[0xd5; 160]);
+        let rtp = parse_rtp(&second).unwrap();
+        assert!(!rtp.marked());
+        assert_eq!((rtp.seq(), rtp.ts()), (0, 260));
+    }
+
+    #[test]
+    fn test_frame_meta() {
+        let meta = FrameMeta {
+            frame_type: 7,
+            bitrate: Some(12200),
+            sid: false,
+            bfi: true,
+            duration: Some(160),
+        };
+        let mut data = vec![0x3c; 32];
+        meta.append(&mut data);
+        assert_eq!(data.len(), 32 + FrameMeta::LEN);
+        assert_eq!(FrameMeta::split(&data), (&[0x3c; 32][..], Some(meta)));
+
+        let sid = FrameMeta {
+            frame_type: 8,
+            sid: true,
+            ..Default::default()
+        };
+        let mut data = vec![];
+        sid.append(&mut data);
+        assert_eq!(FrameMeta::split(&data), (&[][..], Some(sid)));
+
+        // frames without metadata are left alone
+        assert_eq!(FrameMeta::split(&[0x3c; 32]), (&[0x3c; 32][..], None));
+        assert_eq!(FrameMeta::split(b"FMD1"), (&b"FMD1"[..], None));
+
+        assert!(FrameMeta::attached(Some(b"FMD1")));
+        assert!(!FrameMeta::attached(Some(b"FMD")));
+        assert!(!FrameMeta::attached(None));
+    }
+}
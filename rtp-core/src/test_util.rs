@@ -0,0 +1,29 @@
+//! Deterministic pseudo random data for the tests of the workspace, enabled by the `test-util`
+//! feature
+
+/// Linear congruential generator, deterministic random patterns without a dependency
+#[derive(Clone, Debug)]
+pub struct Lcg(pub u32);
+
+impl Lcg {
+    /// Next 24 bits of the sequence
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        self.0 >> 8
+    }
+
+    /// Random byte, e.g. of codec frames where any bit pattern is valid
+    pub fn byte(&mut self) -> u8 {
+        (self.next_u32() >> 16) as u8
+    }
+
+    /// Sample of full scale white noise
+    pub fn sample(&mut self) -> i16 {
+        (self.next_u32() >> 8) as i16
+    }
+
+    /// Whether an event of probability `percent` % happens
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.next_u32() % 100 < percent
+    }
+}
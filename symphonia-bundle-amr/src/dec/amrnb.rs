@@ -4,8 +4,7 @@ use symphonia_core::audio::{
     AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
 };
 use symphonia_core::codecs::{
-    decl_codec_type, CodecDescriptor, CodecParameters, CodecType, Decoder as D, DecoderOptions,
-    FinalizeResult,
+    CodecDescriptor, CodecParameters, Decoder as D, DecoderOptions, FinalizeResult,
 };
 use symphonia_core::errors::Result;
 use symphonia_core::formats::Packet;
@@ -13,9 +12,21 @@ use symphonia_core::support_codec;
 
 use opencore_amr_sys::{Decoder_Interface_Decode, Decoder_Interface_exit, Decoder_Interface_init};
 
+use rtp_core::FrameMeta;
+
+use super::{rx_frame_type, rx_frame_type_from_meta, validate_frame, RxFrameType};
 use crate::{AMR_BUFFER_SIZE, AMR_SAMPLE_RATE};
 
-pub const CODEC_TYPE_AMR: CodecType = decl_codec_type(b"amr");
+use voip_codecs::Validator;
+
+pub use voip_codecs::CODEC_TYPE_AMR;
+
+/// Storage size, ToC included, of every AMR frame type
+pub(super) const FRAME_SIZES: [usize; 16] =
+    [13, 14, 16, 18, 20, 21, 27, 32, 6, 1, 1, 1, 1, 1, 1, 1];
+
+/// NO_DATA frame
+const NO_DATA: [u8; 1] = [0x7c];
 
 /// A dummy Decoder struct to handle c_void casting
 #[derive(Default)]
@@ -24,6 +35,9 @@ struct AmrDecoder;
 pub struct Decoder {
     decoded_data: AudioBuffer<c_short>,
     params: CodecParameters,
+    validator: Validator,
+    /// Frames end with a [`FrameMeta`], see [`FrameMeta::attached`]
+    frame_meta: bool,
     st: Box<AmrDecoder>,
 }
 
@@ -36,6 +50,8 @@ impl Default for Decoder {
                     SignalSpec::new(AMR_SAMPLE_RATE, Channels::FRONT_CENTRE),
                 ),
                 params: CodecParameters::default(),
+                validator: Validator::default(),
+                frame_meta: false,
                 st: Box::from_raw(Decoder_Interface_init().cast()),
             }
         }
@@ -43,7 +59,29 @@ impl Default for Decoder {
 }
 
 impl Decoder {
-    pub fn decode(&mut self, data: &[u8]) {
+    /// Decode a MIME storage frame, an empty frame is decoded as NO_DATA. On tracks carrying
+    /// metadata, the [`FrameMeta`] appended to the frame tells whether it is a bad or SID frame
+    /// rather than its ToC.
+    pub fn decode(&mut self, data: &[u8]) -> Result<()> {
+        let (data, meta) = match self.frame_meta {
+            true => FrameMeta::split(data),
+            false => (data, None),
+        };
+        validate_frame(data, false)?;
+        let rx_frame_type = match &meta {
+            Some(meta) => rx_frame_type_from_meta(data, meta, &FRAME_SIZES, 8)?,
+            None => rx_frame_type(data, &FRAME_SIZES, 8)?,
+        };
+        // opencore ignores both the bad frame indication and the quality bit, frames received
+        // damaged are replaced by NO_DATA so that they are concealed rather than decoded
+        let data = match rx_frame_type {
+            RxFrameType::SpeechGood | RxFrameType::Sid => data,
+            RxFrameType::SpeechBad
+            | RxFrameType::SidBad
+            | RxFrameType::SpeechLost
+            | RxFrameType::NoData => &NO_DATA,
+        };
+
         unsafe {
             Decoder_Interface_Decode(
                 (self.st.as_mut() as *mut AmrDecoder).cast(),
@@ -52,6 +90,7 @@ impl Decoder {
                 0,
             )
         }
+        Ok(())
     }
 }
 
@@ -65,12 +104,14 @@ impl Drop for Decoder {
 }
 
 impl D for Decoder {
-    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self>
+    fn try_new(params: &CodecParameters, options: &DecoderOptions) -> Result<Self>
     where
         Self: Sized,
     {
         let mut decoder = Self::default();
         decoder.params = params.clone();
+        decoder.validator = Validator::new(options.verify);
+        decoder.frame_meta = FrameMeta::attached(params.extra_data.as_deref());
         Ok(decoder)
     }
 
@@ -95,13 +136,14 @@ impl D for Decoder {
         self.decoded_data
             .render_reserved(Some(AMR_BUFFER_SIZE as usize));
 
-        self.decode(&packet.data);
+        self.decode(&packet.data)?;
+        self.validator.update(self.decoded_data.chan(0));
 
         Ok(self.decoded_data.as_audio_buffer_ref())
     }
 
     fn finalize(&mut self) -> FinalizeResult {
-        Default::default()
+        self.validator.finalize(self.params.verification_check)
     }
 
     fn last_decoded(&self) -> AudioBufferRef {
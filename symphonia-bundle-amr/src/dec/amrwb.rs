@@ -4,18 +4,32 @@ use symphonia_core::audio::{
     AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
 };
 use symphonia_core::codecs::{
-    decl_codec_type, CodecDescriptor, CodecParameters, CodecType, Decoder as D, DecoderOptions,
-    FinalizeResult,
+    CodecDescriptor, CodecParameters, Decoder as D, DecoderOptions, FinalizeResult,
 };
 use symphonia_core::errors::Result;
 use symphonia_core::formats::Packet;
 use symphonia_core::support_codec;
 
-use opencore_amr_sys::{D_IF_decode, D_IF_exit, D_IF_init};
+use opencore_amr_sys::{_good_frame, D_IF_decode, D_IF_exit, D_IF_init};
 
+use rtp_core::FrameMeta;
+
+use super::{rx_frame_type, rx_frame_type_from_meta, validate_frame, RxFrameType};
 use crate::{AMRWB_BUFFER_SIZE, AMRWB_SAMPLE_RATE};
 
-pub const CODEC_TYPE_AMRWB: CodecType = decl_codec_type(b"amrwb");
+use voip_codecs::Validator;
+
+pub use voip_codecs::CODEC_TYPE_AMRWB;
+
+/// Storage size, ToC included, of every AMR-WB frame type
+pub(super) const FRAME_SIZES: [usize; 16] =
+    [18, 24, 33, 37, 41, 47, 51, 59, 61, 6, 1, 1, 1, 1, 1, 1];
+
+/// NO_DATA frame
+const NO_DATA: [u8; 1] = [0x7c];
+
+/// Frame types AMR-WB reserves for future use, AMR-WB+ (RFC 4352) carries its frames with them
+pub(super) const WBPLUS_FRAME_TYPES: std::ops::RangeInclusive<u8> = 10..=13;
 
 /// A dummy Decoder struct to handle c_void casting
 #[derive(Default)]
@@ -24,6 +38,9 @@ struct AmrwbDecoder;
 pub struct Decoder {
     decoded_data: AudioBuffer<c_short>,
     params: CodecParameters,
+    validator: Validator,
+    /// Frames end with a [`FrameMeta`], see [`FrameMeta::attached`]
+    frame_meta: bool,
     st: Box<AmrwbDecoder>,
 }
 
@@ -36,6 +53,8 @@ impl Default for Decoder {
                     SignalSpec::new(AMRWB_SAMPLE_RATE, Channels::FRONT_CENTRE),
                 ),
                 params: CodecParameters::default(),
+                validator: Validator::default(),
+                frame_meta: false,
                 st: Box::from_raw(D_IF_init().cast()),
             }
         }
@@ -43,15 +62,40 @@ impl Default for Decoder {
 }
 
 impl Decoder {
-    pub fn decode(&mut self, data: &[u8]) {
+    /// Decode a MIME storage frame, an empty frame is decoded as NO_DATA. On tracks carrying
+    /// metadata, the [`FrameMeta`] appended to the frame tells whether it is a bad or SID frame
+    /// rather than its ToC.
+    pub fn decode(&mut self, data: &[u8]) -> Result<()> {
+        let (data, meta) = match self.frame_meta {
+            true => FrameMeta::split(data),
+            false => (data, None),
+        };
+        validate_frame(data, true)?;
+        let rx_frame_type = match &meta {
+            Some(meta) => rx_frame_type_from_meta(data, meta, &FRAME_SIZES, 9)?,
+            None => rx_frame_type(data, &FRAME_SIZES, 9)?,
+        };
+        // opencore reads the quality bit from the ToC and turns frames received damaged into
+        // RX_SPEECH_BAD or RX_SID_BAD itself, the ToC of a bad frame gets it cleared
+        let damaged;
+        let data: &[u8] = match rx_frame_type {
+            RxFrameType::NoData => &NO_DATA,
+            RxFrameType::SpeechBad | RxFrameType::SidBad if data[0] & 0x04 != 0 => {
+                damaged = [&[data[0] & !0x04], &data[1..]].concat();
+                &damaged
+            }
+            _ => data,
+        };
+
         unsafe {
             D_IF_decode(
                 (self.st.as_mut() as *mut AmrwbDecoder).cast(),
                 data.as_ptr(),
                 self.decoded_data.chan_mut(0).as_mut_ptr(),
-                0,
+                _good_frame as _,
             )
         }
+        Ok(())
     }
 }
 
@@ -65,12 +109,14 @@ impl Drop for Decoder {
 }
 
 impl D for Decoder {
-    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self>
+    fn try_new(params: &CodecParameters, options: &DecoderOptions) -> Result<Self>
     where
         Self: Sized,
     {
         let mut decoder = Self::default();
         decoder.params = params.clone();
+        decoder.validator = Validator::new(options.verify);
+        decoder.frame_meta = FrameMeta::attached(params.extra_data.as_deref());
         Ok(decoder)
     }
 
@@ -95,13 +141,14 @@ impl D for Decoder {
         self.decoded_data
             .render_reserved(Some(AMRWB_BUFFER_SIZE as usize));
 
-        self.decode(&packet.data);
+        self.decode(&packet.data)?;
+        self.validator.update(self.decoded_data.chan(0));
 
         Ok(self.decoded_data.as_audio_buffer_ref())
     }
 
     fn finalize(&mut self) -> FinalizeResult {
-        Default::default()
+        self.validator.finalize(self.params.verification_check)
     }
 
     fn last_decoded(&self) -> AudioBufferRef {
@@ -1,5 +1,203 @@
+use rtp_core::FrameMeta;
+use symphonia_core::errors::{Error, Result};
+
 mod amrnb;
 mod amrwb;
 
 pub use amrnb::{Decoder as AmrDecoder, CODEC_TYPE_AMR};
 pub use amrwb::{Decoder as AmrwbDecoder, CODEC_TYPE_AMRWB};
+
+/// Receive frame types of 3GPP TS 26.093 and TS 26.193, the ones opencore tells apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RxFrameType {
+    SpeechGood,
+    /// Speech frame received with the quality bit cleared
+    SpeechBad,
+    /// SID_FIRST or SID_UPDATE, opencore tells them apart by the STI bit
+    Sid,
+    /// SID frame received with the quality bit cleared
+    SidBad,
+    /// Speech frame lost in transmission
+    SpeechLost,
+    /// Nothing transmitted, e.g. between two SID frames
+    NoData,
+}
+
+/// Check a MIME storage frame before it reaches opencore, which trusts the ToC and reads as many
+/// bytes as its frame type announces: frames shorter than their frame type and AMR-WB+ frames
+/// are rejected. `wideband` selects AMR-WB frame types.
+pub fn validate_frame(data: &[u8], wideband: bool) -> Result<()> {
+    let (sizes, sid) = if wideband {
+        if data
+            .first()
+            .is_some_and(|toc| amrwb::WBPLUS_FRAME_TYPES.contains(&((toc >> 3) & 0x0f)))
+        {
+            return Err(Error::Unsupported("AMR-WB+ frames are not supported"));
+        }
+        (&amrwb::FRAME_SIZES, 9)
+    } else {
+        (&amrnb::FRAME_SIZES, 8)
+    };
+    rx_frame_type(data, sizes, sid).map(|_| ())
+}
+
+/// Classify a MIME storage frame (a ToC byte followed by the frame bits).
+///
+/// `sizes` is the storage size, ToC included, of every frame type and `sid` is the SID frame
+/// type. An empty frame carries no data, a frame shorter than its frame type requires is an
+/// error since the decoder would read past its end.
+fn rx_frame_type(data: &[u8], sizes: &[usize; 16], sid: u8) -> Result<RxFrameType> {
+    let Some(toc) = data.first() else {
+        return Ok(RxFrameType::NoData);
+    };
+    let ft = (toc >> 3) & 0x0f;
+    if data.len() < sizes[ft as usize] {
+        return Err(Error::DecodeError("AMR frame shorter than its frame type"));
+    }
+
+    let good = toc & 0x04 == 0x04;
+    Ok(match ft {
+        ft if ft < sid && good => RxFrameType::SpeechGood,
+        ft if ft < sid => RxFrameType::SpeechBad,
+        ft if ft == sid && good => RxFrameType::Sid,
+        ft if ft == sid => RxFrameType::SidBad,
+        14 => RxFrameType::SpeechLost,
+        _ => RxFrameType::NoData,
+    })
+}
+
+/// Classify a frame by the [`FrameMeta`] its depacketizer attached rather than by its ToC, see
+/// [`rx_frame_type`]. The frame must still be as long as its frame type requires.
+fn rx_frame_type_from_meta(
+    data: &[u8],
+    meta: &FrameMeta,
+    sizes: &[usize; 16],
+    sid: u8,
+) -> Result<RxFrameType> {
+    if data.is_empty() {
+        return Ok(RxFrameType::NoData);
+    }
+    let ft = meta.frame_type & 0x0f;
+    if data.len() < sizes[ft as usize] {
+        return Err(Error::DecodeError("AMR frame shorter than its frame type"));
+    }
+
+    Ok(match ft {
+        14 => RxFrameType::SpeechLost,
+        ft if ft < sid && meta.bfi => RxFrameType::SpeechBad,
+        ft if ft < sid => RxFrameType::SpeechGood,
+        _ if meta.sid && meta.bfi => RxFrameType::SidBad,
+        _ if meta.sid => RxFrameType::Sid,
+        _ => RxFrameType::NoData,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_frame() {
+        assert!(validate_frame(&[], false).is_ok());
+        assert!(validate_frame(&[0x3c; 32], false).is_ok());
+        assert!(validate_frame(&[0x3c; 31], false).is_err());
+        // 23.85k AMR-WB needs 61 bytes, an AMR 12.2k frame is too short for it
+        assert!(validate_frame(&[0x44; 61], true).is_ok());
+        assert!(validate_frame(&[0x44; 32], true).is_err());
+        assert!(matches!(
+            validate_frame(&[0x54; 40], true),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_rx_frame_type() {
+        let sizes = amrnb::FRAME_SIZES;
+        assert_eq!(rx_frame_type(&[], &sizes, 8).unwrap(), RxFrameType::NoData);
+        assert_eq!(
+            rx_frame_type(&[0x3c; 32], &sizes, 8).unwrap(),
+            RxFrameType::SpeechGood
+        );
+        assert_eq!(
+            rx_frame_type(&[0x38; 32], &sizes, 8).unwrap(),
+            RxFrameType::SpeechBad
+        );
+        assert!(rx_frame_type(&[0x3c; 31], &sizes, 8).is_err());
+        assert_eq!(
+            rx_frame_type(&[0x44; 6], &sizes, 8).unwrap(),
+            RxFrameType::Sid
+        );
+        assert_eq!(
+            rx_frame_type(&[0x40; 6], &sizes, 8).unwrap(),
+            RxFrameType::SidBad
+        );
+        assert_eq!(
+            rx_frame_type(&[0x74], &sizes, 8).unwrap(),
+            RxFrameType::SpeechLost
+        );
+        assert_eq!(
+            rx_frame_type(&[0x7c], &sizes, 8).unwrap(),
+            RxFrameType::NoData
+        );
+
+        let sizes = amrwb::FRAME_SIZES;
+        assert_eq!(
+            rx_frame_type(&[0x44; 61], &sizes, 9).unwrap(),
+            RxFrameType::SpeechGood
+        );
+        assert!(rx_frame_type(&[0x44; 60], &sizes, 9).is_err());
+        assert_eq!(
+            rx_frame_type(&[0x4c; 6], &sizes, 9).unwrap(),
+            RxFrameType::Sid
+        );
+    }
+
+    #[test]
+    fn test_rx_frame_type_from_meta() {
+        let sizes = amrnb::FRAME_SIZES;
+        let meta = FrameMeta {
+            frame_type: 7,
+            bitrate: Some(12200),
+            ..Default::default()
+        };
+        assert_eq!(
+            rx_frame_type_from_meta(&[0x3c; 32], &meta, &sizes, 8).unwrap(),
+            RxFrameType::SpeechGood
+        );
+        // the ToC quality bit is set, the depacketizer knows better
+        let bad = FrameMeta { bfi: true, ..meta };
+        assert_eq!(
+            rx_frame_type_from_meta(&[0x3c; 32], &bad, &sizes, 8).unwrap(),
+            RxFrameType::SpeechBad
+        );
+        assert!(rx_frame_type_from_meta(&[0x3c; 31], &meta, &sizes, 8).is_err());
+
+        let sid = FrameMeta {
+            frame_type: 8,
+            sid: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            rx_frame_type_from_meta(&[0x44; 6], &sid, &sizes, 8).unwrap(),
+            RxFrameType::Sid
+        );
+        let sid_bad = FrameMeta { bfi: true, ..sid };
+        assert_eq!(
+            rx_frame_type_from_meta(&[0x44; 6], &sid_bad, &sizes, 8).unwrap(),
+            RxFrameType::SidBad
+        );
+        let lost = FrameMeta {
+            frame_type: 14,
+            bfi: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            rx_frame_type_from_meta(&[0x74], &lost, &sizes, 8).unwrap(),
+            RxFrameType::SpeechLost
+        );
+        assert_eq!(
+            rx_frame_type_from_meta(&[], &meta, &sizes, 8).unwrap(),
+            RxFrameType::NoData
+        );
+    }
+}
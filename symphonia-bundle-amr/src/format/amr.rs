@@ -1,4 +1,4 @@
-use std::io::{Seek, SeekFrom};
+use std::io::{ErrorKind, Seek, SeekFrom};
 
 use symphonia_core::audio::Channels;
 use symphonia_core::codecs::CodecParameters;
@@ -6,7 +6,7 @@ use symphonia_core::errors::{seek_error, Error, Result, SeekErrorKind};
 use symphonia_core::formats::{
     Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
 };
-use symphonia_core::io::{MediaSourceStream, ReadBytes};
+use symphonia_core::io::{MediaSource, MediaSourceStream, ReadBytes};
 use symphonia_core::meta::{Metadata, MetadataLog};
 use symphonia_core::probe::{Descriptor, Instantiate, QueryDescriptor};
 use symphonia_core::support_format;
@@ -53,6 +53,8 @@ pub struct AmrReader {
     channels: usize,
     chl_idx: usize,
     track_ts: Vec<u64>,
+    /// position of the first frame
+    data_start: u64,
 }
 
 impl AmrReader {
@@ -66,6 +68,27 @@ impl AmrReader {
             channels: 0,
             chl_idx: 0,
             track_ts: vec![],
+            data_start: 0,
+        }
+    }
+
+    /// Skip the frame at the current position
+    fn skip_frame(&mut self) -> Result<()> {
+        let toc = AmrToc(self.reader.read_byte()?);
+        self.reader
+            .ignore_bytes(toc.payload_size().unwrap_or_default() as u64)?;
+        Ok(())
+    }
+
+    /// Count the frames from the current position to the end of the file
+    fn count_frames(&mut self) -> Result<u64> {
+        let mut cnt = 0;
+        loop {
+            match self.skip_frame() {
+                Ok(()) => cnt += 1,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(cnt),
+                Err(e) => return Err(e),
+            }
         }
     }
 }
@@ -97,6 +120,16 @@ impl FormatReader for AmrReader {
         }
 
         amr.channels = 1;
+        amr.data_start = amr.reader.pos();
+
+        // the duration is only known if the frames can be counted beforehand
+        let n_frames = if amr.reader.is_seekable() {
+            let frames = amr.count_frames()?;
+            amr.reader.seek(SeekFrom::Start(amr.data_start))?;
+            Some(frames / amr.channels as u64 * AMR_BUFFER_SIZE)
+        } else {
+            None
+        };
 
         for cid in 0..amr.channels {
             let mut codec_params = CodecParameters::new();
@@ -104,7 +137,11 @@ impl FormatReader for AmrReader {
             codec_params.channels = Some(Channels::FRONT_CENTRE);
             codec_params
                 .with_sample_rate(AMR_SAMPLE_RATE)
-                .with_time_base(TimeBase::new(1, AMR_SAMPLE_RATE));
+                .with_time_base(TimeBase::new(1, AMR_SAMPLE_RATE))
+                .with_start_ts(0);
+            if let Some(n_frames) = n_frames {
+                codec_params.with_n_frames(n_frames);
+            }
 
             amr.consumed = consumed;
             amr.tracks.push(Track::new(cid as u32, codec_params));
@@ -154,15 +191,79 @@ impl FormatReader for AmrReader {
         &self.tracks
     }
 
-    fn seek(&mut self, mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
-        if self.tracks.is_empty() {
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        if self.tracks.is_empty() || !self.reader.is_seekable() {
             return seek_error(SeekErrorKind::Unseekable);
         }
 
-        unimplemented!()
+        let params = &self.tracks[0].codec_params;
+        let required_ts = match to {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => TimeBase::new(1, AMR_SAMPLE_RATE).calc_timestamp(time),
+        };
+        if params
+            .n_frames
+            .is_some_and(|n_frames| required_ts >= n_frames)
+        {
+            return seek_error(SeekErrorKind::OutOfRange);
+        }
+
+        // frames vary in size, the ones before the required frame are skipped one by one
+        let frame = required_ts / AMR_BUFFER_SIZE;
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        for _ in 0..frame * self.channels as u64 {
+            self.skip_frame()?;
+        }
+        self.track_ts.iter_mut().for_each(|ts| *ts = frame);
+        self.chl_idx = 0;
+
+        Ok(SeekedTo {
+            track_id: 0,
+            required_ts,
+            actual_ts: frame * AMR_BUFFER_SIZE,
+        })
     }
 
     fn into_inner(self: Box<Self>) -> MediaSourceStream {
         self.reader
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_duration_and_seek() {
+        // 12.2 kbps frames and a NO_DATA frame
+        let mut data = AMR_MIME_MAGIC.to_vec();
+        for i in 0..10 {
+            data.push(if i == 3 { 0x7c } else { 0x3c });
+            if i != 3 {
+                data.extend_from_slice(&[0; 31]);
+            }
+        }
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = AmrReader::try_new(mss, &Default::default()).unwrap();
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.start_ts, 0);
+        assert_eq!(params.n_frames, Some(10 * AMR_BUFFER_SIZE));
+        assert_eq!(params.time_base, Some(TimeBase::new(1, AMR_SAMPLE_RATE)));
+
+        let mut end = 0;
+        while let Ok(pkt) = reader.next_packet() {
+            assert_eq!(pkt.ts(), end);
+            end += pkt.dur();
+        }
+        assert_eq!(end, 10 * AMR_BUFFER_SIZE);
+
+        let to = |ts| SeekTo::TimeStamp { ts, track_id: 0 };
+        let seeked = reader.seek(SeekMode::Accurate, to(850)).unwrap();
+        assert_eq!(seeked.actual_ts, 800);
+        let pkt = reader.next_packet().unwrap();
+        assert_eq!((pkt.ts(), pkt.buf().len()), (800, 32));
+        assert!(reader.seek(SeekMode::Accurate, to(1600)).is_err());
+    }
+}
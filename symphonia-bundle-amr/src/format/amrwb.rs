@@ -1,4 +1,4 @@
-use std::io::{Seek, SeekFrom};
+use std::io::{ErrorKind, Seek, SeekFrom};
 
 use symphonia_core::audio::Channels;
 use symphonia_core::codecs::CodecParameters;
@@ -6,7 +6,7 @@ use symphonia_core::errors::{seek_error, Error, Result, SeekErrorKind};
 use symphonia_core::formats::{
     Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
 };
-use symphonia_core::io::{MediaSourceStream, ReadBytes};
+use symphonia_core::io::{MediaSource, MediaSourceStream, ReadBytes};
 use symphonia_core::meta::{Metadata, MetadataLog};
 use symphonia_core::probe::{Descriptor, Instantiate, QueryDescriptor};
 use symphonia_core::support_format;
@@ -57,6 +57,8 @@ pub struct AmrwbReader {
     channels: usize,
     chl_idx: usize,
     track_ts: Vec<u64>,
+    /// position of the first frame
+    data_start: u64,
 }
 
 impl AmrwbReader {
@@ -70,6 +72,27 @@ impl AmrwbReader {
             channels: 0,
             chl_idx: 0,
             track_ts: vec![],
+            data_start: 0,
+        }
+    }
+
+    /// Skip the frame at the current position
+    fn skip_frame(&mut self) -> Result<()> {
+        let toc = AmrwbToc(self.reader.read_byte()?);
+        self.reader
+            .ignore_bytes(toc.payload_size().unwrap_or_default() as u64)?;
+        Ok(())
+    }
+
+    /// Count the frames from the current position to the end of the file
+    fn count_frames(&mut self) -> Result<u64> {
+        let mut cnt = 0;
+        loop {
+            match self.skip_frame() {
+                Ok(()) => cnt += 1,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(cnt),
+                Err(e) => return Err(e),
+            }
         }
     }
 }
@@ -101,6 +124,16 @@ impl FormatReader for AmrwbReader {
         }
 
         amr.channels = 1;
+        amr.data_start = amr.reader.pos();
+
+        // the duration is only known if the frames can be counted beforehand
+        let n_frames = if amr.reader.is_seekable() {
+            let frames = amr.count_frames()?;
+            amr.reader.seek(SeekFrom::Start(amr.data_start))?;
+            Some(frames / amr.channels as u64 * AMRWB_BUFFER_SIZE)
+        } else {
+            None
+        };
 
         for cid in 0..amr.channels {
             let mut codec_params = CodecParameters::new();
@@ -108,7 +141,11 @@ impl FormatReader for AmrwbReader {
             codec_params.channels = Some(Channels::FRONT_CENTRE);
             codec_params
                 .with_sample_rate(AMRWB_SAMPLE_RATE)
-                .with_time_base(TimeBase::new(1, AMRWB_SAMPLE_RATE));
+                .with_time_base(TimeBase::new(1, AMRWB_SAMPLE_RATE))
+                .with_start_ts(0);
+            if let Some(n_frames) = n_frames {
+                codec_params.with_n_frames(n_frames);
+            }
 
             amr.consumed = consumed;
             amr.tracks.push(Track::new(cid as u32, codec_params));
@@ -158,12 +195,37 @@ impl FormatReader for AmrwbReader {
         &self.tracks
     }
 
-    fn seek(&mut self, mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
-        if self.tracks.is_empty() {
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        if self.tracks.is_empty() || !self.reader.is_seekable() {
             return seek_error(SeekErrorKind::Unseekable);
         }
 
-        unimplemented!()
+        let params = &self.tracks[0].codec_params;
+        let required_ts = match to {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => TimeBase::new(1, AMRWB_SAMPLE_RATE).calc_timestamp(time),
+        };
+        if params
+            .n_frames
+            .is_some_and(|n_frames| required_ts >= n_frames)
+        {
+            return seek_error(SeekErrorKind::OutOfRange);
+        }
+
+        // frames vary in size, the ones before the required frame are skipped one by one
+        let frame = required_ts / AMRWB_BUFFER_SIZE;
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        for _ in 0..frame * self.channels as u64 {
+            self.skip_frame()?;
+        }
+        self.track_ts.iter_mut().for_each(|ts| *ts = frame);
+        self.chl_idx = 0;
+
+        Ok(SeekedTo {
+            track_id: 0,
+            required_ts,
+            actual_ts: frame * AMRWB_BUFFER_SIZE,
+        })
     }
 
     fn into_inner(self: Box<Self>) -> MediaSourceStream {
@@ -1,7 +1,7 @@
 pub mod dec;
 pub mod format;
 
-pub use dec::{AmrDecoder, AmrwbDecoder, CODEC_TYPE_AMR, CODEC_TYPE_AMRWB};
+pub use dec::{validate_frame, AmrDecoder, AmrwbDecoder, CODEC_TYPE_AMR, CODEC_TYPE_AMRWB};
 pub use format::{AmrReader, AmrwbReader};
 
 const AMR_SAMPLE_RATE: u32 = 8000;
@@ -0,0 +1,140 @@
+//! Checks of the decoder against the 3GPP EVS conformance test vectors (TS 26.444).
+//!
+//! The bitstreams have to be stored in the MIME storage format read by
+//! [`EvsReader`](crate::format::EvsReader), with their reference output decoded at 16 kHz,
+//! the only output rate of [`Decoder`].
+
+use std::io::{Cursor, ErrorKind};
+
+use symphonia_core::audio::{AudioBufferRef, Signal};
+use symphonia_core::codecs::Decoder as _;
+use symphonia_core::errors::{Error, Result};
+use symphonia_core::formats::FormatReader;
+use symphonia_core::io::MediaSourceStream;
+
+use crate::dec::Decoder;
+use crate::format::EvsReader;
+
+/// Difference between decoded audio and the reference output of a test vector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PcmDiff {
+    /// Samples compared, the shorter of both lengths
+    pub samples: usize,
+    /// Samples differing by more than the tolerance
+    pub mismatches: usize,
+    /// Largest difference between two samples
+    pub max_diff: u16,
+    /// Difference of both lengths, in samples
+    pub len_diff: usize,
+}
+
+impl PcmDiff {
+    /// Compare `decoded` with `reference`, samples may differ by up to `tolerance`. A tolerance
+    /// of 0 checks the decoder is bit-exact.
+    pub fn compare(decoded: &[i16], reference: &[i16], tolerance: u16) -> Self {
+        let mut diff = Self {
+            samples: decoded.len().min(reference.len()),
+            len_diff: decoded.len().abs_diff(reference.len()),
+            ..Default::default()
+        };
+        for (a, b) in decoded.iter().zip(reference) {
+            let d = a.abs_diff(*b);
+            diff.max_diff = diff.max_diff.max(d);
+            if d > tolerance {
+                diff.mismatches += 1;
+            }
+        }
+        diff
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.mismatches == 0 && self.len_diff == 0
+    }
+}
+
+/// Read 16 bits little endian PCM, the format of the reference outputs
+pub fn read_pcm(bytes: &[u8]) -> Vec<i16> {
+    bytes
+        .chunks_exact(2)
+        .map(|s| i16::from_le_bytes([s[0], s[1]]))
+        .collect()
+}
+
+/// Decode the first channel of a MIME storage EVS bitstream
+pub fn decode_mime(bitstream: &[u8]) -> Result<Vec<i16>> {
+    let mss = MediaSourceStream::new(
+        Box::new(Cursor::new(bitstream.to_vec())),
+        Default::default(),
+    );
+    let mut reader = EvsReader::try_new(mss, &Default::default())?;
+    let Some(track) = reader.tracks().first() else {
+        return Err(Error::DecodeError("No channel found in file"));
+    };
+    let track_id = track.id;
+    let mut decoder = Decoder::try_new(&track.codec_params, &Default::default())?;
+
+    let mut pcm = vec![];
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        if let AudioBufferRef::S16(buf) = decoder.decode(&packet)? {
+            pcm.extend_from_slice(buf.chan(0));
+        }
+    }
+    Ok(pcm)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_compare_pcm() {
+        let reference = [0, 100, -100, 5];
+        assert!(PcmDiff::compare(&reference, &reference, 0).is_ok());
+
+        let diff = PcmDiff::compare(&[0, 101, -103], &reference, 1);
+        assert_eq!(
+            diff,
+            PcmDiff {
+                samples: 3,
+                mismatches: 1,
+                max_diff: 3,
+                len_diff: 1,
+            }
+        );
+        assert!(!diff.is_ok());
+        assert_eq!(read_pcm(&[0x01, 0x00, 0xff, 0xff, 0x7f]), vec![1, -1]);
+    }
+
+    /// Every `<name>.evs` bitstream of `$EVS_CONFORMANCE_DIR` is decoded and compared with the
+    /// reference output `<name>.pcm`, e.g.
+    /// `EVS_CONFORMANCE_DIR=/path/to/vectors cargo test -p symphonia-bundle-evs -- --ignored`
+    #[test]
+    #[ignore = "needs the 3GPP conformance vectors in $EVS_CONFORMANCE_DIR"]
+    fn test_conformance_vectors() {
+        let dir = std::env::var("EVS_CONFORMANCE_DIR").expect("EVS_CONFORMANCE_DIR is not set");
+        let mut failed = vec![];
+        for entry in std::fs::read_dir(Path::new(&dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "evs") {
+                continue;
+            }
+            let reference = read_pcm(&std::fs::read(path.with_extension("pcm")).unwrap());
+            let decoded = decode_mime(&std::fs::read(&path).unwrap()).unwrap();
+            let diff = PcmDiff::compare(&decoded, &reference, 0);
+            if !diff.is_ok() {
+                failed.push((path, diff));
+            }
+        }
+        assert!(failed.is_empty(), "{failed:#?}");
+    }
+}
@@ -1,27 +1,95 @@
 use std::mem::size_of;
 use std::num::NonZeroUsize;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
 
+use log::debug;
+use rtp_core::FrameMeta;
 use symphonia_core::audio::{
     AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
 };
 use symphonia_core::codecs::{
-    decl_codec_type, CodecDescriptor, CodecParameters, CodecType, Decoder as D, DecoderOptions,
-    FinalizeResult,
+    CodecDescriptor, CodecParameters, Decoder as D, DecoderOptions, FinalizeResult,
 };
 use symphonia_core::errors::{decode_error, Error, Result};
 use symphonia_core::formats::Packet;
 use symphonia_core::support_codec;
 
 use evs_codec_sys::{
-    amr_wb_dec, evs_dec, init_decoder, read_indices_from_djb, reset_indices_dec, syn_output,
-    Decoder_State, Word16, Word32, MIME,
+    amr_wb_dec, destroy_decoder, evs_dec, init_decoder, read_indices_from_djb, reset_indices_dec,
+    syn_output, Decoder_State, Word16, Word32, MIME,
 };
 
 use crate::consts::{CodecFormat, FrameMode, FrameTypeIndex};
 use crate::utils::u8_slice_to_any;
 use crate::{AmrToc, EvsToc};
 
-pub const CODEC_TYPE_EVS: CodecType = decl_codec_type(b"evs");
+use voip_codecs::Validator;
+
+pub use voip_codecs::CODEC_TYPE_EVS;
+
+/// Serializes calls into the EVS reference code.
+///
+/// The decoder state is self-contained, but the basic operators shared by every instance
+/// record saturation in the global `Overflow` and `Carry` flags, which some decoder paths
+/// clear and read back (e.g. `env_stab.c`). Decoding on several threads at once would race on
+/// those flags and change the output.
+static LIBEVS: Mutex<()> = Mutex::new(());
+
+fn lock_libevs() -> MutexGuard<'static, ()> {
+    // the guarded data is `()`, a panic while holding the lock leaves nothing inconsistent
+    LIBEVS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Output sample rates of the EVS decoder
+const OUTPUT_SAMPLE_RATES: [u32; 4] = [8000, 16000, 32000, 48000];
+
+/// State of the EVS reference decoder.
+///
+/// The C struct is boxed so that it never moves once `init_decoder` set it up, and
+/// `destroy_decoder` frees the buffers it allocated on drop. It isn't `Clone`, a copy would
+/// point to the same buffers and free them twice.
+struct EvsState(Box<Decoder_State>);
+
+impl EvsState {
+    /// Set up a decoder of MIME storage frames with `output_fs` Hz output
+    fn new(output_fs: i32) -> Self {
+        let mut raw = Box::<Decoder_State>::default();
+        raw.bitstreamformat = MIME as Word16;
+        raw.output_Fs = output_fs;
+        let _lock = lock_libevs();
+        unsafe {
+            init_decoder(raw.as_mut());
+            reset_indices_dec(raw.as_mut());
+        }
+        Self(raw)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut Decoder_State {
+        self.0.as_mut()
+    }
+}
+
+impl Deref for EvsState {
+    type Target = Decoder_State;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for EvsState {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for EvsState {
+    fn drop(&mut self) {
+        let _lock = lock_libevs();
+        unsafe { destroy_decoder(self.as_mut_ptr()) };
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
@@ -43,11 +111,13 @@ impl Default for DecoderParams {
     }
 }
 
-#[derive(Clone)]
 pub struct Decoder {
     decode_param: DecoderParams,
     params: CodecParameters,
-    raw: Decoder_State,
+    validator: Validator,
+    /// Frames end with a [`FrameMeta`], see [`FrameMeta::attached`]
+    frame_meta: bool,
+    raw: EvsState,
     decoded_len: usize,
     output: [f32; 128000 / 50],
     decoded_data: AudioBuffer<i16>,
@@ -58,7 +128,9 @@ impl Default for Decoder {
         Self {
             decode_param: Default::default(),
             params: CodecParameters::default(),
-            raw: Decoder_State::default(),
+            validator: Validator::default(),
+            frame_meta: false,
+            raw: EvsState::new(16000),
             decoded_len: 0,
             output: [0.0; 128000 / 50],
             decoded_data: AudioBuffer::new(960, SignalSpec::new(1, Channels::all())),
@@ -66,6 +138,9 @@ impl Default for Decoder {
     }
 }
 
+// SAFETY: `raw` owns the buffers `init_decoder` allocates and nothing else points to them, so
+// the decoder may move to another thread. Every call into the C code goes through `&mut self`
+// and holds `LIBEVS`, while `&self` only reads plain fields, so sharing references is sound.
 unsafe impl Send for Decoder {}
 unsafe impl Sync for Decoder {}
 
@@ -81,23 +156,36 @@ impl Decoder {
 
 impl D for Decoder {
     fn try_new(params: &CodecParameters, options: &DecoderOptions) -> Result<Self> {
-        let param =
-            unsafe { u8_slice_to_any::<DecoderParams>(params.extra_data.as_ref().unwrap()) };
-        let mut decoder = Self::default();
-        decoder.decode_param = param.clone();
-        decoder.decoded_data =
-            AudioBuffer::new(960, SignalSpec::new(16000, Channels::FRONT_CENTRE));
-
-        decoder.raw.bitstreamformat = MIME as Word16;
-        decoder.raw.output_Fs = 16000;
-        unsafe {
-            // decoder.raw.cldfbAna = std::ptr::null_mut();
-            // decoder.raw.cldfbBPF = std::ptr::null_mut();
-            // decoder.raw.cldfbSyn = std::ptr::null_mut();
-            // decoder.raw.hFdCngDec = std::ptr::null_mut();
-            init_decoder(&mut decoder.raw);
-            reset_indices_dec(&mut decoder.raw);
+        let frame_meta = FrameMeta::attached(params.extra_data.as_deref());
+        let mut decoder = Self {
+            params: params.clone(),
+            validator: Validator::new(options.verify),
+            frame_meta,
+            ..Default::default()
+        };
+        // streams from other containers (e.g. RTP) carry MIME storage frames without params
+        match params.extra_data.as_ref() {
+            Some(extra_data) if !frame_meta => {
+                decoder.decode_param = *unsafe { u8_slice_to_any::<DecoderParams>(extra_data) };
+            }
+            _ => {}
         }
+        // the output rate is up to the receiver, 16 kHz unless asked otherwise
+        let sample_rate = params
+            .sample_rate
+            .or(decoder.decode_param.sample_rate)
+            .unwrap_or(16000);
+        if !OUTPUT_SAMPLE_RATES.contains(&sample_rate) {
+            return Err(Error::Unsupported("Unsupported EVS output sample rate"));
+        }
+        if sample_rate != 16000 {
+            decoder.raw = EvsState::new(sample_rate as i32);
+        }
+        decoder.decoded_data = AudioBuffer::new(
+            sample_rate as u64 / 50,
+            SignalSpec::new(sample_rate, Channels::FRONT_CENTRE),
+        );
+
         Ok(decoder)
     }
 
@@ -122,7 +210,7 @@ impl D for Decoder {
     }
 
     fn finalize(&mut self) -> FinalizeResult {
-        Default::default()
+        self.validator.finalize(self.params.verification_check)
     }
 
     fn last_decoded(&self) -> AudioBufferRef {
@@ -130,33 +218,70 @@ impl D for Decoder {
     }
 }
 
+/// Check a MIME storage frame (one ToC byte followed by speech bits) before it reaches the EVS
+/// reference code, which trusts the ToC and reads as many bits as its frame type announces:
+/// frames shorter than their frame type, and ToCs with no bit rate or the header type bit set
+/// are rejected. An empty frame is a lost frame, and is valid.
+pub fn validate_frame(data: &[u8]) -> Result<()> {
+    let Some(toc) = data.first().map(|toc| EvsToc(*toc)) else {
+        return Ok(());
+    };
+    // a CMR byte rather than a ToC
+    if toc.header_type() {
+        return Err(Error::DecodeError("Invalid EVS ToC"));
+    }
+    if toc.frame_type().bit_rate().is_none() {
+        return Err(Error::DecodeError("Invalid bitrate"));
+    }
+    let frame_len = match toc.payload_size() {
+        None => return Err(Error::DecodeError("Future use or speech lost")),
+        Some(size) => size,
+    };
+    if data.len() - size_of::<EvsToc>() < frame_len {
+        debug!(
+            "invalid packet {} < {} + {}",
+            data.len(),
+            frame_len,
+            size_of::<EvsToc>(),
+        );
+        return Err(Error::DecodeError("Invalid packet len"));
+    }
+    Ok(())
+}
+
 impl Decoder {
+    /// Decode a MIME storage frame. On tracks carrying metadata, the [`FrameMeta`] appended to
+    /// the frame tells whether it is bad, and must agree with the ToC on its frame type and
+    /// bitrate.
     fn decode_mime(&mut self, packet: &Packet) -> Result<AudioBufferRef> {
-        if !packet.data.is_empty() {
-            self.check(packet)?;
+        let (data, meta) = match self.frame_meta {
+            true => FrameMeta::split(&packet.data),
+            false => (&packet.data[..], None),
+        };
+        validate_frame(data)?;
+        let _lock = lock_libevs();
+        if !data.is_empty() {
+            self.check(data, meta)?;
         }
 
         self.reset();
 
         unsafe {
             evs_dec(
-                &mut self.raw,
+                self.raw.as_mut_ptr(),
                 self.output.as_mut_ptr(),
                 FrameMode::Normal as _,
             );
 
-            self.decoded_data.clear();
-            self.decoded_data
-                .render_reserved(Some(self.raw.output_Fs as usize / 50));
+            self.reserve_frame();
 
             syn_output(
                 self.output.as_mut_ptr(),
                 (self.raw.output_Fs / 50) as Word16,
-                self.decoded_data
-                    .chan_mut(packet.track_id() as _)
-                    .as_mut_ptr()
-                    .cast(),
+                // every track is decoded by its own mono decoder
+                self.decoded_data.chan_mut(0).as_mut_ptr().cast(),
             );
+            self.validator.update(self.decoded_data.chan(0));
             // println!(
             //     "decoded len: {}, frames: {}, capacity: {}",
             //     self.decoded_data.chan(packet.track_id() as _).len(),
@@ -168,13 +293,22 @@ impl Decoder {
         Ok(self.decoded_data.as_audio_buffer_ref())
     }
 
-    fn check(&mut self, packet: &Packet) -> Result<()> {
-        let mut data = packet.buf();
-        let is_amrwb: bool;
-        let frame_type: usize;
-        let qbit: bool;
-        let total_bitrate: i32;
+    /// Make room for a frame at the output rate, the C code writing through a raw pointer
+    fn reserve_frame(&mut self) {
+        let rate = self.raw.output_Fs as u32;
+        let frame_len = rate as usize / 50;
+        let spec = self.decoded_data.spec();
+        if spec.rate != rate || self.decoded_data.capacity() < frame_len {
+            self.decoded_data = AudioBuffer::new(
+                frame_len as u64,
+                SignalSpec::new(rate, Channels::FRONT_CENTRE),
+            );
+        }
+        self.decoded_data.clear();
+        self.decoded_data.render_reserved(Some(frame_len));
+    }
 
+    fn check(&mut self, mut data: &[u8], meta: Option<FrameMeta>) -> Result<()> {
         // if self.raw.amrwb_rfc4867_flag != 0 {
         //     let toc = AmrToc(data[0]);
         //     is_amrwb = true;
@@ -183,33 +317,30 @@ impl Decoder {
         //     total_bitrate = 0;
         //     data = &data[size_of::<AmrToc>()..];
         // }
+        // what the C code reads is up to the ToC validate_frame checked, the metadata only
+        // tells whether the frame is bad
         let toc = EvsToc(data[0]);
-        is_amrwb = toc.is_amrwb();
-        let ft: u8 = toc.frame_type().into();
-        frame_type = ft as usize;
-        qbit = toc.quality();
-
+        let is_amrwb = toc.is_amrwb();
+        let frame_type: u8 = toc.frame_type().into();
         let total_bitrate = toc
             .frame_type()
             .bit_rate()
-            .ok_or_else(|| Error::DecodeError("Invalid bitrate"))?;
-
-        data = &data[size_of::<EvsToc>()..];
-
-        let frame_len = match toc.payload_size() {
-            None => return Err(Error::DecodeError("Future use or speech lost")),
-            Some(size) => size,
+            .ok_or(Error::DecodeError("Invalid bitrate"))?;
+        let qbit = match meta {
+            // the frame type keeps the AMR-WB IO mode bit of the ToC
+            Some(meta)
+                if meta.frame_type != data[0] & 0x2f || meta.bitrate != Some(total_bitrate) =>
+            {
+                return Err(Error::DecodeError(
+                    "Frame metadata doesn't match the EVS ToC",
+                ));
+            }
+            Some(meta) => !meta.bfi,
+            None => toc.quality(),
         };
 
-        if data.len() < frame_len {
-            eprintln!(
-                "Invalid packet {} < {} + {}",
-                packet.data.len(),
-                frame_len,
-                packet.data.len() - data.len(),
-            );
-            return Err(Error::DecodeError("Invalid packet len"));
-        }
+        // validate_frame made sure every bit the ToC announces is there
+        data = &data[size_of::<EvsToc>()..];
 
         self.raw.Opt_AMR_WB = is_amrwb as Word16;
 
@@ -220,7 +351,7 @@ impl Decoder {
         // println!("qbit: {}", qbit);
         unsafe {
             read_indices_from_djb(
-                &mut self.raw,
+                self.raw.as_mut_ptr(),
                 data.as_ptr().cast_mut(),
                 total_bitrate as Word32 / 50,
                 is_amrwb as Word16,
@@ -234,3 +365,147 @@ impl Decoder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use rtp_core::test_util::Lcg;
+
+    use super::*;
+
+    /// 13.2 kbps frames with pseudo random speech bits, any bit pattern is a valid frame
+    fn frames(seed: u32) -> Vec<Vec<u8>> {
+        let mut rng = Lcg(seed);
+        (0..50)
+            .map(|_| {
+                let mut frame = vec![0x04];
+                frame.extend((0..33).map(|_| rng.byte()));
+                frame
+            })
+            .collect()
+    }
+
+    fn decode_all(frames: &[Vec<u8>]) -> Vec<i16> {
+        decode_all_with(&CodecParameters::new(), frames)
+    }
+
+    fn decode_all_with(params: &CodecParameters, frames: &[Vec<u8>]) -> Vec<i16> {
+        let mut decoder = Decoder::try_new(params, &Default::default()).unwrap();
+        let mut pcm = vec![];
+        for (i, frame) in frames.iter().enumerate() {
+            let pkt = Packet::new_from_slice(0, i as u64 * 320, 320, frame);
+            let AudioBufferRef::S16(buf) = D::decode(&mut decoder, &pkt).unwrap() else {
+                unreachable!()
+            };
+            pcm.extend_from_slice(buf.chan(0));
+        }
+        pcm
+    }
+
+    #[test]
+    fn test_validate_frame() {
+        let frame = &frames(1)[0];
+        assert!(validate_frame(&[]).is_ok());
+        assert!(validate_frame(frame).is_ok());
+        assert!(validate_frame(&frame[..33]).is_err());
+        // header type bit of a CMR byte
+        assert!(validate_frame(&[0x84; 34]).is_err());
+        // AMR-WB IO 23.85 kbps, 60 bytes long
+        assert!(validate_frame(&[0x28; 61]).is_ok());
+        assert!(validate_frame(&[0x28; 60]).is_err());
+    }
+
+    #[test]
+    fn test_output_sample_rate() {
+        let frame = &frames(1)[0];
+        for rate in OUTPUT_SAMPLE_RATES {
+            let mut params = CodecParameters::new();
+            params.with_sample_rate(rate);
+            let mut decoder = Decoder::try_new(&params, &Default::default()).unwrap();
+            let pkt = Packet::new_from_slice(0, 0, 320, frame);
+            let decoded = D::decode(&mut decoder, &pkt).unwrap();
+            assert_eq!(decoded.spec().rate, rate);
+            assert_eq!(decoded.frames(), rate as usize / 50);
+        }
+
+        let mut params = CodecParameters::new();
+        params.with_sample_rate(44100);
+        assert!(Decoder::try_new(&params, &Default::default()).is_err());
+    }
+
+    #[test]
+    fn test_state_teardown() {
+        // every decoder starts from a state of its own, whatever was set up and freed before
+        let frames = frames(1);
+        let expected = decode_all(&frames);
+        for _ in 0..4 {
+            assert_eq!(decode_all(&frames), expected);
+        }
+    }
+
+    #[test]
+    fn test_frame_meta() {
+        let frames = frames(1);
+        let expected = decode_all(&frames);
+        let meta = FrameMeta {
+            frame_type: 4,
+            bitrate: Some(13200),
+            duration: Some(320),
+            ..Default::default()
+        };
+        let with_meta = |meta: FrameMeta| {
+            frames
+                .iter()
+                .map(|frame| {
+                    let mut frame = frame.clone();
+                    meta.append(&mut frame);
+                    frame
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut params = CodecParameters::new();
+        params.with_extra_data(Box::new(FrameMeta::MAGIC));
+        assert_eq!(decode_all_with(&params, &with_meta(meta)), expected);
+        // bad frames are concealed, whatever their bits
+        let bad = FrameMeta { bfi: true, ..meta };
+        assert_ne!(decode_all_with(&params, &with_meta(bad)), expected);
+        // the trailer is frame data on tracks without metadata
+        assert_eq!(decode_all(&with_meta(bad)), expected);
+
+        // a trailer claiming more bits than the ToC announces is rejected
+        let mut decoder = Decoder::try_new(&params, &Default::default()).unwrap();
+        for lie in [
+            FrameMeta {
+                bitrate: Some(200_000),
+                ..meta
+            },
+            FrameMeta {
+                frame_type: 11,
+                ..meta
+            },
+        ] {
+            let mut frame = frames[0].clone();
+            lie.append(&mut frame);
+            let pkt = Packet::new_from_slice(0, 0, 320, &frame);
+            assert!(matches!(
+                D::decode(&mut decoder, &pkt),
+                Err(Error::DecodeError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_decode() {
+        let tracks = (0..8).map(frames).collect::<Vec<_>>();
+        let expected = tracks.iter().map(|t| decode_all(t)).collect::<Vec<_>>();
+
+        let handles = tracks
+            .into_iter()
+            .map(|t| thread::spawn(move || decode_all(&t)))
+            .collect::<Vec<_>>();
+        for (handle, expected) in handles.into_iter().zip(expected) {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+}
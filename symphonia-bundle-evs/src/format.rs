@@ -1,4 +1,4 @@
-use std::io::{Seek, SeekFrom};
+use std::io::{ErrorKind, Seek, SeekFrom};
 use std::num::NonZeroUsize;
 
 use symphonia_core::audio::Channels;
@@ -7,7 +7,7 @@ use symphonia_core::errors::{seek_error, Error, Result, SeekErrorKind};
 use symphonia_core::formats::{
     Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
 };
-use symphonia_core::io::{MediaSourceStream, ReadBytes};
+use symphonia_core::io::{MediaSource, MediaSourceStream, ReadBytes};
 use symphonia_core::meta::{Metadata, MetadataLog};
 use symphonia_core::probe::{Descriptor, Instantiate, QueryDescriptor};
 use symphonia_core::support_format;
@@ -52,6 +52,8 @@ pub struct EvsReader {
     channels: usize,
     chl_idx: usize,
     pkt_cnt: u64,
+    /// position of the first frame
+    data_start: u64,
     pub sample_rate: Option<u32>,
     pub timestamp_interval: u64,
 }
@@ -67,10 +69,31 @@ impl EvsReader {
             channels: 0,
             chl_idx: 0,
             pkt_cnt: 0,
+            data_start: 0,
             sample_rate: Some(16000),
             timestamp_interval: 320,
         }
     }
+
+    /// Skip the frame at the current position
+    fn skip_frame(&mut self) -> Result<()> {
+        let toc = EvsToc(self.reader.read_byte()?);
+        self.reader
+            .ignore_bytes(toc.payload_size().unwrap_or_default() as u64)?;
+        Ok(())
+    }
+
+    /// Count the frames from the current position to the end of the file
+    fn count_frames(&mut self) -> Result<u64> {
+        let mut cnt = 0;
+        loop {
+            match self.skip_frame() {
+                Ok(()) => cnt += 1,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(cnt),
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
 
 impl QueryDescriptor for EvsReader {
@@ -102,6 +125,17 @@ impl FormatReader for EvsReader {
 
         evs.channels = evs.reader.read_be_u32()? as usize;
         consumed += 4;
+        evs.data_start = evs.reader.pos();
+
+        // the duration is only known if the frames can be counted beforehand, frames of the
+        // channels are interleaved
+        let n_frames = if evs.reader.is_seekable() {
+            let frames = evs.count_frames()?;
+            evs.reader.seek(SeekFrom::Start(evs.data_start))?;
+            Some(frames / evs.channels.max(1) as u64 * evs.timestamp_interval)
+        } else {
+            None
+        };
 
         for cid in 0..evs.channels {
             let mut codec_params = CodecParameters::new();
@@ -112,6 +146,10 @@ impl FormatReader for EvsReader {
                     .with_sample_rate(sr)
                     .with_time_base(TimeBase::new(1, sr));
             }
+            codec_params.with_start_ts(0);
+            if let Some(n_frames) = n_frames {
+                codec_params.with_n_frames(n_frames);
+            }
 
             let param = Box::new(DecoderParams {
                 channel: NonZeroUsize::new(evs.channels)
@@ -172,15 +210,105 @@ impl FormatReader for EvsReader {
         &self.tracks
     }
 
-    fn seek(&mut self, mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
-        if self.tracks.is_empty() {
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        if self.tracks.is_empty() || !self.reader.is_seekable() {
             return seek_error(SeekErrorKind::Unseekable);
         }
 
-        unimplemented!()
+        let (track_id, required_ts) = match to {
+            SeekTo::TimeStamp { ts, track_id } => (track_id, ts),
+            SeekTo::Time { time, track_id } => {
+                let track_id = track_id.unwrap_or(0);
+                let Some(tb) = self
+                    .tracks
+                    .get(track_id as usize)
+                    .and_then(|t| t.codec_params.time_base)
+                else {
+                    return seek_error(SeekErrorKind::InvalidTrack);
+                };
+                (track_id, tb.calc_timestamp(time))
+            }
+        };
+        let Some(params) = self.tracks.get(track_id as usize).map(|t| &t.codec_params) else {
+            return seek_error(SeekErrorKind::InvalidTrack);
+        };
+        if params
+            .n_frames
+            .is_some_and(|n_frames| required_ts >= n_frames)
+        {
+            return seek_error(SeekErrorKind::OutOfRange);
+        }
+
+        // frames vary in size, the ones before the required frame are skipped one by one
+        let frame = required_ts / self.timestamp_interval;
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        for _ in 0..frame * self.channels as u64 {
+            self.skip_frame()?;
+        }
+        self.track_ts.iter_mut().for_each(|ts| *ts = frame);
+        self.chl_idx = 0;
+
+        Ok(SeekedTo {
+            track_id,
+            required_ts,
+            actual_ts: frame * self.timestamp_interval,
+        })
     }
 
     fn into_inner(self: Box<Self>) -> MediaSourceStream {
         self.reader
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::units::Time;
+
+    use super::*;
+
+    #[test]
+    fn test_duration_and_seek() {
+        // two channels of 13.2 kbps frames
+        let mut data = EVS_MIME_MAGIC.to_vec();
+        data.extend_from_slice(&2u32.to_be_bytes());
+        for _ in 0..20 {
+            data.push(0x04);
+            data.extend_from_slice(&[0; 33]);
+        }
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = EvsReader::try_new(mss, &Default::default()).unwrap();
+        for track in reader.tracks() {
+            assert_eq!(track.codec_params.start_ts, 0);
+            assert_eq!(track.codec_params.n_frames, Some(10 * 320));
+            assert_eq!(track.codec_params.time_base, Some(TimeBase::new(1, 16000)));
+        }
+
+        let mut ends = [0; 2];
+        while let Ok(pkt) = reader.next_packet() {
+            let end = &mut ends[pkt.track_id() as usize];
+            assert_eq!(pkt.ts(), *end);
+            *end += pkt.dur();
+        }
+        assert_eq!(ends, [10 * 320; 2]);
+
+        let seeked = reader
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::new(0, 0.1),
+                    track_id: Some(1),
+                },
+            )
+            .unwrap();
+        assert_eq!((seeked.track_id, seeked.actual_ts), (1, 1600));
+        let pkt = reader.next_packet().unwrap();
+        assert_eq!((pkt.track_id(), pkt.ts()), (0, 1600));
+        let to = SeekTo::TimeStamp {
+            ts: 3200,
+            track_id: 0,
+        };
+        assert!(reader.seek(SeekMode::Accurate, to).is_err());
+    }
+}
@@ -3,6 +3,7 @@ extern crate num_derive;
 
 use num_traits::FromPrimitive;
 
+pub mod conformance;
 mod consts;
 pub mod dec;
 pub mod format;
@@ -4,8 +4,7 @@ use symphonia_core::audio::{
     AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
 };
 use symphonia_core::codecs::{
-    decl_codec_type, CodecDescriptor, CodecParameters, CodecType, Decoder as D, DecoderOptions,
-    FinalizeResult,
+    CodecDescriptor, CodecParameters, Decoder as D, DecoderOptions, FinalizeResult,
 };
 use symphonia_core::errors::{Error, Result};
 use symphonia_core::formats::Packet;
@@ -19,16 +18,44 @@ const G722_1_BIT_RATE_24000: u32 = g722_1_bit_rates_t_G722_1_BIT_RATE_24000;
 const G722_1_BIT_RATE_32000: u32 = g722_1_bit_rates_t_G722_1_BIT_RATE_32000;
 const G722_1_BIT_RATE_48000: u32 = g722_1_bit_rates_t_G722_1_BIT_RATE_48000;
 
-pub const CODEC_TYPE_G722_1: CodecType = decl_codec_type(b"g7221");
+use voip_codecs::Validator;
+
+pub use voip_codecs::CODEC_TYPE_G722_1;
+
+/// Check a payload before it reaches libg7221, which decodes as many frames of `bit_rate` as
+/// the payload length announces: empty payloads, partial frames and unknown bit rates are
+/// rejected
+pub fn validate_payload(data: &[u8], bit_rate: u32) -> Result<()> {
+    if ![
+        G722_1_BIT_RATE_24000,
+        G722_1_BIT_RATE_32000,
+        G722_1_BIT_RATE_48000,
+    ]
+    .contains(&bit_rate)
+    {
+        return Err(Error::Unsupported("Unsupported G.722.1 bit rate"));
+    }
+    let frame_len = bit_rate as usize / 50 / 8;
+    if data.is_empty() || !data.len().is_multiple_of(frame_len) {
+        return Err(Error::DecodeError(
+            "G.722.1 payload is not a whole number of frames",
+        ));
+    }
+    Ok(())
+}
 
 pub struct Decoder {
     decoded_data: AudioBuffer<i16>,
     params: CodecParameters,
+    validator: Validator,
     st: g722_1_decode_state_t,
     sample_rate: u32,
     bit_per_sample: u32,
 }
 
+// SAFETY: libg7221 keeps everything in the `g722_1_decode_state_t` embedded in the decoder
+// and only reads global constant tables, so each instance is independent of the others. The
+// state is only handed to the library through `&mut self`.
 unsafe impl Send for Decoder {}
 unsafe impl Sync for Decoder {}
 
@@ -40,26 +67,51 @@ impl Decoder {
                 SignalSpec::new(G722_1_SAMPLE_RATE_16000, Channels::FRONT_CENTRE),
             ),
             params: CodecParameters::default(),
+            validator: Validator::default(),
             st: g722_1_decode_state_t::default(),
             sample_rate: G722_1_SAMPLE_RATE_16000,
             bit_per_sample: G722_1_BIT_RATE_24000,
         }
     }
 
-    pub fn decode(&mut self, data: &[u8]) {
-        unsafe {
-            let sample_cnt = g722_1_decode(
+    /// Size in bytes of one encoded 20 ms frame
+    fn frame_len(&self) -> usize {
+        self.bit_per_sample as usize / 50 / 8
+    }
+
+    /// Decode one or more whole frames into `decoded_data`, returns the number of samples
+    /// decoded
+    pub fn decode(&mut self, data: &[u8]) -> Result<usize> {
+        validate_payload(data, self.bit_per_sample)?;
+        let frame_len = self.frame_len();
+
+        // the library decodes every frame of the payload in one go
+        let samples = data.len() / frame_len * self.sample_rate as usize / 50;
+        if samples > self.decoded_data.capacity() {
+            self.decoded_data = AudioBuffer::new(samples as u64, *self.decoded_data.spec());
+        }
+        self.decoded_data.clear();
+        self.decoded_data.render_reserved(Some(samples));
+
+        let sample_cnt = unsafe {
+            g722_1_decode(
                 &mut self.st,
                 self.decoded_data.chan_mut(0).as_mut_ptr(),
                 data.as_ptr().cast_mut(),
                 data.len() as _,
-            );
+            )
+        };
+        if sample_cnt <= 0 || sample_cnt as usize > samples {
+            self.decoded_data.clear();
+            return Err(Error::DecodeError("Failed to decode G.722.1 frame"));
         }
+        self.decoded_data.truncate(sample_cnt as usize);
+        Ok(sample_cnt as usize)
     }
 }
 
 impl D for Decoder {
-    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self>
+    fn try_new(params: &CodecParameters, options: &DecoderOptions) -> Result<Self>
     where
         Self: Sized,
     {
@@ -87,6 +139,11 @@ impl D for Decoder {
             }
         };
         decoder.params = params.clone();
+        decoder.validator = Validator::new(options.verify);
+        decoder.decoded_data = AudioBuffer::new(
+            decoder.sample_rate as u64 / 50,
+            SignalSpec::new(decoder.sample_rate, Channels::FRONT_CENTRE),
+        );
         unsafe {
             let r = g722_1_decode_init(
                 &mut decoder.st,
@@ -123,20 +180,50 @@ impl D for Decoder {
     }
 
     fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef> {
-        self.decoded_data.clear();
-        self.decoded_data
-            .render_reserved(Some(self.sample_rate as usize / 50));
-
-        self.decode(&packet.data);
+        self.decode(&packet.data)?;
+        self.validator.update(self.decoded_data.chan(0));
 
         Ok(self.decoded_data.as_audio_buffer_ref())
     }
 
     fn finalize(&mut self) -> FinalizeResult {
-        Default::default()
+        self.validator.finalize(self.params.verification_check)
     }
 
     fn last_decoded(&self) -> AudioBufferRef {
         self.decoded_data.as_audio_buffer_ref()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_payload() {
+        // 24 kbit/s frames are 60 bytes, 32 kbit/s 80 and 48 kbit/s 120
+        assert!(validate_payload(&[0; 60], G722_1_BIT_RATE_24000).is_ok());
+        assert!(validate_payload(&[0; 80], G722_1_BIT_RATE_32000).is_ok());
+        assert!(validate_payload(&[0; 120], G722_1_BIT_RATE_48000).is_ok());
+        // several frames in one payload
+        assert!(validate_payload(&[0; 180], G722_1_BIT_RATE_24000).is_ok());
+        assert!(validate_payload(&[0; 240], G722_1_BIT_RATE_48000).is_ok());
+
+        assert!(matches!(
+            validate_payload(&[], G722_1_BIT_RATE_24000),
+            Err(Error::DecodeError(_))
+        ));
+        assert!(matches!(
+            validate_payload(&[0; 59], G722_1_BIT_RATE_24000),
+            Err(Error::DecodeError(_))
+        ));
+        assert!(matches!(
+            validate_payload(&[0; 100], G722_1_BIT_RATE_32000),
+            Err(Error::DecodeError(_))
+        ));
+        assert!(matches!(
+            validate_payload(&[0; 60], 16000),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+//! Decode every track of a rtpdump capture to a 16-bit mono WAV file of its own.
+//!
+//! ```sh
+//! cargo run -p symphonia-format-rtpdump --example decode_wav -- call.rtpdump out/
+//! ```
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use symphonia_format_rtpdump::{decode_capture, DecodedTrack};
+
+/// Write a WAV file of `track`
+fn write_wav(path: &Path, track: &DecodedTrack) -> std::io::Result<()> {
+    let data_len = track.pcm.len() as u32 * 2;
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // PCM, mono
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&track.sample_rate.to_le_bytes())?;
+    out.write_all(&(track.sample_rate * 2).to_le_bytes())?;
+    // block align and bits per sample
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for s in &track.pcm {
+        out.write_all(&s.to_le_bytes())?;
+    }
+    out.flush()
+}
+
+/// Decode `capture` into `dir`, one file per track named after its index, SSRC and codec.
+/// Returns the files written.
+fn decode_to_wavs(capture: &Path, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let tracks = decode_capture(&std::fs::read(capture)?)?;
+    std::fs::create_dir_all(dir)?;
+    let mut files = vec![];
+    for (idx, track) in tracks.iter().enumerate() {
+        let path = dir.join(format!("{idx}_{:08x}_{}.wav", track.ssrc, track.codec));
+        write_wav(&path, track)?;
+        println!(
+            "{}: {} Hz, {:.2} s",
+            path.display(),
+            track.sample_rate,
+            track.pcm.len() as f64 / track.sample_rate.max(1) as f64
+        );
+        files.push(path);
+    }
+    Ok(files)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(capture), Some(dir)) = (args.next(), args.next()) else {
+        eprintln!("usage: decode_wav <capture.rtpdump> <output directory>");
+        std::process::exit(2);
+    };
+    decode_to_wavs(Path::new(&capture), Path::new(&dir))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_to_wavs() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+        let dir = std::env::temp_dir().join(format!("decode_wav-{}", std::process::id()));
+        let files = decode_to_wavs(&assets.join("evs.rtpdump"), &dir).unwrap();
+        assert_eq!(files, [dir.join("0_00001060_evs.wav")]);
+
+        let wav = std::fs::read(&files[0]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[24..28], &16000u32.to_le_bytes());
+        // a second of audio
+        assert_eq!(wav.len(), 44 + 16000 * 2);
+    }
+}
@@ -0,0 +1,103 @@
+//! List the RTP streams of a rtpdump capture as JSON, with the codec detected for each and the
+//! track it is decoded as.
+//!
+//! ```sh
+//! cargo run -p symphonia-format-rtpdump --example list_streams -- call.rtpdump
+//! ```
+
+use std::fmt::Display;
+use std::fs::File;
+
+use symphonia_core::formats::FormatReader;
+use symphonia_core::io::MediaSourceStream;
+use symphonia_format_rtpdump::{DetectedStream, RtpdumpReader};
+
+/// `value` as a JSON string
+fn string(value: &str) -> String {
+    let mut json = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// `value` as JSON, `null` if `None`
+fn nullable<T>(value: Option<T>, to_json: impl Fn(T) -> String) -> String {
+    value.map_or("null".to_string(), to_json)
+}
+
+fn number(value: impl Display) -> String {
+    value.to_string()
+}
+
+/// A stream as a JSON object on one line
+fn stream_json(stream: &DetectedStream) -> String {
+    let fields = [
+        ("ssrc", number(stream.ssrc)),
+        ("generation", number(stream.generation)),
+        ("payloadType", number(stream.payload_type)),
+        ("firstOffset", number(stream.first_offset)),
+        (
+            "codec",
+            nullable(stream.codec.as_ref(), |c| string(&c.name)),
+        ),
+        ("clockRate", nullable(stream.clock_rate, number)),
+        ("trackId", nullable(stream.track_id, number)),
+        ("duplicateOf", nullable(stream.duplicate_of, number)),
+        ("mid", nullable(stream.mid.as_deref(), string)),
+    ];
+    let fields = fields
+        .iter()
+        .map(|(name, value)| format!("{}: {value}", string(name)))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// Every stream of `reader` as a JSON array, one stream per line
+fn streams_json(reader: &RtpdumpReader) -> String {
+    let streams = reader
+        .detection()
+        .streams
+        .iter()
+        .map(|stream| format!("  {}", stream_json(stream)))
+        .collect::<Vec<_>>();
+    format!("[\n{}\n]", streams.join(",\n"))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(capture) = std::env::args().nth(1) else {
+        eprintln!("usage: list_streams <capture.rtpdump>");
+        std::process::exit(2);
+    };
+    let mss = MediaSourceStream::new(Box::new(File::open(capture)?), Default::default());
+    let reader = RtpdumpReader::try_new(mss, &Default::default())?;
+    println!("{}", streams_json(&reader));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_streams_json() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets/amr.rtpdump");
+        let mss = MediaSourceStream::new(Box::new(File::open(path).unwrap()), Default::default());
+        let reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        assert_eq!(
+            streams_json(&reader),
+            "[\n  {\"ssrc\": 4193, \"generation\": 0, \"payloadType\": 97, \"firstOffset\": 0, \
+             \"codec\": \"amr\", \"clockRate\": 8000, \"trackId\": 0, \"duplicateOf\": null, \
+             \"mid\": null}\n]"
+        );
+        assert_eq!(string("a\"b\\\n"), "\"a\\\"b\\\\\\u000a\"");
+    }
+}
@@ -0,0 +1,91 @@
+//! Decode the RTP streams sent to a UDP port as the packets arrive, writing the audio of the
+//! first stream to stdout as 16-bit little endian PCM. Receiving stops once no packet arrived
+//! for a second.
+//!
+//! ```sh
+//! cargo run -p symphonia-format-rtpdump --example live_udp -- 127.0.0.1:5004 > call.raw
+//! ```
+
+use std::io::{ErrorKind, Write};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use symphonia_format_rtpdump::{DecodedFrame, LiveDecoder};
+
+/// Feed the packets received on `socket` to a [`LiveDecoder`] until none arrived for `idle`,
+/// then flush it. `on_frame` receives the decoded audio of every stream.
+fn receive(
+    socket: &UdpSocket,
+    idle: Duration,
+    mut on_frame: impl FnMut(DecodedFrame),
+) -> std::io::Result<()> {
+    socket.set_read_timeout(Some(idle))?;
+    let mut decoder = LiveDecoder::new();
+    let start = Instant::now();
+    let mut buf = [0; 65536];
+    loop {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e),
+        };
+        let arrival = start.elapsed().as_millis() as u32;
+        decoder.push(&buf[..len], arrival, &mut on_frame);
+    }
+    decoder.flush(on_frame);
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(addr) = std::env::args().nth(1) else {
+        eprintln!("usage: live_udp <address:port>");
+        std::process::exit(2);
+    };
+    let socket = UdpSocket::bind(addr)?;
+    let mut stdout = std::io::stdout().lock();
+    let mut first = None;
+    let mut result = Ok(());
+    receive(&socket, Duration::from_secs(1), |frame| {
+        if *first.get_or_insert(frame.ssrc) != frame.ssrc || result.is_err() {
+            return;
+        }
+        let bytes = frame.pcm.iter().flat_map(|s| s.to_le_bytes());
+        result = stdout.write_all(&bytes.collect::<Vec<_>>());
+    })?;
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use symphonia_format_rtpdump::RtpdumpPackets;
+
+    use super::*;
+
+    #[test]
+    fn test_receive() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let sender = std::thread::spawn(move || {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets/amrwb.rtpdump");
+            let capture = std::fs::read(path).unwrap();
+            let out = UdpSocket::bind("127.0.0.1:0").unwrap();
+            for (_, data) in RtpdumpPackets::new(&capture).unwrap() {
+                out.send_to(data, addr).unwrap();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        let mut samples: HashMap<u32, (u32, usize)> = HashMap::new();
+        receive(&socket, Duration::from_millis(500), |frame| {
+            let entry = samples.entry(frame.ssrc).or_insert((frame.sample_rate, 0));
+            entry.1 += frame.pcm.len();
+        })
+        .unwrap();
+        sender.join().unwrap();
+        // a second of AMR-WB
+        assert_eq!(samples, HashMap::from([(0x1062, (16000, 16000))]));
+    }
+}
@@ -0,0 +1,108 @@
+//! Register the AMR formats and decoders with symphonia, then play an .amr or .awb file by
+//! writing its audio to stdout as 16-bit little endian PCM.
+//!
+//! ```sh
+//! cargo run -p symphonia-format-rtpdump --example play_amr -- speech.amr | aplay -f S16_LE -r 8000
+//! ```
+
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use symphonia_core::audio::SampleBuffer;
+use symphonia_core::codecs::CodecRegistry;
+use symphonia_core::errors::Error;
+use symphonia_core::io::{MediaSource, MediaSourceStream};
+use symphonia_core::probe::{Hint, Probe};
+
+/// Decode `source` with the formats and codecs of the AMR bundle, writing its audio to `out`.
+/// Returns the sample rate and the samples written.
+fn play(
+    source: Box<dyn MediaSource>,
+    hint: &Hint,
+    out: &mut impl Write,
+) -> Result<(u32, usize), Box<dyn std::error::Error>> {
+    let mut probe = Probe::default();
+    probe.register_all::<symphonia_bundle_amr::AmrReader>();
+    probe.register_all::<symphonia_bundle_amr::AmrwbReader>();
+    let mut codecs = CodecRegistry::new();
+    codecs.register_all::<symphonia_bundle_amr::AmrDecoder>();
+    codecs.register_all::<symphonia_bundle_amr::AmrwbDecoder>();
+
+    let mss = MediaSourceStream::new(source, Default::default());
+    let mut reader = probe
+        .format(hint, mss, &Default::default(), &Default::default())?
+        .format;
+    let track = reader.default_track().ok_or("no audio track")?;
+    let (track_id, sample_rate) = (track.id, track.codec_params.sample_rate.unwrap_or(8000));
+    let mut decoder = codecs.make(&track.codec_params, &Default::default())?;
+
+    let mut samples = 0;
+    let mut buf: Option<SampleBuffer<i16>> = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a damaged frame is skipped like a player would
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let buf = buf
+            .get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        buf.copy_interleaved_ref(decoded);
+        let bytes = buf.samples().iter().flat_map(|s| s.to_le_bytes());
+        out.write_all(&bytes.collect::<Vec<_>>())?;
+        samples += buf.samples().len();
+    }
+    Ok((sample_rate, samples))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: play_amr <file.amr>");
+        std::process::exit(2);
+    };
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(&path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let (sample_rate, samples) = play(
+        Box::new(File::open(&path)?),
+        &hint,
+        &mut std::io::stdout().lock(),
+    )?;
+    eprintln!(
+        "{path}: {:.2} s at {sample_rate} Hz",
+        samples as f64 / sample_rate as f64
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_play() {
+        // a second of AMR 12.2 kbps frames: F0 FT7 Q1 then 31 bytes of speech bits
+        let mut amr = b"#!AMR\n".to_vec();
+        for i in 0..50u8 {
+            amr.push(0x3c);
+            amr.extend((0..31).map(|j| i.wrapping_mul(31).wrapping_add(j)));
+        }
+        let mut pcm = vec![];
+        let (sample_rate, samples) =
+            play(Box::new(Cursor::new(amr)), &Hint::new(), &mut pcm).unwrap();
+        assert_eq!((sample_rate, samples), (8000, 8000));
+        assert_eq!(pcm.len(), 8000 * 2);
+    }
+}
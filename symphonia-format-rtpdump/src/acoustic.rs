@@ -0,0 +1,245 @@
+use std::f64::consts::PI;
+use std::fmt::{self, Display, Write};
+
+/// Sample rate audio is brought to before fingerprinting
+const RATE: u32 = 8000;
+/// Samples of the frames whose spectra are compared, and between the starts of two frames
+/// (128 ms)
+const FRAME: usize = 2048;
+const HOP: usize = 1024;
+/// Log-spaced frequency bands, neighbours are compared to make the 32 bits of a frame
+const BANDS: usize = 33;
+const LOW_HZ: f64 = 300.0;
+const HIGH_HZ: f64 = 3000.0;
+/// Sub-fingerprints two fingerprints must overlap by to be compared
+const MIN_OVERLAP: usize = 8;
+
+/// Fingerprint of what decoded audio sounds like, to find the same audio in other captures.
+///
+/// Like Chromaprint, it follows Haitsma and Kalker, "A Highly Robust Audio Fingerprinting
+/// System" (2002): every 128 ms, a 32-bit sub-fingerprint tells for every pair of neighbour
+/// frequency bands whether their energy difference grew since the previous frame. Unlike a
+/// [`StreamFingerprint`](crate::StreamFingerprint), it survives transcoding, packet loss and
+/// another sample rate, see [`AudioFingerprint::similarity`]. Printed in hexadecimal.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AudioFingerprint(pub Vec<u32>);
+
+impl AudioFingerprint {
+    /// Fingerprint of mono `pcm` sampled at `sample_rate`, empty for less than 256 ms
+    pub fn new(pcm: &[i16], sample_rate: u32) -> Self {
+        let audio = resample(pcm, sample_rate);
+        let window = (0..FRAME)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / FRAME as f64).cos())
+            .collect::<Vec<_>>();
+        let edges = (0..=BANDS)
+            .map(|b| {
+                let hz = LOW_HZ * (HIGH_HZ / LOW_HZ).powf(b as f64 / BANDS as f64);
+                (hz * FRAME as f64 / RATE as f64).round() as usize
+            })
+            .collect::<Vec<_>>();
+
+        let mut prev: Option<Vec<f64>> = None;
+        let mut subs = vec![];
+        for start in (0..audio.len().saturating_sub(FRAME - 1)).step_by(HOP) {
+            let mut re = audio[start..start + FRAME]
+                .iter()
+                .zip(&window)
+                .map(|(s, w)| s * w)
+                .collect::<Vec<_>>();
+            let mut im = vec![0.0; FRAME];
+            fft(&mut re, &mut im);
+            let energies = edges
+                .windows(2)
+                .map(|e| (e[0]..e[1]).map(|k| re[k] * re[k] + im[k] * im[k]).sum())
+                .collect::<Vec<f64>>();
+            if let Some(prev) = &prev {
+                let mut sub = 0;
+                for band in 0..BANDS - 1 {
+                    let diff = energies[band] - energies[band + 1];
+                    let prev_diff = prev[band] - prev[band + 1];
+                    sub = sub << 1 | (diff > prev_diff) as u32;
+                }
+                subs.push(sub);
+            }
+            prev = Some(energies);
+        }
+        Self(subs)
+    }
+
+    /// Parse a fingerprint printed in hexadecimal
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if !hex.len().is_multiple_of(8) || !hex.is_ascii() {
+            return None;
+        }
+        let subs = (0..hex.len())
+            .step_by(8)
+            .map(|i| u32::from_str_radix(&hex[i..i + 8], 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self(subs))
+    }
+
+    /// Share of bits the fingerprints agree on where they line up best, trying shifts of up to
+    /// half the shorter one. About 0.5 for unrelated audio, above 0.8 for the same audio. 0 if
+    /// they can't overlap by 1 s.
+    pub fn similarity(&self, other: &Self) -> f32 {
+        let (a, b) = (&self.0, &other.0);
+        let max_shift = (a.len().min(b.len()) / 2) as isize;
+        let mut best = 0.0;
+        for shift in -max_shift..=max_shift {
+            // a[i] lines up with b[i + shift]
+            let pairs = a
+                .iter()
+                .enumerate()
+                .filter_map(|(i, x)| Some((x, b.get(usize::try_from(i as isize + shift).ok()?)?)));
+            let (n, diff) = pairs.fold((0, 0), |(n, diff), (x, y)| {
+                (n + 1, diff + (x ^ y).count_ones())
+            });
+            if n < MIN_OVERLAP {
+                continue;
+            }
+            let agree = 1.0 - diff as f32 / (n * 32) as f32;
+            if agree > best {
+                best = agree;
+            }
+        }
+        best
+    }
+}
+
+impl Display for AudioFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut hex = String::with_capacity(self.0.len() * 8);
+        for sub in &self.0 {
+            let _ = write!(hex, "{sub:08x}");
+        }
+        f.write_str(&hex)
+    }
+}
+
+/// `pcm` at [`RATE`], every output sample averaging the input samples it spans
+fn resample(pcm: &[i16], sample_rate: u32) -> Vec<f64> {
+    let n = (pcm.len() as u64 * RATE as u64 / sample_rate.max(1) as u64) as usize;
+    (0..n)
+        .map(|i| {
+            let from = i as u64 * sample_rate as u64 / RATE as u64;
+            let to = ((i as u64 + 1) * sample_rate as u64 / RATE as u64).max(from + 1);
+            let span = &pcm[from as usize..(to as usize).min(pcm.len())];
+            span.iter().map(|s| *s as f64).sum::<f64>() / span.len().max(1) as f64
+        })
+        .collect()
+}
+
+/// In place radix-2 FFT, the length must be a power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    /// Gliding harmonics with a syllable-like envelope, `seconds` long at `sample_rate`
+    fn speech_like(seconds: u32, sample_rate: u32, base_hz: f64) -> Vec<i16> {
+        let mut phase = [0.0; 12];
+        (0..seconds * sample_rate)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                let envelope = (PI * t * 3.0).sin().abs();
+                let mut s = 0.0;
+                for (k, phase) in phase.iter_mut().enumerate() {
+                    let hz =
+                        base_hz * (k + 1) as f64 * (1.0 + 0.3 * (t * 0.7 * (k + 1) as f64).sin());
+                    *phase += 2.0 * PI * hz / sample_rate as f64;
+                    s += phase.sin() / (k + 1) as f64;
+                }
+                (4000.0 * envelope * s) as i16
+            })
+            .collect()
+    }
+
+    fn with_noise(pcm: &[i16], level: i32) -> Vec<i16> {
+        let mut rng = Lcg(7);
+        pcm.iter()
+            .map(|s| s.saturating_add((rng.sample() as i32 * level / 32768) as i16))
+            .collect()
+    }
+
+    #[test]
+    fn test_fft() {
+        let mut re = (0..16)
+            .map(|i| (2.0 * PI * 3.0 * i as f64 / 16.0).cos())
+            .collect::<Vec<_>>();
+        let mut im = vec![0.0; 16];
+        fft(&mut re, &mut im);
+        for k in 0..16 {
+            let expected = if k == 3 || k == 13 { 8.0 } else { 0.0 };
+            assert!(
+                (re[k] - expected).abs() < 1e-9 && im[k].abs() < 1e-9,
+                "bin {k}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        let speech = speech_like(10, 16000, 220.0);
+        let fp = AudioFingerprint::new(&speech, 16000);
+        // 10 s of 128 ms frames, the first one has no sub-fingerprint
+        assert_eq!(fp.0.len(), (10 * RATE as usize - FRAME) / HOP);
+        assert_eq!(fp.similarity(&fp), 1.0);
+
+        let noisy = AudioFingerprint::new(&with_noise(&speech, 300), 16000);
+        assert!(fp.similarity(&noisy) > 0.8);
+        let narrowband = AudioFingerprint::new(&speech_like(10, 8000, 220.0), 8000);
+        assert!(fp.similarity(&narrowband) > 0.8);
+        // the same audio starting 8 sub-fingerprints later
+        let mut delayed = vec![0; 16000 * HOP / 1000];
+        delayed.extend_from_slice(&speech);
+        assert!(fp.similarity(&AudioFingerprint::new(&delayed, 16000)) > 0.8);
+
+        let other = AudioFingerprint::new(&speech_like(10, 16000, 310.0), 16000);
+        assert!(fp.similarity(&other) < 0.65);
+        assert_eq!(fp.similarity(&AudioFingerprint::default()), 0.0);
+        assert!(AudioFingerprint::new(&speech[..4000], 16000).0.is_empty());
+    }
+
+    #[test]
+    fn test_hex() {
+        let fp = AudioFingerprint(vec![0xdeadbeef, 0x1]);
+        assert_eq!(fp.to_string(), "deadbeef00000001");
+        assert_eq!(AudioFingerprint::from_hex(&fp.to_string()), Some(fp));
+        assert_eq!(AudioFingerprint::from_hex("deadbee"), None);
+        assert_eq!(AudioFingerprint::from_hex("deadbeeg"), None);
+    }
+}
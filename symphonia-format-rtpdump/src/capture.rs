@@ -0,0 +1,336 @@
+use std::io::{Cursor, ErrorKind};
+
+use symphonia_core::audio::SampleBuffer;
+use symphonia_core::codecs::CodecRegistry;
+use symphonia_core::errors::{Error, Result};
+use symphonia_core::formats::FormatReader;
+use symphonia_core::io::MediaSourceStream;
+
+use crate::{
+    BudgetedDecoder, CodecFrame, DecodeBudget, DecodeIssue, DecodeStats, EffectChain, FrameStats,
+    RtpdumpReader,
+};
+
+/// Audio of one track of a capture decoded by [`decode_capture`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodedTrack {
+    pub ssrc: u32,
+    /// Short name of the codec, e.g. "evs"
+    pub codec: &'static str,
+    pub sample_rate: u32,
+    /// Position of the first sample of `pcm` on the capture timeline, in samples at
+    /// `sample_rate`
+    pub start_ts: u64,
+    /// Decoded mono audio
+    pub pcm: Vec<i16>,
+    /// Packets dropped as malformed, their frames were concealed
+    pub issues: Vec<DecodeIssue>,
+    pub stats: DecodeStats,
+    pub frame_stats: FrameStats,
+    /// Decoding was given up for going over its [`DecodeBudget`] too often, `pcm` stops there
+    pub degraded: bool,
+}
+
+/// Frames of one track of a capture exported by [`export_frames`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportedTrack {
+    pub ssrc: u32,
+    /// Short name of the codec, e.g. "evs"
+    pub codec: &'static str,
+    pub frames: Vec<CodecFrame>,
+}
+
+/// Decoders of every codec the rtpdump reader creates tracks for
+pub(crate) fn codec_registry() -> CodecRegistry {
+    let mut registry = CodecRegistry::new();
+    registry.register_all::<symphonia_bundle_evs::dec::Decoder>();
+    registry.register_all::<symphonia_bundle_amr::AmrDecoder>();
+    registry.register_all::<symphonia_bundle_amr::AmrwbDecoder>();
+    registry.register_all::<symphonia_codec_g7221::Decoder>();
+    registry
+}
+
+/// Decode every track of a rtpdump capture held in memory.
+///
+/// Packets the decoders reject are skipped, like a player would, so the result only fails if
+/// the capture itself can't be read.
+pub fn decode_capture(bytes: &[u8]) -> Result<Vec<DecodedTrack>> {
+    decode_capture_with_budget(bytes, DecodeBudget::default())
+}
+
+/// Frames of a run of stand-in frames decoded before the rest is rendered as silence, letting
+/// the decoder conceal the loss and fade out
+pub const SILENCE_HANGOVER: usize = 8;
+
+/// How [`decode_capture_with_options`] decodes a capture
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CaptureDecodeOptions {
+    /// Tracks whose decoder goes over the budget are given up
+    pub budget: DecodeBudget,
+    /// Past the first [`SILENCE_HANGOVER`] frames of a run of stand-in frames (lost or not
+    /// sent, e.g. DTX), silence is rendered this many frames at a time without decoding them.
+    /// 0 decodes every frame.
+    pub silence_chunk: usize,
+    /// Post-processing of the audio of every track
+    pub effects: EffectChain,
+}
+
+/// Decode every track of a rtpdump capture held in memory like [`decode_capture`], giving up
+/// the tracks whose decoder goes over `budget`
+pub fn decode_capture_with_budget(bytes: &[u8], budget: DecodeBudget) -> Result<Vec<DecodedTrack>> {
+    let options = CaptureDecodeOptions {
+        budget,
+        ..Default::default()
+    };
+    decode_capture_with_options(bytes, &options)
+}
+
+/// Consecutive stand-in frames of a track, and the silence not rendered yet
+#[derive(Clone, Copy, Debug, Default)]
+struct SilenceRun {
+    frames: usize,
+    pending_frames: usize,
+    pending_samples: u64,
+}
+
+impl SilenceRun {
+    fn render(&mut self, pcm: &mut Vec<i16>) {
+        pcm.resize(pcm.len() + self.pending_samples as usize, 0);
+        self.pending_frames = 0;
+        self.pending_samples = 0;
+    }
+}
+
+/// Decode every track of a rtpdump capture held in memory like [`decode_capture`], with
+/// `options`
+pub fn decode_capture_with_options(
+    bytes: &[u8],
+    options: &CaptureDecodeOptions,
+) -> Result<Vec<DecodedTrack>> {
+    let codecs = codec_registry();
+    let mss = MediaSourceStream::new(Box::new(Cursor::new(bytes.to_vec())), Default::default());
+    let mut reader = RtpdumpReader::try_new(mss, &Default::default())?;
+    // the AMR and EVS decoders tell bad and SID frames from the metadata
+    reader.set_frame_meta(true);
+
+    let mut decoders = vec![];
+    let mut tracks = vec![];
+    for track in reader.tracks() {
+        let params = &track.codec_params;
+        decoders.push(BudgetedDecoder::new(
+            codecs.make(params, &Default::default())?,
+            options.budget,
+        ));
+        tracks.push(DecodedTrack {
+            ssrc: reader.track_session(track.id).map_or(0, |(ssrc, _)| ssrc),
+            codec: codecs
+                .get_codec(params.codec)
+                .map_or("", |desc| desc.short_name),
+            sample_rate: params.sample_rate.unwrap_or_default(),
+            start_ts: params.start_ts,
+            pcm: vec![],
+            issues: vec![],
+            stats: DecodeStats::default(),
+            frame_stats: FrameStats::default(),
+            degraded: false,
+        });
+    }
+    let missing_frames = (0..tracks.len() as u32)
+        .map(|id| reader.missing_frame(id).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let mut silences = vec![SilenceRun::default(); tracks.len()];
+
+    let mut sample_buf: Option<SampleBuffer<i16>> = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let idx = packet.track_id() as usize;
+        if decoders[idx].is_degraded() {
+            continue;
+        }
+        let silence = &mut silences[idx];
+        if options.silence_chunk > 0 && packet.data == missing_frames[idx] {
+            silence.frames += 1;
+            if silence.frames > SILENCE_HANGOVER {
+                silence.pending_frames += 1;
+                silence.pending_samples += packet.dur;
+                if silence.pending_frames >= options.silence_chunk {
+                    silence.render(&mut tracks[idx].pcm);
+                }
+                continue;
+            }
+        } else {
+            silence.render(&mut tracks[idx].pcm);
+            silence.frames = 0;
+        }
+        let decoded = match decoders[idx].decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(_)) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let frames = decoded.capacity() as u64;
+        let buf = match sample_buf.as_mut() {
+            Some(buf) if buf.capacity() >= decoded.capacity() => buf,
+            _ => sample_buf.insert(SampleBuffer::new(frames, *decoded.spec())),
+        };
+        buf.copy_interleaved_ref(decoded);
+        tracks[idx].pcm.extend_from_slice(buf.samples());
+    }
+
+    for (silence, track) in silences.iter_mut().zip(&mut tracks) {
+        silence.render(&mut track.pcm);
+        if !options.effects.is_empty() {
+            track.pcm = options.effects.apply(&track.pcm, track.sample_rate);
+        }
+    }
+    for issue in reader.take_issues() {
+        tracks[issue.track_id as usize].issues.push(issue);
+    }
+    for (id, (track, decoder)) in tracks.iter_mut().zip(&decoders).enumerate() {
+        track.stats = decoder.stats();
+        track.frame_stats = reader.frame_stats(id as u32).cloned().unwrap_or_default();
+        track.degraded = decoder.is_degraded();
+    }
+    Ok(tracks)
+}
+
+/// Depacketize every track of a rtpdump capture held in memory without decoding it, to
+/// replay the exact frames its decoder gets into another decoder.
+pub fn export_frames(bytes: &[u8]) -> Result<Vec<ExportedTrack>> {
+    let codecs = codec_registry();
+    let mss = MediaSourceStream::new(Box::new(Cursor::new(bytes.to_vec())), Default::default());
+    let mut reader = RtpdumpReader::try_new(mss, &Default::default())?;
+    reader.record_frames();
+    loop {
+        match reader.next_packet() {
+            Ok(_) => (),
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    let ids = reader.tracks().iter().map(|t| t.id).collect::<Vec<_>>();
+    let mut tracks = vec![];
+    for id in ids {
+        let codec = reader.tracks()[id as usize].codec_params.codec;
+        tracks.push(ExportedTrack {
+            ssrc: reader.track_session(id).map_or(0, |(ssrc, _)| ssrc),
+            codec: codecs.get_codec(codec).map_or("", |desc| desc.short_name),
+            frames: reader.take_frames(id),
+        });
+    }
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{evs_capture, evs_packets, rtpdump};
+
+    #[test]
+    fn test_decode_capture() {
+        let tracks = decode_capture(&evs_capture(100, 0)).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].ssrc, 0x1234);
+        assert_eq!(tracks[0].codec, "evs");
+        assert_eq!(tracks[0].pcm.len(), 100 * 320);
+        assert_eq!(tracks[0].stats.packets, 100);
+        assert!(!tracks[0].degraded);
+
+        assert!(decode_capture(b"not a capture").is_err());
+        // header only
+        assert!(matches!(
+            decode_capture(&evs_capture(0, 0)),
+            Err(Error::Unsupported(crate::NO_RTP_STREAM))
+        ));
+    }
+
+    #[test]
+    fn test_export_frames() {
+        let tracks = export_frames(&evs_capture(10, 40)).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].ssrc, 0x1234);
+        assert_eq!(tracks[0].codec, "evs");
+        let frames = &tracks[0].frames;
+        assert_eq!(frames.len(), 10);
+        assert_eq!(
+            (frames[3].seq, frames[3].ts, frames[3].offset),
+            (3, 1000 + 3 * 320, 40 + 3 * 20)
+        );
+        // 13.2 kbps storage format frames
+        assert!(frames.iter().all(|f| f.frame_type == Some(4)));
+        assert!(frames
+            .iter()
+            .all(|f| f.data.len() == 34 && f.data[0] == 0x04));
+    }
+
+    #[test]
+    fn test_frame_stats() {
+        let tracks = decode_capture(&evs_capture(100, 0)).unwrap();
+        let stats = &tracks[0].frame_stats;
+        assert_eq!(stats.frame_types.get(&4), Some(&100));
+        assert_eq!(stats.bitrate, [13200, 13200]);
+        assert_eq!(stats.modes.len(), 1);
+        assert_eq!(stats.modes[0].bitrate, Some(13200));
+    }
+
+    #[test]
+    fn test_decode_issues() {
+        // the payload of the 6th packet is cut to 3 bytes, too short for any EVS frame
+        let mut pkts = evs_packets(10, 0);
+        pkts[5].1.truncate(15);
+        pkts[5].1[12..].fill(0xff);
+        let data = rtpdump(pkts);
+
+        let tracks = decode_capture(&data).unwrap();
+        assert_eq!(
+            tracks[0].issues,
+            vec![DecodeIssue {
+                track_id: 0,
+                seq: 5,
+                rtp_ts: Some(1000 + 5 * 320),
+                offset: 100,
+                reason: "malformed stream: Invalid EVS RTP payload".to_string(),
+            }]
+        );
+        // the dropped frame is concealed
+        assert_eq!(tracks[0].pcm.len(), 10 * 320);
+    }
+
+    #[test]
+    fn test_silence_chunk() {
+        let mut pkts = evs_packets(200, 0);
+        pkts.drain(20..180);
+        let data = rtpdump(pkts);
+        let every = &decode_capture(&data).unwrap()[0];
+        let options = CaptureDecodeOptions {
+            silence_chunk: 16,
+            ..Default::default()
+        };
+        let bulk = &decode_capture_with_options(&data, &options).unwrap()[0];
+        assert_eq!(bulk.pcm.len(), every.pcm.len());
+
+        // the decoder conceals the first lost frames, the rest of the gap is silent
+        let concealed = (20 + SILENCE_HANGOVER) * 320;
+        assert_eq!(bulk.pcm[..concealed], every.pcm[..concealed]);
+        assert!(bulk.pcm[concealed..180 * 320].iter().all(|s| *s == 0));
+        let skipped = (160 - SILENCE_HANGOVER) as u64;
+        assert_eq!(bulk.stats.packets, every.stats.packets - skipped);
+    }
+
+    #[test]
+    fn test_effects() {
+        let data = evs_capture(100, 0);
+        let options = CaptureDecodeOptions {
+            effects: EffectChain::parse("highpass, speed=2").unwrap(),
+            ..Default::default()
+        };
+        let tracks = decode_capture_with_options(&data, &options).unwrap();
+        // half the 2 s
+        assert_eq!(tracks[0].pcm.len(), 16000);
+    }
+}
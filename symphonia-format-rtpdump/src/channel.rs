@@ -0,0 +1,286 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use codec_detector::rtp::SeqNum;
+
+/// A RTP packet buffered by a [`Channel`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelPacket {
+    pub seq: SeqNum,
+    /// Milliseconds since the start of recording
+    pub offset: u32,
+    /// The whole RTP packet
+    pub data: Bytes,
+}
+
+/// What a full [`Channel`] does with a new packet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued packet to make room
+    #[default]
+    DropOldest,
+    /// Refuse the new packet, for the caller to report an error
+    Error,
+}
+
+/// Reorder buffer of a single RTP stream.
+///
+/// Packets are kept sorted by sequence number (wrap-aware), and only leave the buffer once
+/// more than `depth` packets are queued, giving late packets a chance to be put back in
+/// order. Packets a caller doesn't take out pile up unbounded, unless a limit is set with
+/// [`Channel::set_limit`].
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pkts: VecDeque<ChannelPacket>,
+    depth: usize,
+    /// Sequence number of the last packet released
+    last_seq: Option<SeqNum>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    overflows: u64,
+}
+
+impl Channel {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            pkts: VecDeque::with_capacity(depth + 1),
+            depth,
+            last_seq: None,
+            capacity: usize::MAX,
+            policy: OverflowPolicy::default(),
+            overflows: 0,
+        }
+    }
+
+    /// Hold at most `capacity` packets, what happens to the packets over it depends on
+    /// `policy`
+    pub fn set_limit(&mut self, capacity: usize, policy: OverflowPolicy) {
+        self.capacity = capacity.max(1);
+        self.policy = policy;
+    }
+
+    /// Packets dropped or refused for the channel being full so far
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    /// Index of the first queued packet with a sequence number greater than `seq`
+    fn find_first_greater_seq_pkt(&self, seq: SeqNum) -> Option<usize> {
+        self.pkts
+            .iter()
+            .position(|p| p.seq.serial_cmp(&seq) == Ordering::Greater)
+    }
+
+    /// Queue a packet, returns false if it is a duplicate, arrived after later packets were
+    /// already released, or was refused by a full channel. A full channel dropping the oldest
+    /// packet releases it unseen.
+    pub fn add_pkt(&mut self, pkt: ChannelPacket) -> bool {
+        if self.released(pkt.seq) {
+            return false;
+        }
+
+        let pos = self
+            .find_first_greater_seq_pkt(pkt.seq)
+            .unwrap_or(self.pkts.len());
+        if pos > 0 && self.pkts[pos - 1].seq == pkt.seq {
+            return false;
+        }
+        if self.pkts.len() >= self.capacity {
+            self.overflows += 1;
+            match self.policy {
+                OverflowPolicy::Error => return false,
+                OverflowPolicy::DropOldest if pos == 0 => {
+                    // older than every queued packet, it would be dropped right away
+                    self.last_seq = Some(pkt.seq);
+                    return false;
+                }
+                OverflowPolicy::DropOldest => {
+                    self.drain_pkt();
+                    self.pkts.insert(pos - 1, pkt);
+                    return true;
+                }
+            }
+        }
+        self.pkts.insert(pos, pkt);
+        true
+    }
+
+    /// Whether a packet with sequence number `seq` is queued
+    pub fn contains(&self, seq: SeqNum) -> bool {
+        self.pkts.iter().any(|p| p.seq == seq)
+    }
+
+    /// Whether a packet with sequence number `seq` would arrive too late, after later packets
+    /// were released
+    pub fn released(&self, seq: SeqNum) -> bool {
+        self.last_seq
+            .is_some_and(|last| seq.serial_cmp(&last) != Ordering::Greater)
+    }
+
+    /// Release the oldest packet once the reorder buffer is full
+    pub fn pop_pkt(&mut self) -> Option<ChannelPacket> {
+        if self.pkts.len() <= self.depth {
+            return None;
+        }
+        self.drain_pkt()
+    }
+
+    /// Release the oldest packet regardless of the reorder depth, used at end of stream
+    pub fn drain_pkt(&mut self) -> Option<ChannelPacket> {
+        let pkt = self.pkts.pop_front()?;
+        self.last_seq = Some(pkt.seq);
+        Some(pkt)
+    }
+
+    /// Packets released by [`Self::pop_pkt`], until the reorder buffer is no longer full
+    pub fn ready(&mut self) -> impl Iterator<Item = ChannelPacket> + '_ {
+        std::iter::from_fn(|| self.pop_pkt())
+    }
+
+    /// Every queued packet in order, released by [`Self::drain_pkt`]
+    pub fn drain(&mut self) -> impl Iterator<Item = ChannelPacket> + '_ {
+        std::iter::from_fn(|| self.drain_pkt())
+    }
+
+    /// Forget every queued packet and the last sequence number released, e.g. after a seek
+    pub fn clear(&mut self) {
+        self.pkts.clear();
+        self.last_seq = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.pkts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pkts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::errors::Error;
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::test_util::evs_capture;
+    use crate::RtpdumpReader;
+
+    fn pkt(seq: u16) -> ChannelPacket {
+        ChannelPacket {
+            seq: SeqNum(seq),
+            ..Default::default()
+        }
+    }
+
+    fn collect(chl: &mut Channel, seqs: &[u16]) -> Vec<u16> {
+        let mut out = vec![];
+        for seq in seqs {
+            chl.add_pkt(pkt(*seq));
+            out.extend(chl.ready().map(|p| p.seq.0));
+        }
+        out.extend(chl.drain().map(|p| p.seq.0));
+        assert!(chl.is_empty());
+        out
+    }
+
+    #[test]
+    fn test_reorder_across_wrap() {
+        let sorted = (65526..=65535).chain(0..10).collect::<Vec<u16>>();
+
+        // shuffle every window of 4 packets with a fixed permutation
+        let mut shuffled = vec![];
+        for chunk in sorted.chunks(4) {
+            for i in [2, 0, 3, 1] {
+                if let Some(seq) = chunk.get(i) {
+                    shuffled.push(*seq);
+                }
+            }
+        }
+        assert_ne!(shuffled, sorted);
+
+        let mut chl = Channel::new(4);
+        assert_eq!(collect(&mut chl, &shuffled), sorted);
+
+        let mut reversed = sorted.clone();
+        reversed.reverse();
+        let mut chl = Channel::new(sorted.len());
+        assert_eq!(collect(&mut chl, &reversed), sorted);
+    }
+
+    #[test]
+    fn test_duplicate_and_late_packets() {
+        let mut chl = Channel::new(1);
+        assert!(chl.add_pkt(pkt(65535)));
+        assert!(!chl.add_pkt(pkt(65535)));
+        assert!(chl.add_pkt(pkt(0)));
+        assert_eq!(chl.pop_pkt().map(|p| p.seq), Some(SeqNum(65535)));
+        assert!(chl.pop_pkt().is_none());
+        // already released
+        assert!(!chl.add_pkt(pkt(65534)));
+        assert!(!chl.add_pkt(pkt(65535)));
+        assert!(chl.add_pkt(pkt(1)));
+        assert_eq!(chl.len(), 2);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut chl = Channel::new(8);
+        chl.set_limit(3, OverflowPolicy::DropOldest);
+        for seq in [2, 4, 5] {
+            assert!(chl.add_pkt(pkt(seq)));
+        }
+        // older than every queued packet, dropped right away
+        assert!(!chl.add_pkt(pkt(1)));
+        assert!(chl.released(SeqNum(1)));
+        // 2 makes room for 3
+        assert!(chl.add_pkt(pkt(3)));
+        assert!(chl.released(SeqNum(2)));
+        assert_eq!(chl.overflows(), 2);
+        assert_eq!(
+            chl.drain().map(|p| p.seq.0).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+
+        let mut chl = Channel::new(8);
+        chl.set_limit(2, OverflowPolicy::Error);
+        assert!(chl.add_pkt(pkt(1)) && chl.add_pkt(pkt(2)));
+        assert!(!chl.add_pkt(pkt(3)));
+        assert_eq!(chl.overflows(), 1);
+        assert_eq!(chl.drain().map(|p| p.seq.0).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_channel_overflow() {
+        let open = |policy| {
+            let mss = MediaSourceStream::new(
+                Box::new(Cursor::new(evs_capture(40, 0))),
+                Default::default(),
+            );
+            let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+            // less than the reorder depth, every packet goes through a full channel
+            reader.channel_capacity = 8;
+            reader.overflow_policy = policy;
+            reader
+        };
+
+        let mut reader = open(OverflowPolicy::DropOldest);
+        let mut packets = 0;
+        while reader.next_packet().is_ok() {
+            packets += 1;
+        }
+        assert_eq!(packets, 8);
+        assert_eq!(reader.overflowed_packets(), 32);
+
+        let mut reader = open(OverflowPolicy::Error);
+        assert!(matches!(
+            reader.next_packet(),
+            Err(Error::DecodeError("Channel buffer overflow"))
+        ));
+        assert_eq!(reader.overflowed_packets(), 1);
+    }
+}
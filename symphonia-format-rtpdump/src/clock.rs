@@ -0,0 +1,209 @@
+/// Usual RTP clock rates of audio payload types
+const CLOCK_RATES: [u32; 7] = [8000, 12000, 16000, 24000, 32000, 44100, 48000];
+pub(crate) const MAX_CLOCK_RATE: u32 = 48000;
+
+/// RTP clock rate of a stream whose timestamps advanced by `ext` units in `elapsed_ms`
+/// milliseconds of capture time, e.g. 160 units every 20 ms make 8 kHz.
+///
+/// The rate is rounded to the nearest usual one, `None` if none is within 10% or the stream
+/// lasted less than 200 ms, too short for the arrival jitter to average out.
+pub(crate) fn infer_clock_rate(ext: i64, elapsed_ms: u32) -> Option<u32> {
+    if elapsed_ms < 200 || ext <= 0 {
+        return None;
+    }
+    let rate = ext as f64 * 1000.0 / elapsed_ms as f64;
+    let nearest = CLOCK_RATES
+        .into_iter()
+        .min_by_key(|r| (rate - *r as f64).abs() as u64)?;
+    ((rate - nearest as f64).abs() <= nearest as f64 / 10.0).then_some(nearest)
+}
+
+/// Milliseconds the timestamp of a packet may move back, or ahead of the capture time elapsed
+/// since the previous packet, before the sender is taken to have restarted its timeline
+pub(crate) const RESTART_JUMP: u32 = 1000;
+
+/// Whether a packet following the previous packet of its stream by sequence number, with a
+/// timestamp `ts_delta` units further and captured `elapsed_ms` later, restarts the timeline of
+/// the stream: its sender started over from another timestamp without changing SSRC.
+///
+/// A timestamp moving back more than [`RESTART_JUMP`] ms does, or moving ahead more than that
+/// past the capture time elapsed. Silences (DTX) move both ahead alike.
+pub(crate) fn restarts_timeline(ts_delta: i64, elapsed_ms: u32, clock_rate: u32) -> bool {
+    let jump = RESTART_JUMP as i64 * clock_rate as i64 / 1000;
+    let elapsed = elapsed_ms as i64 * clock_rate as i64 / 1000;
+    ts_delta < -jump || ts_delta > elapsed + jump
+}
+
+/// Maps the RTP timestamps of a track onto the track timeline.
+///
+/// The first frame of the track is the origin of its RTP timestamps, later timestamps are
+/// unwrapped and converted from the RTP clock rate to the sample rate of the decoded audio.
+/// The origin itself is placed at the capture time of the first packet, so tracks starting
+/// at different times stay aligned.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TrackClock {
+    clock_rate: u32,
+    sample_rate: u32,
+    /// Position of the origin on the track timeline, in samples
+    start: Option<u64>,
+    /// RTP timestamp of the origin
+    origin: Option<u32>,
+    /// Last RTP timestamp seen, with its unwrapped offset from the origin
+    last: Option<(u32, i64)>,
+    /// Unwrapped timestamp expected for the next frame
+    next: Option<i64>,
+}
+
+impl TrackClock {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            sample_rate,
+            start: None,
+            origin: None,
+            last: None,
+            next: None,
+        }
+    }
+
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Place the origin at `offset` milliseconds since the start of recording, only the first
+    /// call has an effect
+    pub fn start(&mut self, offset: u32) {
+        self.start
+            .get_or_insert(offset as u64 * self.sample_rate as u64 / 1000);
+    }
+
+    /// Unwrapped offset of `ts` from the origin, in RTP timestamp units
+    pub fn extend(&mut self, ts: u32) -> i64 {
+        let ext = match self.last {
+            None => {
+                self.origin = Some(ts);
+                0
+            }
+            Some((last, ext)) => ext + ts.wrapping_sub(last) as i32 as i64,
+        };
+        self.last = Some((ts, ext));
+        ext
+    }
+
+    /// Continue the timeline after the sender restarted its timestamps from `ts`, which is
+    /// placed where the previous frame ended
+    pub fn restart(&mut self, ts: u32) {
+        let Some((_, ext)) = self.last else {
+            return;
+        };
+        self.last = Some((ts, self.next.unwrap_or(ext)));
+    }
+
+    /// RTP timestamp units missing between the previous frame and a frame at `ext`
+    pub fn gap(&self, ext: i64) -> i64 {
+        self.next.map_or(0, |next| ext - next)
+    }
+
+    /// Unwrapped timestamp expected after the frame at `ext`
+    pub fn advance(&mut self, ext: i64, dur: u32) {
+        self.next = Some(ext + dur as i64);
+    }
+
+    /// Forget everything, the next frame becomes the origin again
+    pub fn reset(&mut self) {
+        *self = Self::new(self.clock_rate, self.sample_rate);
+    }
+
+    /// Difference, in RTP timestamp units, between the origin and `origin`
+    fn shift(&self, origin: u32) -> i64 {
+        self.origin
+            .map_or(0, |own| origin.wrapping_sub(own) as i32 as i64)
+    }
+
+    /// Unwrapped offset from `origin` of the frame at position `ts` on the track timeline, a
+    /// clock not started yet would start at `start` samples with `origin` as its origin
+    pub fn ext_at(&self, origin: u32, start: u64, ts: u64) -> i64 {
+        let start = self.start.unwrap_or(start);
+        let ext = ts.saturating_sub(start) * self.clock_rate as u64 / self.sample_rate as u64;
+        ext as i64 - self.shift(origin)
+    }
+
+    /// Continue after a seek with a packet `ext` units after `origin`, gaps to the next frame
+    /// aren't filled. A clock not started yet starts at `start` samples with `origin` as its
+    /// origin. Returns the position of the packet on the track timeline.
+    pub fn resume(&mut self, origin: u32, start: u64, ext: i64) -> u64 {
+        let ext = ext + self.shift(origin);
+        self.origin.get_or_insert(origin);
+        self.start.get_or_insert(start);
+        let own = self.origin.unwrap_or(origin);
+        self.last = Some((own.wrapping_add(ext as u32), ext));
+        self.next = None;
+        self.samples_at(ext)
+    }
+
+    /// Convert an unwrapped timestamp to a position on the track timeline, timestamps before
+    /// the origin map to the origin
+    pub fn samples_at(&self, rtp: i64) -> u64 {
+        self.start.unwrap_or(0)
+            + rtp.max(0) as u64 * self.sample_rate as u64 / self.clock_rate as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unwrap_timestamp() {
+        let mut clock = TrackClock::new(16000, 8000);
+        clock.start(0);
+        assert_eq!(clock.extend(u32::MAX - 319), 0);
+        assert_eq!(clock.extend(0), 320);
+        assert_eq!(clock.extend(320), 640);
+        // reordered frame
+        assert_eq!(clock.extend(0), 320);
+        assert_eq!(clock.samples_at(640), 320);
+        assert_eq!(clock.samples_at(-320), 0);
+
+        let mut late = TrackClock::new(8000, 8000);
+        late.start(1500);
+        late.start(3000);
+        assert_eq!(late.samples_at(160), 12160);
+
+        clock.advance(640, 320);
+        assert_eq!(clock.gap(960), 0);
+        assert_eq!(clock.gap(1600), 640);
+
+        // the sender starts over from timestamp 5
+        clock.restart(5);
+        assert_eq!(clock.extend(5), 960);
+        assert_eq!(clock.extend(325), 1280);
+    }
+
+    #[test]
+    fn test_restarts_timeline() {
+        assert!(!restarts_timeline(160, 20, 8000));
+        // reordered packets and silences
+        assert!(!restarts_timeline(-1600, 0, 8000));
+        assert!(!restarts_timeline(48000, 5980, 8000));
+        assert!(!restarts_timeline(160, 3000, 8000));
+
+        assert!(restarts_timeline(-16000, 20, 8000));
+        assert!(restarts_timeline(i32::MIN as i64, 20, 16000));
+        assert!(restarts_timeline(48000, 20, 16000));
+    }
+
+    #[test]
+    fn test_infer_clock_rate() {
+        // 50 packets of 20 ms at 8 kHz, arriving a bit late
+        assert_eq!(infer_clock_rate(49 * 160, 49 * 20 + 7), Some(8000));
+        assert_eq!(infer_clock_rate(49 * 320, 49 * 20 - 5), Some(16000));
+        assert_eq!(infer_clock_rate(48000 * 3, 3000), Some(48000));
+        assert_eq!(infer_clock_rate(44100 * 3, 3000), Some(44100));
+        // too short
+        assert_eq!(infer_clock_rate(160, 20), None);
+        // no usual rate
+        assert_eq!(infer_clock_rate(20000, 1000), None);
+        assert_eq!(infer_clock_rate(-160, 1000), None);
+    }
+}
@@ -0,0 +1,204 @@
+/// Objective quality score of decoded audio against a reference, higher is better.
+///
+/// Implement it to plug an external scorer, e.g. a PESQ or POLQA implementation, into
+/// [`PcmComparison::scores`].
+pub trait QualityScorer {
+    /// Short name of the score, e.g. "segsnr"
+    fn name(&self) -> &str;
+
+    /// Score mono `decoded` against `reference` sampled at `sample_rate`, both of the same
+    /// length
+    fn score(&self, reference: &[i16], decoded: &[i16], sample_rate: u32) -> f64;
+}
+
+/// Peak signal to noise ratio in dB, infinite if both are identical
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Psnr;
+
+impl QualityScorer for Psnr {
+    fn name(&self) -> &str {
+        "psnr"
+    }
+
+    fn score(&self, reference: &[i16], decoded: &[i16], _sample_rate: u32) -> f64 {
+        let (_, noise) = energies(reference, decoded);
+        if noise == 0.0 {
+            return f64::INFINITY;
+        }
+        let mse = noise / reference.len().min(decoded.len()) as f64;
+        10.0 * (i16::MAX as f64 * i16::MAX as f64 / mse).log10()
+    }
+}
+
+/// Segmental signal to noise ratio in dB: the mean SNR of 20 ms frames, each clamped to
+/// [-10, 35] dB so that silent or identical frames don't dominate
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SegSnr;
+
+impl SegSnr {
+    const MIN_DB: f64 = -10.0;
+    const MAX_DB: f64 = 35.0;
+}
+
+impl QualityScorer for SegSnr {
+    fn name(&self) -> &str {
+        "segsnr"
+    }
+
+    fn score(&self, reference: &[i16], decoded: &[i16], sample_rate: u32) -> f64 {
+        let frame_len = (sample_rate as usize / 50).max(1);
+        let snrs = reference
+            .chunks(frame_len)
+            .zip(decoded.chunks(frame_len))
+            .map(|(reference, decoded)| {
+                let (signal, noise) = energies(reference, decoded);
+                if noise == 0.0 {
+                    return Self::MAX_DB;
+                }
+                (10.0 * (signal / noise).log10()).clamp(Self::MIN_DB, Self::MAX_DB)
+            })
+            .collect::<Vec<_>>();
+        if snrs.is_empty() {
+            return Self::MAX_DB;
+        }
+        snrs.iter().sum::<f64>() / snrs.len() as f64
+    }
+}
+
+/// Energy of `reference`, and of its difference with `decoded`
+fn energies(reference: &[i16], decoded: &[i16]) -> (f64, f64) {
+    reference
+        .iter()
+        .zip(decoded)
+        .fold((0.0, 0.0), |(signal, noise), (&r, &d)| {
+            let diff = r as f64 - d as f64;
+            (signal + r as f64 * r as f64, noise + diff * diff)
+        })
+}
+
+/// Differences of one segment of two decodes of the same audio
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SegmentDiff {
+    /// Index of the first sample of the segment
+    pub start: usize,
+    /// Samples compared, the last segment may be shorter
+    pub samples: usize,
+    /// Largest difference between two samples
+    pub max_delta: u16,
+    /// Samples which differ
+    pub mismatches: usize,
+}
+
+/// Sample by sample comparison of two decodes, e.g. of the same capture by two versions of a
+/// decoder or with two codec mappings
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PcmComparison {
+    /// Segments of `segment_len` samples, up to the end of the shorter decode
+    pub segments: Vec<SegmentDiff>,
+    /// Difference of both lengths, in samples
+    pub len_diff: usize,
+}
+
+impl PcmComparison {
+    pub fn new(a: &[i16], b: &[i16], segment_len: usize) -> Self {
+        let segment_len = segment_len.max(1);
+        let segments = a
+            .chunks(segment_len)
+            .zip(b.chunks(segment_len))
+            .enumerate()
+            .map(|(i, (a, b))| {
+                let mut diff = SegmentDiff {
+                    start: i * segment_len,
+                    samples: a.len().min(b.len()),
+                    ..Default::default()
+                };
+                for (x, y) in a.iter().zip(b) {
+                    let delta = x.abs_diff(*y);
+                    diff.max_delta = diff.max_delta.max(delta);
+                    diff.mismatches += usize::from(delta != 0);
+                }
+                diff
+            })
+            .collect();
+        Self {
+            segments,
+            len_diff: a.len().abs_diff(b.len()),
+        }
+    }
+
+    /// Largest difference between two samples of both decodes
+    pub fn max_delta(&self) -> u16 {
+        self.segments.iter().map(|s| s.max_delta).max().unwrap_or(0)
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.len_diff == 0 && self.segments.iter().all(|s| s.mismatches == 0)
+    }
+
+    /// Score of every segment, `a` and `b` being the decodes compared with `a` as reference
+    pub fn scores(
+        &self,
+        a: &[i16],
+        b: &[i16],
+        sample_rate: u32,
+        scorer: &dyn QualityScorer,
+    ) -> Vec<f64> {
+        self.segments
+            .iter()
+            .map(|s| {
+                let range = s.start..s.start + s.samples;
+                scorer.score(&a[range.clone()], &b[range], sample_rate)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare_pcm() {
+        let a = [0i16, 1, 2, 3, 4, 5, 6];
+        assert!(PcmComparison::new(&a, &a, 3).is_identical());
+
+        let cmp = PcmComparison::new(&a, &[0, 1, 2, 3, -4, 5], 3);
+        assert_eq!(cmp.segments.len(), 2);
+        assert_eq!(cmp.segments[0].mismatches, 0);
+        assert_eq!(
+            cmp.segments[1],
+            SegmentDiff {
+                start: 3,
+                samples: 3,
+                max_delta: 8,
+                mismatches: 1,
+            }
+        );
+        assert_eq!(cmp.len_diff, 1);
+        assert_eq!(cmp.max_delta(), 8);
+        assert!(!cmp.is_identical());
+    }
+
+    #[test]
+    fn test_scorers() {
+        let reference = (0..320)
+            .map(|i| ((i as f64 / 8.0).sin() * 8000.0) as i16)
+            .collect::<Vec<_>>();
+        let noisy = reference.iter().map(|s| s + 400).collect::<Vec<_>>();
+
+        assert_eq!(Psnr.score(&reference, &reference, 8000), f64::INFINITY);
+        // a constant error of 400
+        let psnr = Psnr.score(&reference, &noisy, 8000);
+        assert!((psnr - 20.0 * (32767.0f64 / 400.0).log10()).abs() < 1e-9);
+
+        assert_eq!(SegSnr.score(&reference, &reference, 8000), 35.0);
+        let segsnr = SegSnr.score(&reference, &noisy, 8000);
+        assert!(segsnr > 20.0 && segsnr < 30.0, "{segsnr}");
+        assert_eq!(SegSnr.score(&reference, &vec![0; 320], 8000), 0.0);
+
+        let cmp = PcmComparison::new(&reference, &noisy, 160);
+        let scores = cmp.scores(&reference, &noisy, 8000, &SegSnr);
+        assert_eq!(scores.len(), 2);
+        assert!(scores.iter().all(|s| *s < 35.0));
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use codec_detector::rtp::RawRtpPacket;
+use codec_detector::Codec;
+use log::{debug, warn};
+use symphonia_core::audio::SampleBuffer;
+use symphonia_core::codecs::CodecRegistry;
+use symphonia_core::formats::Packet;
+
+use crate::{at_clock_rate, codec_to_param, RtpCodecDescriptor, RtpCodecRegistry};
+
+/// Packets of a stream trial-decoded by the content check, a second of 20 ms frames
+pub(crate) const TRIAL_PACKETS: usize = 50;
+
+/// Below this RMS level the audio is digital silence, which tells nothing of the codec
+const SILENCE_RMS: f64 = 8.0;
+/// Samples at least this loud are clipped
+const CLIP_LEVEL: u16 = 32000;
+
+/// What trial decoding checks of the codecs detected for a stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TrialCheck {
+    /// Among the codecs matching as many packets, keep the one yielding the most plausible
+    /// audio
+    Content,
+    /// Keep the best ranked codec whose audio isn't pathological
+    Verify,
+}
+
+/// Audio decoded from the first packets of a stream
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Trial {
+    pub pcm: Vec<i16>,
+    /// Frames decoded, and packets or frames the depacketizer or the decoder rejected
+    pub decoded: usize,
+    pub errors: usize,
+}
+
+impl Trial {
+    /// How plausible the audio is, scaled down by the share of rejected frames
+    pub fn score(&self) -> f32 {
+        let total = self.decoded + self.errors;
+        if total == 0 {
+            return 0.0;
+        }
+        plausibility(&self.pcm) * self.decoded as f32 / total as f32
+    }
+}
+
+/// How much `pcm` looks like speech or background noise, from 0 to 1.
+///
+/// A stream decoded with the wrong codec mostly yields loud broadband noise, clipped or stuck
+/// at a DC offset: such audio scores low, and so does digital silence as it could come out of
+/// any decoder.
+pub(crate) fn plausibility(pcm: &[i16]) -> f32 {
+    if pcm.is_empty() {
+        return 0.0;
+    }
+    let n = pcm.len() as f64;
+    let mean = pcm.iter().map(|s| *s as f64).sum::<f64>() / n;
+    let rms = (pcm.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / n).sqrt();
+    if rms < SILENCE_RMS {
+        return 0.0;
+    }
+    let clipped = pcm
+        .iter()
+        .filter(|s| s.unsigned_abs() >= CLIP_LEVEL)
+        .count() as f64
+        / n;
+    let crossings = pcm.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count() as f64 / n;
+
+    // nothing is left once 5% of the samples are clipped
+    let clipping = (1.0 - clipped * 20.0).max(0.0);
+    // speech and background noise cross zero on less than one sample in four, white noise on
+    // every other
+    let spectrum = ((0.5 - crossings) / 0.25).clamp(0.0, 1.0);
+    // share of the energy not in a DC offset
+    let dc = 1.0 - (mean.abs() / rms).min(1.0);
+    (clipping * spectrum * dc) as f32
+}
+
+/// What makes `pcm` unusable: no audio at all, silence, a constant DC offset or clipping on
+/// more than a tenth of the samples
+pub(crate) fn pathology(pcm: &[i16]) -> Option<&'static str> {
+    let (Some(min), Some(max)) = (pcm.iter().min(), pcm.iter().max()) else {
+        return Some("no audio");
+    };
+    if *min == 0 && *max == 0 {
+        return Some("all zeros");
+    }
+    if max.abs_diff(*min) < 16 {
+        return Some("constant DC");
+    }
+    let clipped = pcm
+        .iter()
+        .filter(|s| s.unsigned_abs() >= CLIP_LEVEL)
+        .count();
+    if clipped * 10 > pcm.len() {
+        return Some("clipping");
+    }
+    None
+}
+
+/// Decode `pkts`, whole RTP packets, as `codec`. `None` if `codecs` has no decoder for it.
+pub(crate) fn trial_decode(
+    codec: &Codec,
+    desc: &RtpCodecDescriptor,
+    pkts: &[Bytes],
+    codecs: &CodecRegistry,
+) -> Option<Trial> {
+    let params = codec_to_param(desc, &at_clock_rate(codec, desc));
+    let mut decoder = codecs.make(&params, &Default::default()).ok()?;
+    let mut depacketizer = (desc.depacketizer)();
+    let mut frames = VecDeque::new();
+    let mut sample_buf: Option<SampleBuffer<i16>> = None;
+    let mut trial = Trial::default();
+    for pkt in pkts {
+        if depacketizer
+            .depacketize(&RawRtpPacket::new(pkt), &mut frames)
+            .is_err()
+        {
+            trial.errors += 1;
+            continue;
+        }
+        for frame in frames.drain(..) {
+            let packet = Packet::new_from_boxed_slice(0, frame.ts as u64, 0, frame.data);
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(_) => {
+                    trial.errors += 1;
+                    continue;
+                }
+            };
+            let frames = decoded.capacity() as u64;
+            let buf = match sample_buf.as_mut() {
+                Some(buf) if buf.capacity() >= decoded.capacity() => buf,
+                _ => sample_buf.insert(SampleBuffer::new(frames, *decoded.spec())),
+            };
+            buf.copy_interleaved_ref(decoded);
+            trial.pcm.extend_from_slice(buf.samples());
+            trial.decoded += 1;
+        }
+    }
+    Some(trial)
+}
+
+/// The candidate whose trial decoding of `pkts` gives the most plausible audio, the first
+/// one on a tie or if none could be decoded
+pub(crate) fn select_by_content(
+    candidates: &[Codec],
+    pkts: &[Bytes],
+    registry: &RtpCodecRegistry,
+    codecs: &CodecRegistry,
+) -> Option<Codec> {
+    let mut best: Option<(f32, &Codec)> = None;
+    for codec in candidates {
+        let Some(desc) = registry.get(codec.name.as_str()) else {
+            continue;
+        };
+        let Some(trial) = trial_decode(codec, desc, pkts, codecs) else {
+            continue;
+        };
+        let score = trial.score();
+        debug!("content check: {} scores {:.2}", codec.name, score);
+        if best.is_none_or(|(best, _)| score > best) {
+            best = Some((score, codec));
+        }
+    }
+    best.map(|(_, codec)| codec)
+        .or_else(|| candidates.first())
+        .cloned()
+}
+
+/// The first of the ranked `candidates` whose trial decoding of `pkts` isn't pathological,
+/// the first one if all are
+pub(crate) fn select_verified(
+    candidates: &[Codec],
+    pkts: &[Bytes],
+    registry: &RtpCodecRegistry,
+    codecs: &CodecRegistry,
+) -> Option<Codec> {
+    for codec in candidates {
+        let Some(desc) = registry.get(codec.name.as_str()) else {
+            continue;
+        };
+        let Some(trial) = trial_decode(codec, desc, pkts, codecs) else {
+            continue;
+        };
+        match pathology(&trial.pcm) {
+            None => return Some(codec.clone()),
+            Some(reason) => warn!("decoded as {}, the stream gives {}", codec.name, reason),
+        }
+    }
+    candidates.first().cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_bundle_amr::CODEC_TYPE_AMRWB;
+    use symphonia_bundle_evs::dec::CODEC_TYPE_EVS;
+    use symphonia_core::audio::{
+        AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+    };
+    use symphonia_core::codecs::{
+        CodecDescriptor, CodecParameters, CodecType, Decoder, DecoderOptions, FinalizeResult,
+    };
+    use symphonia_core::errors::Result;
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::test_util::{evs_packets, rtpdump, Lcg};
+    use crate::RtpdumpReader;
+
+    /// `n` samples of white noise at `level`
+    fn noise(n: usize, level: i32) -> Vec<i16> {
+        let mut rng = Lcg(1);
+        (0..n)
+            .map(|_| (rng.sample() as i32 * level / 32768) as i16)
+            .collect()
+    }
+
+    fn tone(n: usize, level: f64) -> Vec<i16> {
+        (0..n)
+            .map(|i| {
+                (level * (i as f64 * 2.0 * std::f64::consts::PI * 440.0 / 16000.0).sin()) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plausibility() {
+        assert!(plausibility(&tone(16000, 3000.0)) > 0.9);
+        assert!(plausibility(&noise(16000, 20000)) < 0.1);
+        assert_eq!(plausibility(&[]), 0.0);
+        assert_eq!(plausibility(&[0; 16000]), 0.0);
+        // stuck at a DC offset
+        assert_eq!(plausibility(&[5000; 16000]), 0.0);
+        // overdriven
+        assert!(plausibility(&tone(16000, 60000.0)) < 0.1);
+
+        let trial = Trial {
+            pcm: tone(16000, 3000.0),
+            decoded: 30,
+            errors: 20,
+        };
+        assert!((trial.score() - 0.6 * plausibility(&trial.pcm)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pathology() {
+        assert_eq!(pathology(&tone(16000, 3000.0)), None);
+        assert_eq!(pathology(&noise(16000, 20000)), None);
+        assert_eq!(pathology(&[]), Some("no audio"));
+        assert_eq!(pathology(&[0; 16000]), Some("all zeros"));
+        assert_eq!(pathology(&[-1200; 16000]), Some("constant DC"));
+        assert_eq!(pathology(&tone(16000, 60000.0)), Some("clipping"));
+        // a few clipped peaks are fine
+        let mut pcm = tone(16000, 3000.0);
+        pcm[..1000].fill(i16::MAX);
+        assert_eq!(pathology(&pcm), None);
+    }
+
+    const TONE: u8 = 0;
+    const NOISE: u8 = 1;
+    const SILENCE: u8 = 2;
+
+    /// Decoder giving a tone, loud noise or silence whatever the packets
+    struct FakeDecoder<const OUTPUT: u8> {
+        params: CodecParameters,
+        buf: AudioBuffer<i16>,
+        pos: u32,
+        rng: Lcg,
+    }
+
+    impl<const OUTPUT: u8> Decoder for FakeDecoder<OUTPUT> {
+        fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+            let buf = AudioBuffer::new(320, SignalSpec::new(16000, Channels::FRONT_CENTRE));
+            Ok(Self {
+                params: params.clone(),
+                buf,
+                pos: 0,
+                rng: Lcg(1),
+            })
+        }
+
+        fn supported_codecs() -> &'static [CodecDescriptor] {
+            &[]
+        }
+
+        fn reset(&mut self) {}
+
+        fn codec_params(&self) -> &CodecParameters {
+            &self.params
+        }
+
+        fn decode(&mut self, _packet: &Packet) -> Result<AudioBufferRef<'_>> {
+            self.buf.clear();
+            self.buf.render_reserved(Some(320));
+            for s in self.buf.chan_mut(0) {
+                self.pos = self.pos.wrapping_add(1);
+                *s = match OUTPUT {
+                    TONE => ((self.pos as f64 * 0.1).sin() * 3000.0) as i16,
+                    NOISE => self.rng.sample(),
+                    _ => 0,
+                };
+            }
+            Ok(self.buf.as_audio_buffer_ref())
+        }
+
+        fn finalize(&mut self) -> FinalizeResult {
+            Default::default()
+        }
+
+        fn last_decoded(&self) -> AudioBufferRef<'_> {
+            self.buf.as_audio_buffer_ref()
+        }
+    }
+
+    /// Decoders of EVS and AMR-WB giving `EVS` and `AMRWB` outputs
+    fn fake_codecs<const EVS: u8, const AMRWB: u8>() -> CodecRegistry {
+        let mut codecs = CodecRegistry::new();
+        codecs.register(&CodecDescriptor {
+            codec: CODEC_TYPE_EVS,
+            short_name: "evs",
+            long_name: "",
+            inst_func: |params, opts| Ok(Box::new(FakeDecoder::<EVS>::try_new(params, opts)?)),
+        });
+        codecs.register(&CodecDescriptor {
+            codec: CODEC_TYPE_AMRWB,
+            short_name: "amrwb",
+            long_name: "",
+            inst_func: |params, opts| Ok(Box::new(FakeDecoder::<AMRWB>::try_new(params, opts)?)),
+        });
+        codecs
+    }
+
+    /// Capture of AMR-WB 12.65 bandwidth efficient payloads: CMR 15 | F0 FT2 Q1 | 253 bits of
+    /// speech, as large as EVS 13.2 ones
+    fn amrwb_capture(frames: u16) -> Vec<u8> {
+        let mut pkts = evs_packets(frames, 0);
+        for (_, pkt) in &mut pkts {
+            pkt[12] = 0xf1;
+            pkt[13] = 0x40 | (pkt[13] & 0x3f);
+        }
+        rtpdump(pkts)
+    }
+
+    /// Codec of the first track, detected with the given trial decoding
+    fn detected_codec(data: &[u8], trial: Option<(&CodecRegistry, TrialCheck)>) -> CodecType {
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+        let (options, registry) = (&Default::default(), &Default::default());
+        let reader = match trial {
+            Some((codecs, TrialCheck::Content)) => {
+                RtpdumpReader::try_new_with_content_check(mss, options, registry, codecs)
+            }
+            Some((codecs, TrialCheck::Verify)) => {
+                RtpdumpReader::try_new_with_verification(mss, options, registry, codecs)
+            }
+            None => RtpdumpReader::try_new(mss, options),
+        };
+        reader.unwrap().tracks()[0].codec_params.codec
+    }
+
+    #[test]
+    fn test_content_check() {
+        let data = amrwb_capture(50);
+        let codecs = fake_codecs::<NOISE, TONE>();
+        // payload sizes alone make it EVS, the only codec decoding to plausible audio is AMR-WB
+        assert_eq!(detected_codec(&data, None), CODEC_TYPE_EVS);
+        assert_eq!(
+            detected_codec(&data, Some((&codecs, TrialCheck::Content))),
+            CODEC_TYPE_AMRWB
+        );
+    }
+
+    #[test]
+    fn test_verification() {
+        let data = amrwb_capture(50);
+        // noise isn't pathological, EVS is kept
+        let codecs = fake_codecs::<NOISE, TONE>();
+        assert_eq!(
+            detected_codec(&data, Some((&codecs, TrialCheck::Verify))),
+            CODEC_TYPE_EVS
+        );
+        // EVS decodes to silence, AMR-WB is next
+        let codecs = fake_codecs::<SILENCE, TONE>();
+        assert_eq!(
+            detected_codec(&data, Some((&codecs, TrialCheck::Verify))),
+            CODEC_TYPE_AMRWB
+        );
+        // nothing better than EVS
+        let codecs = fake_codecs::<SILENCE, SILENCE>();
+        assert_eq!(
+            detected_codec(&data, Some((&codecs, TrialCheck::Verify))),
+            CODEC_TYPE_EVS
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! AMR and AMR-WB RTP payload format, see RFC 4867 section 4
+
+use std::collections::VecDeque;
+
+use codec_detector::rtp::{RawRtpPacket, RtpPacket};
+use symphonia_core::errors::{Error, Result};
+
+use super::{copy_bits, read_bits, Frame, FrameDedup, FrameMeta, PayloadDepacketizer};
+
+/// Speech bits of every AMR frame type, `None` for frame types reserved for future use
+const AMR_FRAME_BITS: [Option<usize>; 16] = [
+    Some(95),
+    Some(103),
+    Some(118),
+    Some(134),
+    Some(148),
+    Some(159),
+    Some(204),
+    Some(244),
+    Some(39),
+    Some(43),
+    Some(38),
+    Some(37),
+    None,
+    None,
+    None,
+    Some(0),
+];
+
+/// Speech bits of every AMR-WB frame type, `None` for frame types reserved for future use
+const AMRWB_FRAME_BITS: [Option<usize>; 16] = [
+    Some(132),
+    Some(177),
+    Some(253),
+    Some(285),
+    Some(317),
+    Some(365),
+    Some(397),
+    Some(461),
+    Some(477),
+    Some(40),
+    None,
+    None,
+    None,
+    None,
+    Some(0),
+    Some(0),
+];
+
+/// Payload packing used by the sender, negotiated by the `octet-align` SDP parameter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmrPacking {
+    OctetAligned,
+    /// Octet-aligned with the `interleaving` SDP parameter, see RFC 4867 section 4.4.1
+    Interleaved,
+    BandwidthEfficient,
+    /// Try octet-aligned, then interleaved, then bandwidth-efficient
+    #[default]
+    Auto,
+}
+
+/// Converts RFC 4867 payloads into storage format frames (one ToC byte followed by speech
+/// bits), which is what the AMR decoders expect.
+///
+/// Redundant frames repeated by later packets are dropped by timestamp, unless the packet
+/// originally carrying them was lost. Interleaved frames are held back until every frame
+/// before them arrived or the interleaving group is known to be incomplete.
+#[derive(Clone, Debug, Default)]
+pub struct AmrDepacketizer {
+    wb: bool,
+    packing: AmrPacking,
+    dedup: FrameDedup,
+    /// Interleaved frames waiting for earlier frames, sorted by timestamp
+    pending: Vec<Frame>,
+}
+
+/// Frames of one payload, with the ILL/ILP values of interleaved payloads
+#[derive(Clone, Debug)]
+struct Payload {
+    interleave: Option<(u8, u8)>,
+    frames: Vec<Box<[u8]>>,
+}
+
+/// ToC entry of a payload: frame type and quality bit
+#[derive(Clone, Copy, Debug)]
+struct Toc {
+    ft: u8,
+    q: bool,
+}
+
+impl AmrDepacketizer {
+    pub fn new(wb: bool, packing: AmrPacking) -> Self {
+        Self {
+            wb,
+            packing,
+            dedup: FrameDedup::default(),
+            pending: vec![],
+        }
+    }
+
+    fn frame_bits(&self, ft: u8) -> Option<usize> {
+        match self.wb {
+            false => AMR_FRAME_BITS[ft as usize & 0x0f],
+            true => AMRWB_FRAME_BITS[ft as usize & 0x0f],
+        }
+    }
+
+    /// RTP timestamp increment of a single frame
+    pub fn frame_duration(&self) -> u32 {
+        match self.wb {
+            false => 160,
+            true => 320,
+        }
+    }
+
+    /// Metadata of a storage format frame
+    fn meta(&self, data: &[u8]) -> Option<FrameMeta> {
+        let toc = data.first()?;
+        let ft = (toc >> 3) & 0x0f;
+        let sid = if self.wb { 9 } else { 8 };
+        Some(FrameMeta {
+            frame_type: ft,
+            // speech bits of a 20 ms frame, 244 bits make 12.2 kbps
+            bitrate: self.frame_bits(ft).map(|bits| bits as u32 * 50),
+            sid: ft == sid,
+            // quality bit cleared, or SPEECH_LOST
+            bfi: toc & 0x04 == 0 || ft == 14,
+            duration: Some(self.frame_duration()),
+        })
+    }
+
+    fn storage_frame(toc: Toc, bits: &[u8]) -> Box<[u8]> {
+        let mut data = Vec::with_capacity(bits.len() + 1);
+        data.push((toc.ft << 3) | ((toc.q as u8) << 2));
+        data.extend_from_slice(bits);
+        data.into_boxed_slice()
+    }
+
+    /// Whether an AMR-WB payload which failed to parse starts with a frame type AMR-WB
+    /// reserves, the ones AMR-WB+ (RFC 4352) uses
+    fn is_wbplus(&self, payload: &[u8]) -> bool {
+        let wbplus = |ft: u8| (10..=13).contains(&ft);
+        let octet_aligned = payload.get(1).is_some_and(|toc| wbplus((toc >> 3) & 0x0f));
+        let bandwidth_efficient =
+            payload.len() >= 2 && wbplus((read_bits(payload, 4, 6) >> 1) & 0x0f);
+        self.wb && (octet_aligned || bandwidth_efficient)
+    }
+
+    fn parse_octet_aligned(&self, payload: &[u8], interleaved: bool) -> Option<Payload> {
+        // CMR: 4 bits request + 4 reserved bits
+        if payload.first()? & 0x0f != 0 {
+            return None;
+        }
+
+        let mut idx = 1;
+        let mut interleave = None;
+        if interleaved {
+            let ill = payload.get(idx)? >> 4;
+            let ilp = payload.get(idx)? & 0x0f;
+            if ilp > ill {
+                return None;
+            }
+            interleave = Some((ill, ilp));
+            idx += 1;
+        }
+
+        let mut tocs = vec![];
+        loop {
+            let toc = *payload.get(idx)?;
+            idx += 1;
+            // padding bits of the ToC must be zero
+            if toc & 0x03 != 0 {
+                return None;
+            }
+            let ft = (toc >> 3) & 0x0f;
+            self.frame_bits(ft)?;
+            tocs.push(Toc {
+                ft,
+                q: toc & 0x04 == 0x04,
+            });
+            if toc & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let mut frames = Vec::with_capacity(tocs.len());
+        for toc in tocs {
+            let len = self.frame_bits(toc.ft)?.div_ceil(8);
+            let bits = payload.get(idx..idx + len)?;
+            idx += len;
+            frames.push(Self::storage_frame(toc, bits));
+        }
+
+        (idx == payload.len()).then_some(Payload { interleave, frames })
+    }
+
+    fn parse_bandwidth_efficient(&self, payload: &[u8]) -> Option<Payload> {
+        let total = payload.len() * 8;
+
+        // skip 4 bits CMR
+        let mut offset = 4;
+        let mut tocs = vec![];
+        loop {
+            if offset + 6 > total {
+                return None;
+            }
+            let toc = read_bits(payload, offset, 6);
+            offset += 6;
+            let ft = (toc >> 1) & 0x0f;
+            self.frame_bits(ft)?;
+            tocs.push(Toc {
+                ft,
+                q: toc & 0x01 == 0x01,
+            });
+            if toc & 0x20 == 0 {
+                break;
+            }
+        }
+
+        let mut frames = Vec::with_capacity(tocs.len());
+        for toc in tocs {
+            let len = self.frame_bits(toc.ft)?;
+            if offset + len > total {
+                return None;
+            }
+            let mut bits = vec![];
+            copy_bits(payload, offset, len, &mut bits);
+            offset += len;
+            frames.push(Self::storage_frame(toc, &bits));
+        }
+
+        // at most 7 bits of padding
+        (total - offset < 8).then_some(Payload {
+            interleave: None,
+            frames,
+        })
+    }
+
+    /// Queue an interleaved frame, frames already delivered or queued are dropped
+    fn deinterleave(&mut self, frame: Frame) {
+        if let Some(next_ts) = self.dedup.next_ts {
+            if next_ts.wrapping_sub(frame.ts) as i32 > 0 {
+                return;
+            }
+        }
+
+        let pos = self
+            .pending
+            .iter()
+            .position(|f| f.ts.wrapping_sub(frame.ts) as i32 >= 0)
+            .unwrap_or(self.pending.len());
+        if self.pending.get(pos).is_some_and(|f| f.ts == frame.ts) {
+            return;
+        }
+        self.pending.insert(pos, frame);
+    }
+
+    /// Deliver queued frames in order, once more than `window` frames wait for a missing
+    /// frame it is considered lost
+    fn release(&mut self, window: usize, frames: &mut VecDeque<Frame>) {
+        let dur = self.frame_duration();
+        while let Some(first) = self.pending.first() {
+            if self.dedup.next_ts != Some(first.ts) && self.pending.len() <= window {
+                break;
+            }
+            let frame = self.pending.remove(0);
+            if self.dedup.accept(frame.ts, dur) {
+                frames.push_back(frame);
+            }
+        }
+    }
+}
+
+impl PayloadDepacketizer for AmrDepacketizer {
+    fn depacketize(&mut self, pkt: &RawRtpPacket, frames: &mut VecDeque<Frame>) -> Result<()> {
+        let payload = pkt.payload();
+        let parsed = match self.packing {
+            AmrPacking::OctetAligned => self.parse_octet_aligned(payload, false),
+            AmrPacking::Interleaved => self.parse_octet_aligned(payload, true),
+            AmrPacking::BandwidthEfficient => self.parse_bandwidth_efficient(payload),
+            AmrPacking::Auto => self
+                .parse_octet_aligned(payload, false)
+                .or_else(|| self.parse_octet_aligned(payload, true))
+                .or_else(|| self.parse_bandwidth_efficient(payload)),
+        }
+        .ok_or_else(|| match self.is_wbplus(payload) {
+            true => Error::Unsupported("AMR-WB+ RTP payloads are not supported"),
+            false => Error::DecodeError("Invalid AMR RTP payload"),
+        })?;
+
+        let dur = self.frame_duration();
+        let Some((ill, ilp)) = parsed.interleave else {
+            for (i, data) in parsed.frames.into_iter().enumerate() {
+                let ts = pkt.ts().wrapping_add(dur * i as u32);
+                if self.dedup.accept(ts, dur) {
+                    frames.push_back(Frame {
+                        ts,
+                        dur: Some(dur),
+                        meta: self.meta(&data),
+                        data,
+                    });
+                }
+            }
+            return Ok(());
+        };
+
+        // the first packet of a group (ILP 0) starts with its earliest frame, whatever is
+        // still missing from earlier groups is lost
+        if ilp == 0
+            && self
+                .dedup
+                .next_ts
+                .is_none_or(|next_ts| pkt.ts().wrapping_sub(next_ts) as i32 >= 0)
+        {
+            while self
+                .pending
+                .first()
+                .is_some_and(|f| pkt.ts().wrapping_sub(f.ts) as i32 > 0)
+            {
+                let frame = self.pending.remove(0);
+                if self.dedup.accept(frame.ts, dur) {
+                    frames.push_back(frame);
+                }
+            }
+            self.dedup.next_ts = Some(pkt.ts());
+        }
+
+        // frames of a packet are ILL + 1 frame blocks apart
+        let window = (ill as usize + 1) * parsed.frames.len();
+        let stride = dur * (ill as u32 + 1);
+        for (i, data) in parsed.frames.into_iter().enumerate() {
+            let ts = pkt.ts().wrapping_add(stride * i as u32);
+            self.deinterleave(Frame {
+                ts,
+                dur: Some(dur),
+                meta: self.meta(&data),
+                data,
+            });
+        }
+        self.release(window, frames);
+        Ok(())
+    }
+
+    fn missing_frame(&self) -> Box<[u8]> {
+        // NO_DATA for AMR, SPEECH_LOST for AMR-WB
+        match self.wb {
+            false => Box::new([15 << 3]),
+            true => Box::new([14 << 3]),
+        }
+    }
+
+    fn flush(&mut self, frames: &mut VecDeque<Frame>) {
+        self.release(0, frames);
+    }
+
+    fn frame_meta(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rtp(ts: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x80, 0x60, 0x00, 0x01];
+        data.extend_from_slice(&ts.to_be_bytes());
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn test_octet_aligned() {
+        // CMR 15, two 12.2k frames
+        let mut payload = vec![0xf0, 0xbc, 0x3c];
+        payload.extend_from_slice(&[0x11; 31]);
+        payload.extend_from_slice(&[0x22; 31]);
+        let data = rtp(1000, &payload);
+
+        let mut frames = VecDeque::new();
+        let mut depack = AmrDepacketizer::new(false, AmrPacking::Auto);
+        depack
+            .depacketize(&RawRtpPacket::new(&data), &mut frames)
+            .unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].ts, 1000);
+        assert_eq!(frames[1].ts, 1160);
+        assert_eq!(frames[0].data[0], 0x3c);
+        assert_eq!(
+            frames[0].meta,
+            Some(FrameMeta {
+                frame_type: 7,
+                bitrate: Some(12200),
+                sid: false,
+                bfi: false,
+                duration: Some(160)
+            })
+        );
+        let wb = AmrDepacketizer::new(true, AmrPacking::Auto);
+        assert_eq!(wb.meta(&[0x14; 33]).unwrap().bitrate, Some(12650));
+        assert!(wb.meta(&[0x4c; 6]).unwrap().sid);
+        assert_eq!(depack.meta(&[0x6c]).unwrap().bitrate, None);
+        // quality bit cleared
+        assert!(depack.meta(&[0x38; 32]).unwrap().bfi);
+        assert_eq!(&frames[0].data[1..], &[0x11; 31]);
+        assert_eq!(&frames[1].data[1..], &[0x22; 31]);
+    }
+
+    #[test]
+    fn test_bandwidth_efficient() {
+        // CMR 15 | F0 FT7 Q1 | 244 bits of speech, total 254 bits
+        let mut payload = vec![0u8; 32];
+        payload[0] = 0xf3;
+        payload[1] = 0xea;
+        payload[2..31].fill(0xaa);
+        payload[31] = 0xa8;
+        let data = rtp(0, &payload);
+
+        let mut frames = VecDeque::new();
+        let mut depack = AmrDepacketizer::new(false, AmrPacking::Auto);
+        depack
+            .depacketize(&RawRtpPacket::new(&data), &mut frames)
+            .unwrap();
+
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0].data;
+        assert_eq!(frame.len(), 32);
+        assert_eq!(frame[0], 0x3c);
+        assert_eq!(frame[1], 0xaa);
+        assert_eq!(frame[31], 0xa0);
+    }
+
+    #[test]
+    fn test_redundancy() {
+        // every packet repeats the previous frame
+        let frame = |ft: u8, fill: u8| {
+            let mut data = vec![(ft << 3) | 0x04];
+            data.extend_from_slice(&[fill; 31][..AMR_FRAME_BITS[ft as usize].unwrap().div_ceil(8)]);
+            data
+        };
+        let payload = |frames: &[(u8, u8)]| {
+            let mut payload = vec![0xf0];
+            for (i, (ft, _)) in frames.iter().enumerate() {
+                let f = if i + 1 < frames.len() { 0x80 } else { 0 };
+                payload.push(f | (ft << 3) | 0x04);
+            }
+            for (ft, fill) in frames {
+                payload.extend_from_slice(&frame(*ft, *fill)[1..]);
+            }
+            payload
+        };
+
+        let mut depack = AmrDepacketizer::new(false, AmrPacking::OctetAligned);
+        let mut frames = VecDeque::new();
+        // the packet at 160 carrying frames 1 and 2 is lost
+        for (ts, content) in [
+            (0, [(15, 0), (7, 1)]),
+            (320, [(7, 2), (7, 3)]),
+            (480, [(7, 3), (7, 4)]),
+        ] {
+            let data = rtp(ts, &payload(&content));
+            depack
+                .depacketize(&RawRtpPacket::new(&data), &mut frames)
+                .unwrap();
+        }
+
+        let ts = frames.iter().map(|f| f.ts).collect::<Vec<_>>();
+        assert_eq!(ts, vec![0, 160, 320, 480, 640]);
+        assert_eq!(frames[0].data.as_ref(), &frame(15, 0));
+        assert_eq!(frames[2].data.as_ref(), &frame(7, 2));
+        assert_eq!(frames[4].data.as_ref(), &frame(7, 4));
+    }
+
+    #[test]
+    fn test_interleaved() {
+        // ILL 1: frames 0 2 in the first packet, 1 3 in the second
+        let payload = |ilp: u8, fills: [u8; 2]| {
+            let mut payload = vec![0xf0, 0x10 | ilp, 0xbc, 0x3c];
+            payload.extend_from_slice(&[fills[0]; 31]);
+            payload.extend_from_slice(&[fills[1]; 31]);
+            payload
+        };
+
+        let mut depack = AmrDepacketizer::new(false, AmrPacking::Auto);
+        let mut frames = VecDeque::new();
+        let data = rtp(0, &payload(0, [0, 2]));
+        depack
+            .depacketize(&RawRtpPacket::new(&data), &mut frames)
+            .unwrap();
+        assert_eq!(frames.len(), 1);
+
+        let data = rtp(160, &payload(1, [1, 3]));
+        depack
+            .depacketize(&RawRtpPacket::new(&data), &mut frames)
+            .unwrap();
+        let ts = frames.iter().map(|f| f.ts).collect::<Vec<_>>();
+        assert_eq!(ts, vec![0, 160, 320, 480]);
+        let fills = frames.iter().map(|f| f.data[1]).collect::<Vec<_>>();
+        assert_eq!(fills, vec![0, 1, 2, 3]);
+
+        // second packet of the next group is lost, its frames are skipped
+        frames.clear();
+        for (ts, ilp, fills) in [(640, 0, [4, 6]), (1280, 0, [8, 10]), (1440, 1, [9, 11])] {
+            let data = rtp(ts, &payload(ilp, fills));
+            depack
+                .depacketize(&RawRtpPacket::new(&data), &mut frames)
+                .unwrap();
+        }
+        let fills = frames.iter().map(|f| f.data[1]).collect::<Vec<_>>();
+        assert_eq!(fills, vec![4, 6, 8, 9, 10, 11]);
+
+        // the stream ends before the rest of the group arrives
+        frames.clear();
+        let data = rtp(1920, &payload(0, [12, 14]));
+        depack
+            .depacketize(&RawRtpPacket::new(&data), &mut frames)
+            .unwrap();
+        assert_eq!(frames.len(), 1);
+        depack.flush(&mut frames);
+        let fills = frames.iter().map(|f| f.data[1]).collect::<Vec<_>>();
+        assert_eq!(fills, vec![12, 14]);
+        depack.flush(&mut frames);
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_payload() {
+        let data = rtp(0, &[0xf0, 0x3c, 0x00]);
+        let mut frames = VecDeque::new();
+        let mut depack = AmrDepacketizer::new(false, AmrPacking::OctetAligned);
+        assert!(depack
+            .depacketize(&RawRtpPacket::new(&data), &mut frames)
+            .is_err());
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn test_wbplus_payload() {
+        // octet-aligned ToC with frame type 10
+        let data = rtp(0, &[0xf0, 0x54, 0x00, 0x00]);
+        let mut frames = VecDeque::new();
+        let mut depack = AmrDepacketizer::new(true, AmrPacking::Auto);
+        assert!(matches!(
+            depack.depacketize(&RawRtpPacket::new(&data), &mut frames),
+            Err(Error::Unsupported(_))
+        ));
+
+        // the same frame type is merely invalid for AMR
+        let mut depack = AmrDepacketizer::new(false, AmrPacking::OctetAligned);
+        assert!(matches!(
+            depack.depacketize(&RawRtpPacket::new(&data), &mut frames),
+            Err(Error::DecodeError(_))
+        ));
+    }
+}
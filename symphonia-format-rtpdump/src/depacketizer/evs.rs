@@ -0,0 +1,267 @@
+//! EVS RTP payload format, see 3GPP TS 26.445 Annex A
+
+use std::collections::VecDeque;
+
+use codec_detector::rtp::{RawRtpPacket, RtpPacket};
+use symphonia_core::errors::{Error, Result};
+
+use super::{copy_bits, Frame, FrameDedup, FrameMeta, PayloadDepacketizer};
+
+/// EVS primary frame size in bytes of every frame type, `None` for future use
+const PRIMARY_FRAME_SIZES: [Option<usize>; 16] = [
+    Some(7),
+    Some(18),
+    Some(20),
+    Some(24),
+    Some(33),
+    Some(41),
+    Some(61),
+    Some(80),
+    Some(120),
+    Some(160),
+    Some(240),
+    Some(320),
+    Some(6),
+    None,
+    Some(0),
+    Some(0),
+];
+
+/// EVS AMR-WB IO frame size in bytes of every frame type, `None` for future use
+const AMRWBIO_FRAME_SIZES: [Option<usize>; 16] = [
+    Some(17),
+    Some(23),
+    Some(32),
+    Some(36),
+    Some(40),
+    Some(46),
+    Some(50),
+    Some(58),
+    Some(60),
+    Some(5),
+    None,
+    None,
+    None,
+    None,
+    Some(0),
+    Some(0),
+];
+
+/// Speech bits of AMR-WB IO frame types 0~8, carried by compact payloads after a 3 bits CMR
+const AMRWBIO_FRAME_BITS: [usize; 9] = [132, 177, 253, 285, 317, 365, 397, 461, 477];
+
+/// EVS ToC mode bit, set for AMR-WB IO frames
+const TOC_AMRWBIO: u8 = 0x20;
+/// EVS ToC Q bit, only meaningful for AMR-WB IO frames
+const TOC_Q: u8 = 0x10;
+
+/// Converts EVS compact and header-full payloads into MIME storage format frames (one ToC
+/// byte followed by speech bits), which is what the EVS decoder expects.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EvsDepacketizer {
+    dedup: FrameDedup,
+}
+
+impl EvsDepacketizer {
+    /// RTP timestamp increment of a single frame, EVS always uses a 16 kHz RTP clock
+    pub fn frame_duration(&self) -> u32 {
+        320
+    }
+
+    fn frame_size(toc: u8) -> Option<usize> {
+        let ft = (toc & 0x0f) as usize;
+        match toc & TOC_AMRWBIO {
+            0 => PRIMARY_FRAME_SIZES[ft],
+            _ => AMRWBIO_FRAME_SIZES[ft],
+        }
+    }
+
+    /// Metadata of a storage format frame, its frame type keeps the AMR-WB IO mode bit
+    fn meta(&self, data: &[u8]) -> Option<FrameMeta> {
+        let toc = data.first()?;
+        let ft = toc & 0x0f;
+        let amrwbio = toc & TOC_AMRWBIO != 0;
+        let bitrate = match amrwbio {
+            // 33 bytes every 20 ms make 13.2 kbps, SID frames 2.4 kbps or 1.75 kbps in AMR-WB IO
+            false => PRIMARY_FRAME_SIZES[ft as usize].map(|size| size as u32 * 8 * 50),
+            true => match ft {
+                0..=8 => Some(AMRWBIO_FRAME_BITS[ft as usize] as u32 * 50),
+                9 => Some(35 * 50),
+                _ => AMRWBIO_FRAME_SIZES[ft as usize].map(|_| 0),
+            },
+        };
+        Some(FrameMeta {
+            frame_type: toc & (TOC_AMRWBIO | 0x0f),
+            bitrate,
+            sid: ft == if amrwbio { 9 } else { 12 },
+            // the Q bit of AMR-WB IO frames, or SPEECH_LOST
+            bfi: (amrwbio && toc & TOC_Q == 0) || ft == 14,
+            duration: Some(self.frame_duration()),
+        })
+    }
+
+    fn storage_frame(toc: u8, bits: &[u8]) -> Box<[u8]> {
+        let mut data = Vec::with_capacity(bits.len() + 1);
+        data.push(toc);
+        data.extend_from_slice(bits);
+        data.into_boxed_slice()
+    }
+
+    /// A compact payload is identified by its size, see TS 26.445 table A.1
+    fn parse_compact(payload: &[u8]) -> Option<Box<[u8]>> {
+        // primary and AMR-WB IO compact sizes never overlap
+        if let Some(ft) = PRIMARY_FRAME_SIZES
+            .iter()
+            .take(13)
+            .position(|s| *s == Some(payload.len()))
+        {
+            return Some(Self::storage_frame(ft as u8, payload));
+        }
+
+        // AMR-WB IO: 3 bits CMR followed by the speech bits
+        let ft = AMRWBIO_FRAME_BITS
+            .iter()
+            .position(|bits| (bits + 3).div_ceil(8) == payload.len())?;
+        let mut bits = vec![];
+        copy_bits(payload, 3, AMRWBIO_FRAME_BITS[ft], &mut bits);
+        Some(Self::storage_frame(TOC_AMRWBIO | TOC_Q | ft as u8, &bits))
+    }
+
+    fn parse_header_full(payload: &[u8]) -> Option<Vec<Box<[u8]>>> {
+        let mut idx = 0;
+        // optional CMR byte has the H bit set
+        if payload.first()? & 0x80 == 0x80 {
+            idx += 1;
+        }
+
+        let mut tocs = vec![];
+        loop {
+            let toc = *payload.get(idx)?;
+            idx += 1;
+            if toc & 0x80 == 0x80 {
+                return None;
+            }
+            Self::frame_size(toc)?;
+            tocs.push(toc);
+            if toc & 0x40 == 0 {
+                break;
+            }
+        }
+
+        let mut frames = Vec::with_capacity(tocs.len());
+        for toc in tocs {
+            let len = Self::frame_size(toc)?;
+            let bits = payload.get(idx..idx + len)?;
+            idx += len;
+            // storage format has no followed bit
+            frames.push(Self::storage_frame(toc & !0x40, bits));
+        }
+
+        // trailing bytes are padding
+        Some(frames)
+    }
+}
+
+impl PayloadDepacketizer for EvsDepacketizer {
+    fn depacketize(&mut self, pkt: &RawRtpPacket, frames: &mut VecDeque<Frame>) -> Result<()> {
+        let payload = pkt.payload();
+        let parsed = match Self::parse_compact(payload) {
+            Some(frame) => vec![frame],
+            None => Self::parse_header_full(payload)
+                .ok_or(Error::DecodeError("Invalid EVS RTP payload"))?,
+        };
+
+        let dur = self.frame_duration();
+        for (i, data) in parsed.into_iter().enumerate() {
+            let ts = pkt.ts().wrapping_add(dur * i as u32);
+            if self.dedup.accept(ts, dur) {
+                frames.push_back(Frame {
+                    ts,
+                    dur: Some(dur),
+                    meta: self.meta(&data),
+                    data,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn missing_frame(&self) -> Box<[u8]> {
+        // NO_DATA
+        Box::new([0x0f])
+    }
+
+    fn frame_meta(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn depacketize(payload: &[u8]) -> Result<VecDeque<Frame>> {
+        let mut data = vec![
+            0x80, 0x7f, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        ];
+        data.extend_from_slice(payload);
+        let mut frames = VecDeque::new();
+        EvsDepacketizer::default().depacketize(&RawRtpPacket::new(&data), &mut frames)?;
+        Ok(frames)
+    }
+
+    #[test]
+    fn test_compact() {
+        let frames = depacketize(&[0x55; 33]).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data[0], 0x04);
+        assert_eq!(&frames[0].data[1..], &[0x55; 33]);
+        assert_eq!(
+            frames[0].meta,
+            Some(FrameMeta {
+                frame_type: 4,
+                bitrate: Some(13200),
+                sid: false,
+                bfi: false,
+                duration: Some(320)
+            })
+        );
+        let depack = EvsDepacketizer::default();
+        let sid = depack.meta(&[0x0c; 7]).unwrap();
+        assert_eq!((sid.bitrate, sid.sid), (Some(2400), true));
+        assert_eq!(depack.meta(&[0x0d]).unwrap().bitrate, None);
+        assert!(depack.meta(&[0x0e]).unwrap().bfi);
+
+        // AMR-WB IO 12.65 kbps, CMR 0b111 followed by 253 bits
+        let mut payload = [0xff; 32];
+        payload[0] = 0xe0 | 0x15;
+        let frames = depacketize(&payload).unwrap();
+        assert_eq!(frames[0].data[0], 0x32);
+        let meta = frames[0].meta.unwrap();
+        assert_eq!((meta.frame_type, meta.bitrate), (0x22, Some(12650)));
+        assert!(!meta.bfi);
+        // Q bit cleared
+        assert!(depack.meta(&[0x22; 33]).unwrap().bfi);
+        assert_eq!(frames[0].data.len(), 33);
+        assert_eq!(frames[0].data[1], 0xaf);
+        assert_eq!(frames[0].data[32], 0xf8);
+    }
+
+    #[test]
+    fn test_header_full() {
+        // CMR, two 13.2 kbps frames, one NO_DATA frame
+        let mut payload = vec![0xff, 0x44, 0x4f, 0x04];
+        payload.extend_from_slice(&[0x11; 33]);
+        payload.extend_from_slice(&[0x22; 33]);
+        let frames = depacketize(&payload).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].data[0], 0x04);
+        assert_eq!(&frames[0].data[1..], &[0x11; 33]);
+        assert_eq!(frames[1].data.as_ref(), &[0x0f]);
+        assert_eq!(frames[1].ts, 320);
+        assert_eq!(&frames[2].data[1..], &[0x22; 33]);
+        assert_eq!(frames[2].ts, 640);
+
+        assert!(depacketize(&[0x04, 0x00]).is_err());
+    }
+}
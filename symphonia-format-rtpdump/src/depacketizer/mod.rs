@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+
+pub use codec_detector::rtp::FrameMeta;
+use codec_detector::rtp::{RawRtpPacket, RtpPacket};
+use symphonia_core::errors::Result;
+
+mod amr;
+mod evs;
+mod t140;
+
+pub use amr::{AmrDepacketizer, AmrPacking};
+pub use evs::EvsDepacketizer;
+pub use t140::{T140Depacketizer, LOST_TEXT};
+
+/// One decoder frame extracted from a RTP payload
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    /// RTP timestamp of the first sample of this frame
+    pub ts: u32,
+    /// Duration in RTP timestamp units, `None` if the payload format doesn't tell
+    pub dur: Option<u32>,
+    pub data: Box<[u8]>,
+    /// What the payload format tells of the frame, `None` if it has no frame types
+    pub meta: Option<FrameMeta>,
+}
+
+/// Turns RTP payloads of one stream into the frame layout expected by a decoder.
+///
+/// A depacketizer instance is created for every track, so implementations are free to keep
+/// state across packets of the same stream.
+pub trait PayloadDepacketizer: Send + Sync {
+    /// Split the payload of `pkt` into decoder frames and append them to `frames`
+    fn depacketize(&mut self, pkt: &RawRtpPacket, frames: &mut VecDeque<Frame>) -> Result<()>;
+
+    /// Frame handed to the decoder in place of a frame that never arrived
+    fn missing_frame(&self) -> Box<[u8]> {
+        Box::new([])
+    }
+
+    /// Append the frames still held back, e.g. waiting for the rest of an interleaving group,
+    /// once the stream ended
+    fn flush(&mut self, _frames: &mut VecDeque<Frame>) {}
+
+    /// Whether the frames carry a [`FrameMeta`], which the decoder of the stream understands
+    fn frame_meta(&self) -> bool {
+        false
+    }
+}
+
+/// Hand the whole RTP payload to the decoder as a single frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Passthrough;
+
+impl PayloadDepacketizer for Passthrough {
+    fn depacketize(&mut self, pkt: &RawRtpPacket, frames: &mut VecDeque<Frame>) -> Result<()> {
+        frames.push_back(Frame {
+            ts: pkt.ts(),
+            dur: None,
+            data: Box::from(pkt.payload()),
+            meta: None,
+        });
+        Ok(())
+    }
+}
+
+/// Tracks the RTP timestamp following the last delivered frame of a stream.
+///
+/// Payload formats with redundancy (e.g. RFC 4867 section 4.5) repeat earlier frames in later
+/// packets: copies of frames already delivered are dropped, while copies of frames whose
+/// original packet was lost are let through and so recover the loss.
+#[derive(Clone, Copy, Debug, Default)]
+struct FrameDedup {
+    next_ts: Option<u32>,
+}
+
+impl FrameDedup {
+    /// How many frames back a redundant copy may be, older frames mean the stream restarted
+    const MAX_REDUNDANCY: u32 = 16;
+
+    /// Whether the frame starting at `ts` lasting `dur` has not been delivered yet
+    fn accept(&mut self, ts: u32, dur: u32) -> bool {
+        if let Some(next_ts) = self.next_ts {
+            let behind = next_ts.wrapping_sub(ts);
+            if behind as i32 > 0 && behind <= dur * Self::MAX_REDUNDANCY {
+                return false;
+            }
+        }
+        self.next_ts = Some(ts.wrapping_add(dur));
+        true
+    }
+}
+
+/// Copy `len` bits starting at bit `offset` of `src` into a MSB first, zero padded byte buffer
+fn copy_bits(src: &[u8], offset: usize, len: usize, dst: &mut Vec<u8>) {
+    let start = dst.len();
+    dst.resize(start + len.div_ceil(8), 0);
+    for i in 0..len {
+        let bit = (src[(offset + i) / 8] >> (7 - (offset + i) % 8)) & 0x01;
+        dst[start + i / 8] |= bit << (7 - i % 8);
+    }
+}
+
+/// Read `len` (<= 8) bits starting at bit `offset` of `src`
+fn read_bits(src: &[u8], offset: usize, len: usize) -> u8 {
+    let mut value = 0;
+    for i in 0..len {
+        value = (value << 1) | ((src[(offset + i) / 8] >> (7 - (offset + i) % 8)) & 0x01);
+    }
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_copy_bits() {
+        let mut dst = vec![];
+        copy_bits(&[0b1010_1100, 0b0111_0000], 4, 7, &mut dst);
+        assert_eq!(dst, vec![0b1100_0110]);
+        assert_eq!(read_bits(&[0b1010_1100, 0b0111_0000], 6, 4), 0b0001);
+    }
+
+    #[test]
+    fn test_frame_dedup() {
+        let mut dedup = FrameDedup::default();
+        assert!(dedup.accept(u32::MAX - 159, 160));
+        assert!(dedup.accept(0, 160));
+        assert!(!dedup.accept(u32::MAX - 159, 160));
+        assert!(!dedup.accept(0, 160));
+        assert!(dedup.accept(480, 160));
+        assert!(!dedup.accept(320, 160));
+        // far behind, the stream restarted
+        assert!(dedup.accept(480u32.wrapping_sub(160 * 17), 160));
+    }
+}
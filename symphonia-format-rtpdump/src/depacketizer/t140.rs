@@ -0,0 +1,170 @@
+//! Real-time text RTP payload format, see RFC 4103
+
+use std::collections::VecDeque;
+
+use codec_detector::rtp::{RawRtpPacket, RtpPacket};
+use symphonia_core::errors::{Error, Result};
+
+use super::{Frame, PayloadDepacketizer};
+
+/// Marks text lost beyond what redundancy recovers (RFC 4103 section 5)
+pub const LOST_TEXT: char = '\u{fffd}';
+
+/// Extracts the T.140 blocks of a real-time text stream, frames being UTF-8 text.
+///
+/// Packets of the redundancy payload type carry previous blocks in RFC 2198 format, those
+/// whose original packet was lost are recovered, a [`LOST_TEXT`] frame stands for the others.
+/// Duplicated and late packets are dropped.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct T140Depacketizer {
+    red_pt: Option<u8>,
+    last_seq: Option<u16>,
+}
+
+impl T140Depacketizer {
+    pub fn new(red_pt: Option<u8>) -> Self {
+        Self {
+            red_pt,
+            last_seq: None,
+        }
+    }
+}
+
+/// Redundant blocks of a RFC 2198 payload, oldest first with their timestamp offset, and the
+/// primary block
+type RedBlocks<'a> = (Vec<(u32, &'a [u8])>, &'a [u8]);
+
+fn parse_red(payload: &[u8]) -> Result<RedBlocks<'_>> {
+    const INVALID: Error = Error::DecodeError("Invalid RTP redundancy payload");
+
+    let mut lens = vec![];
+    let mut pos = 0;
+    loop {
+        let hdr = payload.get(pos).ok_or(INVALID)?;
+        // the last header only holds the payload type of the primary block
+        if hdr & 0x80 == 0 {
+            pos += 1;
+            break;
+        }
+        let hdr = payload.get(pos..pos + 4).ok_or(INVALID)?;
+        let ts_offset = ((hdr[1] as u32) << 6) | (hdr[2] as u32 >> 2);
+        let len = (((hdr[2] & 0x03) as usize) << 8) | hdr[3] as usize;
+        lens.push((ts_offset, len));
+        pos += 4;
+    }
+
+    let mut blocks = Vec::with_capacity(lens.len());
+    for (ts_offset, len) in lens {
+        blocks.push((ts_offset, payload.get(pos..pos + len).ok_or(INVALID)?));
+        pos += len;
+    }
+    Ok((blocks, &payload[pos..]))
+}
+
+impl PayloadDepacketizer for T140Depacketizer {
+    fn depacketize(&mut self, pkt: &RawRtpPacket, frames: &mut VecDeque<Frame>) -> Result<()> {
+        let lost = match self.last_seq {
+            Some(last) => match pkt.seq().wrapping_sub(last) {
+                // duplicated or late
+                0 | 0x8000.. => return Ok(()),
+                d => d as usize - 1,
+            },
+            None => 0,
+        };
+
+        let (redundant, primary) = if self.red_pt == Some(pkt.payload_type().to_u8()) {
+            parse_red(pkt.payload())?
+        } else {
+            (vec![], pkt.payload())
+        };
+        self.last_seq = Some(pkt.seq());
+
+        let recovered = &redundant[redundant.len() - lost.min(redundant.len())..];
+        if lost > recovered.len() {
+            let ts_offset = recovered.first().map_or(0, |(offset, _)| *offset);
+            frames.push_back(Frame {
+                ts: pkt.ts().wrapping_sub(ts_offset),
+                dur: None,
+                data: LOST_TEXT.to_string().into_bytes().into(),
+                meta: None,
+            });
+        }
+        let blocks = recovered.iter().copied().chain([(0, primary)]);
+        for (ts_offset, block) in blocks.filter(|(_, b)| !b.is_empty()) {
+            frames.push_back(Frame {
+                ts: pkt.ts().wrapping_sub(ts_offset),
+                dur: None,
+                data: Box::from(block),
+                meta: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Packet of payload type 100 carrying `blocks` as redundancy, the primary one last
+    fn red(seq: u16, ts: u32, blocks: &[(u32, &str)]) -> Vec<u8> {
+        let mut data = vec![0x80, 100];
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&ts.to_be_bytes());
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        let (primary, redundant) = blocks.split_last().unwrap();
+        for (ts_offset, text) in redundant {
+            let len = text.len() as u32;
+            data.extend_from_slice(&(0x8000_0000 | 98 << 24 | ts_offset << 10 | len).to_be_bytes());
+        }
+        data.push(98);
+        for (_, text) in redundant.iter().chain([primary]) {
+            data.extend_from_slice(text.as_bytes());
+        }
+        data
+    }
+
+    fn texts(frames: &VecDeque<Frame>) -> Vec<(u32, String)> {
+        frames
+            .iter()
+            .map(|f| (f.ts, String::from_utf8(f.data.to_vec()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_redundancy() {
+        let mut depack = T140Depacketizer::new(Some(100));
+        let mut frames = VecDeque::new();
+        let mut push = |data: Vec<u8>, frames: &mut VecDeque<Frame>| {
+            depack.depacketize(&RawRtpPacket::new(&data), frames)
+        };
+
+        push(red(1, 1000, &[(0, ""), (0, ""), (0, "he")]), &mut frames).unwrap();
+        // seq 2 lost, recovered from seq 3; then seq 3 again
+        let pkt = red(3, 1600, &[(600, "he"), (300, "ll"), (0, "o")]);
+        push(pkt.clone(), &mut frames).unwrap();
+        push(pkt, &mut frames).unwrap();
+        // seq 4 to 6 lost, only two generations of redundancy
+        push(
+            red(7, 2800, &[(600, "wo"), (300, "r"), (0, "ld")]),
+            &mut frames,
+        )
+        .unwrap();
+        assert_eq!(
+            texts(&frames),
+            [
+                (1000, "he".to_string()),
+                (1300, "ll".to_string()),
+                (1600, "o".to_string()),
+                (2200, LOST_TEXT.to_string()),
+                (2200, "wo".to_string()),
+                (2500, "r".to_string()),
+                (2800, "ld".to_string()),
+            ]
+        );
+
+        let mut data = red(8, 3100, &[(0, "x")]);
+        data[12] = 0x80;
+        assert!(push(data, &mut frames).is_err());
+    }
+}
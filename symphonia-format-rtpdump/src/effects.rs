@@ -0,0 +1,391 @@
+use std::f32::consts::PI;
+use std::fmt::{self, Display};
+
+use symphonia_core::errors::{Error, Result};
+
+use crate::TimeStretch;
+
+/// One stage of an [`EffectChain`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// Second order Butterworth high-pass at this frequency, removing mains hum and rumble
+    HighPass(f32),
+    /// Turns the audio down whenever the band above this frequency makes more than a third of
+    /// its level, as sibilants do
+    DeEsser(f32),
+    /// Brings the level of speech to this RMS level in dBFS, by at most 20 dB either way
+    Agc(f32),
+    /// Plays the audio this many times as fast without changing its pitch, see
+    /// [`TimeStretch`]
+    Speed(f64),
+}
+
+impl Effect {
+    fn parse(spec: &str) -> Result<Self> {
+        let (name, value) = match spec.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (spec, None),
+        };
+        let value = |default: Option<f64>| match value {
+            Some(value) => value
+                .parse::<f64>()
+                .map_err(|_| Error::DecodeError("Invalid effect parameter")),
+            None => default.ok_or(Error::DecodeError("Missing effect parameter")),
+        };
+        let effect = match name {
+            "highpass" => Effect::HighPass(value(Some(100.0))? as f32),
+            "deesser" => Effect::DeEsser(value(Some(5000.0))? as f32),
+            "agc" => Effect::Agc(value(Some(-20.0))? as f32),
+            "speed" => Effect::Speed(value(None)?),
+            _ => return Err(Error::Unsupported("Unknown effect")),
+        };
+        let valid = match effect {
+            Effect::HighPass(hz) | Effect::DeEsser(hz) => hz > 0.0,
+            Effect::Agc(dbfs) => (-60.0..=0.0).contains(&dbfs),
+            Effect::Speed(speed) => speed > 0.0,
+        };
+        match valid {
+            true => Ok(effect),
+            false => Err(Error::DecodeError("Invalid effect parameter")),
+        }
+    }
+}
+
+impl Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::HighPass(hz) => write!(f, "highpass={hz}"),
+            Effect::DeEsser(hz) => write!(f, "deesser={hz}"),
+            Effect::Agc(dbfs) => write!(f, "agc={dbfs}"),
+            Effect::Speed(speed) => write!(f, "speed={speed}"),
+        }
+    }
+}
+
+/// Post-processing of decoded mono audio, effects applied in order.
+///
+/// Written as effects separated by commas or lines, `#` starting a comment, e.g.
+/// `highpass=120, deesser, agc=-18, speed=1.5`. Without a value, `highpass` is at 100 Hz,
+/// `deesser` at 5 kHz and `agc` at -20 dBFS.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EffectChain(pub Vec<Effect>);
+
+impl EffectChain {
+    /// Parse effects written like [`EffectChain`] prints them, e.g. from a config file
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut effects = vec![];
+        for line in spec.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            for effect in line.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                effects.push(Effect::parse(effect)?);
+            }
+        }
+        Ok(Self(effects))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// How many times as fast the audio plays once through the chain
+    pub fn speed(&self) -> f64 {
+        self.0
+            .iter()
+            .map(|e| match e {
+                Effect::Speed(speed) => *speed,
+                _ => 1.0,
+            })
+            .product()
+    }
+
+    /// Effects of the chain for a stream of audio at `sample_rate`
+    pub fn processor(&self, sample_rate: u32) -> EffectProcessor {
+        let fs = sample_rate.max(1) as f32;
+        let stages = self
+            .0
+            .iter()
+            .map(|effect| match *effect {
+                Effect::HighPass(hz) => Stage::HighPass(Biquad::high_pass(hz, fs)),
+                Effect::DeEsser(hz) => Stage::DeEsser {
+                    band: Biquad::high_pass(hz, fs),
+                    band_env: Envelope::new(0.001, 0.05, fs),
+                    env: Envelope::new(0.001, 0.05, fs),
+                },
+                Effect::Agc(dbfs) => Stage::Agc {
+                    target: 32768.0 * 10f32.powf(dbfs / 20.0),
+                    power: Envelope::new(0.3, 0.3, fs),
+                    gain: 1.0,
+                    smoothing: smoothing(0.05, fs),
+                },
+                Effect::Speed(speed) => Stage::Stretch(TimeStretch::new(sample_rate, speed)),
+            })
+            .collect();
+        EffectProcessor { stages }
+    }
+
+    /// Run a whole mono track through the chain
+    pub fn apply(&self, pcm: &[i16], sample_rate: u32) -> Vec<i16> {
+        let mut processor = self.processor(sample_rate);
+        let mut out = vec![];
+        processor.process(pcm, &mut out);
+        processor.flush(&mut out);
+        out
+    }
+}
+
+impl Display for EffectChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, effect) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{effect}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of the effects of an [`EffectChain`] running over a stream
+#[derive(Clone, Debug)]
+pub struct EffectProcessor {
+    stages: Vec<Stage>,
+}
+
+impl EffectProcessor {
+    /// Run `pcm` through the effects, appending to `out` what they output so far
+    pub fn process(&mut self, pcm: &[i16], out: &mut Vec<i16>) {
+        self.run(pcm, false, out);
+    }
+
+    /// Append what the effects still hold back once the stream ended
+    pub fn flush(&mut self, out: &mut Vec<i16>) {
+        self.run(&[], true, out);
+    }
+
+    fn run(&mut self, pcm: &[i16], flush: bool, out: &mut Vec<i16>) {
+        let mut buf = pcm.iter().map(|s| *s as f32).collect::<Vec<_>>();
+        for stage in &mut self.stages {
+            match stage {
+                Stage::HighPass(filter) => buf.iter_mut().for_each(|s| *s = filter.run(*s)),
+                Stage::DeEsser {
+                    band,
+                    band_env,
+                    env,
+                } => {
+                    for s in &mut buf {
+                        let high = band.run(*s);
+                        let level = env.run(s.abs());
+                        let high_level = band_env.run(high.abs());
+                        // the whole signal rather than the band, the filter shifts its phase
+                        if high_level * 3.0 > level {
+                            *s *= level / (high_level * 3.0);
+                        }
+                    }
+                }
+                Stage::Agc {
+                    target,
+                    power,
+                    gain,
+                    smoothing,
+                } => {
+                    for s in &mut buf {
+                        let rms = power.run(*s * *s).sqrt();
+                        // silence keeps the gain of the speech before it
+                        if rms > 32.0 {
+                            let wanted = (*target / rms).clamp(0.1, 10.0);
+                            *gain += (wanted - *gain) * *smoothing;
+                        }
+                        *s *= *gain;
+                    }
+                }
+                Stage::Stretch(stretch) => {
+                    let pcm = buf.iter().map(|s| to_i16(*s)).collect::<Vec<_>>();
+                    let mut stretched = vec![];
+                    stretch.process(&pcm, &mut stretched);
+                    if flush {
+                        stretch.flush(&mut stretched);
+                    }
+                    buf = stretched.iter().map(|s| *s as f32).collect();
+                }
+            }
+        }
+        out.extend(buf.iter().map(|s| to_i16(*s)));
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Stage {
+    HighPass(Biquad),
+    DeEsser {
+        band: Biquad,
+        band_env: Envelope,
+        env: Envelope,
+    },
+    Agc {
+        target: f32,
+        power: Envelope,
+        gain: f32,
+        smoothing: f32,
+    },
+    Stretch(TimeStretch),
+}
+
+/// Biquad filter of the Audio EQ Cookbook (Robert Bristow-Johnson)
+#[derive(Clone, Copy, Debug, Default)]
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    x: [f32; 2],
+    y: [f32; 2],
+}
+
+impl Biquad {
+    fn high_pass(hz: f32, fs: f32) -> Self {
+        let w0 = 2.0 * PI * hz.min(fs * 0.45) / fs;
+        let alpha = w0.sin() / std::f32::consts::SQRT_2;
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b: [
+                (1.0 + cos) / 2.0 / a0,
+                -(1.0 + cos) / a0,
+                (1.0 + cos) / 2.0 / a0,
+            ],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            ..Default::default()
+        }
+    }
+
+    fn run(&mut self, x: f32) -> f32 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// Follows a level, rising and falling with the given time constants in seconds
+#[derive(Clone, Copy, Debug)]
+struct Envelope {
+    attack: f32,
+    release: f32,
+    level: f32,
+}
+
+impl Envelope {
+    fn new(attack: f32, release: f32, fs: f32) -> Self {
+        Self {
+            attack: smoothing(attack, fs),
+            release: smoothing(release, fs),
+            level: 0.0,
+        }
+    }
+
+    fn run(&mut self, x: f32) -> f32 {
+        let k = if x > self.level {
+            self.attack
+        } else {
+            self.release
+        };
+        self.level += (x - self.level) * k;
+        self.level
+    }
+}
+
+/// Coefficient of a one-pole smoother with time constant `seconds`
+fn smoothing(seconds: f32, fs: f32) -> f32 {
+    1.0 - (-1.0 / (seconds * fs)).exp()
+}
+
+fn to_i16(s: f32) -> i16 {
+    s.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tone(n: usize, hz: f32, level: f32, fs: u32) -> Vec<i16> {
+        (0..n)
+            .map(|i| (level * (2.0 * PI * hz * i as f32 / fs as f32).sin()) as i16)
+            .collect()
+    }
+
+    fn rms(pcm: &[i16]) -> f32 {
+        (pcm.iter().map(|s| (*s as f32).powi(2)).sum::<f32>() / pcm.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_parse() {
+        let chain =
+            EffectChain::parse("highpass=120, deesser\n# louder\nagc=-18,speed=1.5").unwrap();
+        assert_eq!(
+            chain.0,
+            vec![
+                Effect::HighPass(120.0),
+                Effect::DeEsser(5000.0),
+                Effect::Agc(-18.0),
+                Effect::Speed(1.5)
+            ]
+        );
+        assert_eq!(
+            chain.to_string(),
+            "highpass=120, deesser=5000, agc=-18, speed=1.5"
+        );
+        assert_eq!(EffectChain::parse(&chain.to_string()).unwrap(), chain);
+        assert_eq!(chain.speed(), 1.5);
+        assert!(EffectChain::parse("").unwrap().is_empty());
+
+        assert!(EffectChain::parse("reverb").is_err());
+        assert!(EffectChain::parse("speed").is_err());
+        assert!(EffectChain::parse("highpass=-5").is_err());
+        assert!(EffectChain::parse("agc=loud").is_err());
+    }
+
+    #[test]
+    fn test_effects() {
+        // 50 Hz hum under a 1 kHz tone
+        let hum = tone(16000, 50.0, 4000.0, 16000);
+        let speech = tone(16000, 1000.0, 4000.0, 16000);
+        let mixed = hum
+            .iter()
+            .zip(&speech)
+            .map(|(a, b)| a + b)
+            .collect::<Vec<_>>();
+        let filtered = EffectChain(vec![Effect::HighPass(200.0)]).apply(&mixed, 16000);
+        // what is left at 50 Hz, whatever its phase
+        let at_50hz = |pcm: &[i16]| {
+            let (re, im) = pcm.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, s)| {
+                let x = 2.0 * PI * 50.0 * i as f32 / 16000.0;
+                (re + *s as f32 * x.cos(), im + *s as f32 * x.sin())
+            });
+            (re * re + im * im).sqrt() / pcm.len() as f32
+        };
+        assert!(at_50hz(&filtered[4000..]) < at_50hz(&mixed[4000..]) * 0.1);
+        assert!((rms(&filtered[4000..]) / rms(&speech) - 1.0).abs() < 0.05);
+
+        // a loud 6 kHz hiss over a 500 Hz tone is turned down, the tone isn't
+        let deesser = EffectChain(vec![Effect::DeEsser(4000.0)]);
+        let hiss = tone(16000, 6000.0, 8000.0, 16000);
+        assert!(rms(&deesser.apply(&hiss, 16000)[4000..]) < rms(&hiss) * 0.4);
+        let low = tone(16000, 500.0, 8000.0, 16000);
+        assert!(rms(&deesser.apply(&low, 16000)[4000..]) > rms(&low) * 0.95);
+
+        // a quiet tone is brought up to -20 dBFS
+        let quiet = tone(32000, 440.0, 500.0, 16000);
+        let out = EffectChain(vec![Effect::Agc(-20.0)]).apply(&quiet, 16000);
+        let level = 20.0 * (rms(&out[16000..]) / 32768.0).log10();
+        assert!((level + 20.0).abs() < 1.0, "{level} dBFS");
+
+        let chain = EffectChain::parse("highpass, agc, speed=2").unwrap();
+        let mut processor = chain.processor(16000);
+        let mut out = vec![];
+        for chunk in quiet.chunks(320) {
+            processor.process(chunk, &mut out);
+        }
+        processor.flush(&mut out);
+        assert_eq!(out.len(), 16000);
+    }
+}
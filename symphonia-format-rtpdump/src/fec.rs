@@ -0,0 +1,391 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use codec_detector::rtp::{RawRtpPacket, RtpPacket, SeqNum};
+
+/// Media packets of a stream kept to recover lost ones with later FEC packets
+const HISTORY_LEN: usize = 256;
+/// FEC packets waiting for enough media packets to recover one
+const PENDING_FEC: usize = 32;
+
+/// Forward error correction scheme of a payload type, see
+/// [`RtpdumpReader::set_fec_payload_type`](crate::RtpdumpReader::set_fec_payload_type)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FecScheme {
+    /// RFC 5109 ULP-FEC sent on its own payload type, with the SSRC of the media it protects.
+    /// Only the first protection level is used.
+    Ulpfec,
+    /// RFC 8627 FlexFEC with a flexible mask, naming the SSRC it protects in its CSRC list.
+    /// Packets protecting several streams, retransmissions and fixed masks are ignored.
+    Flexfec,
+}
+
+/// Parsed FEC packet, protecting packets of a single stream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FecPacket {
+    /// SSRC of the media stream
+    pub ssrc: u32,
+    /// Sequence numbers of the protected packets
+    pub seqs: Vec<u16>,
+    /// XOR of the first two header bytes of the protected packets
+    pub header: [u8; 2],
+    pub ts_recovery: u32,
+    /// XOR of the lengths of the protected packets, without their 12 bytes fixed header
+    pub len_recovery: u16,
+    /// XOR of the protected packets following their fixed header, zero padded
+    pub payload: Vec<u8>,
+}
+
+impl FecPacket {
+    pub fn parse(scheme: FecScheme, rtp: &RawRtpPacket) -> Option<Self> {
+        match scheme {
+            FecScheme::Ulpfec => Self::parse_ulpfec(rtp),
+            FecScheme::Flexfec => Self::parse_flexfec(rtp),
+        }
+    }
+
+    /// RFC 5109 section 7.3: FEC header, then a ULP level header per level
+    fn parse_ulpfec(rtp: &RawRtpPacket) -> Option<Self> {
+        let data = rtp.payload();
+        let hdr = data.get(..10)?;
+        // E bit, reserved for header extensions
+        if hdr[0] & 0x80 != 0 {
+            return None;
+        }
+        let long_mask = hdr[0] & 0x40 != 0;
+        let sn_base = u16::from_be_bytes([hdr[2], hdr[3]]);
+        let level_len = if long_mask { 8 } else { 4 };
+        let level = data.get(10..10 + level_len)?;
+        let protection_len = u16::from_be_bytes([level[0], level[1]]) as usize;
+
+        let mut mask = level[2..]
+            .iter()
+            .fold(0u64, |mask, b| mask << 8 | *b as u64);
+        let bits = (level_len - 2) * 8;
+        mask <<= 64 - bits;
+        let seqs = (0..bits)
+            .filter(|i| mask & (1 << (63 - i)) != 0)
+            .map(|i| sn_base.wrapping_add(i as u16))
+            .collect();
+
+        let payload = data.get(10 + level_len..)?;
+        Some(Self {
+            ssrc: rtp.ssrc(),
+            seqs,
+            header: [hdr[0], hdr[1]],
+            ts_recovery: u32::from_be_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]),
+            len_recovery: u16::from_be_bytes([hdr[8], hdr[9]]),
+            payload: payload[..protection_len.min(payload.len())].to_vec(),
+        })
+    }
+
+    /// RFC 8627 section 4.2.2: FEC header with a flexible mask per protected stream
+    fn parse_flexfec(rtp: &RawRtpPacket) -> Option<Self> {
+        // the CSRC list holds the protected SSRCs
+        let [ssrc] = rtp.csrcs()[..] else {
+            return None;
+        };
+        let data = rtp.payload();
+        let hdr = data.get(..12)?;
+        // R (retransmission) and F (fixed mask) bits
+        if hdr[0] & 0xc0 != 0 {
+            return None;
+        }
+
+        let sn_base = u16::from_be_bytes([hdr[8], hdr[9]]);
+        let mut seqs = vec![];
+        let mut push = |bits: u64, cnt: usize, first: usize| {
+            for i in 0..cnt {
+                if bits & (1 << (cnt - 1 - i)) != 0 {
+                    seqs.push(sn_base.wrapping_add((first + i) as u16));
+                }
+            }
+        };
+        let mask0 = u16::from_be_bytes([hdr[10], hdr[11]]);
+        push((mask0 & 0x7fff) as u64, 15, 0);
+        let mut len = 12;
+        if mask0 & 0x8000 == 0 {
+            let m = data.get(12..16)?;
+            let mask1 = u32::from_be_bytes([m[0], m[1], m[2], m[3]]);
+            push((mask1 & 0x7fff_ffff) as u64, 31, 15);
+            len = 16;
+            if mask1 & 0x8000_0000 == 0 {
+                let m = data.get(16..24)?;
+                push(u64::from_be_bytes(m.try_into().ok()?), 64, 46);
+                len = 24;
+            }
+        }
+
+        Some(Self {
+            ssrc,
+            seqs,
+            header: [hdr[0], hdr[1]],
+            ts_recovery: u32::from_be_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]),
+            len_recovery: u16::from_be_bytes([hdr[2], hdr[3]]),
+            payload: data[len..].to_vec(),
+        })
+    }
+}
+
+/// Recovers lost media packets of a stream from FEC packets, by XORing a FEC packet with
+/// every other packet it protects (RFC 5109 section 8)
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FecDecoder {
+    history: VecDeque<(SeqNum, Bytes)>,
+    pending: VecDeque<FecPacket>,
+}
+
+impl FecDecoder {
+    /// Record a media packet, returns the packets it allowed to recover
+    pub fn on_media(&mut self, seq: SeqNum, data: &Bytes) -> Vec<Bytes> {
+        if self.has(seq) {
+            return vec![];
+        }
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((seq, data.clone()));
+        self.recover_pending()
+    }
+
+    /// Record a FEC packet, returns the packets it allowed to recover
+    pub fn on_fec(&mut self, fec: FecPacket) -> Vec<Bytes> {
+        if self.pending.len() == PENDING_FEC {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(fec);
+        self.recover_pending()
+    }
+
+    fn has(&self, seq: SeqNum) -> bool {
+        self.history.iter().any(|(s, _)| *s == seq)
+    }
+
+    fn get(&self, seq: SeqNum) -> Option<&Bytes> {
+        self.history.iter().find(|(s, _)| *s == seq).map(|(_, p)| p)
+    }
+
+    /// Recover with every FEC packet missing a single protected packet, until none does.
+    /// FEC packets missing none are dropped, those missing more wait for recovered packets.
+    fn recover_pending(&mut self) -> Vec<Bytes> {
+        let mut recovered = vec![];
+        loop {
+            let mut progress = false;
+            let mut idx = 0;
+            while idx < self.pending.len() {
+                let fec = &self.pending[idx];
+                let missing = fec
+                    .seqs
+                    .iter()
+                    .filter(|seq| !self.has(SeqNum(**seq)))
+                    .collect::<Vec<_>>();
+                match missing[..] {
+                    [] => {
+                        self.pending.remove(idx);
+                    }
+                    [seq] => {
+                        let seq = SeqNum(*seq);
+                        let fec = self.pending.remove(idx).unwrap_or_default();
+                        if let Some(pkt) = self.recover(&fec, seq) {
+                            if self.history.len() == HISTORY_LEN {
+                                self.history.pop_front();
+                            }
+                            self.history.push_back((seq, pkt.clone()));
+                            recovered.push(pkt);
+                            progress = true;
+                        }
+                    }
+                    _ => idx += 1,
+                }
+            }
+            if !progress {
+                return recovered;
+            }
+        }
+    }
+
+    /// Rebuild packet `seq` from `fec` and the other packets it protects
+    fn recover(&self, fec: &FecPacket, seq: SeqNum) -> Option<Bytes> {
+        let mut header = fec.header;
+        let mut ts = fec.ts_recovery;
+        let mut len = fec.len_recovery;
+        let mut payload = fec.payload.clone();
+        for pkt in fec.seqs.iter().filter(|s| **s != seq.0) {
+            let pkt = self.get(SeqNum(*pkt))?;
+            let rest = pkt.get(12..)?;
+            header[0] ^= pkt[0];
+            header[1] ^= pkt[1];
+            ts ^= u32::from_be_bytes([pkt[4], pkt[5], pkt[6], pkt[7]]);
+            len ^= rest.len() as u16;
+            for (p, b) in payload.iter_mut().zip(rest) {
+                *p ^= b;
+            }
+        }
+        // only the protected part of the packet could be recovered
+        let len = len as usize;
+        if len > payload.len() {
+            return None;
+        }
+
+        let mut pkt = Vec::with_capacity(12 + len);
+        pkt.push(0x80 | (header[0] & 0x3f));
+        pkt.push(header[1]);
+        pkt.extend_from_slice(&seq.0.to_be_bytes());
+        pkt.extend_from_slice(&ts.to_be_bytes());
+        pkt.extend_from_slice(&fec.ssrc.to_be_bytes());
+        pkt.extend_from_slice(&payload[..len]);
+        Some(pkt.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::test_util::{evs_packets, rtpdump};
+    use crate::RtpdumpReader;
+
+    fn media(seq: u16, payload: &[u8]) -> Bytes {
+        let mut pkt = vec![0x80, 0x60];
+        pkt.extend_from_slice(&seq.to_be_bytes());
+        pkt.extend_from_slice(&(seq as u32 * 320).to_be_bytes());
+        pkt.extend_from_slice(&0x1234u32.to_be_bytes());
+        pkt.extend_from_slice(payload);
+        pkt.into()
+    }
+
+    /// ULP-FEC packet protecting `pkts`, whose sequence numbers start at `sn_base`
+    fn ulpfec(sn_base: u16, mask: u16, pkts: &[Bytes]) -> Vec<u8> {
+        let mut hdr = [0u8; 10];
+        let mut len = 0u16;
+        let mut xor = vec![];
+        for pkt in pkts {
+            hdr[0] ^= pkt[0];
+            hdr[1] ^= pkt[1];
+            for i in 4..8 {
+                hdr[i] ^= pkt[i];
+            }
+            len ^= pkt.len() as u16 - 12;
+            xor.resize(xor.len().max(pkt.len() - 12), 0);
+            for (x, b) in xor.iter_mut().zip(&pkt[12..]) {
+                *x ^= b;
+            }
+        }
+        hdr[0] &= 0x3f;
+        hdr[2..4].copy_from_slice(&sn_base.to_be_bytes());
+        hdr[8..10].copy_from_slice(&len.to_be_bytes());
+
+        let mut fec = vec![0x80, 0x61, 0, 99, 0, 0, 0, 0, 0, 0, 0x12, 0x34];
+        fec.extend_from_slice(&hdr);
+        fec.extend_from_slice(&(xor.len() as u16).to_be_bytes());
+        fec.extend_from_slice(&mask.to_be_bytes());
+        fec.extend_from_slice(&xor);
+        fec
+    }
+
+    #[test]
+    fn test_ulpfec_recovery() {
+        let pkts = [
+            media(10, &[1, 2, 3]),
+            media(11, &[4, 5, 6, 7, 8]),
+            media(12, &[9]),
+        ];
+        // protects 10, 11 and 12
+        let fec = ulpfec(10, 0xe000, &pkts);
+        let fec = FecPacket::parse(FecScheme::Ulpfec, &RawRtpPacket::new(&fec)).unwrap();
+        assert_eq!(fec.seqs, vec![10, 11, 12]);
+
+        // the FEC packet arrives before the packet after the lost one
+        let mut decoder = FecDecoder::default();
+        assert!(decoder.on_media(SeqNum(10), &pkts[0]).is_empty());
+        assert!(decoder.on_fec(fec.clone()).is_empty());
+        assert_eq!(
+            decoder.on_media(SeqNum(12), &pkts[2]),
+            vec![pkts[1].clone()]
+        );
+
+        // nothing to recover if nothing was lost
+        let mut decoder = FecDecoder::default();
+        for (i, pkt) in pkts.iter().enumerate() {
+            decoder.on_media(SeqNum(10 + i as u16), pkt);
+        }
+        assert!(decoder.on_fec(fec).is_empty());
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn test_flexfec_mask() {
+        let mut fec = vec![0x81, 0x62, 0, 1, 0, 0, 0, 0, 0, 0, 0x56, 0x78];
+        fec.extend_from_slice(&0x1234u32.to_be_bytes());
+        fec.extend_from_slice(&[0x00, 0xe0, 0x00, 0x62, 0, 0, 0, 0]);
+        // SN base 100, protects 100 and 114, then 115 and 145 with the second mask
+        fec.extend_from_slice(&[0x00, 0x64, 0x40, 0x01]);
+        fec.extend_from_slice(&[0xc0, 0x00, 0x00, 0x01]);
+        fec.push(0xaa);
+        let fec = FecPacket::parse(FecScheme::Flexfec, &RawRtpPacket::new(&fec)).unwrap();
+        assert_eq!(fec.ssrc, 0x1234);
+        assert_eq!(fec.seqs, vec![100, 114, 115, 145]);
+        assert_eq!(fec.len_recovery, 0x62);
+        assert_eq!(fec.payload, vec![0xaa]);
+    }
+
+    #[test]
+    fn test_fec_recovery() {
+        // packet 5 is lost, a ULP-FEC packet with payload type 98 protects packets 4 to 6
+        let pkts = evs_packets(10, 0);
+        let rtp = |i: usize| &pkts[i].1;
+
+        let mut fec_hdr = [0u8; 10];
+        let mut xor = [0u8; 33];
+        for pkt in [rtp(4), rtp(5), rtp(6)] {
+            fec_hdr[0] ^= pkt[0];
+            fec_hdr[1] ^= pkt[1];
+            for i in 4..8 {
+                fec_hdr[i] ^= pkt[i];
+            }
+            for (x, b) in xor.iter_mut().zip(&pkt[12..]) {
+                *x ^= b;
+            }
+        }
+        fec_hdr[0] &= 0x3f;
+        fec_hdr[2..4].copy_from_slice(&4u16.to_be_bytes());
+        fec_hdr[8..10].copy_from_slice(&33u16.to_be_bytes());
+        let mut fec = vec![0x80, 98, 0, 0, 0, 0, 0, 0, 0, 0, 0x12, 0x34];
+        fec.extend_from_slice(&fec_hdr);
+        fec.extend_from_slice(&[0, 33, 0xe0, 0x00]);
+        fec.extend_from_slice(&xor);
+
+        let mut lossy = pkts.clone();
+        lossy.remove(5);
+        lossy.insert(6, (120, fec));
+        let lossy = rtpdump(lossy);
+
+        let read = |fec: bool| {
+            let mss =
+                MediaSourceStream::new(Box::new(Cursor::new(lossy.clone())), Default::default());
+            let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+            if fec {
+                reader.set_fec_payload_type(98, FecScheme::Ulpfec);
+            }
+            let mut frames = vec![];
+            while let Ok(pkt) = reader.next_packet() {
+                frames.push(pkt.data.to_vec());
+            }
+            (frames, reader.recovered_packets())
+        };
+
+        let (frames, recovered) = read(false);
+        assert_eq!(recovered, 0);
+        // NO_DATA stands in for the lost frame
+        assert_eq!(frames[5], vec![0x0f]);
+
+        let (frames, recovered) = read(true);
+        assert_eq!(recovered, 1);
+        assert_eq!(frames.len(), 10);
+        assert_eq!(&frames[5][1..], &rtp(5)[12..]);
+    }
+}
@@ -0,0 +1,94 @@
+use codec_detector::rtp::RtpPacket;
+
+/// Packets of a stream its fingerprint covers
+pub const FINGERPRINT_PACKETS: usize = 8;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Identifies the media of a RTP stream by its first packets, to tell apart the copies of a
+/// stream captured by redundant taps.
+///
+/// The SSRC is kept to report which stream a copy duplicates, but [`Self::same_media`] doesn't
+/// compare it: a copy relayed under another SSRC is still a copy.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct StreamFingerprint {
+    pub ssrc: u32,
+    /// Sequence number and RTP timestamp of the first packets
+    pub head: Vec<(u16, u32)>,
+    /// FNV-1a hash of the payloads of the first packets
+    pub payload_hash: u64,
+}
+
+impl StreamFingerprint {
+    pub fn new(ssrc: u32) -> Self {
+        Self {
+            ssrc,
+            head: Vec::with_capacity(FINGERPRINT_PACKETS),
+            payload_hash: FNV_OFFSET,
+        }
+    }
+
+    /// Add a packet of the stream, in capture order, ignored once the fingerprint is complete
+    pub fn on_pkt(&mut self, pkt: &impl RtpPacket) {
+        if self.is_complete() {
+            return;
+        }
+        self.head.push((pkt.seq(), pkt.ts()));
+        for b in pkt.payload() {
+            self.payload_hash = (self.payload_hash ^ *b as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Whether the first [`FINGERPRINT_PACKETS`] packets were seen, shorter streams are never
+    /// considered copies
+    pub fn is_complete(&self) -> bool {
+        self.head.len() == FINGERPRINT_PACKETS
+    }
+
+    /// Whether both streams start with the same packets
+    pub fn same_media(&self, other: &Self) -> bool {
+        self.is_complete()
+            && other.is_complete()
+            && self.head == other.head
+            && self.payload_hash == other.payload_hash
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use codec_detector::rtp::RawRtpPacket;
+
+    use super::*;
+
+    fn pkt(ssrc: u32, seq: u16, payload: u8) -> Vec<u8> {
+        let mut data = vec![0x80, 0x60];
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&(seq as u32 * 160).to_be_bytes());
+        data.extend_from_slice(&ssrc.to_be_bytes());
+        data.push(payload);
+        data
+    }
+
+    #[test]
+    fn test_same_media() {
+        let fingerprint = |ssrc: u32, payloads: &[u8]| {
+            let mut fp = StreamFingerprint::new(ssrc);
+            for (seq, payload) in payloads.iter().enumerate() {
+                fp.on_pkt(&RawRtpPacket::new(&pkt(ssrc, seq as u16, *payload)));
+            }
+            fp
+        };
+
+        let a = fingerprint(1, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(a.is_complete());
+        assert!(a.same_media(&fingerprint(2, &[1, 2, 3, 4, 5, 6, 7, 8])));
+        assert!(!a.same_media(&fingerprint(1, &[1, 2, 3, 4, 5, 6, 7, 0])));
+
+        let short = fingerprint(1, &[1, 2, 3]);
+        assert!(!short.is_complete());
+        assert!(!short.same_media(&short));
+    }
+}
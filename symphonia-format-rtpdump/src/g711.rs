@@ -0,0 +1,133 @@
+//! G.711 companding, see ITU-T G.711
+
+/// Segment ends of the A-law and µ-law encoders
+const ALAW_SEG_END: [i32; 8] = [0x1f, 0x3f, 0x7f, 0xff, 0x1ff, 0x3ff, 0x7ff, 0xfff];
+const ULAW_SEG_END: [i32; 8] = [0x3f, 0x7f, 0xff, 0x1ff, 0x3ff, 0x7ff, 0xfff, 0x1fff];
+const ULAW_BIAS: i32 = 0x84;
+const ULAW_CLIP: i32 = 8159;
+
+/// Companding law of a G.711 stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum G711Law {
+    /// PCMA, used outside North America and Japan
+    ALaw,
+    /// PCMU
+    MuLaw,
+}
+
+impl G711Law {
+    /// Static payload type of RFC 3551
+    pub fn payload_type(&self) -> u8 {
+        match self {
+            Self::ALaw => 8,
+            Self::MuLaw => 0,
+        }
+    }
+
+    pub fn encode(&self, sample: i16) -> u8 {
+        match self {
+            Self::ALaw => alaw_encode(sample),
+            Self::MuLaw => ulaw_encode(sample),
+        }
+    }
+
+    pub fn decode(&self, code: u8) -> i16 {
+        match self {
+            Self::ALaw => alaw_decode(code),
+            Self::MuLaw => ulaw_decode(code),
+        }
+    }
+}
+
+fn segment(value: i32, ends: &[i32; 8]) -> usize {
+    ends.iter().position(|end| value <= *end).unwrap_or(8)
+}
+
+fn alaw_encode(sample: i16) -> u8 {
+    let mut pcm = sample as i32 >> 3;
+    let mask = if pcm >= 0 {
+        0xd5
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+    let seg = segment(pcm, &ALAW_SEG_END);
+    if seg >= 8 {
+        return 0x7f ^ mask;
+    }
+    let shift = if seg < 2 { 1 } else { seg };
+    ((seg << 4) as u8 | ((pcm >> shift) & 0x0f) as u8) ^ mask
+}
+
+fn alaw_decode(code: u8) -> i16 {
+    let code = code ^ 0x55;
+    let mut t = ((code & 0x0f) as i32) << 4;
+    match (code & 0x70) >> 4 {
+        0 => t += 8,
+        1 => t += 0x108,
+        seg => t = (t + 0x108) << (seg - 1),
+    }
+    if code & 0x80 != 0 {
+        t as i16
+    } else {
+        -t as i16
+    }
+}
+
+fn ulaw_encode(sample: i16) -> u8 {
+    let mut pcm = sample as i32 >> 2;
+    let mask = if pcm < 0 {
+        pcm = -pcm;
+        0x7f
+    } else {
+        0xff
+    };
+    pcm = pcm.min(ULAW_CLIP) + (ULAW_BIAS >> 2);
+    let seg = segment(pcm, &ULAW_SEG_END);
+    if seg >= 8 {
+        return 0x7f ^ mask;
+    }
+    ((seg << 4) as u8 | ((pcm >> (seg + 1)) & 0x0f) as u8) ^ mask
+}
+
+fn ulaw_decode(code: u8) -> i16 {
+    let code = !code;
+    let t = ((((code & 0x0f) as i32) << 3) + ULAW_BIAS) << ((code & 0x70) >> 4);
+    if code & 0x80 != 0 {
+        (ULAW_BIAS - t) as i16
+    } else {
+        (t - ULAW_BIAS) as i16
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_g711() {
+        assert_eq!(G711Law::ALaw.encode(0), 0xd5);
+        assert_eq!(G711Law::MuLaw.encode(0), 0xff);
+        assert_eq!(G711Law::ALaw.encode(i16::MAX), 0xaa);
+        assert_eq!(G711Law::MuLaw.encode(i16::MIN), 0x00);
+        assert_eq!(G711Law::ALaw.decode(0xd5), 8);
+        assert_eq!(G711Law::MuLaw.decode(0x80), 32124);
+
+        // every code word decodes to a value encoded back to it, but µ-law's negative zero
+        for law in [G711Law::ALaw, G711Law::MuLaw] {
+            for code in 0..=255u8 {
+                if law == G711Law::MuLaw && code == 0x7f {
+                    continue;
+                }
+                assert_eq!(law.encode(law.decode(code)), code, "{law:?} {code:#04x}");
+            }
+        }
+        // quantization error grows with the amplitude
+        for sample in (-32768..32767).step_by(97) {
+            let sample = sample as i16;
+            let decoded = G711Law::ALaw.decode(G711Law::ALaw.encode(sample)) as i32;
+            let err = (decoded - sample as i32).abs();
+            assert!(err <= (sample as i32).abs() / 16 + 16);
+        }
+    }
+}
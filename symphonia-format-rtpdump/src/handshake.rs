@@ -0,0 +1,186 @@
+use codec_detector::rtp::NotRtp;
+use symphonia_core::meta::{MetadataBuilder, MetadataRevision, Tag, Value};
+
+/// ZRTP message type of the Commit message, which names the negotiated cipher
+const ZRTP_COMMIT: &[u8; 8] = b"Commit  ";
+/// DTLS content type and handshake type of a ServerHello, which names the negotiated cipher
+const DTLS_HANDSHAKE: u8 = 22;
+const DTLS_SERVER_HELLO: u8 = 2;
+
+/// Capture time of the first and last packet of a handshake, and the cipher it negotiated
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Handshake {
+    first: u32,
+    last: u32,
+    cipher: Option<String>,
+}
+
+impl Handshake {
+    fn on_pkt(this: &mut Option<Self>, offset: u32, cipher: Option<String>) {
+        let handshake = this.get_or_insert(Self {
+            first: offset,
+            last: offset,
+            cipher: None,
+        });
+        handshake.last = offset;
+        if cipher.is_some() {
+            handshake.cipher = cipher;
+        }
+    }
+
+    /// Continue with `next`, the handshake seen in the following packets
+    fn merge(this: &mut Option<Self>, next: Option<Self>) {
+        match (this.as_mut(), next) {
+            (_, None) => {}
+            (None, next) => *this = next,
+            (Some(handshake), Some(next)) => {
+                handshake.last = next.last;
+                if next.cipher.is_some() {
+                    handshake.cipher = next.cipher;
+                }
+            }
+        }
+    }
+
+    fn tags(&self, name: &str, tags: &mut Vec<Tag>) {
+        tags.push(Tag::new(
+            None,
+            &format!("{name}_START_MS"),
+            Value::UnsignedInt(self.first as u64),
+        ));
+        tags.push(Tag::new(
+            None,
+            &format!("{name}_END_MS"),
+            Value::UnsignedInt(self.last as u64),
+        ));
+        if let Some(cipher) = &self.cipher {
+            tags.push(Tag::new(
+                None,
+                &format!("{name}_CIPHER"),
+                Value::String(cipher.clone()),
+            ));
+        }
+    }
+}
+
+/// Key agreement handshakes sharing the media port with RTP, kept for forensic context since
+/// they tell how the media was protected on the wire
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HandshakeSummary {
+    zrtp: Option<Handshake>,
+    dtls: Option<Handshake>,
+}
+
+impl HandshakeSummary {
+    /// Record a packet the RTP filter classified as `kind`, captured at `offset` milliseconds
+    pub fn on_pkt(&mut self, kind: NotRtp, data: &[u8], offset: u32) {
+        match kind {
+            NotRtp::Zrtp => Handshake::on_pkt(&mut self.zrtp, offset, zrtp_cipher(data)),
+            NotRtp::Dtls => Handshake::on_pkt(&mut self.dtls, offset, dtls_cipher(data)),
+            _ => {}
+        }
+    }
+
+    /// Add the handshakes seen in the packets following the recorded ones
+    pub fn merge(&mut self, next: Self) {
+        Handshake::merge(&mut self.zrtp, next.zrtp);
+        Handshake::merge(&mut self.dtls, next.dtls);
+    }
+
+    /// Metadata describing the handshakes seen, `None` if there were none
+    pub fn metadata(&self) -> Option<MetadataRevision> {
+        let mut tags = vec![];
+        if let Some(zrtp) = &self.zrtp {
+            zrtp.tags("ZRTP", &mut tags);
+        }
+        if let Some(dtls) = &self.dtls {
+            dtls.tags("DTLS", &mut tags);
+        }
+        if tags.is_empty() {
+            return None;
+        }
+
+        let mut builder = MetadataBuilder::new();
+        for tag in tags {
+            builder.add_tag(tag);
+        }
+        Some(builder.metadata())
+    }
+}
+
+/// Cipher type block of a ZRTP Commit message (RFC 6189 section 5.4), e.g. "AES1"
+fn zrtp_cipher(data: &[u8]) -> Option<String> {
+    // 12 bytes packet header, then preamble, length and message type
+    let msg = data.get(12..)?;
+    if msg.get(4..12)? != ZRTP_COMMIT {
+        return None;
+    }
+    // hash image, ZID and hash algorithm come first
+    let cipher = msg.get(60..64)?;
+    Some(String::from_utf8_lossy(cipher).trim_end().to_string())
+}
+
+/// Cipher suite of a DTLS ServerHello (RFC 6347 section 4.3.2), e.g. "0xc02b"
+fn dtls_cipher(data: &[u8]) -> Option<String> {
+    // record header
+    if *data.first()? != DTLS_HANDSHAKE {
+        return None;
+    }
+    // handshake header
+    let msg = data.get(13..)?;
+    if *msg.first()? != DTLS_SERVER_HELLO {
+        return None;
+    }
+    // server version and random, then the session id
+    let hello = msg.get(12..)?;
+    let session_id_len = *hello.get(34)? as usize;
+    let suite = hello.get(35 + session_id_len..37 + session_id_len)?;
+    Some(format!("0x{:02x}{:02x}", suite[0], suite[1]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_handshake_summary() {
+        let mut zrtp = vec![0x10, 0x00, 0x00, 0x01, 0x5a, 0x52, 0x54, 0x50, 0, 0, 0, 1];
+        zrtp.extend_from_slice(&[0x50, 0x5a, 0x00, 0x1d]);
+        zrtp.extend_from_slice(ZRTP_COMMIT);
+        zrtp.extend_from_slice(&[0; 44]);
+        zrtp.extend_from_slice(b"S256AES1HS32DH3kB32 ");
+
+        let mut dtls = vec![DTLS_HANDSHAKE, 0xfe, 0xfd];
+        dtls.extend_from_slice(&[0; 10]);
+        dtls.extend_from_slice(&[DTLS_SERVER_HELLO, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        dtls.extend_from_slice(&[0xfe, 0xfd]);
+        dtls.extend_from_slice(&[0; 32]);
+        dtls.extend_from_slice(&[2, 0xaa, 0xbb, 0xc0, 0x2b, 0x00]);
+
+        let mut summary = HandshakeSummary::default();
+        assert!(summary.metadata().is_none());
+        summary.on_pkt(NotRtp::Zrtp, &zrtp[..20], 100);
+        summary.on_pkt(NotRtp::Zrtp, &zrtp, 120);
+        summary.on_pkt(NotRtp::Zrtp, &zrtp[..20], 150);
+        summary.on_pkt(NotRtp::Dtls, &dtls, 300);
+        summary.on_pkt(NotRtp::Stun, &[], 400);
+
+        let rev = summary.metadata().unwrap();
+        let tags = rev
+            .tags()
+            .iter()
+            .map(|t| (t.key.as_str(), t.value.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            vec![
+                ("ZRTP_START_MS", "100".to_string()),
+                ("ZRTP_END_MS", "150".to_string()),
+                ("ZRTP_CIPHER", "AES1".to_string()),
+                ("DTLS_START_MS", "300".to_string()),
+                ("DTLS_END_MS", "300".to_string()),
+                ("DTLS_CIPHER", "0xc02b".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::io::{Read, Write};
+
+use symphonia_core::errors::{Error, Result};
+
+use crate::session::SessionId;
+
+const MAGIC: &[u8; 4] = b"RDIX";
+const VERSION: u8 = 1;
+
+/// A packet of a track the index can resume reading at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Byte position of the packet in the capture
+    pub pos: u64,
+    /// Milliseconds since the start of recording
+    pub offset: u32,
+    /// RTP timestamp of the packet, unwrapped and relative to the first packet of the track
+    pub ext: i64,
+}
+
+/// Index of a single track, entries are in capture order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackIndex {
+    pub ssrc: u32,
+    /// Session of the SSRC the track belongs to, see [`crate::SESSION_GAP`]
+    pub generation: u32,
+    pub payload_type: u8,
+    /// RTP timestamp of the first packet of the track
+    pub first_ts: u32,
+    pub entries: Vec<IndexEntry>,
+}
+
+impl TrackIndex {
+    /// Last entry at or before byte position `pos`
+    pub fn entry_before(&self, pos: u64) -> Option<&IndexEntry> {
+        let idx = self.entries.partition_point(|e| e.pos <= pos);
+        idx.checked_sub(1).map(|idx| &self.entries[idx])
+    }
+
+    /// Last entry at or before unwrapped timestamp `ext`, or the first entry
+    pub fn entry_at(&self, ext: i64) -> Option<&IndexEntry> {
+        let idx = self.entries.partition_point(|e| e.ext <= ext);
+        self.entries.get(idx.saturating_sub(1))
+    }
+}
+
+/// Random access index of a rtpdump capture, with an entry every `interval` packets of every
+/// track, built by [`RtpdumpReader::build_index`](crate::RtpdumpReader::build_index).
+///
+/// Building the index reads the whole capture, saving it with [`CaptureIndex::write_to`] lets
+/// players seek in long captures right after opening them. Readers scanning the whole capture
+/// for codec detection record an index on the way, see
+/// [`RtpdumpReader::index`](crate::RtpdumpReader::index).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CaptureIndex {
+    /// Packets between two entries of a track, 0 for the index of the codec detection scan,
+    /// which has an entry every second of capture time
+    pub interval: u32,
+    /// Index of every track, in track order
+    pub tracks: Vec<TrackIndex>,
+}
+
+impl CaptureIndex {
+    /// Save the index in its binary format, big-endian like rtpdump itself
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&self.interval.to_be_bytes())?;
+        w.write_all(&(self.tracks.len() as u32).to_be_bytes())?;
+        for track in &self.tracks {
+            w.write_all(&track.ssrc.to_be_bytes())?;
+            w.write_all(&track.generation.to_be_bytes())?;
+            w.write_all(&[track.payload_type])?;
+            w.write_all(&track.first_ts.to_be_bytes())?;
+            w.write_all(&(track.entries.len() as u32).to_be_bytes())?;
+            for entry in &track.entries {
+                w.write_all(&entry.pos.to_be_bytes())?;
+                w.write_all(&entry.offset.to_be_bytes())?;
+                w.write_all(&entry.ext.to_be_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load an index saved by [`CaptureIndex::write_to`]
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::DecodeError("Not a rtpdump index"));
+        }
+        if read_array::<1>(r)?[0] != VERSION {
+            return Err(Error::DecodeError("Unsupported rtpdump index version"));
+        }
+
+        let interval = u32::from_be_bytes(read_array(r)?);
+        let track_cnt = u32::from_be_bytes(read_array(r)?);
+        let mut tracks = vec![];
+        for _ in 0..track_cnt {
+            let mut track = TrackIndex {
+                ssrc: u32::from_be_bytes(read_array(r)?),
+                generation: u32::from_be_bytes(read_array(r)?),
+                payload_type: read_array::<1>(r)?[0],
+                first_ts: u32::from_be_bytes(read_array(r)?),
+                entries: vec![],
+            };
+            let entry_cnt = u32::from_be_bytes(read_array(r)?);
+            for _ in 0..entry_cnt {
+                track.entries.push(IndexEntry {
+                    pos: u64::from_be_bytes(read_array(r)?),
+                    offset: u32::from_be_bytes(read_array(r)?),
+                    ext: i64::from_be_bytes(read_array(r)?),
+                });
+            }
+            tracks.push(track);
+        }
+        Ok(Self { interval, tracks })
+    }
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Collects the index entries of the packets of a capture, fed in capture order
+pub(crate) struct IndexBuilder {
+    index: CaptureIndex,
+    /// Packets seen and last RTP timestamp, with its unwrapped value, of every track
+    state: Vec<(u32, Option<(u32, i64)>)>,
+}
+
+impl IndexBuilder {
+    /// Index tracks of the given sessions and payload types, in track order
+    pub fn new(interval: u32, tracks: impl Iterator<Item = (SessionId, u8)>) -> Self {
+        let tracks = tracks
+            .map(|((ssrc, generation), payload_type)| TrackIndex {
+                ssrc,
+                generation,
+                payload_type,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        Self {
+            state: vec![(0, None); tracks.len()],
+            index: CaptureIndex {
+                interval: interval.max(1),
+                tracks,
+            },
+        }
+    }
+
+    /// Record a packet of track `track_idx` read at byte position `pos`
+    pub fn on_pkt(&mut self, track_idx: usize, pos: u64, offset: u32, rtp_ts: u32) {
+        let (cnt, last) = &mut self.state[track_idx];
+        let track = &mut self.index.tracks[track_idx];
+        let ext = match *last {
+            None => {
+                track.first_ts = rtp_ts;
+                0
+            }
+            Some((ts, ext)) => ext + rtp_ts.wrapping_sub(ts) as i32 as i64,
+        };
+        *last = Some((rtp_ts, ext));
+
+        if *cnt % self.index.interval == 0 {
+            track.entries.push(IndexEntry { pos, offset, ext });
+        }
+        *cnt += 1;
+    }
+
+    pub fn finish(self) -> CaptureIndex {
+        self.index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_index_roundtrip() {
+        let mut builder = IndexBuilder::new(2, [((1, 0), 96), ((2, 1), 97)].into_iter());
+        builder.on_pkt(0, 100, 0, u32::MAX - 319);
+        builder.on_pkt(1, 200, 10, 1000);
+        builder.on_pkt(0, 300, 20, 0);
+        builder.on_pkt(0, 400, 40, 320);
+        let index = builder.finish();
+
+        assert_eq!(index.tracks[0].first_ts, u32::MAX - 319);
+        assert_eq!(
+            index.tracks[0].entries,
+            vec![
+                IndexEntry {
+                    pos: 100,
+                    offset: 0,
+                    ext: 0
+                },
+                IndexEntry {
+                    pos: 400,
+                    offset: 40,
+                    ext: 640
+                },
+            ]
+        );
+        assert_eq!(index.tracks[0].entry_before(399).unwrap().pos, 100);
+        assert_eq!(index.tracks[0].entry_at(639).unwrap().pos, 100);
+        assert_eq!(index.tracks[0].entry_at(-1).unwrap().pos, 100);
+        assert!(index.tracks[1].entry_before(199).is_none());
+
+        let mut saved = vec![];
+        index.write_to(&mut saved).unwrap();
+        assert_eq!(CaptureIndex::read_from(&mut &saved[..]).unwrap(), index);
+        assert!(CaptureIndex::read_from(&mut &saved[1..]).is_err());
+    }
+}
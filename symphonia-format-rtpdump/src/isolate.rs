@@ -0,0 +1,471 @@
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+use symphonia_core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia_core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult,
+};
+use symphonia_core::errors::{Error, Result};
+use symphonia_core::formats::Packet;
+use symphonia_core::sample::i24;
+
+const STATUS_OK: u8 = 0;
+const STATUS_DECODE_ERROR: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+/// Largest packet or decoded frame accepted over the pipes
+const MAX_LEN: usize = 1 << 20;
+
+/// Time a worker has to answer a packet before it is deemed hung
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn read_u32(r: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(r: &mut impl Read) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(r: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let len = read_u32(r)? as usize;
+    if len > MAX_LEN {
+        return Err(ErrorKind::InvalidData.into());
+    }
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn write_bytes(w: &mut impl Write, data: &[u8]) -> std::io::Result<()> {
+    w.write_all(&(data.len() as u32).to_le_bytes())?;
+    w.write_all(data)
+}
+
+/// Answer of a worker to a packet
+enum Answer {
+    Decoded {
+        rate: u32,
+        channels: Channels,
+        samples: Vec<u8>,
+    },
+    DecodeError,
+}
+
+fn read_answer(r: &mut impl Read) -> std::io::Result<Answer> {
+    let mut status = [0];
+    r.read_exact(&mut status)?;
+    match status[0] {
+        STATUS_OK => {}
+        STATUS_DECODE_ERROR => return Ok(Answer::DecodeError),
+        _ => return Err(ErrorKind::InvalidData.into()),
+    }
+
+    let rate = read_u32(r)?;
+    let channels = Channels::from_bits(read_u32(r)?)
+        .filter(|c| !c.is_empty())
+        .ok_or(ErrorKind::InvalidData)?;
+    let samples = read_bytes(r)?;
+    Ok(Answer::Decoded {
+        rate,
+        channels,
+        samples,
+    })
+}
+
+/// Pipes of a running worker, its answers being read by a thread so that waiting for them can
+/// time out. The receiver is only in a mutex for the decoder to be `Sync`.
+struct Worker {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    answers: Mutex<Receiver<std::io::Result<Answer>>>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Decoder running a C-backed decoder in a worker process, so that a crash of the C code on a
+/// hostile capture takes down the worker instead of the host.
+///
+/// The worker is a program calling [`serve_decoder`], typically the host executable started
+/// with a dedicated argument. Parameters are sent once, then each packet is answered by its
+/// decoded samples as 16-bit PCM. When the worker dies or hangs the packet fails and a new worker
+/// is started for the next one, the decoder state being lost. Decoded audio isn't verified.
+pub struct IsolatedDecoder {
+    program: PathBuf,
+    args: Vec<String>,
+    codec: &'static str,
+    params: CodecParameters,
+    worker: Option<Worker>,
+    buf: AudioBuffer<i16>,
+    timeout: Duration,
+    crashes: u64,
+}
+
+impl IsolatedDecoder {
+    /// Start `program` with `args` as the worker of the decoder registered as `codec` (its
+    /// short name)
+    pub fn spawn(
+        program: impl Into<PathBuf>,
+        args: &[&str],
+        codec: &'static str,
+        params: &CodecParameters,
+    ) -> Result<Self> {
+        let mut decoder = Self {
+            program: program.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            codec,
+            params: params.clone(),
+            worker: None,
+            buf: AudioBuffer::unused(),
+            timeout: DEFAULT_TIMEOUT,
+            crashes: 0,
+        };
+        decoder.worker = Some(decoder.start()?);
+        Ok(decoder)
+    }
+
+    /// Time the worker has to answer a packet, 5 s by default. A worker taking longer is killed
+    /// and replaced like a crashed one.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Times the worker died, hung or answered garbage, and was replaced
+    pub fn crashes(&self) -> u64 {
+        self.crashes
+    }
+
+    fn start(&self) -> Result<Worker> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env_clear()
+            .current_dir("/")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let (tx, answers) = mpsc::channel();
+        // ends when the worker closes its stdout, at the latest when it is killed
+        thread::spawn(move || loop {
+            let answer = read_answer(&mut stdout);
+            let failed = answer.is_err();
+            if tx.send(answer).is_err() || failed {
+                break;
+            }
+        });
+        let mut worker = Worker {
+            stdin: BufWriter::new(child.stdin.take().unwrap()),
+            answers: Mutex::new(answers),
+            child,
+        };
+
+        let w = &mut worker.stdin;
+        write_bytes(w, self.codec.as_bytes())?;
+        w.write_all(&self.params.sample_rate.unwrap_or(0).to_le_bytes())?;
+        w.write_all(&self.params.bits_per_sample.unwrap_or(0).to_le_bytes())?;
+        w.write_all(&self.params.channels.map_or(0, |c| c.bits()).to_le_bytes())?;
+        write_bytes(w, self.params.extra_data.as_deref().unwrap_or_default())?;
+        w.flush()?;
+        Ok(worker)
+    }
+
+    /// Send a packet and read back the samples, `Ok(Err(_))` being an error of the decoder
+    fn exchange(&mut self, packet: &Packet) -> std::io::Result<Result<()>> {
+        if self.worker.is_none() {
+            self.worker = Some(self.start().map_err(|_| ErrorKind::BrokenPipe)?);
+        }
+        let worker = self.worker.as_mut().unwrap();
+
+        worker.stdin.write_all(&packet.ts.to_le_bytes())?;
+        worker.stdin.write_all(&packet.dur.to_le_bytes())?;
+        write_bytes(&mut worker.stdin, &packet.data)?;
+        worker.stdin.flush()?;
+
+        let answers = worker.answers.get_mut().unwrap();
+        let (rate, channels, samples) = match answers.recv_timeout(self.timeout) {
+            Ok(answer) => match answer? {
+                Answer::Decoded {
+                    rate,
+                    channels,
+                    samples,
+                } => (rate, channels, samples),
+                Answer::DecodeError => {
+                    return Ok(Err(Error::DecodeError("Worker failed to decode")))
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => return Err(ErrorKind::TimedOut.into()),
+            Err(RecvTimeoutError::Disconnected) => return Err(ErrorKind::BrokenPipe.into()),
+        };
+        let count = channels.count();
+        let frames = samples.len() / 2 / count;
+
+        self.buf = AudioBuffer::new(frames.max(1) as u64, SignalSpec::new(rate, channels));
+        self.buf.render_reserved(Some(frames));
+        for (i, sample) in samples.chunks_exact(2).take(frames * count).enumerate() {
+            self.buf.chan_mut(i % count)[i / count] = i16::from_le_bytes([sample[0], sample[1]]);
+        }
+        Ok(Ok(()))
+    }
+}
+
+impl Decoder for IsolatedDecoder {
+    fn try_new(_params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        Err(Error::Unsupported(
+            "An isolated decoder needs a worker program",
+        ))
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[]
+    }
+
+    /// Restart the worker, the only way to reset the decoder it runs
+    fn reset(&mut self) {
+        self.worker = None;
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        match self.exchange(packet) {
+            Ok(res) => res?,
+            Err(err) => {
+                self.crashes += 1;
+                warn!("decoder worker of {} died: {}", self.codec, err);
+                // killed and waited for on drop, the next packet starts a new one
+                self.worker = None;
+                return Err(Error::DecodeError("Decoder worker died"));
+            }
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        Default::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+/// Samples of a decoded buffer as interleaved 16-bit PCM
+fn interleave(buf: &AudioBufferRef, out: &mut Vec<u8>) {
+    macro_rules! interleave {
+        ($buf:expr, $conv:expr) => {
+            for i in 0..$buf.frames() {
+                for c in 0..$buf.spec().channels.count() {
+                    let sample: i16 = $conv($buf.chan(c)[i]);
+                    out.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+        };
+    }
+    match buf {
+        AudioBufferRef::S16(buf) => interleave!(buf, |s| s),
+        AudioBufferRef::S32(buf) => interleave!(buf, |s: i32| (s >> 16) as i16),
+        AudioBufferRef::S24(buf) => interleave!(buf, |s: i24| (s.inner() >> 8) as i16),
+        AudioBufferRef::F32(buf) => interleave!(buf, |s: f32| (s * 32767.0) as i16),
+        AudioBufferRef::F64(buf) => interleave!(buf, |s: f64| (s * 32767.0) as i16),
+        AudioBufferRef::U8(buf) => interleave!(buf, |s: u8| ((s as i16) - 128) << 8),
+        _ => {}
+    }
+}
+
+/// Worker side of [`IsolatedDecoder`]: read the parameters and packets sent on `input`, decode
+/// them with the decoder of `codecs` named by the parent and answer on `output`, until `input`
+/// is closed.
+pub fn serve_decoder(
+    codecs: &[CodecDescriptor],
+    input: impl Read,
+    output: impl Write,
+) -> Result<()> {
+    let mut input = BufReader::new(input);
+    let mut output = BufWriter::new(output);
+
+    let name = read_bytes(&mut input)?;
+    let desc = codecs
+        .iter()
+        .find(|d| d.short_name.as_bytes() == name)
+        .ok_or(Error::Unsupported("Unknown codec of the decoder worker"))?;
+    let mut params = CodecParameters::new();
+    params.for_codec(desc.codec);
+    match read_u32(&mut input)? {
+        0 => {}
+        rate => params.sample_rate = Some(rate),
+    }
+    match read_u32(&mut input)? {
+        0 => {}
+        bits => params.bits_per_sample = Some(bits),
+    }
+    params.channels = Channels::from_bits(read_u32(&mut input)?).filter(|c| !c.is_empty());
+    let extra_data = read_bytes(&mut input)?;
+    if !extra_data.is_empty() {
+        params.extra_data = Some(extra_data.into_boxed_slice());
+    }
+    let mut decoder = (desc.inst_func)(&params, &DecoderOptions::default())?;
+
+    let mut samples = vec![];
+    loop {
+        let ts = match read_u64(&mut input) {
+            Ok(ts) => ts,
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let dur = read_u64(&mut input)?;
+        let packet = Packet::new_from_boxed_slice(0, ts, dur, read_bytes(&mut input)?.into());
+
+        match decoder.decode(&packet) {
+            Ok(buf) => {
+                samples.clear();
+                interleave(&buf, &mut samples);
+                output.write_all(&[STATUS_OK])?;
+                output.write_all(&buf.spec().rate.to_le_bytes())?;
+                output.write_all(&buf.spec().channels.bits().to_le_bytes())?;
+                write_bytes(&mut output, &samples)?;
+            }
+            Err(Error::DecodeError(_)) => output.write_all(&[STATUS_DECODE_ERROR])?,
+            Err(err) => {
+                output.write_all(&[STATUS_ERROR])?;
+                output.flush()?;
+                return Err(err);
+            }
+        }
+        output.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use symphonia_core::support_codec;
+
+    use super::*;
+
+    /// Decoder outputting stereo frames of as many samples as packets have bytes, each sample
+    /// being the byte, an empty packet being invalid
+    struct EchoDecoder {
+        params: CodecParameters,
+        buf: AudioBuffer<i16>,
+    }
+
+    impl Decoder for EchoDecoder {
+        fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+            Ok(Self {
+                params: params.clone(),
+                buf: AudioBuffer::unused(),
+            })
+        }
+
+        fn supported_codecs() -> &'static [CodecDescriptor] {
+            &[support_codec!(
+                symphonia_core::codecs::CODEC_TYPE_PCM_S16LE,
+                "echo",
+                "Echo"
+            )]
+        }
+
+        fn reset(&mut self) {}
+
+        fn codec_params(&self) -> &CodecParameters {
+            &self.params
+        }
+
+        fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+            if packet.data.is_empty() {
+                return Err(Error::DecodeError("empty"));
+            }
+            let rate = self.params.sample_rate.unwrap();
+            let spec = SignalSpec::new(rate, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+            self.buf = AudioBuffer::new(packet.data.len() as u64, spec);
+            self.buf.render_reserved(None);
+            for (i, b) in packet.data.iter().enumerate() {
+                self.buf.chan_mut(0)[i] = *b as i16;
+                self.buf.chan_mut(1)[i] = -(*b as i16);
+            }
+            Ok(self.buf.as_audio_buffer_ref())
+        }
+
+        fn finalize(&mut self) -> FinalizeResult {
+            Default::default()
+        }
+
+        fn last_decoded(&self) -> AudioBufferRef<'_> {
+            self.buf.as_audio_buffer_ref()
+        }
+    }
+
+    #[test]
+    fn test_serve_decoder() {
+        let mut input = vec![];
+        write_bytes(&mut input, b"echo").unwrap();
+        input.extend_from_slice(&8000u32.to_le_bytes());
+        input.extend_from_slice(&[0; 8]);
+        write_bytes(&mut input, &[]).unwrap();
+        for data in [&[1u8, 2][..], &[]] {
+            input.extend_from_slice(&0u64.to_le_bytes());
+            input.extend_from_slice(&2u64.to_le_bytes());
+            write_bytes(&mut input, data).unwrap();
+        }
+
+        let mut output = vec![];
+        serve_decoder(EchoDecoder::supported_codecs(), &input[..], &mut output).unwrap();
+        let mut expected = vec![STATUS_OK];
+        expected.extend_from_slice(&8000u32.to_le_bytes());
+        expected.extend_from_slice(&3u32.to_le_bytes());
+        write_bytes(&mut expected, &[1, 0, 0xff, 0xff, 2, 0, 0xfe, 0xff]).unwrap();
+        expected.push(STATUS_DECODE_ERROR);
+        assert_eq!(output, expected);
+
+        let mut input = vec![];
+        write_bytes(&mut input, b"evs").unwrap();
+        assert!(serve_decoder(EchoDecoder::supported_codecs(), &input[..], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_worker_died() {
+        let params = CodecParameters::new();
+        let mut decoder =
+            IsolatedDecoder::spawn("sh", &["-c", "head -c 1 >/dev/null"], "echo", &params).unwrap();
+        let packet = Packet::new_from_slice(0, 0, 160, &[1]);
+        assert!(matches!(
+            decoder.decode(&packet),
+            Err(Error::DecodeError(_))
+        ));
+        assert!(decoder.decode(&packet).is_err());
+        assert_eq!(decoder.crashes(), 2);
+    }
+
+    #[test]
+    fn test_worker_hung() {
+        let params = CodecParameters::new();
+        let mut decoder =
+            IsolatedDecoder::spawn("sh", &["-c", "exec sleep 60"], "echo", &params).unwrap();
+        decoder.set_timeout(Duration::from_millis(100));
+        let packet = Packet::new_from_slice(0, 0, 160, &[1]);
+        assert!(matches!(
+            decoder.decode(&packet),
+            Err(Error::DecodeError(_))
+        ));
+        assert_eq!(decoder.crashes(), 1);
+        assert!(decoder.worker.is_none());
+    }
+}
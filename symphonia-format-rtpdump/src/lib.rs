@@ -1,29 +1,118 @@
+use std::collections::{HashMap, VecDeque};
 use std::io::{Error as IOError, ErrorKind, Read, Seek, SeekFrom};
 use std::net::Ipv4Addr;
-use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use binrw::{BinRead, BinResult};
-use codec_detector::rtp::RawRtpPacket;
-use codec_detector::{Codec, CodecDetector};
+use bytes::{Bytes, BytesMut};
+use codec_detector::rtp::{parse_rtp, NotRtpFilter, PayloadType, RawRtpPacket, RtpPacket, SeqNum};
+pub use codec_detector::Calibrator;
+use codec_detector::Codec;
+use log::debug;
 use symphonia_core::audio::Channels;
-use symphonia_core::codecs::CodecParameters;
+use symphonia_core::codecs::{CodecParameters, CodecRegistry};
 use symphonia_core::errors::{seek_error, Error, Result, SeekErrorKind};
 use symphonia_core::formats::{
     Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
 };
-use symphonia_core::io::{MediaSourceStream, ReadBytes};
-use symphonia_core::meta::{Metadata, MetadataLog};
+use symphonia_core::io::{MediaSource, MediaSourceStream, ReadBytes};
+use symphonia_core::meta::{Metadata, MetadataBuilder, MetadataLog, Tag, Value};
 use symphonia_core::probe::{Descriptor, Instantiate, QueryDescriptor};
 use symphonia_core::support_format;
 use symphonia_core::units::TimeBase;
 
-use symphonia_bundle_amr::{CODEC_TYPE_AMR, CODEC_TYPE_AMRWB};
-use symphonia_bundle_evs::dec::CODEC_TYPE_EVS;
-use symphonia_codec_g7221::CODEC_TYPE_G722_1;
+mod acoustic;
+mod capture;
+pub mod channel;
+mod clock;
+mod compare;
+mod content;
+pub mod depacketizer;
+mod effects;
+mod fec;
+mod fingerprint;
+mod g711;
+mod handshake;
+mod index;
+mod isolate;
+mod live;
+mod metrics;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod registry;
+#[cfg(feature = "serde")]
+mod report;
+mod retime;
+mod rtsp;
+mod rtx;
+mod scan;
+mod sdp;
+mod session;
+mod splice;
+mod stretch;
+#[cfg(test)]
+mod test_util;
+mod text;
+mod timeline;
+mod track;
+mod watchdog;
+mod writer;
+
+pub use acoustic::AudioFingerprint;
+pub use capture::{
+    decode_capture, decode_capture_with_budget, decode_capture_with_options, export_frames,
+    CaptureDecodeOptions, DecodedTrack, ExportedTrack, SILENCE_HANGOVER,
+};
+use channel::{ChannelPacket, OverflowPolicy};
+use clock::infer_clock_rate;
+pub use compare::{PcmComparison, Psnr, QualityScorer, SegSnr, SegmentDiff};
+use content::TrialCheck;
+use depacketizer::FrameMeta;
+pub use effects::{Effect, EffectChain, EffectProcessor};
+pub use fec::FecScheme;
+use fec::{FecDecoder, FecPacket};
+pub use fingerprint::{StreamFingerprint, FINGERPRINT_PACKETS};
+pub use g711::G711Law;
+use index::IndexBuilder;
+pub use index::{CaptureIndex, IndexEntry, TrackIndex};
+pub use isolate::{serve_decoder, IsolatedDecoder};
+pub use live::{DecodedFrame, LiveDecoder};
+pub use metrics::Metrics;
+use metrics::ReaderMetrics;
+#[cfg(all(feature = "mmap", unix))]
+pub use mmap::MmapSource;
+pub use registry::{CodecCapability, DepacketizerFactory, RtpCodecDescriptor, RtpCodecRegistry};
+#[cfg(feature = "serde")]
+pub use report::{Report, REPORT_VERSION};
+pub use retime::{retimestamp, RetimestampMode, RetimestampOptions};
+pub use rtsp::RtspClient;
+use scan::CaptureScan;
+pub use sdp::{MediaSection, RtpMap, SessionDescription};
+use session::{SessionId, SessionSplitter};
+pub use splice::{splice_tracks, SplicedTrack, SPLICE_FADE};
+pub use stretch::{time_stretch, TimeStretch, SPEED_RANGE};
+pub use text::{extract_text, TextCue, TextTranscript};
+pub use timeline::{
+    packet_timeline, Loss, PacketTimeline, StreamTimeline, Talkspurt, TALKSPURT_GAP,
+};
+use track::TrackPipeline;
+pub use track::{CodecFrame, DecodeIssue, FrameStats, ModeChange};
+pub use watchdog::{BudgetedDecoder, DecodeBudget, DecodeStats};
+pub use writer::{g711_capture, RtpdumpWriter};
 
 const MAGIC: &[u8] = b"#!rtpplay1.0 ";
 
+/// Reason of the error returned for captures without any RTP packet
+pub const NO_RTP_STREAM: &str = "No RTP stream found";
+
+/// Silence, in milliseconds, after which a SSRC seen again is considered a new stream
+pub const SESSION_GAP: u32 = 30_000;
+
+/// Packets inspected to detect codecs when the source can't be rewound (e.g. stdin), streams
+/// starting later are ignored
+pub const PRESCAN_PACKETS: usize = 2000;
+
 #[binrw::parser(reader, endian)]
 fn parse_src_ip() -> BinResult<Ipv4Addr> {
     let pos = reader.stream_position()?;
@@ -72,6 +161,8 @@ fn parse_src_port() -> BinResult<u16> {
 #[derive(BinRead, Clone, Copy, Debug)]
 #[br(big, magic = b"#!rtpplay1.0 ")]
 #[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 pub struct FileHeader {
     #[br(parse_with = parse_src_ip)]
     pub ip: Ipv4Addr,
@@ -109,17 +200,170 @@ pub struct RDPacket {
     pub offset: u32,
 }
 
+/// RTP packet of a capture as recorded, returned by [`RtpdumpReader::next_rtp_packet`]
+#[derive(Clone, Debug)]
+pub struct RtpdumpPacket {
+    /// Milliseconds since the start of recording
+    pub offset: u32,
+    /// Track the packet belongs to, if its session and payload type carry one
+    pub track_id: Option<u32>,
+    /// RTP header and payload
+    pub data: Bytes,
+}
+
+impl RtpdumpPacket {
+    /// Header fields and payload of the packet
+    pub fn rtp(&self) -> RawRtpPacket<'_> {
+        RawRtpPacket::new(&self.data)
+    }
+}
+
+/// A payload type of a session seen by codec detection
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DetectedStream {
+    pub ssrc: u32,
+    /// Session of the SSRC, see [`SESSION_GAP`]
+    pub generation: u32,
+    pub payload_type: u8,
+    /// Milliseconds since the start of recording of the first packet
+    pub first_offset: u32,
+    /// Codec detected for the payload type, `None` e.g. for telephone events
+    pub codec: Option<Codec>,
+    /// Track of the stream, `None` if the registry doesn't know its codec or the stream is a
+    /// copy of another
+    pub track_id: Option<u32>,
+    pub fingerprint: StreamFingerprint,
+    /// Track of the stream this one is a copy of, e.g. captured twice by redundant taps. Its
+    /// packets are dropped.
+    pub duplicate_of: Option<u32>,
+    /// Media section of the stream in the SDP the reader was given
+    pub mid: Option<String>,
+    /// RTP clock rate inferred from the timestamps and capture times of the packets
+    pub clock_rate: Option<u32>,
+}
+
+/// What the reader found while detecting codecs, see [`RtpdumpReader::detection`]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DetectionSummary {
+    pub header: FileHeader,
+    /// Every stream in order of appearance
+    pub streams: Vec<DetectedStream>,
+}
+
+/// Records of a rtpdump capture held in memory, borrowed from it without copying
+///
+/// Every record comes as is, RTCP and malformed packets included: check it with
+/// [`parse_rtp`](codec_detector::rtp::parse_rtp) before reading it as RTP. Iteration stops at
+/// the end of the capture or at a truncated record.
+pub struct RtpdumpPackets<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RtpdumpPackets<'a> {
+    /// Packets following the file header of `capture`, e.g. the slice of a
+    /// [`MmapSource`](crate::MmapSource)
+    pub fn new(capture: &'a [u8]) -> Result<Self> {
+        let mut cursor = std::io::Cursor::new(capture);
+        match FileHeader::read(&mut cursor) {
+            Ok(_) => Ok(Self {
+                data: &capture[cursor.position() as usize..],
+            }),
+            Err(binrw::Error::Io(e)) => Err(Error::IoError(e)),
+            Err(_) => Err(Error::DecodeError("Failed to decode rtpdump header")),
+        }
+    }
+}
+
+impl<'a> Iterator for RtpdumpPackets<'a> {
+    type Item = (RDPacket, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hdr = self.data.get(..std::mem::size_of::<RDPacket>())?;
+        let pkt = RDPacket {
+            len: u16::from_be_bytes([hdr[0], hdr[1]]),
+            org_len: u16::from_be_bytes([hdr[2], hdr[3]]),
+            offset: u32::from_be_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]),
+        };
+        let data = self
+            .data
+            .get(std::mem::size_of::<RDPacket>()..pkt.len as usize)?;
+        self.data = &self.data[pkt.len as usize..];
+        Some((pkt, data))
+    }
+}
+
+/// Payload sizes and timestamp deltas of the RTP streams of `capture`, to write the features
+/// of the codec it is known to carry
+pub fn calibrate(capture: &[u8]) -> Result<Calibrator> {
+    let mut calibrator = Calibrator::new();
+    for (_, data) in RtpdumpPackets::new(capture)? {
+        if let Ok(pkt) = parse_rtp(data) {
+            calibrator.on_pkt(&pkt);
+        }
+    }
+    Ok(calibrator)
+}
+
 pub struct RtpdumpReader {
     reader: MediaSourceStream,
     tracks: Vec<Track>,
-    track_ts: Vec<u64>,
     cues: Vec<Cue>,
     metadata: MetadataLog,
     ssrcs: Vec<u32>,
-    track_idx: usize,
+    /// track index of every decodable session and payload type, a session changing codec
+    /// mid-call (e.g. after a re-INVITE) has a track per codec
+    ssrc_tracks: HashMap<(SessionId, u8), usize>,
+    /// SSRC and generation of each track
+    track_sessions: Vec<SessionId>,
+    sessions: SessionSplitter,
+    /// payload type carrying the codec of each track
+    track_pts: Vec<u8>,
+    /// position of the first packet of each track on the capture timeline, in samples
+    track_starts: Vec<u64>,
+    pipelines: Vec<TrackPipeline>,
+    pending: VecDeque<Packet>,
+    /// packets read by the codec detection of a non-seekable source, replayed before reading on
+    prescanned: VecDeque<(RDPacket, Bytes)>,
+    /// buffer the packets are read into, shared by the packets of a chunk
+    read_buf: BytesMut,
+    /// byte position of the first packet
+    data_start: u64,
+    index: Option<CaptureIndex>,
     pkt_cnt: u64,
+    issues: Vec<DecodeIssue>,
+    detection: DetectionSummary,
+    /// FEC scheme of the payload types carrying FEC packets
+    fec_pts: HashMap<u8, FecScheme>,
+    /// FEC recovery state of every session protected by FEC
+    fec: HashMap<SessionId, FecDecoder>,
+    recovered_cnt: u64,
+    /// payload type of the media each RTX payload type repairs
+    rtx_pts: HashMap<u8, u8>,
+    /// media session repaired by every RTX SSRC
+    rtx_sessions: HashMap<u32, SessionId>,
+    /// latest sequence number of every session, to pair RTX streams with
+    last_seqs: HashMap<SessionId, SeqNum>,
+    retransmitted_cnt: u64,
+    metrics: Option<ReaderMetrics>,
+    /// a channel refused a packet with [`OverflowPolicy::Error`], reported by the next read
+    overflowed: bool,
     pub sample_rate: Option<u32>,
-    pub timestamp_interval: u64,
+    /// Packets every channel buffers to put reordered packets back in sequence
+    pub reorder_depth: usize,
+    /// Most packets a channel holds, however deep its reorder buffer, and what it does with
+    /// the packets over that. With [`OverflowPolicy::Error`] reading fails.
+    pub channel_capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+    /// Longest timestamp gap, in milliseconds, filled with missing frames so that the decoder
+    /// conceals the loss, longer gaps are left as is
+    pub max_gap_fill: u32,
+    /// Heuristics skipping packets multiplexed with RTP, e.g. STUN or DTLS. Codec detection
+    /// when the reader is created always uses the default ones.
+    pub not_rtp: NotRtpFilter,
 }
 
 impl QueryDescriptor for RtpdumpReader {
@@ -138,7 +382,10 @@ impl QueryDescriptor for RtpdumpReader {
     }
 }
 
-fn read_rd_pkt(source: &mut MediaSourceStream) -> Result<Box<[u8]>> {
+/// Packets are read into chunks of this size, saving an allocation per packet
+const READ_CHUNK: usize = 64 * 1024;
+
+fn read_rd_pkt(source: &mut MediaSourceStream, buf: &mut BytesMut) -> Result<(RDPacket, Bytes)> {
     let len = source.read_be_u16()?;
     let org_len = source.read_be_u16()?;
     let offset = source.read_be_u32()?;
@@ -147,34 +394,210 @@ fn read_rd_pkt(source: &mut MediaSourceStream) -> Result<Box<[u8]>> {
         org_len,
         offset,
     };
-    Ok(source.read_boxed_slice_exact(pkt.org_len as usize)?)
+    let data_len = (pkt.len as usize)
+        .checked_sub(std::mem::size_of::<RDPacket>())
+        .ok_or(Error::DecodeError("Invalid rtpdump packet length"))?;
+    if buf.capacity() < data_len {
+        buf.reserve(READ_CHUNK.max(data_len));
+    }
+    buf.resize(data_len, 0);
+    source.read_exact(buf)?;
+    Ok((pkt, buf.split().freeze()))
+}
+
+/// The most used payload type of a stream with a codec known to `registry` carries the stream,
+/// `pts` holds the packet count of every payload type
+fn select_codec<'a>(
+    pts: HashMap<u8, u64>,
+    detected: &'a HashMap<PayloadType, Codec>,
+    registry: &'a RtpCodecRegistry,
+) -> Option<(u8, &'a Codec, &'a RtpCodecDescriptor)> {
+    let mut pts = pts.into_iter().collect::<Vec<_>>();
+    pts.sort_by_key(|(pt, cnt)| (std::cmp::Reverse(*cnt), *pt));
+    pts.into_iter().find_map(|(pt, _)| {
+        let codec = detected.get(&PayloadType::from_u8(pt))?;
+        let desc = registry.get(codec.name.as_str())?;
+        Some((pt, codec, desc))
+    })
+}
+
+/// Detected codecs of a stream whose RTP clock matches the `clock_rate` inferred for it, all of
+/// them if none does. Every codec is kept once, at its best ranked sample rate.
+fn stream_candidates(
+    candidates: &[Codec],
+    clock_rate: Option<u32>,
+    registry: &RtpCodecRegistry,
+) -> Vec<Codec> {
+    let rtp_clock = |codec: &Codec| {
+        registry
+            .get(codec.name.as_str())
+            .and_then(|desc| desc.clock_rate)
+            .unwrap_or(codec.sample_rate)
+    };
+    let matching = candidates
+        .iter()
+        .filter(|codec| Some(rtp_clock(codec)) == clock_rate)
+        .collect::<Vec<_>>();
+    let ranked = match matching.is_empty() {
+        true => candidates.iter().collect(),
+        false => matching,
+    };
+    let mut codecs: Vec<Codec> = vec![];
+    for codec in ranked {
+        if !codecs.iter().any(|c| c.name == codec.name) {
+            codecs.push(codec.clone());
+        }
+    }
+    codecs
 }
 
-fn codec_to_param(codec: &Codec) -> Option<CodecParameters> {
+/// Codec detection guesses the sample rate from payload sizes, which tell nothing of the audio
+/// bandwidth of codecs with a fixed RTP clock (EVS): without a SDP those are decoded at their
+/// clock rate
+fn at_clock_rate(codec: &Codec, desc: &RtpCodecDescriptor) -> Codec {
+    let mut codec = codec.clone();
+    if let Some(clock_rate) = desc.clock_rate {
+        codec.sample_rate = clock_rate;
+    }
+    codec
+}
+
+fn codec_to_param(desc: &RtpCodecDescriptor, codec: &Codec) -> CodecParameters {
     let mut params = CodecParameters::new();
     params
+        .for_codec(desc.codec)
         .with_sample_rate(codec.sample_rate)
         .with_time_base(TimeBase::new(1, codec.sample_rate))
         .with_channels(Channels::FRONT_CENTRE);
     if let Some(br) = codec.bit_rate {
         params.with_bits_per_sample(br);
     }
-    params.codec = match codec.name.as_str() {
-        "amr" => CODEC_TYPE_AMR,
-        "amrwb" => CODEC_TYPE_AMRWB,
-        "evs" => CODEC_TYPE_EVS,
-        "G.722.1" => CODEC_TYPE_G722_1,
-        _ => return None,
-    };
-    Some(params)
+    params
 }
 
-impl FormatReader for RtpdumpReader {
-    fn try_new(mut source: MediaSourceStream, options: &FormatOptions) -> Result<Self>
+/// How much of the capture codec detection reads before decoding starts
+#[derive(Clone, Copy, Debug)]
+enum Prescan {
+    /// The whole capture, rewinding the source afterwards
+    Whole,
+    /// The first packets, kept for decoding
+    Packets(usize),
+    /// Until every stream seen so far was scanned for this many milliseconds, kept for decoding
+    Window(u32),
+}
+
+impl RtpdumpReader {
+    /// Create a reader which maps detected codecs to decoders with a user supplied registry
+    pub fn try_new_with_registry(
+        source: MediaSourceStream,
+        _options: &FormatOptions,
+        registry: &RtpCodecRegistry,
+    ) -> Result<Self> {
+        Self::try_new_inner(source, registry, Prescan::Whole, None, None, None)
+    }
+
+    /// Create a reader which tells apart the codecs sharing the payload sizes of a stream, e.g.
+    /// EVS 13.2 and AMR-WB 12.65, by trial-decoding its first packets with the decoders of
+    /// `codecs`. The codec yielding the most plausible audio is kept.
+    pub fn try_new_with_content_check(
+        source: MediaSourceStream,
+        _options: &FormatOptions,
+        registry: &RtpCodecRegistry,
+        codecs: &CodecRegistry,
+    ) -> Result<Self> {
+        Self::try_new_inner(
+            source,
+            registry,
+            Prescan::Whole,
+            None,
+            Some((codecs, TrialCheck::Content)),
+            None,
+        )
+    }
+
+    /// Create a reader which checks the codec detected for every stream by trial-decoding its
+    /// first second with the decoders of `codecs`. If the audio is silent, stuck at a DC offset
+    /// or clipped, the next codec matching as many packets is tried instead.
+    pub fn try_new_with_verification(
+        source: MediaSourceStream,
+        _options: &FormatOptions,
+        registry: &RtpCodecRegistry,
+        codecs: &CodecRegistry,
+    ) -> Result<Self> {
+        Self::try_new_inner(
+            source,
+            registry,
+            Prescan::Whole,
+            None,
+            Some((codecs, TrialCheck::Verify)),
+            None,
+        )
+    }
+
+    /// Create a reader which takes the codec of every stream from the media section of `sdp` it
+    /// belongs to, found by its SSRC or the mid its packets carry. Streams outside of the SDP
+    /// fall back to codec detection.
+    ///
+    /// Tracks of a media section get `TRACK<id>_MID` and, if bundled, `TRACK<id>_BUNDLE` tags,
+    /// the latter holding the mids of the group.
+    pub fn try_new_with_sdp(
+        source: MediaSourceStream,
+        _options: &FormatOptions,
+        registry: &RtpCodecRegistry,
+        sdp: &SessionDescription,
+    ) -> Result<Self> {
+        Self::try_new_inner(source, registry, Prescan::Whole, Some(sdp), None, None)
+    }
+
+    /// Create a reader which reads the capture once, even if the source can be rewound.
+    ///
+    /// Codec detection stops once every stream seen has been scanned for `window_ms`
+    /// milliseconds, what it read is decoded from memory and the rest is streamed. Streams
+    /// starting after that get no track.
+    pub fn try_new_single_pass(
+        source: MediaSourceStream,
+        _options: &FormatOptions,
+        registry: &RtpCodecRegistry,
+        window_ms: u32,
+    ) -> Result<Self> {
+        Self::try_new_inner(
+            source,
+            registry,
+            Prescan::Window(window_ms),
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Create a reader of a capture held in memory, e.g. a [`MmapSource`](crate::MmapSource),
+    /// whose packets are scanned for codec detection by up to `threads` threads, each scanning
+    /// a chunk of the capture
+    pub fn try_new_parallel<S>(
+        source: S,
+        _options: &FormatOptions,
+        registry: &RtpCodecRegistry,
+        threads: usize,
+    ) -> Result<Self>
     where
-        Self: Sized,
+        S: MediaSource + AsRef<[u8]> + 'static,
     {
-        let _hdr = match FileHeader::read(&mut source) {
+        let scanned = RtpdumpPackets::new(source.as_ref())
+            .ok()
+            .and_then(|packets| scan::scan_parallel(packets.data, threads));
+        let source = MediaSourceStream::new(Box::new(source), Default::default());
+        Self::try_new_inner(source, registry, Prescan::Whole, None, None, scanned)
+    }
+
+    fn try_new_inner(
+        mut source: MediaSourceStream,
+        registry: &RtpCodecRegistry,
+        prescan: Prescan,
+        sdp: Option<&SessionDescription>,
+        trial: Option<(&CodecRegistry, TrialCheck)>,
+        scanned: Option<CaptureScan>,
+    ) -> Result<Self> {
+        let hdr = match FileHeader::read(&mut source) {
             Ok(hdr) => hdr,
             Err(binrw::Error::Io(e)) => return Err(Error::IoError(e)),
             Err(_) => return Err(Error::DecodeError("Failed to decode rtpdump header")),
@@ -184,20 +607,215 @@ impl FormatReader for RtpdumpReader {
         let mut r = Self {
             reader: source,
             tracks: vec![],
-            track_ts: vec![],
             cues: vec![],
             metadata: Default::default(),
             ssrcs: vec![],
-            track_idx: 0,
+            ssrc_tracks: HashMap::new(),
+            track_sessions: vec![],
+            sessions: SessionSplitter::new(SESSION_GAP),
+            track_pts: vec![],
+            track_starts: vec![],
+            pipelines: vec![],
+            pending: VecDeque::new(),
+            prescanned: VecDeque::new(),
+            read_buf: BytesMut::new(),
+            data_start: hdr_len,
+            index: None,
             pkt_cnt: 0,
+            issues: vec![],
+            detection: DetectionSummary {
+                header: hdr,
+                streams: vec![],
+            },
+            fec_pts: HashMap::new(),
+            fec: HashMap::new(),
+            recovered_cnt: 0,
+            rtx_pts: HashMap::new(),
+            rtx_sessions: HashMap::new(),
+            last_seqs: HashMap::new(),
+            retransmitted_cnt: 0,
+            metrics: None,
+            overflowed: false,
             sample_rate: None,
-            timestamp_interval: 320,
+            reorder_depth: 16,
+            channel_capacity: 1024,
+            overflow_policy: OverflowPolicy::DropOldest,
+            max_gap_fill: 60_000,
+            not_rtp: NotRtpFilter::default(),
+        };
+
+        // a non-seekable source is only scanned partially, and what was read is kept
+        let prescan = match prescan {
+            Prescan::Whole if !r.reader.is_seekable() => Prescan::Packets(PRESCAN_PACKETS),
+            prescan => prescan,
         };
+        let mid_ext_ids = sdp.map_or(vec![], |sdp| sdp.mid_ext_ids());
+        let mut scan = match scanned {
+            Some(scan) => scan,
+            None => r.prescan(prescan, mid_ext_ids)?,
+        };
+        r.ssrcs = std::mem::take(&mut scan.ssrcs);
+
+        let candidates = scan.detector.get_candidates();
+        if let Some(rev) = scan.handshakes.metadata() {
+            r.metadata.push(rev);
+        }
+
+        if matches!(prescan, Prescan::Whole) {
+            r.reader.seek(SeekFrom::Start(hdr_len))?;
+        }
+        r.sessions = SessionSplitter::new(SESSION_GAP);
+        // telephone events, comfort noise and other payload types without a known codec get no
+        // track
+        let mut group_tags = vec![];
+        let mut index_tracks = vec![];
+        for &((session, pt), ref stream) in &scan.streams {
+            let start = stream.first_offset;
+            let mid = scan.mids.get(&session).map(String::as_str);
+            let section = sdp.and_then(|sdp| sdp.section(session.0, mid));
+            // the SDP names the codec of the payload types it maps
+            let sdp_codec = section.and_then(|s| s.rtpmap(pt)).map(RtpMap::codec);
+            let elapsed = stream.last_offset.saturating_sub(start);
+            let clock_rate = infer_clock_rate(stream.ext, elapsed);
+            let codec = sdp_codec.clone().or_else(|| {
+                let candidates = candidates.get(&PayloadType::from_u8(pt))?;
+                let candidates = stream_candidates(candidates, clock_rate, registry);
+                let pkts = &stream.head;
+                match trial {
+                    Some((codecs, TrialCheck::Content)) if candidates.len() > 1 => {
+                        content::select_by_content(&candidates, pkts, registry, codecs)
+                    }
+                    Some((codecs, TrialCheck::Verify)) => {
+                        content::select_verified(&candidates, pkts, registry, codecs)
+                    }
+                    _ => candidates.first().cloned(),
+                }
+            });
+            let codec = codec.as_ref();
+            let mut desc = codec.and_then(|codec| registry.get(codec.name.as_str()));
+            let fingerprint = stream.fingerprint(session.0);
+            // copies of a stream with a track, e.g. captured by redundant taps, get none
+            let duplicate_of = r
+                .detection
+                .streams
+                .iter()
+                .filter(|s| s.payload_type == pt && s.fingerprint.same_media(&fingerprint))
+                .find_map(|s| s.track_id);
+            if duplicate_of.is_some() {
+                desc = None;
+            }
+            debug!(
+                "ssrc {:#010x} pt {pt}: {}{}",
+                session.0,
+                codec.map_or("unknown codec", |codec| codec.name.as_str()),
+                duplicate_of.map_or(String::new(), |t| format!(", copy of track {t}"))
+            );
+            r.detection.streams.push(DetectedStream {
+                ssrc: session.0,
+                generation: session.1,
+                payload_type: pt,
+                first_offset: start,
+                codec: codec.cloned(),
+                track_id: desc.map(|_| r.tracks.len() as u32),
+                fingerprint,
+                duplicate_of,
+                mid: section.and_then(|s| s.mid.clone()),
+                clock_rate,
+            });
+            let (Some(codec), Some(desc)) = (codec, desc) else {
+                continue;
+            };
+            let detected;
+            let codec = match sdp_codec {
+                Some(_) => codec,
+                None => {
+                    detected = at_clock_rate(codec, desc);
+                    &detected
+                }
+            };
+            if let Some(mid) = section.and_then(|s| s.mid.as_deref()) {
+                let track_id = r.tracks.len();
+                group_tags.push(Tag::new(
+                    None,
+                    &format!("TRACK{track_id}_MID"),
+                    Value::String(mid.to_string()),
+                ));
+                if let Some(bundle) = sdp.and_then(|sdp| sdp.bundle(mid)) {
+                    group_tags.push(Tag::new(
+                        None,
+                        &format!("TRACK{track_id}_BUNDLE"),
+                        Value::String(bundle.join(" ")),
+                    ));
+                }
+            }
+
+            r.ssrc_tracks.insert((session, pt), r.tracks.len());
+            r.track_sessions.push(session);
+            r.track_pts.push(pt);
+            r.pipelines
+                .push(TrackPipeline::new(desc, codec.sample_rate, r.reorder_depth));
+            let start = start as u64 * codec.sample_rate as u64 / 1000;
+            r.track_starts.push(start);
+            let mut params = codec_to_param(desc, codec);
+            params.with_start_ts(start);
+            // the whole capture was scanned, the track lasts until the end of its last frame,
+            // assumed to be 20 ms long
+            if matches!(prescan, Prescan::Whole) {
+                let clock_rate = desc.clock_rate.unwrap_or(codec.sample_rate) as i64;
+                let ext = stream.ext.max(0);
+                let n_frames = (ext + clock_rate / 50) * codec.sample_rate as i64 / clock_rate;
+                params.with_n_frames(n_frames as u64);
+            }
+            r.tracks.push(Track::new(r.tracks.len() as u32, params));
+            index_tracks.push(TrackIndex {
+                ssrc: session.0,
+                generation: session.1,
+                payload_type: pt,
+                first_ts: stream.first_ts,
+                entries: stream
+                    .entries
+                    .iter()
+                    .map(|e| IndexEntry {
+                        pos: e.pos + hdr_len,
+                        ..*e
+                    })
+                    .collect(),
+            });
+        }
+        // the whole capture was scanned, the packets it indexed make it seekable
+        if matches!(prescan, Prescan::Whole) {
+            r.index = Some(CaptureIndex {
+                interval: 0,
+                tracks: index_tracks,
+            });
+        }
 
-        let mut detector = CodecDetector::new();
-        detector.get_features_from_yaml(Path::new("codec.yaml"));
+        if r.detection.streams.is_empty() {
+            return Err(Error::Unsupported(NO_RTP_STREAM));
+        }
+        if r.tracks.is_empty() {
+            return Err(Error::Unsupported("Unsupported codec"));
+        }
+        if !group_tags.is_empty() {
+            let mut builder = MetadataBuilder::new();
+            for tag in group_tags {
+                builder.add_tag(tag);
+            }
+            r.metadata.push(builder.metadata());
+        }
+        Ok(r)
+    }
+
+    /// Scan the packets following the file header for codec detection, as far as `prescan`
+    /// tells
+    fn prescan(&mut self, prescan: Prescan, mid_ext_ids: Vec<u8>) -> Result<CaptureScan> {
+        let mut scan = CaptureScan::new(mid_ext_ids);
         loop {
-            let pkt = match read_rd_pkt(&mut r.reader) {
+            if matches!(prescan, Prescan::Packets(max) if self.prescanned.len() >= max) {
+                break;
+            }
+            let pos = self.reader.pos();
+            let (rd, pkt) = match read_rd_pkt(&mut self.reader, &mut self.read_buf) {
                 Ok(pkt) => pkt,
                 Err(Error::IoError(e)) => {
                     if e.kind() == ErrorKind::UnexpectedEof {
@@ -208,39 +826,461 @@ impl FormatReader for RtpdumpReader {
                 }
                 Err(e) => return Err(e),
             };
-            let pkt = RawRtpPacket::new(pkt.as_ref());
-            detector.on_pkt(&pkt);
+            if !matches!(prescan, Prescan::Whole) {
+                self.prescanned.push_back((rd, pkt.clone()));
+            }
+            if let (Prescan::Window(ms), Some(start)) = (prescan, scan.last_start) {
+                if rd.offset.saturating_sub(start) > ms {
+                    break;
+                }
+            }
+            scan.on_pkt(pos - self.data_start, rd.offset, &pkt);
+        }
+        Ok(scan)
+    }
+
+    /// Streams and codecs found when the reader was created, including the streams without a
+    /// track
+    pub fn detection(&self) -> &DetectionSummary {
+        &self.detection
+    }
+
+    /// SSRC of a track, and which of the sessions using this SSRC it carries (0 for the first)
+    pub fn track_session(&self, track_id: u32) -> Option<(u32, u32)> {
+        self.track_sessions.get(track_id as usize).copied()
+    }
+
+    /// Frame types and bitrate over time of the frames of a track handed to its decoder so far
+    pub fn frame_stats(&self, track_id: u32) -> Option<&FrameStats> {
+        self.pipelines.get(track_id as usize).map(|p| &p.stats)
+    }
+
+    /// Data of the packets standing in for the frames of a track lost or not sent (e.g. DTX),
+    /// see [`PayloadDepacketizer::missing_frame`](depacketizer::PayloadDepacketizer::missing_frame)
+    pub fn missing_frame(&self, track_id: u32) -> Option<Box<[u8]>> {
+        self.pipelines
+            .get(track_id as usize)
+            .map(TrackPipeline::missing_frame)
+    }
+
+    /// Record the frames handed to the decoders from now on, to be taken with
+    /// [`Self::take_frames`]. Stand-in frames of the lost or missing ones aren't recorded.
+    pub fn record_frames(&mut self) {
+        for pipeline in &mut self.pipelines {
+            pipeline.recorded.get_or_insert_with(Vec::new);
         }
+    }
 
-        let result = detector.get_result();
+    /// Take the frames of a track recorded so far, in the order they were handed to its
+    /// decoder
+    pub fn take_frames(&mut self, track_id: u32) -> Vec<CodecFrame> {
+        self.pipelines
+            .get_mut(track_id as usize)
+            .and_then(|p| p.recorded.as_mut())
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
 
-        r.reader.seek(SeekFrom::Start(hdr_len))?;
-        for (id, (pt, codec)) in result.iter().enumerate() {
-            let param =
-                codec_to_param(&codec).ok_or_else(|| Error::Unsupported("Unsupported codec"))?;
-            r.tracks.push(Track::new(id as u32, param));
-            r.track_ts.push(0);
+    /// Count the packets, decode errors, channels and buffered packets of the reader in
+    /// `metrics` from now on, until the reader is dropped
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(ReaderMetrics::new(metrics, self.pipelines.len()));
+        self.update_buffered();
+    }
+
+    /// Bring the buffered packets of the metrics up to date
+    fn update_buffered(&mut self) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.set_buffered(self.pipelines.iter().map(|p| p.channel.len()).sum());
         }
-        Ok(r)
     }
 
-    fn next_packet(&mut self) -> Result<Packet> {
-        let len = self.reader.read_be_u16()?;
-        let org_len = self.reader.read_be_u16()?;
-        let offset = self.reader.read_be_u32()?;
-        let pkt = RDPacket {
-            len,
-            org_len,
-            offset,
-        };
-        let data = self.reader.read_boxed_slice_exact(pkt.org_len as usize)?;
-        let pkt = Packet::new_from_slice(
-            self.track_idx as u32,
-            self.track_ts[self.track_idx] * self.timestamp_interval,
-            self.timestamp_interval,
-            &data[12..],
+    /// Payload type carrying the codec of a track
+    pub fn track_payload_type(&self, track_id: u32) -> Option<u8> {
+        self.track_pts.get(track_id as usize).copied()
+    }
+
+    /// Choose whether tracks keep their place on the capture timeline (the default), so that
+    /// the first packet of a stream starting late is timestamped from the capture start, or
+    /// start at 0 with their first packet.
+    ///
+    /// Only takes effect if called before reading packets.
+    pub fn set_align_to_capture(&mut self, align: bool) {
+        for pipeline in &mut self.pipelines {
+            pipeline.align_to_capture = align;
+        }
+        for (track, start) in self.tracks.iter_mut().zip(&self.track_starts) {
+            track.codec_params.start_ts = if align { *start } else { 0 };
+        }
+    }
+
+    /// Choose whether the packets of the frames the payload format tells the type of carry
+    /// their [`FrameMeta`](depacketizer::FrameMeta) after the frame, to be taken back with
+    /// [`FrameMeta::split`](depacketizer::FrameMeta::split). Off by default, decoders which
+    /// don't split it would take it for frame data.
+    ///
+    /// The codec extra data of the tracks concerned becomes [`FrameMeta::MAGIC`], decoders only
+    /// split the frames of such tracks. Call it before creating the decoders.
+    pub fn set_frame_meta(&mut self, attach: bool) {
+        for (pipeline, track) in self.pipelines.iter_mut().zip(&mut self.tracks) {
+            pipeline.attach_meta = attach && pipeline.frame_meta();
+            let params = &mut track.codec_params;
+            if pipeline.attach_meta {
+                params.extra_data = Some(Box::new(FrameMeta::MAGIC));
+            } else if FrameMeta::attached(params.extra_data.as_deref()) {
+                params.extra_data = None;
+            }
+        }
+    }
+
+    /// Read the next RTP packet of the capture as is, without reordering or depacketizing it,
+    /// for tools working on RTP itself. RTCP and other non RTP records are skipped.
+    ///
+    /// This reads from the same position as [`FormatReader::next_packet`], the two shouldn't be
+    /// mixed on the same reader.
+    pub fn next_rtp_packet(&mut self) -> Result<RtpdumpPacket> {
+        loop {
+            let (rd, data) = match self.prescanned.pop_front() {
+                Some(pkt) => pkt,
+                None => read_rd_pkt(&mut self.reader, &mut self.read_buf)?,
+            };
+            if self.not_rtp.detect(&data, &self.ssrcs) {
+                continue;
+            }
+            let Ok(rtp) = parse_rtp(&data) else {
+                continue;
+            };
+            if matches!(rtp.payload_type(), PayloadType::Reserved(_)) {
+                continue;
+            }
+            let session = (rtp.ssrc(), self.sessions.generation(rtp.ssrc(), rd.offset));
+            let track_id = self
+                .ssrc_tracks
+                .get(&(session, rtp.payload_type().to_u8()))
+                .map(|&idx| idx as u32);
+            return Ok(RtpdumpPacket {
+                offset: rd.offset,
+                track_id,
+                data,
+            });
+        }
+    }
+
+    /// The RTP packets of the capture from the current position with their SSRC, read by
+    /// [`Self::next_rtp_packet`]. Iteration ends at the end of the capture, or after the error
+    /// which stopped reading.
+    pub fn rtp_packets(&mut self) -> impl Iterator<Item = Result<(u32, RtpdumpPacket)>> + '_ {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            match self.next_rtp_packet() {
+                Ok(pkt) => Some(Ok((pkt.rtp().ssrc(), pkt))),
+                Err(err) => {
+                    done = true;
+                    match err {
+                        Error::IoError(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+                        err => Some(Err(err)),
+                    }
+                }
+            }
+        })
+    }
+
+    /// Read the whole capture to index every track with an entry every `interval` packets,
+    /// the reader then goes on where it was. The source must be seekable.
+    pub fn build_index(&mut self, interval: u32) -> Result<CaptureIndex> {
+        if !self.reader.is_seekable() {
+            return seek_error(SeekErrorKind::Unseekable);
+        }
+        let resume_at = self.reader.pos();
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+
+        let mut builder = IndexBuilder::new(
+            interval,
+            self.track_sessions
+                .iter()
+                .copied()
+                .zip(self.track_pts.iter().copied()),
         );
-        Ok(pkt)
+        let mut sessions = SessionSplitter::new(SESSION_GAP);
+        loop {
+            let pos = self.reader.pos();
+            let (rd, data) = match read_rd_pkt(&mut self.reader, &mut self.read_buf) {
+                Ok(pkt) => pkt,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            if self.not_rtp.detect(&data, &self.ssrcs) {
+                continue;
+            }
+            let Ok(rtp) = parse_rtp(&data) else {
+                continue;
+            };
+            if matches!(rtp.payload_type(), PayloadType::Reserved(_)) {
+                continue;
+            }
+            let session = (rtp.ssrc(), sessions.generation(rtp.ssrc(), rd.offset));
+            if let Some(&track_idx) = self.ssrc_tracks.get(&(session, rtp.payload_type().to_u8())) {
+                builder.on_pkt(track_idx, pos, rd.offset, rtp.ts());
+            }
+        }
+
+        self.reader.seek(SeekFrom::Start(resume_at))?;
+        Ok(builder.finish())
+    }
+
+    /// Index the reader seeks with: the one given to [`Self::set_index`], or else the one
+    /// recorded while scanning the whole capture for codec detection. `None` if only the start
+    /// of the capture was scanned, e.g. from a source that can't be rewound.
+    pub fn index(&self) -> Option<&CaptureIndex> {
+        self.index.as_ref()
+    }
+
+    /// Seek with `index`, built by [`RtpdumpReader::build_index`] from the same capture
+    pub fn set_index(&mut self, index: CaptureIndex) -> Result<()> {
+        let matches = index.tracks.len() == self.tracks.len()
+            && index.tracks.iter().enumerate().all(|(idx, track)| {
+                (track.ssrc, track.generation) == self.track_sessions[idx]
+                    && track.payload_type == self.track_pts[idx]
+            });
+        if !matches {
+            return Err(Error::DecodeError("Index of another capture"));
+        }
+        self.index = Some(index);
+        Ok(())
+    }
+
+    /// Queue a RTP packet of `session` in the channel of its track, then depacketize what the
+    /// channel releases
+    fn add_pkt(&mut self, session: SessionId, data: Bytes, offset: u32) {
+        let rtp = RawRtpPacket::new(&data);
+        let Some(&track_idx) = self.ssrc_tracks.get(&(session, rtp.payload_type().to_u8())) else {
+            return;
+        };
+
+        let seq = SeqNum(rtp.seq());
+        if !self.rtx_pts.is_empty() {
+            self.last_seqs.insert(session, seq);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.add_packet();
+        }
+        let channel = &mut self.pipelines[track_idx].channel;
+        channel.set_limit(self.channel_capacity, self.overflow_policy);
+        let overflows = channel.overflows();
+        if !channel.add_pkt(ChannelPacket { seq, offset, data })
+            && channel.overflows() > overflows
+            && self.overflow_policy == OverflowPolicy::Error
+        {
+            self.overflowed = true;
+        }
+        while let Some(pkt) = self.pipelines[track_idx].channel.pop_pkt() {
+            self.depacketize(track_idx, &pkt);
+        }
+        self.update_buffered();
+    }
+
+    /// Recover the lost packets of the latest session of the SSRC a FEC packet protects
+    fn on_fec(&mut self, fec: FecPacket, offset: u32) {
+        let Some(session) = self
+            .track_sessions
+            .iter()
+            .filter(|(ssrc, _)| *ssrc == fec.ssrc)
+            .max_by_key(|(_, generation)| *generation)
+            .copied()
+        else {
+            return;
+        };
+        let recovered = self.fec.entry(session).or_default().on_fec(fec);
+        self.recovered_cnt += recovered.len() as u64;
+        for pkt in recovered {
+            self.add_pkt(session, pkt, offset);
+        }
+    }
+
+    /// Fold a retransmission back into the media stream it repairs.
+    ///
+    /// A RTX stream is paired with the session of payload type `apt` whose latest sequence
+    /// number is the closest to its first OSN, e.g. the session of the same direction when
+    /// both directions use the same payload types.
+    fn on_rtx(&mut self, rtx: &RawRtpPacket, apt: u8, offset: u32) {
+        let Some(osn) = rtx::osn(rtx) else {
+            return;
+        };
+        let session = match self.rtx_sessions.get(&rtx.ssrc()) {
+            Some(session) => *session,
+            None => {
+                let Some(session) = self
+                    .last_seqs
+                    .iter()
+                    .filter(|(session, _)| self.ssrc_tracks.contains_key(&(**session, apt)))
+                    .min_by_key(|(_, seq)| (SeqNum(osn) - **seq).min(**seq - SeqNum(osn)))
+                    .map(|(session, _)| *session)
+                else {
+                    return;
+                };
+                self.rtx_sessions.insert(rtx.ssrc(), session);
+                session
+            }
+        };
+        let Some(pkt) = rtx::unwrap_rtx(rtx, apt, session.0) else {
+            return;
+        };
+        let rtp = RawRtpPacket::new(&pkt);
+        let seq = SeqNum(rtp.seq());
+        if let Some(&track_idx) = self.ssrc_tracks.get(&(session, apt)) {
+            // packets already received or released are dropped by the channel
+            let channel = &self.pipelines[track_idx].channel;
+            if !channel.contains(seq) && !channel.released(seq) {
+                self.retransmitted_cnt += 1;
+            }
+        }
+        self.add_pkt(session, pkt, offset);
+    }
+
+    /// Fold the retransmissions of payload type `rtx_pt` (RFC 4588) into the streams of payload
+    /// type `apt`, as negotiated in SDP with `a=fmtp:<rtx_pt> apt=<apt>`. Takes effect for the
+    /// packets read next.
+    pub fn set_rtx_payload_type(&mut self, rtx_pt: u8, apt: u8) {
+        self.rtx_pts.insert(rtx_pt, apt);
+    }
+
+    /// Retransmitted packets which filled a gap of their stream so far
+    pub fn retransmitted_packets(&self) -> u64 {
+        self.retransmitted_cnt
+    }
+
+    /// Use the packets of payload type `pt` to recover lost media packets, e.g. the ULP-FEC
+    /// payload type negotiated in SDP. Takes effect for the packets read next.
+    pub fn set_fec_payload_type(&mut self, pt: u8, scheme: FecScheme) {
+        self.fec_pts.insert(pt, scheme);
+    }
+
+    /// Media packets recovered from FEC packets so far
+    pub fn recovered_packets(&self) -> u64 {
+        self.recovered_cnt
+    }
+
+    /// Packets the channels dropped or refused for being full so far, see
+    /// [`Self::channel_capacity`]
+    pub fn overflowed_packets(&self) -> u64 {
+        self.pipelines.iter().map(|p| p.channel.overflows()).sum()
+    }
+
+    /// Turn a packet released by the channel of a track into decoder packets
+    fn depacketize(&mut self, track_idx: usize, pkt: &ChannelPacket) {
+        match self.pipelines[track_idx].depacketize(
+            track_idx as u32,
+            pkt,
+            self.max_gap_fill,
+            &mut self.pending,
+        ) {
+            Ok(()) => self.pkt_cnt += 1,
+            Err(e) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.add_decode_error();
+                }
+                self.issues.push(DecodeIssue {
+                    track_id: track_idx as u32,
+                    seq: pkt.seq.0,
+                    rtp_ts: parse_rtp(&pkt.data).ok().map(|rtp| rtp.ts()),
+                    offset: pkt.offset,
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Packets dropped as malformed so far, in the order they were read. The decoder conceals
+    /// their frames, the list tells which parts of the capture were damaged.
+    pub fn issues(&self) -> &[DecodeIssue] {
+        &self.issues
+    }
+
+    /// Take the packets dropped as malformed so far, e.g. to report them while decoding
+    pub fn take_issues(&mut self) -> Vec<DecodeIssue> {
+        std::mem::take(&mut self.issues)
+    }
+}
+
+impl FormatReader for RtpdumpReader {
+    fn try_new(source: MediaSourceStream, options: &FormatOptions) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::try_new_with_registry(source, options, &RtpCodecRegistry::default())
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        loop {
+            if let Some(pkt) = self.pending.pop_front() {
+                return Ok(pkt);
+            }
+            if std::mem::take(&mut self.overflowed) {
+                return Err(Error::DecodeError("Channel buffer overflow"));
+            }
+
+            let next = match self.prescanned.pop_front() {
+                Some(pkt) => Ok(pkt),
+                None => read_rd_pkt(&mut self.reader, &mut self.read_buf),
+            };
+            let (rd, data) = match next {
+                Ok(pkt) => pkt,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    // flush whatever the channels still hold before reporting end of stream
+                    let Some((track_idx, pkt)) = self
+                        .pipelines
+                        .iter_mut()
+                        .enumerate()
+                        .find_map(|(idx, p)| p.channel.drain_pkt().map(|pkt| (idx, pkt)))
+                    else {
+                        // then the frames the depacketizers still hold back
+                        for (idx, pipeline) in self.pipelines.iter_mut().enumerate() {
+                            pipeline.flush(idx as u32, self.max_gap_fill, &mut self.pending);
+                        }
+                        match self.pending.pop_front() {
+                            Some(packet) => return Ok(packet),
+                            None => return Err(Error::IoError(e)),
+                        }
+                    };
+                    self.depacketize(track_idx, &pkt);
+                    self.update_buffered();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if self.not_rtp.detect(&data, &self.ssrcs) {
+                continue;
+            }
+            let Ok(rtp) = parse_rtp(&data) else {
+                continue;
+            };
+            if matches!(rtp.payload_type(), PayloadType::Reserved(_)) {
+                continue;
+            }
+            if let Some(&apt) = self.rtx_pts.get(&rtp.payload_type().to_u8()) {
+                self.on_rtx(&rtp, apt, rd.offset);
+                continue;
+            }
+            let session = (rtp.ssrc(), self.sessions.generation(rtp.ssrc(), rd.offset));
+            if let Some(&scheme) = self.fec_pts.get(&rtp.payload_type().to_u8()) {
+                if let Some(fec) = FecPacket::parse(scheme, &rtp) {
+                    self.on_fec(fec, rd.offset);
+                }
+                continue;
+            }
+            let pt = rtp.payload_type().to_u8();
+            if !self.fec_pts.is_empty() && self.ssrc_tracks.contains_key(&(session, pt)) {
+                let seq = SeqNum(rtp.seq());
+                let recovered = self.fec.entry(session).or_default().on_media(seq, &data);
+                for pkt in recovered {
+                    self.add_pkt(session, pkt, rd.offset);
+                }
+            }
+            self.add_pkt(session, data, rd.offset);
+        }
     }
 
     fn metadata(&mut self) -> Metadata<'_> {
@@ -256,11 +1296,94 @@ impl FormatReader for RtpdumpReader {
     }
 
     fn seek(&mut self, mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
-        if self.tracks.is_empty() {
+        if self.tracks.is_empty() || !self.reader.is_seekable() {
             return seek_error(SeekErrorKind::Unseekable);
         }
+        let Some(index) = &self.index else {
+            return seek_error(SeekErrorKind::Unseekable);
+        };
+
+        let (track_idx, required_ts) = match to {
+            SeekTo::TimeStamp { ts, track_id } => (track_id as usize, ts),
+            SeekTo::Time { time, track_id } => {
+                let track_idx = track_id.unwrap_or(0) as usize;
+                let Some(tb) = self
+                    .tracks
+                    .get(track_idx)
+                    .and_then(|t| t.codec_params.time_base)
+                else {
+                    return seek_error(SeekErrorKind::InvalidTrack);
+                };
+                (track_idx, tb.calc_timestamp(time))
+            }
+        };
+        if track_idx >= self.tracks.len() {
+            return seek_error(SeekErrorKind::InvalidTrack);
+        }
+
+        // a track not started yet starts where the reader would have started it
+        let start = |idx: usize, pipeline: &TrackPipeline| match pipeline.align_to_capture {
+            true => self.track_starts[idx],
+            false => 0,
+        };
+        let track = &index.tracks[track_idx];
+        let pipeline = &self.pipelines[track_idx];
+        let ext = pipeline.ext_at(track.first_ts, start(track_idx, pipeline), required_ts);
+        let Some(pos) = track.entry_at(ext).map(|e| e.pos) else {
+            return seek_error(SeekErrorKind::OutOfRange);
+        };
+
+        // every track goes on from its last indexed packet before the new position, the
+        // packets in between are read again
+        let mut actual_ts = 0;
+        self.sessions = SessionSplitter::new(SESSION_GAP);
+        for (idx, pipeline) in self.pipelines.iter_mut().enumerate() {
+            let track = &index.tracks[idx];
+            let anchor = track.entry_before(pos).map(|entry| {
+                self.sessions
+                    .resume(track.ssrc, track.generation, entry.offset);
+                (track.first_ts, start(idx, pipeline), entry.ext)
+            });
+            let ts = pipeline.seek(anchor);
+            if idx == track_idx {
+                actual_ts = ts.unwrap_or_default();
+            }
+        }
+        self.pending.clear();
+        self.prescanned.clear();
+        self.fec.clear();
+        self.last_seqs.clear();
+        self.update_buffered();
+        self.reader.seek(SeekFrom::Start(pos))?;
+
+        if mode == SeekMode::Accurate {
+            // drop the packets of the track ending before the required timestamp
+            let mut kept = VecDeque::new();
+            loop {
+                let packet = match self.next_packet() {
+                    Ok(packet) => packet,
+                    Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                        return seek_error(SeekErrorKind::OutOfRange);
+                    }
+                    Err(e) => return Err(e),
+                };
+                if packet.track_id() != track_idx as u32 {
+                    kept.push_back(packet);
+                } else if packet.ts() + packet.dur() > required_ts {
+                    actual_ts = packet.ts();
+                    kept.push_back(packet);
+                    break;
+                }
+            }
+            kept.append(&mut self.pending);
+            self.pending = kept;
+        }
 
-        unimplemented!()
+        Ok(SeekedTo {
+            track_id: track_idx as u32,
+            required_ts,
+            actual_ts,
+        })
     }
 
     fn into_inner(self: Box<Self>) -> MediaSourceStream {
@@ -270,10 +1393,365 @@ impl FormatReader for RtpdumpReader {
 
 #[cfg(test)]
 mod test {
+    use std::io::Cursor;
+
     use super::*;
+    use crate::test_util::{evs_capture, evs_packets, rtpdump};
 
     #[test]
     fn read_rtpdump_header() {
         let header = b"#!rtpplay1.0 192.168.1.1/12345";
     }
+
+    #[test]
+    fn test_rtpdump_packets() {
+        let mut data = evs_capture(10, 500);
+        let pkts = RtpdumpPackets::new(&data).unwrap().collect::<Vec<_>>();
+        assert_eq!(pkts.len(), 10);
+        for (i, (rd, data)) in pkts.iter().enumerate() {
+            let rtp = parse_rtp(data).unwrap();
+            assert_eq!(rd.offset, 500 + i as u32 * 20);
+            assert_eq!(rtp.seq(), i as u16);
+            assert_eq!(rtp.payload().len(), 33);
+        }
+
+        // records too short for RTP come as they are
+        let mut short = data.clone();
+        short.extend_from_slice(&[0, 12, 0, 4, 0, 0, 0x03, 0x20, 0x80, 0x60, 0, 1]);
+        let (_, last) = RtpdumpPackets::new(&short).unwrap().last().unwrap();
+        assert_eq!(last, [0x80, 0x60, 0, 1]);
+        assert!(parse_rtp(last).is_err());
+
+        // a truncated last packet ends the iteration
+        data.truncate(data.len() - 1);
+        assert_eq!(RtpdumpPackets::new(&data).unwrap().count(), 9);
+        assert!(RtpdumpPackets::new(b"not a capture").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_stream() {
+        // a second tap captured the stream again under another SSRC
+        let dup = rtpdump(evs_packets(20, 0).into_iter().flat_map(|(offset, pkt)| {
+            let mut copy = pkt.clone();
+            copy[8..12].copy_from_slice(&0x5678u32.to_be_bytes());
+            [(offset, pkt), (offset, copy)]
+        }));
+
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(dup.clone())), Default::default());
+        let reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        let streams = &reader.detection().streams;
+        assert_eq!(streams.len(), 2);
+        assert_eq!((streams[1].ssrc, streams[1].track_id), (0x5678, None));
+        assert_eq!(streams[1].duplicate_of, Some(0));
+
+        let tracks = decode_capture(&dup).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].pcm.len(), 20 * 320);
+    }
+
+    #[test]
+    fn test_codec_change() {
+        // the second half of the call switches to payload type 97
+        let mut pkts = evs_packets(100, 0);
+        for (_, pkt) in &mut pkts[50..] {
+            pkt[1] = 97;
+        }
+        let data = rtpdump(pkts);
+
+        let tracks = decode_capture(&data).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert!(tracks.iter().all(|t| t.ssrc == 0x1234 && t.codec == "evs"));
+        assert!(tracks.iter().all(|t| t.pcm.len() == 50 * 320));
+
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        assert_eq!(reader.track_payload_type(0), Some(96));
+        assert_eq!(reader.track_payload_type(1), Some(97));
+        let streams = &reader.detection().streams;
+        assert_eq!(streams.len(), 2);
+        assert_eq!(
+            (
+                streams[1].ssrc,
+                streams[1].payload_type,
+                streams[1].first_offset
+            ),
+            (0x1234, 97, 1000)
+        );
+        assert_eq!(streams[1].codec.as_ref().unwrap().name.as_str(), "evs");
+        assert_eq!(streams[1].track_id, Some(1));
+        let starts = reader
+            .tracks()
+            .iter()
+            .map(|t| t.codec_params.start_ts * 1000 / t.codec_params.sample_rate.unwrap() as u64)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 1000]);
+    }
+
+    #[test]
+    fn test_timeline_restart() {
+        // the sender starts over from timestamp 7 at the 50th packet, keeping its sequence
+        let mut pkts = evs_packets(100, 0);
+        for (i, (_, pkt)) in pkts.iter_mut().enumerate().skip(50) {
+            let ts = 7 + (i as u32 - 50) * 320;
+            pkt[4..8].copy_from_slice(&ts.to_be_bytes());
+        }
+        let data = rtpdump(pkts);
+
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data.clone())), Default::default());
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        assert_eq!(reader.tracks()[0].codec_params.n_frames, Some(100 * 320));
+        let mut timestamps = vec![];
+        while let Ok(packet) = reader.next_packet() {
+            timestamps.push(packet.ts());
+        }
+        assert_eq!(timestamps, (0..100).map(|i| i * 320).collect::<Vec<_>>());
+        assert_eq!(decode_capture(&data).unwrap()[0].pcm.len(), 100 * 320);
+    }
+
+    #[test]
+    fn test_seek_with_index() {
+        let data = evs_capture(100, 500);
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        let sr = reader.tracks()[0].codec_params.sample_rate.unwrap() as u64;
+        let to = |ts| SeekTo::TimeStamp { ts, track_id: 0 };
+
+        let index = reader.build_index(10).unwrap();
+        assert_eq!(index.tracks[0].entries.len(), 10);
+        let mut saved = vec![];
+        index.write_to(&mut saved).unwrap();
+        reader
+            .set_index(CaptureIndex::read_from(&mut &saved[..]).unwrap())
+            .unwrap();
+
+        // before reading anything, 1.1 s into the track lands on the entry of 1 s
+        let seeked = reader.seek(SeekMode::Coarse, to(sr * 16 / 10)).unwrap();
+        assert_eq!(seeked.actual_ts, sr * 3 / 2);
+        assert_eq!(reader.next_packet().unwrap().ts(), sr * 3 / 2);
+
+        // back from the middle of the track, to the exact frame
+        for _ in 0..20 {
+            reader.next_packet().unwrap();
+        }
+        let seeked = reader.seek(SeekMode::Accurate, to(sr * 9 / 10)).unwrap();
+        assert_eq!(seeked.actual_ts, sr * 9 / 10);
+        let mut ts = vec![];
+        while let Ok(packet) = reader.next_packet() {
+            ts.push(packet.ts());
+        }
+        let frame = sr / 50;
+        assert_eq!(ts, (45..125).map(|i| i * frame).collect::<Vec<_>>());
+
+        // the track ends with its last frame
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.n_frames, Some(100 * frame));
+        assert_eq!(params.start_ts + params.n_frames.unwrap(), 125 * frame);
+    }
+
+    #[test]
+    fn test_seek_with_scan_index() {
+        let data = evs_capture(250, 500);
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        // an entry for the first packet of every second of capture time
+        let entries = &reader.index().unwrap().tracks[0].entries;
+        let offsets = entries.iter().map(|e| e.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [500, 1000, 2000, 3000, 4000, 5000]);
+        assert_eq!(entries[2].ext, 75 * 320);
+
+        let frame = 320;
+        let to = |ts| SeekTo::TimeStamp { ts, track_id: 0 };
+        // 2.5 s into the capture lands on the packet of 2 s
+        let seeked = reader.seek(SeekMode::Coarse, to(2500 * 16)).unwrap();
+        assert_eq!(seeked.actual_ts, 2000 * 16);
+        assert_eq!(reader.next_packet().unwrap().ts(), 2000 * 16);
+
+        let seeked = reader.seek(SeekMode::Accurate, to(1234 * 16)).unwrap();
+        assert_eq!(seeked.actual_ts, 1220 * 16);
+        let mut ts = vec![];
+        while let Ok(packet) = reader.next_packet() {
+            ts.push(packet.ts());
+        }
+        assert_eq!(ts, (61..275).map(|i| i * frame).collect::<Vec<_>>());
+    }
+
+    /// A capture in memory, scanned in place like a memory map
+    struct InMemory(Cursor<Vec<u8>>);
+
+    impl Read for InMemory {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for InMemory {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    impl MediaSource for InMemory {
+        fn is_seekable(&self) -> bool {
+            true
+        }
+
+        fn byte_len(&self) -> Option<u64> {
+            Some(self.0.get_ref().len() as u64)
+        }
+    }
+
+    impl AsRef<[u8]> for InMemory {
+        fn as_ref(&self) -> &[u8] {
+            self.0.get_ref()
+        }
+    }
+
+    #[test]
+    fn test_parallel_prescan() {
+        let data = evs_capture(100, 500);
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data.clone())), Default::default());
+        let expected = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        let source = InMemory(Cursor::new(data));
+        let mut reader =
+            RtpdumpReader::try_new_parallel(source, &Default::default(), &Default::default(), 4)
+                .unwrap();
+        assert_eq!(reader.detection().streams, expected.detection().streams);
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.n_frames, expected.tracks()[0].codec_params.n_frames);
+        assert_eq!(params.start_ts, 500 * 16);
+        assert_eq!(reader.index(), expected.index());
+
+        let mut seqs = vec![];
+        while let Ok(pkt) = reader.next_rtp_packet() {
+            seqs.push(pkt.rtp().seq());
+        }
+        assert_eq!(seqs, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_frame_meta() {
+        let mss = MediaSourceStream::new(
+            Box::new(Cursor::new(evs_capture(10, 0))),
+            Default::default(),
+        );
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        let packet = reader.next_packet().unwrap();
+        assert_eq!(packet.data.len(), 34);
+        assert_eq!(FrameMeta::split(&packet.data).1, None);
+        assert_eq!(reader.tracks()[0].codec_params.extra_data, None);
+
+        reader.set_frame_meta(true);
+        let extra_data = reader.tracks()[0].codec_params.extra_data.as_deref();
+        assert!(FrameMeta::attached(extra_data));
+        let packet = reader.next_packet().unwrap();
+        let (frame, meta) = FrameMeta::split(&packet.data);
+        assert_eq!(frame.len(), 34);
+        let meta = meta.unwrap();
+        assert_eq!((meta.frame_type, meta.bitrate), (4, Some(13200)));
+        assert_eq!(
+            (meta.sid, meta.bfi, meta.duration),
+            (false, false, Some(320))
+        );
+    }
+
+    #[test]
+    fn test_align_to_capture() {
+        let first_ts = |align: bool| {
+            let data = evs_capture(10, 500);
+            let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+            let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+            reader.set_align_to_capture(align);
+            let start_ts = reader.tracks()[0].codec_params.start_ts;
+            let sample_rate = reader.tracks()[0].codec_params.sample_rate.unwrap() as u64;
+            (start_ts, reader.next_packet().unwrap().ts(), sample_rate)
+        };
+
+        let (start_ts, ts, sample_rate) = first_ts(true);
+        assert_eq!(start_ts, sample_rate / 2);
+        assert_eq!(ts, start_ts);
+        assert_eq!(first_ts(false), (0, 0, sample_rate));
+    }
+
+    #[test]
+    fn test_next_rtp_packet() {
+        let data = evs_capture(10, 500);
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+
+        let mut seqs = vec![];
+        while let Ok(pkt) = reader.next_rtp_packet() {
+            assert_eq!(pkt.track_id, Some(0));
+            assert_eq!(pkt.offset, 500 + pkt.rtp().seq() as u32 * 20);
+            assert_eq!(pkt.rtp().payload().len(), 33);
+            seqs.push(pkt.rtp().seq());
+        }
+        assert_eq!(seqs, (0..10).collect::<Vec<_>>());
+
+        let data = evs_capture(10, 500);
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        let pkts = reader.rtp_packets().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(pkts.len(), 10);
+        assert!(pkts.iter().all(|(ssrc, _)| *ssrc == 0x1234));
+        assert!(reader.rtp_packets().next().is_none());
+    }
+
+    #[test]
+    fn test_single_pass() {
+        let data = evs_capture(100, 0);
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader =
+            RtpdumpReader::try_new_single_pass(mss, &Default::default(), &Default::default(), 500)
+                .unwrap();
+        // up to the first packet past the window
+        assert_eq!(reader.prescanned.len(), 27);
+        assert_eq!(reader.tracks().len(), 1);
+        // the end of the track isn't known, nor where to seek
+        assert_eq!(reader.tracks()[0].codec_params.n_frames, None);
+        assert!(reader.index().is_none());
+        let to = SeekTo::TimeStamp { ts: 0, track_id: 0 };
+        assert!(reader.seek(SeekMode::Coarse, to).is_err());
+
+        let mut seqs = vec![];
+        while let Ok(pkt) = reader.next_rtp_packet() {
+            seqs.push(pkt.rtp().seq());
+        }
+        assert_eq!(seqs, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_infer_clock_rate() {
+        let mut registry = crate::RtpCodecRegistry::default();
+        registry.register(crate::RtpCodecDescriptor {
+            name: "SILK",
+            codec: symphonia_core::codecs::CODEC_TYPE_NULL,
+            clock_rate: None,
+            depacketizer: || Box::new(crate::depacketizer::Passthrough),
+        });
+        let streams = |data: Vec<u8>| {
+            let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+            let reader =
+                RtpdumpReader::try_new_with_registry(mss, &Default::default(), &registry).unwrap();
+            reader.detection().streams.clone()
+        };
+        assert_eq!(streams(evs_capture(100, 0))[0].clock_rate, Some(16000));
+
+        // SILK payloads vary in size, 320 timestamp units are 40 ms at 8 kHz or 20 ms at 16 kHz
+        let silk = |ptime: u32| {
+            let mut packetizer =
+                codec_detector::rtp::RtpPacketizer::new(0x1234, 96, 320 * 1000 / ptime, ptime);
+            rtpdump((0..50).map(|i| {
+                let payload = vec![0x55; 70 + i as usize % 7];
+                (i * ptime, packetizer.packetize(&payload))
+            }))
+        };
+        let stream = &streams(silk(40))[0];
+        assert_eq!(stream.clock_rate, Some(8000));
+        let codec = stream.codec.as_ref().unwrap();
+        assert_eq!((codec.name.as_str(), codec.sample_rate), ("SILK", 8000));
+        // variable sizes every 320 ticks also match the 16 kHz codecs
+        let stream = &streams(silk(20))[0];
+        assert_eq!(stream.clock_rate, Some(16000));
+        assert_eq!(stream.codec.as_ref().unwrap().sample_rate, 16000);
+    }
 }
@@ -0,0 +1,301 @@
+use std::collections::{HashMap, VecDeque};
+
+use bytes::Bytes;
+use codec_detector::rtp::{parse_rtp, NotRtpFilter, PayloadType, RtpPacket, SeqNum};
+use codec_detector::CodecDetector;
+use symphonia_core::audio::SampleBuffer;
+use symphonia_core::codecs::{CodecRegistry, Decoder};
+use symphonia_core::errors::Error;
+use symphonia_core::formats::Packet;
+
+use crate::capture::codec_registry;
+use crate::channel::ChannelPacket;
+use crate::track::TrackPipeline;
+use crate::{at_clock_rate, codec_to_param, select_codec, RtpCodecRegistry};
+
+/// Decoded audio of one or more frames of a stream, emitted by [`LiveDecoder`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodedFrame {
+    pub ssrc: u32,
+    pub sample_rate: u32,
+    /// Position of the first sample on the stream timeline, in samples since the first packet
+    /// of the stream
+    pub ts: u64,
+    /// Decoded mono audio
+    pub pcm: Vec<i16>,
+}
+
+enum LiveStream {
+    /// Packets waiting for the codec of the stream to be detected
+    Detecting {
+        detector: CodecDetector,
+        pkts: Vec<(u32, Bytes)>,
+    },
+    Decoding {
+        pt: u8,
+        sample_rate: u32,
+        pipeline: TrackPipeline,
+        decoder: Box<dyn Decoder>,
+    },
+    /// No registered codec matches the stream
+    Unsupported,
+}
+
+/// Push-style counterpart of [`RtpdumpReader`](crate::RtpdumpReader) for live traffic.
+///
+/// Raw RTP packets are fed one at a time with their arrival time, every SSRC gets its own
+/// codec detection, reorder buffer and decoder, and decoded audio is handed to a callback as
+/// soon as the reorder buffer releases it.
+pub struct LiveDecoder {
+    registry: RtpCodecRegistry,
+    codecs: CodecRegistry,
+    streams: HashMap<u32, LiveStream>,
+    packets: VecDeque<Packet>,
+    sample_buf: Option<SampleBuffer<i16>>,
+    /// Packets of a SSRC inspected before picking its codec
+    pub detect_packets: usize,
+    /// Packets every stream buffers to put reordered packets back in sequence
+    pub reorder_depth: usize,
+    /// Longest timestamp gap, in milliseconds, filled with missing frames
+    pub max_gap_fill: u32,
+    /// Heuristics skipping packets multiplexed with RTP, e.g. STUN or DTLS
+    pub not_rtp: NotRtpFilter,
+}
+
+impl Default for LiveDecoder {
+    fn default() -> Self {
+        Self::with_registry(RtpCodecRegistry::default())
+    }
+}
+
+impl LiveDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a decoder which maps detected codecs to decoders with a user supplied registry
+    pub fn with_registry(registry: RtpCodecRegistry) -> Self {
+        Self {
+            registry,
+            codecs: codec_registry(),
+            streams: HashMap::new(),
+            packets: VecDeque::new(),
+            sample_buf: None,
+            detect_packets: 50,
+            reorder_depth: 4,
+            max_gap_fill: 60_000,
+            not_rtp: NotRtpFilter::default(),
+        }
+    }
+
+    /// Feed a RTP packet which arrived at `arrival` milliseconds (any fixed origin), `on_frame`
+    /// receives the audio this packet makes available
+    pub fn push(&mut self, data: &[u8], arrival: u32, mut on_frame: impl FnMut(DecodedFrame)) {
+        if self.not_rtp.detect(data, &[]) {
+            return;
+        }
+        let Ok(rtp) = parse_rtp(data) else {
+            return;
+        };
+        // RTCP packets parse as RTP with a reserved payload type
+        if matches!(rtp.payload_type(), PayloadType::Reserved(_)) {
+            return;
+        }
+        let ssrc = rtp.ssrc();
+
+        let stream = self
+            .streams
+            .entry(ssrc)
+            .or_insert_with(|| LiveStream::Detecting {
+                detector: CodecDetector::with_default_features(),
+                pkts: vec![],
+            });
+        match stream {
+            LiveStream::Detecting { detector, pkts } => {
+                detector.on_pkt(&rtp);
+                pkts.push((arrival, Bytes::copy_from_slice(data)));
+                if pkts.len() < self.detect_packets {
+                    return;
+                }
+                let pkts = std::mem::take(pkts);
+                self.start_decoding(ssrc, pkts, &mut on_frame);
+            }
+            LiveStream::Decoding { .. } => {
+                self.decode(ssrc, Bytes::copy_from_slice(data), arrival, &mut on_frame)
+            }
+            LiveStream::Unsupported => {}
+        }
+    }
+
+    /// Decode whatever is still buffered, e.g. once the capture ended
+    pub fn flush(&mut self, mut on_frame: impl FnMut(DecodedFrame)) {
+        let detecting = self
+            .streams
+            .iter_mut()
+            .filter_map(|(ssrc, stream)| match stream {
+                LiveStream::Detecting { pkts, .. } => Some((*ssrc, std::mem::take(pkts))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (ssrc, pkts) in detecting {
+            self.start_decoding(ssrc, pkts, &mut on_frame);
+        }
+
+        let ssrcs = self.streams.keys().copied().collect::<Vec<_>>();
+        for ssrc in ssrcs {
+            if let Some(LiveStream::Decoding { pipeline, .. }) = self.streams.get_mut(&ssrc) {
+                while let Some(pkt) = pipeline.channel.drain_pkt() {
+                    // malformed packets are concealed by the decoder
+                    let _ = pipeline.depacketize(0, &pkt, self.max_gap_fill, &mut self.packets);
+                }
+                pipeline.flush(0, self.max_gap_fill, &mut self.packets);
+            }
+            self.emit(ssrc, &mut on_frame);
+        }
+    }
+
+    /// Pick the codec of a stream from the packets inspected so far, then decode them
+    fn start_decoding(
+        &mut self,
+        ssrc: u32,
+        pkts: Vec<(u32, Bytes)>,
+        on_frame: &mut impl FnMut(DecodedFrame),
+    ) {
+        let Some(LiveStream::Detecting { detector, .. }) = self.streams.remove(&ssrc) else {
+            return;
+        };
+
+        let mut pts: HashMap<u8, u64> = HashMap::new();
+        for (_, data) in &pkts {
+            if let Ok(rtp) = parse_rtp(data) {
+                *pts.entry(rtp.payload_type().to_u8()).or_default() += 1;
+            }
+        }
+        let detected = detector.get_result();
+        let stream = select_codec(pts, &detected, &self.registry).and_then(|(pt, codec, desc)| {
+            let codec = &at_clock_rate(codec, desc);
+            let decoder = self
+                .codecs
+                .make(&codec_to_param(desc, codec), &Default::default())
+                .ok()?;
+            Some(LiveStream::Decoding {
+                pt,
+                sample_rate: codec.sample_rate,
+                pipeline: TrackPipeline::new(desc, codec.sample_rate, self.reorder_depth),
+                decoder,
+            })
+        });
+        self.streams
+            .insert(ssrc, stream.unwrap_or(LiveStream::Unsupported));
+
+        for (arrival, data) in pkts {
+            self.decode(ssrc, data, arrival, on_frame);
+        }
+    }
+
+    /// Queue a packet of a stream whose codec is known and decode what its channel releases
+    fn decode(
+        &mut self,
+        ssrc: u32,
+        data: Bytes,
+        arrival: u32,
+        on_frame: &mut impl FnMut(DecodedFrame),
+    ) {
+        let Some(LiveStream::Decoding { pt, pipeline, .. }) = self.streams.get_mut(&ssrc) else {
+            return;
+        };
+        let Ok(rtp) = parse_rtp(&data) else {
+            return;
+        };
+        // telephone events, comfort noise and other payload types of the stream
+        if rtp.payload_type().to_u8() != *pt {
+            return;
+        }
+
+        pipeline.channel.add_pkt(ChannelPacket {
+            seq: SeqNum(rtp.seq()),
+            offset: arrival,
+            data,
+        });
+        while let Some(pkt) = pipeline.channel.pop_pkt() {
+            // malformed packets are concealed by the decoder
+            let _ = pipeline.depacketize(0, &pkt, self.max_gap_fill, &mut self.packets);
+        }
+        self.emit(ssrc, on_frame);
+    }
+
+    /// Decode the packets queued for a stream
+    fn emit(&mut self, ssrc: u32, on_frame: &mut impl FnMut(DecodedFrame)) {
+        let Some(LiveStream::Decoding {
+            sample_rate,
+            decoder,
+            ..
+        }) = self.streams.get_mut(&ssrc)
+        else {
+            self.packets.clear();
+            return;
+        };
+
+        for packet in self.packets.drain(..) {
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // the frame is lost, later frames still decode
+                Err(Error::DecodeError(_)) => continue,
+                Err(_) => break,
+            };
+
+            let buf = match self.sample_buf.as_mut() {
+                Some(buf) if buf.capacity() >= decoded.capacity() => buf,
+                _ => self.sample_buf.insert(SampleBuffer::new(
+                    decoded.capacity() as u64,
+                    *decoded.spec(),
+                )),
+            };
+            buf.copy_interleaved_ref(decoded);
+            on_frame(DecodedFrame {
+                ssrc,
+                sample_rate: *sample_rate,
+                ts: packet.ts(),
+                pcm: buf.samples().to_vec(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::evs_packets;
+
+    #[test]
+    fn test_live_decode() {
+        let mut live = LiveDecoder::new();
+        live.detect_packets = 10;
+        let mut frames = vec![];
+
+        let mut pkts = evs_packets(40, 0)
+            .into_iter()
+            .map(|(_, pkt)| pkt)
+            .collect::<Vec<_>>();
+        // reordered and duplicated packets
+        pkts.swap(20, 21);
+        pkts.push(pkts[30].clone());
+
+        for (i, pkt) in pkts.iter().enumerate() {
+            live.push(pkt, i as u32 * 20, |f| frames.push(f));
+            if i == 5 {
+                assert!(frames.is_empty());
+            }
+        }
+        assert!(!frames.is_empty());
+        live.flush(|f| frames.push(f));
+
+        assert_eq!(frames.len(), 40);
+        assert!(frames
+            .iter()
+            .all(|f| f.ssrc == 0x1234 && f.pcm.len() == 320));
+        let step = frames[1].ts - frames[0].ts;
+        assert!(step > 0);
+        assert!(frames.windows(2).all(|w| w[1].ts == w[0].ts + step));
+    }
+}
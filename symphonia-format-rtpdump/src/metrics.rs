@@ -0,0 +1,199 @@
+use std::fmt::Write;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counters of the readers and decoders of a long-running service.
+///
+/// One instance is shared by every reader given it with
+/// [`RtpdumpReader::set_metrics`](crate::RtpdumpReader::set_metrics) and every
+/// [`BudgetedDecoder`](crate::BudgetedDecoder) given it with
+/// [`BudgetedDecoder::set_metrics`](crate::BudgetedDecoder::set_metrics), and
+/// [`Metrics::render`] writes them for a Prometheus scrape.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    packets: AtomicU64,
+    decode_errors: AtomicU64,
+    channels: AtomicI64,
+    buffered: AtomicI64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// RTP packets of the tracks read so far
+    pub fn packets(&self) -> u64 {
+        self.packets.load(Ordering::Relaxed)
+    }
+
+    /// Packets dropped as malformed by the depacketizers, or rejected by the decoders
+    pub fn decode_errors(&self) -> u64 {
+        self.decode_errors.load(Ordering::Relaxed)
+    }
+
+    /// Channels, one per track, of the readers alive
+    pub fn active_channels(&self) -> i64 {
+        self.channels.load(Ordering::Relaxed)
+    }
+
+    /// Packets held in the reorder buffers of the channels
+    pub fn buffered_packets(&self) -> i64 {
+        self.buffered.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_packet(&self) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_channels(&self, delta: i64) {
+        self.channels.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_buffered(&self, delta: i64) {
+        self.buffered.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// The counters in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let metrics = [
+            (
+                "rtpdump_packets_total",
+                "counter",
+                "RTP packets of the tracks read",
+                self.packets() as i64,
+            ),
+            (
+                "rtpdump_decode_errors_total",
+                "counter",
+                "Packets dropped as malformed or rejected by the decoders",
+                self.decode_errors() as i64,
+            ),
+            (
+                "rtpdump_active_channels",
+                "gauge",
+                "Channels of the readers alive",
+                self.active_channels(),
+            ),
+            (
+                "rtpdump_buffered_packets",
+                "gauge",
+                "Packets held in the reorder buffers",
+                self.buffered_packets(),
+            ),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+            );
+        }
+        text
+    }
+}
+
+/// What a reader adds to its [`Metrics`], taken back once the reader is dropped
+#[derive(Debug)]
+pub(crate) struct ReaderMetrics {
+    metrics: Arc<Metrics>,
+    channels: usize,
+    buffered: usize,
+}
+
+impl ReaderMetrics {
+    pub fn new(metrics: Arc<Metrics>, channels: usize) -> Self {
+        metrics.add_channels(channels as i64);
+        Self {
+            metrics,
+            channels,
+            buffered: 0,
+        }
+    }
+
+    pub fn set_buffered(&mut self, buffered: usize) {
+        self.metrics
+            .add_buffered(buffered as i64 - self.buffered as i64);
+        self.buffered = buffered;
+    }
+}
+
+impl Deref for ReaderMetrics {
+    type Target = Metrics;
+
+    fn deref(&self) -> &Metrics {
+        &self.metrics
+    }
+}
+
+impl Drop for ReaderMetrics {
+    fn drop(&mut self) {
+        self.metrics.add_channels(-(self.channels as i64));
+        self.metrics.add_buffered(-(self.buffered as i64));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::test_util::{evs_packets, rtpdump};
+    use crate::RtpdumpReader;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.add_packet();
+        metrics.add_packet();
+        metrics.add_decode_error();
+        metrics.add_channels(3);
+        metrics.add_buffered(5);
+        metrics.add_buffered(-2);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "# HELP rtpdump_packets_total RTP packets of the tracks read\n\
+             # TYPE rtpdump_packets_total counter\n\
+             rtpdump_packets_total 2\n"
+        ));
+        assert!(text.contains("\nrtpdump_decode_errors_total 1\n"));
+        assert!(text.contains("# TYPE rtpdump_active_channels gauge\nrtpdump_active_channels 3\n"));
+        assert!(text.ends_with("\nrtpdump_buffered_packets 3\n"));
+    }
+
+    #[test]
+    fn test_metrics() {
+        // the payload of the 6th packet is cut to 3 bytes, too short for any EVS frame
+        let mut pkts = evs_packets(40, 0);
+        pkts[5].1.truncate(15);
+        pkts[5].1[12..].fill(0xff);
+        let data = rtpdump(pkts);
+
+        let metrics = Arc::new(Metrics::new());
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+        reader.set_metrics(metrics.clone());
+        assert_eq!(metrics.active_channels(), 1);
+        for _ in 0..10 {
+            reader.next_packet().unwrap();
+        }
+        // the reorder buffer holds 16 packets back
+        assert_eq!(metrics.buffered_packets(), 16);
+        while reader.next_packet().is_ok() {}
+        assert_eq!(metrics.packets(), 40);
+        assert_eq!(metrics.decode_errors(), 1);
+        assert_eq!(metrics.buffered_packets(), 0);
+
+        drop(reader);
+        assert_eq!(metrics.active_channels(), 0);
+    }
+}
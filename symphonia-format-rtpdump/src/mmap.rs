@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{Error as IOError, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+
+use symphonia_core::io::MediaSource;
+
+/// Read-only memory map of a whole file, read by the rtpdump reader without a syscall per
+/// buffer refill. [`MmapSource::as_slice`] hands the mapped capture to
+/// [`RtpdumpPackets`](crate::RtpdumpPackets) to borrow its RTP packets without copying them.
+pub struct MmapSource {
+    ptr: *mut libc::c_void,
+    len: usize,
+    pos: u64,
+}
+
+// The mapping is private and read-only, and only unmapped on drop.
+unsafe impl Send for MmapSource {}
+unsafe impl Sync for MmapSource {}
+
+impl MmapSource {
+    /// Map `file` as it is now, later changes to its length aren't seen
+    pub fn open(file: &File) -> Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| IOError::new(ErrorKind::InvalidInput, "file too large to map"))?;
+        if len == 0 {
+            // mapping nothing fails, an empty file reads as such without a map
+            return Ok(Self {
+                ptr: std::ptr::null_mut(),
+                len,
+                pos: 0,
+            });
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(IOError::last_os_error());
+        }
+        // packets are read front to back
+        unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+        Ok(Self { ptr, len, pos: 0 })
+    }
+
+    /// Content of the whole file
+    pub fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for MmapSource {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+impl AsRef<[u8]> for MmapSource {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl Read for MmapSource {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let data = self.as_slice();
+        let rest = &data[(self.pos as usize).min(data.len())..];
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for MmapSource {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => (self.len as u64).checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        let Some(pos) = pos else {
+            return Err(IOError::new(
+                ErrorKind::InvalidInput,
+                "seek to a negative position",
+            ));
+        };
+        // like a file, seeking past the end is allowed and reads nothing
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+impl MediaSource for MmapSource {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_mmap_source() {
+        let path = std::env::temp_dir().join(format!("rtpdump-mmap-{}", std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(b"0123456789")
+            .unwrap();
+        let mut source = MmapSource::open(&File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(source.as_slice(), b"0123456789");
+        assert_eq!(source.byte_len(), Some(10));
+        let mut buf = [0; 4];
+        source.seek(SeekFrom::Start(8)).unwrap();
+        assert_eq!(source.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"89");
+        assert_eq!(source.read(&mut buf).unwrap(), 0);
+        assert!(source.seek(SeekFrom::Current(-11)).is_err());
+        source.seek(SeekFrom::End(-4)).unwrap();
+        source.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"6789");
+    }
+}
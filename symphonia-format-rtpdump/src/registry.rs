@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use symphonia_core::codecs::{CodecRegistry, CodecType};
+
+use voip_codecs::{
+    by_codec_type, VoipCodec, CODEC_TYPE_AMR, CODEC_TYPE_AMRWB, CODEC_TYPE_EVS, CODEC_TYPE_G722_1,
+    EVS,
+};
+
+use crate::depacketizer::{
+    AmrDepacketizer, AmrPacking, EvsDepacketizer, Passthrough, PayloadDepacketizer,
+};
+
+/// Create the depacketizer of a new track.
+pub type DepacketizerFactory = fn() -> Box<dyn PayloadDepacketizer>;
+
+/// Describes how a codec detected inside a RTP stream is handed to symphonia.
+#[derive(Clone, Copy, Debug)]
+pub struct RtpCodecDescriptor {
+    /// Codec name as reported by `codec_detector::CodecDetector`, matched case-insensitively
+    pub name: &'static str,
+    /// Codec type of the decoder registered in symphonia's `CodecRegistry`
+    pub codec: CodecType,
+    /// RTP clock rate, `None` if it equals the sample rate of the codec
+    pub clock_rate: Option<u32>,
+    /// Depacketizer turning RTP payloads of a track into decoder frames
+    pub depacketizer: DepacketizerFactory,
+}
+
+/// Support of a codec of a [`RtpCodecRegistry`], see [`RtpCodecRegistry::capabilities`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodecCapability {
+    pub name: &'static str,
+    pub codec: CodecType,
+    /// Short name of the decoder registered for the codec, `None` if its tracks are demuxed but
+    /// can't be decoded
+    pub decoder: Option<&'static str>,
+    /// Standard name, media type and clock rate of the codec, `None` for codecs not shipped
+    /// with this workspace
+    pub info: Option<&'static VoipCodec>,
+}
+
+impl CodecCapability {
+    pub fn is_decodable(&self) -> bool {
+        self.decoder.is_some()
+    }
+
+    /// Name of the codec for front-ends to show, e.g. "AMR-WB" rather than "amrwb"
+    pub fn display_name(&self) -> &'static str {
+        self.info.map_or(self.name, |info| info.display_name)
+    }
+}
+
+/// Codecs the rtpdump reader knows how to turn into tracks.
+///
+/// `RtpCodecRegistry::default()` contains every codec shipped with this workspace, use
+/// [`RtpCodecRegistry::register`] to add proprietary codecs or to override a builtin one.
+#[derive(Clone, Debug)]
+pub struct RtpCodecRegistry {
+    codecs: HashMap<String, RtpCodecDescriptor>,
+}
+
+impl RtpCodecRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            codecs: HashMap::new(),
+        }
+    }
+
+    /// Register a codec, replacing any codec previously registered with the same name
+    pub fn register(&mut self, desc: RtpCodecDescriptor) {
+        self.codecs.insert(desc.name.to_lowercase(), desc);
+    }
+
+    /// Lookup a codec by the name reported by the codec detector
+    pub fn get(&self, name: &str) -> Option<&RtpCodecDescriptor> {
+        self.codecs.get(&name.to_lowercase())
+    }
+
+    /// Codecs of the registry sorted by name, with the decoder `codecs` provides for each.
+    ///
+    /// The C libraries of the builtin decoders are linked at build time, a registered decoder is
+    /// a working one. Front-ends can warn about a codec without decoder before decoding starts.
+    pub fn capabilities(&self, codecs: &CodecRegistry) -> Vec<CodecCapability> {
+        let mut caps = self
+            .codecs
+            .values()
+            .map(|desc| CodecCapability {
+                name: desc.name,
+                codec: desc.codec,
+                decoder: codecs.get_codec(desc.codec).map(|d| d.short_name),
+                info: by_codec_type(desc.codec),
+            })
+            .collect::<Vec<_>>();
+        caps.sort_by_key(|cap| cap.name.to_lowercase());
+        caps
+    }
+}
+
+impl Default for RtpCodecRegistry {
+    fn default() -> Self {
+        let builtins: [(&'static str, CodecType, Option<u32>, DepacketizerFactory); 5] = [
+            ("amr", CODEC_TYPE_AMR, None, || {
+                Box::new(AmrDepacketizer::new(false, AmrPacking::Auto))
+            }),
+            ("amrbe", CODEC_TYPE_AMR, None, || {
+                Box::new(AmrDepacketizer::new(false, AmrPacking::BandwidthEfficient))
+            }),
+            ("amrwb", CODEC_TYPE_AMRWB, None, || {
+                Box::new(AmrDepacketizer::new(true, AmrPacking::Auto))
+            }),
+            // EVS uses a 16 kHz RTP clock whatever the audio bandwidth
+            ("evs", CODEC_TYPE_EVS, Some(EVS.clock_rate), || {
+                Box::<EvsDepacketizer>::default()
+            }),
+            ("G.722.1", CODEC_TYPE_G722_1, None, || Box::new(Passthrough)),
+        ];
+
+        let mut registry = Self::new();
+        for (name, codec, clock_rate, depacketizer) in builtins {
+            registry.register(RtpCodecDescriptor {
+                name,
+                codec,
+                clock_rate,
+                depacketizer,
+            });
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use symphonia_core::codecs::decl_codec_type;
+
+    use super::*;
+
+    #[test]
+    fn register_external_codec() {
+        let silk = decl_codec_type(b"silk");
+        let mut registry = RtpCodecRegistry::default();
+        assert!(registry.get("SILK").is_none());
+        registry.register(RtpCodecDescriptor {
+            name: "SILK",
+            codec: silk,
+            clock_rate: None,
+            depacketizer: || Box::new(Passthrough),
+        });
+
+        assert_eq!(registry.get("silk").unwrap().codec, silk);
+        assert_eq!(registry.get("g.722.1").unwrap().codec, CODEC_TYPE_G722_1);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let mut codecs = CodecRegistry::new();
+        codecs.register_all::<symphonia_bundle_amr::AmrDecoder>();
+        let caps = RtpCodecRegistry::default().capabilities(&codecs);
+
+        let names = caps.iter().map(|cap| cap.name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["amr", "amrbe", "amrwb", "evs", "G.722.1"]);
+        assert_eq!(caps[0].decoder, Some("amr"));
+        assert!(caps[1].is_decodable());
+        assert!(!caps[2].is_decodable());
+        assert_eq!(caps[3].decoder, None);
+
+        let labels = caps
+            .iter()
+            .map(|cap| cap.display_name())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["AMR", "AMR", "AMR-WB", "EVS", "G.722.1"]);
+        assert_eq!(caps[2].info.map(|i| i.mime_type()).unwrap(), "audio/AMR-WB");
+    }
+}
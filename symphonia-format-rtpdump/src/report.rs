@@ -0,0 +1,90 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Version of the serialized reports, bumped whenever a field is renamed, removed or changes
+/// meaning. Fields may be added without a bump.
+pub const REPORT_VERSION: u32 = 1;
+
+/// A report serialized with the [`REPORT_VERSION`] of its fields, e.g. the
+/// [`DetectionSummary`](crate::DetectionSummary) of a capture handed to another service.
+///
+/// The version sits next to the fields of the report, deserializing a report of a later
+/// version fails.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report<T> {
+    #[serde(deserialize_with = "supported_version")]
+    pub version: u32,
+    #[serde(flatten)]
+    pub report: T,
+}
+
+impl<T> Report<T> {
+    pub fn new(report: T) -> Self {
+        Self {
+            version: REPORT_VERSION,
+            report,
+        }
+    }
+}
+
+fn supported_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version > REPORT_VERSION {
+        return Err(serde::de::Error::custom(format!(
+            "report version {version} is newer than {REPORT_VERSION}"
+        )));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::{packet_timeline, DetectionSummary, PacketTimeline, RtpdumpReader, RtpdumpWriter};
+    use codec_detector::rtp::RtpPacketizer;
+
+    #[test]
+    fn test_report_roundtrip() {
+        let source = std::net::SocketAddrV4::new([10, 0, 0, 1].into(), 5004);
+        let mut writer = RtpdumpWriter::new(vec![], source, std::time::Duration::ZERO).unwrap();
+        let mut packetizer = RtpPacketizer::new(0x1234, 96, 16000, 20);
+        for i in 0..50 {
+            writer
+                .write_packet(i * 20, &packetizer.packetize(&[i as u8; 33]))
+                .unwrap();
+        }
+        let capture = writer.into_inner();
+        let mss =
+            MediaSourceStream::new(Box::new(Cursor::new(capture.clone())), Default::default());
+        let reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+
+        let report = Report::new(reader.detection().clone());
+        let yaml = serde_yaml::to_string(&report).unwrap();
+        assert!(
+            yaml.starts_with("version: 1\nheader:\n  ip: 10.0.0.1\n"),
+            "{yaml}"
+        );
+        assert!(
+            yaml.contains("  payloadType: 96\n  firstOffset: 0\n"),
+            "{yaml}"
+        );
+        let parsed: Report<DetectionSummary> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed.version, REPORT_VERSION);
+        assert_eq!(parsed.report.streams, report.report.streams);
+
+        let timeline = Report::new(packet_timeline(&capture).unwrap());
+        let yaml = serde_yaml::to_string(&timeline).unwrap();
+        let parsed: Report<PacketTimeline> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            parsed.report.streams[0].arrivals,
+            timeline.report.streams[0].arrivals
+        );
+
+        let newer = yaml.replacen("version: 1", "version: 2", 1);
+        assert!(serde_yaml::from_str::<Report<PacketTimeline>>(&newer).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use codec_detector::rtp::{parse_rtp, RtpPacket};
+use symphonia_core::errors::Result;
+
+use crate::clock::infer_clock_rate;
+use crate::RtpdumpPackets;
+
+/// Where [`retimestamp`] takes the new timestamps of a packet from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RetimestampMode {
+    /// One packet time per sequence number since the first packet, for senders whose
+    /// timestamps are constant or advance by the wrong amount. Lost packets keep their room.
+    #[default]
+    Sequence,
+    /// The arrival time since the first packet, rounded to the packet time, for senders whose
+    /// sequence numbers can't be trusted either
+    Arrival,
+}
+
+/// How [`retimestamp`] regenerates the RTP timestamps of a capture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetimestampOptions {
+    pub mode: RetimestampMode,
+    /// Milliseconds of audio of a packet
+    pub ptime: u32,
+    /// RTP clock rate of every stream, `None` to infer it from the timestamps the stream
+    /// advanced by over its duration, or 8 kHz if they don't advance
+    pub clock_rate: Option<u32>,
+}
+
+impl Default for RetimestampOptions {
+    fn default() -> Self {
+        Self {
+            mode: RetimestampMode::default(),
+            ptime: 20,
+            clock_rate: None,
+        }
+    }
+}
+
+/// Where a stream started, and how far its sequence numbers went
+#[derive(Clone, Copy, Debug)]
+struct StreamStart {
+    ts: u32,
+    offset: u32,
+    /// Last sequence number, with its unwrapped distance from the first one
+    last_seq: (u16, i64),
+    /// Last timestamp, with its unwrapped distance from the first one
+    last_ts: (u32, i64),
+    last_offset: u32,
+    clock_rate: u32,
+}
+
+impl StreamStart {
+    fn new(ts: u32, offset: u32, seq: u16) -> Self {
+        Self {
+            ts,
+            offset,
+            last_seq: (seq, 0),
+            last_ts: (ts, 0),
+            last_offset: offset,
+            clock_rate: 8000,
+        }
+    }
+
+    /// Unwrapped distance of `seq` from the first sequence number
+    fn seq_ext(&mut self, seq: u16) -> i64 {
+        let ext = self.last_seq.1 + seq.wrapping_sub(self.last_seq.0) as i16 as i64;
+        self.last_seq = (seq, ext);
+        ext
+    }
+}
+
+/// Copy of a rtpdump capture held in memory whose RTP timestamps are regenerated as set by
+/// `options`, so that the captures of senders emitting constant timestamps or advancing them by
+/// the wrong amount decode with sane timing.
+///
+/// The first packet of every SSRC keeps its timestamp. Packets that aren't RTP are left as
+/// they are.
+pub fn retimestamp(capture: &[u8], options: &RetimestampOptions) -> Result<Vec<u8>> {
+    let mut streams: HashMap<u32, StreamStart> = HashMap::new();
+    // the clock rate of a stream is inferred from its whole duration
+    for (rd, data) in RtpdumpPackets::new(capture)? {
+        // RTCP records have no original length
+        if rd.org_len == 0 {
+            continue;
+        }
+        let Ok(pkt) = parse_rtp(data) else {
+            continue;
+        };
+        let stream = streams
+            .entry(pkt.ssrc())
+            .or_insert_with(|| StreamStart::new(pkt.ts(), rd.offset, pkt.seq()));
+        let ext = stream.last_ts.1 + pkt.ts().wrapping_sub(stream.last_ts.0) as i32 as i64;
+        stream.last_ts = (pkt.ts(), ext);
+        stream.last_offset = rd.offset;
+    }
+    for stream in streams.values_mut() {
+        let elapsed = stream.last_offset.saturating_sub(stream.offset);
+        stream.clock_rate = options
+            .clock_rate
+            .or_else(|| infer_clock_rate(stream.last_ts.1, elapsed))
+            .unwrap_or(8000);
+    }
+
+    let mut repaired = capture.to_vec();
+    let ptime = options.ptime.max(1);
+    for (rd, data) in RtpdumpPackets::new(capture)? {
+        // RTCP records have no original length
+        if rd.org_len == 0 {
+            continue;
+        }
+        let Ok(pkt) = parse_rtp(data) else {
+            continue;
+        };
+        let Some(stream) = streams.get_mut(&pkt.ssrc()) else {
+            continue;
+        };
+        let packets = match options.mode {
+            RetimestampMode::Sequence => stream.seq_ext(pkt.seq()),
+            RetimestampMode::Arrival => {
+                let elapsed = rd.offset.saturating_sub(stream.offset);
+                ((elapsed + ptime / 2) / ptime) as i64
+            }
+        };
+        let samples = packets * (stream.clock_rate as u64 * ptime as u64 / 1000) as i64;
+        let ts = stream.ts.wrapping_add(samples as u32);
+        // the timestamp is the second word of the RTP header
+        let at = data.as_ptr() as usize - capture.as_ptr() as usize + 4;
+        repaired[at..at + 4].copy_from_slice(&ts.to_be_bytes());
+    }
+    Ok(repaired)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RtpdumpWriter;
+    use codec_detector::rtp::RtpPacketizer;
+
+    /// Timestamps of the RTP packets of a capture
+    fn timestamps(capture: &[u8]) -> Vec<u32> {
+        RtpdumpPackets::new(capture)
+            .unwrap()
+            .map(|(_, data)| parse_rtp(data).unwrap().ts())
+            .collect()
+    }
+
+    #[test]
+    fn test_retimestamp() {
+        let source = std::net::SocketAddrV4::new([127, 0, 0, 1].into(), 5004);
+        let mut writer = RtpdumpWriter::new(vec![], source, std::time::Duration::ZERO).unwrap();
+        let mut packetizer = RtpPacketizer::new(0x1234, 96, 8000, 20).with_start(65534, 1000);
+        // every packet stamped 1000, the one of 40 ms lost and the one of 80 ms late
+        for offset in [0, 20, 40, 60, 80] {
+            let mut pkt = packetizer.packetize(&[0; 32]);
+            pkt[4..8].copy_from_slice(&1000u32.to_be_bytes());
+            match offset {
+                40 => {}
+                80 => writer.write_packet(97, &pkt).unwrap(),
+                _ => writer.write_packet(offset, &pkt).unwrap(),
+            }
+        }
+        let capture = writer.into_inner();
+        assert_eq!(timestamps(&capture), [1000; 4]);
+
+        let options = RetimestampOptions::default();
+        let repaired = retimestamp(&capture, &options).unwrap();
+        assert_eq!(timestamps(&repaired), [1000, 1160, 1480, 1640]);
+        assert_eq!(repaired.len(), capture.len());
+
+        let options = RetimestampOptions {
+            mode: RetimestampMode::Arrival,
+            clock_rate: Some(16000),
+            ..Default::default()
+        };
+        let repaired = retimestamp(&capture, &options).unwrap();
+        assert_eq!(timestamps(&repaired), [1000, 1320, 1960, 2600]);
+    }
+}
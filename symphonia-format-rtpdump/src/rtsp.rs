@@ -0,0 +1,431 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use log::debug;
+use symphonia_core::errors::{Error, Result};
+
+use crate::SessionDescription;
+
+/// Default RTSP port (RFC 2326 section 3.2)
+const RTSP_PORT: u16 = 554;
+
+/// Largest message body accepted, SDP descriptions are a few KiB at most
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// Longest status or header line accepted
+const MAX_LINE_LEN: usize = 4096;
+
+/// Most headers accepted in a message
+const MAX_HEADERS: usize = 64;
+
+/// Time the server has to send anything before reads fail, RTCP alone arrives every few seconds
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Headers and body of a successful RTSP response
+struct Response {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Minimal RTSP 1.0 client (RFC 2326) receiving the audio sections of a stream as RTP
+/// interleaved on the control connection, e.g. from an IP intercom or a recording server.
+///
+/// Call [`Self::describe`], [`Self::setup`] and [`Self::play`] in order, then read packets with
+/// [`Self::next_rtp`], typically into a [`LiveDecoder`](crate::LiveDecoder). Authentication
+/// isn't supported.
+pub struct RtspClient {
+    conn: BufReader<TcpStream>,
+    url: String,
+    /// URL media section controls are relative to
+    base: String,
+    cseq: u32,
+    session: Option<String>,
+    sdp: Option<SessionDescription>,
+    /// Interleaved RTP channel of every media section set up
+    channels: Vec<u8>,
+}
+
+impl RtspClient {
+    /// Connect to the server of a `rtsp://host[:port]/path` URL
+    pub fn connect(url: &str) -> Result<Self> {
+        let authority = url
+            .strip_prefix("rtsp://")
+            .and_then(|rem| rem.split('/').next())
+            .filter(|authority| !authority.is_empty() && !authority.contains('@'))
+            .ok_or(Error::DecodeError("Invalid RTSP URL"))?;
+        let conn = match authority.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => TcpStream::connect(authority)?,
+            _ => TcpStream::connect((authority, RTSP_PORT))?,
+        };
+        conn.set_read_timeout(Some(READ_TIMEOUT))?;
+        Ok(Self {
+            conn: BufReader::new(conn),
+            url: url.to_string(),
+            base: url.to_string(),
+            cseq: 0,
+            session: None,
+            sdp: None,
+            channels: vec![],
+        })
+    }
+
+    /// Time the server has to send anything before reads fail, 30 s by default, `None` to wait
+    /// forever
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        Ok(self.conn.get_ref().set_read_timeout(timeout)?)
+    }
+
+    /// Fetch the SDP of the stream
+    pub fn describe(&mut self) -> Result<&SessionDescription> {
+        let url = self.url.clone();
+        let resp = self.request("DESCRIBE", &url, &[("Accept", "application/sdp")])?;
+        if let Some(base) = resp
+            .header("Content-Base")
+            .or_else(|| resp.header("Content-Location"))
+        {
+            self.base = base.to_string();
+        }
+        let sdp = std::str::from_utf8(&resp.body)
+            .map_err(|_| Error::DecodeError("Invalid SDP"))
+            .and_then(SessionDescription::parse)?;
+        Ok(self.sdp.insert(sdp))
+    }
+
+    /// Ask for the RTP of every audio section to be interleaved on the connection, returns the
+    /// number of sections set up
+    pub fn setup(&mut self) -> Result<usize> {
+        let controls = self
+            .sdp
+            .as_ref()
+            .ok_or(Error::DecodeError("RTSP SETUP before DESCRIBE"))?
+            .media
+            .iter()
+            .filter(|m| m.media == "audio")
+            .map(|m| control_url(&self.base, m.control.as_deref()))
+            .collect::<Vec<_>>();
+
+        for control in controls {
+            // each section takes two of the 256 interleaved channels
+            let channel = u8::try_from(self.channels.len())
+                .ok()
+                .and_then(|n| n.checked_mul(2))
+                .ok_or(Error::DecodeError("Too many RTSP audio sections"))?;
+            let transport = format!(
+                "RTP/AVP/TCP;unicast;interleaved={}-{}",
+                channel,
+                channel + 1
+            );
+            let mut headers = vec![("Transport", transport.as_str())];
+            let session = self.session.clone();
+            if let Some(session) = &session {
+                headers.push(("Session", session));
+            }
+            let resp = self.request("SETUP", &control, &headers)?;
+            // the server may pick other channels
+            let channel = resp
+                .header("Transport")
+                .and_then(interleaved_channel)
+                .unwrap_or(channel);
+            self.channels.push(channel);
+            if self.session.is_none() {
+                self.session = resp
+                    .header("Session")
+                    .map(|s| s.split(';').next().unwrap_or_default().trim().to_string());
+            }
+        }
+        Ok(self.channels.len())
+    }
+
+    /// Start the stream
+    pub fn play(&mut self) -> Result<()> {
+        let url = self.base.clone();
+        let session = self
+            .session
+            .clone()
+            .ok_or(Error::DecodeError("RTSP PLAY before SETUP"))?;
+        self.request(
+            "PLAY",
+            &url,
+            &[("Session", &session), ("Range", "npt=0.000-")],
+        )?;
+        Ok(())
+    }
+
+    /// Stop the stream
+    pub fn teardown(&mut self) -> Result<()> {
+        let url = self.base.clone();
+        let Some(session) = self.session.take() else {
+            return Ok(());
+        };
+        self.request("TEARDOWN", &url, &[("Session", &session)])?;
+        Ok(())
+    }
+
+    /// Next RTP packet of the audio sections set up, `None` once the server closed the
+    /// connection. RTCP packets and messages of the server are skipped.
+    pub fn next_rtp(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            let mut magic = [0];
+            match self.conn.read_exact(&mut magic) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            if magic[0] != b'$' {
+                // a request or response of the server, e.g. a keep-alive reply
+                let mut line = vec![magic[0]];
+                self.read_line(&mut line)?;
+                debug!("rtsp: {}", String::from_utf8_lossy(&line).trim_end());
+                self.read_message()?;
+                continue;
+            }
+
+            let mut hdr = [0; 3];
+            self.conn.read_exact(&mut hdr)?;
+            let mut data = vec![0; u16::from_be_bytes([hdr[1], hdr[2]]) as usize];
+            self.conn.read_exact(&mut data)?;
+            if self.channels.contains(&hdr[0]) {
+                return Ok(Some(data));
+            }
+        }
+    }
+
+    /// Send a request and wait for its response, skipping the interleaved packets received in
+    /// between
+    fn request(&mut self, method: &str, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        self.cseq += 1;
+        let mut req = format!("{method} {url} RTSP/1.0\r\nCSeq: {}\r\n", self.cseq);
+        for (name, value) in headers {
+            req += &format!("{name}: {value}\r\n");
+        }
+        req += "User-Agent: symphonia-voip\r\n\r\n";
+        self.conn.get_mut().write_all(req.as_bytes())?;
+
+        loop {
+            let mut magic = [0];
+            self.conn.read_exact(&mut magic)?;
+            if magic[0] == b'$' {
+                let mut hdr = [0; 3];
+                self.conn.read_exact(&mut hdr)?;
+                let len = u16::from_be_bytes([hdr[1], hdr[2]]) as u64;
+                std::io::copy(&mut self.conn.by_ref().take(len), &mut std::io::sink())?;
+                continue;
+            }
+
+            let mut status = vec![magic[0]];
+            self.read_line(&mut status)?;
+            let status = String::from_utf8_lossy(&status).trim_end().to_string();
+            let resp = self.read_message()?;
+            let cseq = resp
+                .header("CSeq")
+                .and_then(|c| c.trim().parse::<u32>().ok());
+            // requests of the server and stale responses
+            if !status.starts_with("RTSP/") || cseq != Some(self.cseq) {
+                continue;
+            }
+            let code = status.split(' ').nth(1).and_then(|c| c.parse::<u16>().ok());
+            if !matches!(code, Some(200..=299)) {
+                debug!("rtsp: {method} {url}: {status}");
+                return Err(Error::DecodeError("RTSP request failed"));
+            }
+            return Ok(resp);
+        }
+    }
+
+    /// Append the rest of a line to `line`, up to [`MAX_LINE_LEN`] bytes, returns the number of
+    /// bytes read
+    fn read_line(&mut self, line: &mut Vec<u8>) -> Result<usize> {
+        let len = self
+            .conn
+            .by_ref()
+            .take(MAX_LINE_LEN as u64)
+            .read_until(b'\n', line)?;
+        if len == MAX_LINE_LEN && line.last() != Some(&b'\n') {
+            return Err(Error::DecodeError("RTSP line is too long"));
+        }
+        Ok(len)
+    }
+
+    /// Headers and body of a message whose first line was read
+    fn read_message(&mut self) -> Result<Response> {
+        let mut headers = vec![];
+        loop {
+            let mut line = vec![];
+            if self.read_line(&mut line)? == 0 {
+                return Err(Error::IoError(ErrorKind::UnexpectedEof.into()));
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(Error::DecodeError("Too many RTSP headers"));
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        let mut resp = Response {
+            headers,
+            body: vec![],
+        };
+        let len = resp
+            .header("Content-Length")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        if len > MAX_BODY_LEN {
+            return Err(Error::DecodeError("RTSP message body is too large"));
+        }
+        resp.body.resize(len, 0);
+        self.conn.read_exact(&mut resp.body)?;
+        Ok(resp)
+    }
+}
+
+/// URL of a media section, `base` being the one of the session
+fn control_url(base: &str, control: Option<&str>) -> String {
+    match control {
+        None | Some("*") => base.to_string(),
+        Some(control) if control.starts_with("rtsp://") => control.to_string(),
+        Some(control) => format!("{}/{}", base.trim_end_matches('/'), control),
+    }
+}
+
+/// RTP channel of an `interleaved=<rtp>-<rtcp>` transport parameter
+fn interleaved_channel(transport: &str) -> Option<u8> {
+    transport.split(';').find_map(|p| {
+        let channels = p.trim().strip_prefix("interleaved=")?;
+        channels.split('-').next()?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_control_url() {
+        let base = "rtsp://10.0.0.1/intercom/";
+        assert_eq!(control_url(base, None), base);
+        assert_eq!(
+            control_url(base, Some("trackID=1")),
+            "rtsp://10.0.0.1/intercom/trackID=1"
+        );
+        assert_eq!(control_url(base, Some("rtsp://x/a")), "rtsp://x/a");
+        assert_eq!(interleaved_channel("RTP/AVP/TCP;interleaved=4-5"), Some(4));
+        assert!(RtspClient::connect("http://10.0.0.1/").is_err());
+    }
+
+    #[test]
+    fn test_rtsp_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (conn, _) = listener.accept().unwrap();
+            let mut conn = BufReader::new(conn);
+            let mut requests = vec![];
+            let sdp = "v=0\r\nm=audio 0 RTP/AVP 96\r\na=control:trackID=1\r\n\
+                       a=rtpmap:96 EVS/16000\r\nm=video 0 RTP/AVP 97\r\n";
+            for reply in [
+                format!(
+                    "Content-Base: rtsp://127.0.0.1:{port}/mic/\r\nContent-Length: {}\r\n\r\n{sdp}",
+                    sdp.len()
+                ),
+                "Transport: RTP/AVP/TCP;unicast;interleaved=2-3\r\nSession: 42;timeout=60\r\n\r\n"
+                    .to_string(),
+                "\r\n".to_string(),
+            ] {
+                let mut req = String::new();
+                loop {
+                    let mut line = String::new();
+                    conn.read_line(&mut line).unwrap();
+                    req += &line;
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let cseq = req
+                    .lines()
+                    .find_map(|l| l.strip_prefix("CSeq: "))
+                    .unwrap()
+                    .to_string();
+                requests.push(req);
+                // a stray RTCP packet before the response
+                conn.get_mut().write_all(b"$\x03\x00\x01\x00").unwrap();
+                write!(conn.get_mut(), "RTSP/1.0 200 OK\r\nCSeq: {cseq}\r\n{reply}").unwrap();
+            }
+            conn.get_mut().write_all(b"$\x02\x00\x02\x80\x60").unwrap();
+            requests
+        });
+
+        let mut client = RtspClient::connect(&format!("rtsp://127.0.0.1:{port}/mic")).unwrap();
+        assert_eq!(client.describe().unwrap().media.len(), 2);
+        assert_eq!(client.setup().unwrap(), 1);
+        client.play().unwrap();
+        assert_eq!(client.next_rtp().unwrap(), Some(vec![0x80, 0x60]));
+        assert_eq!(client.next_rtp().unwrap(), None);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("DESCRIBE rtsp://127.0.0.1"));
+        assert!(requests[1].contains("/mic/trackID=1 RTSP/1.0"));
+        assert!(requests[1].contains("interleaved=0-1"));
+        assert!(requests[2].starts_with("PLAY"));
+        assert!(requests[2].contains("Session: 42\r\n"));
+    }
+
+    #[test]
+    fn test_rtsp_body_too_large() {
+        let long_header = format!("X-Pad: {}\r\n", "a".repeat(MAX_LINE_LEN));
+        let many_headers = "X-Pad: a\r\n".repeat(MAX_HEADERS + 1);
+        let replies = [
+            "Content-Length: 1000000\r\n\r\n".to_string(),
+            format!("{long_header}\r\n"),
+            format!("{many_headers}\r\n"),
+        ];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut conns = vec![];
+            for reply in replies {
+                let (mut conn, _) = listener.accept().unwrap();
+                write!(conn, "RTSP/1.0 200 OK\r\nCSeq: 1\r\n{reply}").unwrap();
+                conns.push(conn);
+            }
+            conns
+        });
+
+        for _ in 0..3 {
+            let mut client = RtspClient::connect(&format!("rtsp://127.0.0.1:{port}/mic")).unwrap();
+            assert!(matches!(client.describe(), Err(Error::DecodeError(_))));
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_rtsp_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || listener.accept().unwrap());
+
+        let mut client = RtspClient::connect(&format!("rtsp://127.0.0.1:{port}/mic")).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        // the server never answers
+        assert!(matches!(client.describe(), Err(Error::IoError(_))));
+        drop(server.join().unwrap());
+    }
+}
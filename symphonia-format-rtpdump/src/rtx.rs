@@ -0,0 +1,98 @@
+use bytes::Bytes;
+use codec_detector::rtp::{RawRtpPacket, RtpPacket};
+
+/// Original sequence number of a RFC 4588 retransmission, `None` for the padding-only packets
+/// senders use to probe bandwidth
+pub(crate) fn osn(rtx: &RawRtpPacket) -> Option<u16> {
+    match rtx.payload() {
+        [a, b, _, ..] => Some(u16::from_be_bytes([*a, *b])),
+        _ => None,
+    }
+}
+
+/// Rebuild the original packet of a retransmission: its sequence number comes from the OSN,
+/// its payload type and SSRC from the media stream. Header extensions and CSRCs aren't kept.
+pub(crate) fn unwrap_rtx(rtx: &RawRtpPacket, apt: u8, ssrc: u32) -> Option<Bytes> {
+    let osn = osn(rtx)?;
+    let mut pkt = Vec::with_capacity(12 + rtx.payload().len() - 2);
+    pkt.push(0x80);
+    pkt.push((rtx.raw()[1] & 0x80) | (apt & 0x7f));
+    pkt.extend_from_slice(&osn.to_be_bytes());
+    pkt.extend_from_slice(&rtx.ts().to_be_bytes());
+    pkt.extend_from_slice(&ssrc.to_be_bytes());
+    pkt.extend_from_slice(&rtx.payload()[2..]);
+    Some(pkt.into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::test_util::{evs_packets, rtpdump};
+    use crate::RtpdumpReader;
+
+    #[test]
+    fn test_unwrap_rtx() {
+        // marker set, payload type 97, OSN 0x0102
+        let rtx = [
+            0x80, 0xe1, 0x00, 0x07, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x56, 0x78, 0x01, 0x02,
+            0xaa, 0xbb,
+        ];
+        let pkt = unwrap_rtx(&RawRtpPacket::new(&rtx), 96, 0x1234).unwrap();
+        let pkt = RawRtpPacket::new(&pkt);
+        assert!(pkt.marked());
+        assert_eq!(pkt.payload_type().to_u8(), 96);
+        assert_eq!(pkt.seq(), 0x0102);
+        assert_eq!(pkt.ts(), 0x140);
+        assert_eq!(pkt.ssrc(), 0x1234);
+        assert_eq!(pkt.payload(), &[0xaa, 0xbb]);
+
+        // padding-only probe
+        assert!(unwrap_rtx(&RawRtpPacket::new(&rtx[..14]), 96, 0x1234).is_none());
+    }
+
+    #[test]
+    fn test_rtx_folding() {
+        // packet 5 is lost, then retransmitted with payload type 99 on SSRC 0x9999
+        let pkts = evs_packets(10, 0);
+        let rtp = |i: usize| &pkts[i].1;
+
+        let mut rtx = vec![0x80, 99, 0, 0];
+        rtx.extend_from_slice(&rtp(5)[4..8]);
+        rtx.extend_from_slice(&0x9999u32.to_be_bytes());
+        rtx.extend_from_slice(&5u16.to_be_bytes());
+        rtx.extend_from_slice(&rtp(5)[12..]);
+
+        let mut lossy = pkts.clone();
+        lossy.remove(5);
+        lossy.insert(7, (150, rtx));
+        let lossy = rtpdump(lossy);
+
+        let read = |rtx: bool| {
+            let mss =
+                MediaSourceStream::new(Box::new(Cursor::new(lossy.clone())), Default::default());
+            let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+            if rtx {
+                reader.set_rtx_payload_type(99, 96);
+            }
+            let mut frames = vec![];
+            while let Ok(pkt) = reader.next_packet() {
+                frames.push(pkt.data.to_vec());
+            }
+            (frames, reader.retransmitted_packets())
+        };
+
+        let (frames, retransmitted) = read(false);
+        assert_eq!(retransmitted, 0);
+        assert_eq!(frames[5], vec![0x0f]);
+
+        let (frames, retransmitted) = read(true);
+        assert_eq!(retransmitted, 1);
+        assert_eq!(frames.len(), 10);
+        assert_eq!(&frames[5][1..], &rtp(5)[12..]);
+    }
+}
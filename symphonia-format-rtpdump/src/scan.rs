@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use codec_detector::rtp::{is_keep_alive, parse_rtp, NotRtpFilter, PayloadType, RtpPacket};
+use codec_detector::CodecDetector;
+
+use crate::clock::{infer_clock_rate, restarts_timeline, MAX_CLOCK_RATE};
+use crate::content::TRIAL_PACKETS;
+use crate::fingerprint::StreamFingerprint;
+use crate::handshake::HandshakeSummary;
+use crate::index::IndexEntry;
+use crate::session::{SessionId, SessionSplitter};
+use crate::{RDPacket, SESSION_GAP};
+
+/// Captures smaller than this per thread are scanned by fewer threads
+const MIN_CHUNK: usize = 1 << 20;
+/// Packet headers which must follow a chunk boundary for it to be trusted
+const SYNC_PACKETS: usize = 32;
+/// Milliseconds of capture time between the index entries of a stream recorded by the scan
+pub(crate) const INDEX_INTERVAL_MS: u32 = 1000;
+
+/// What the detection pre-scan learnt of a payload type of a session
+#[derive(Clone, Debug)]
+pub(crate) struct StreamScan {
+    /// Capture time of the first and the last packet
+    pub first_offset: u32,
+    pub last_offset: u32,
+    pub first_ts: u32,
+    /// Last RTP timestamp, and its unwrapped offset from the first one
+    pub last_ts: u32,
+    pub ext: i64,
+    pub last_seq: u16,
+    /// First packets, whole, to fingerprint the stream and trial-decode it
+    pub head: Vec<Bytes>,
+    /// Entry of the first packet of every [`INDEX_INTERVAL_MS`] of capture time, positions
+    /// counted from the first packet of the capture
+    pub entries: Vec<IndexEntry>,
+}
+
+impl StreamScan {
+    pub fn fingerprint(&self, ssrc: u32) -> StreamFingerprint {
+        let mut fingerprint = StreamFingerprint::new(ssrc);
+        for pkt in &self.head {
+            if let Ok(pkt) = parse_rtp(pkt) {
+                fingerprint.on_pkt(&pkt);
+            }
+        }
+        fingerprint
+    }
+
+    /// Record an index entry for a packet at `pos`, if it is the first one of its interval
+    fn index(&mut self, pos: u64, offset: u32) {
+        let interval = offset / INDEX_INTERVAL_MS;
+        if self
+            .entries
+            .last()
+            .is_none_or(|e| e.offset / INDEX_INTERVAL_MS != interval)
+        {
+            self.entries.push(IndexEntry {
+                pos,
+                offset,
+                ext: self.ext,
+            });
+        }
+    }
+}
+
+/// Codec detection statistics and RTP streams of a capture, or of a chunk of it
+#[derive(Clone, Debug)]
+pub(crate) struct CaptureScan {
+    pub detector: CodecDetector,
+    /// ZRTP and DTLS-SRTP handshakes on the media ports
+    pub handshakes: HandshakeSummary,
+    /// SSRCs in order of appearance
+    pub ssrcs: Vec<u32>,
+    /// Streams in order of appearance
+    pub streams: Vec<((SessionId, u8), StreamScan)>,
+    /// mid carried by the packets of every session, see RFC 9143
+    pub mids: HashMap<SessionId, String>,
+    /// Capture time of the latest stream to appear
+    pub last_start: Option<u32>,
+    mid_ext_ids: Vec<u8>,
+    /// Position of every stream in `streams`
+    index: HashMap<(SessionId, u8), usize>,
+    sessions: SessionSplitter,
+    /// Capture time of the first packet of every SSRC
+    ssrc_starts: HashMap<u32, u32>,
+}
+
+impl CaptureScan {
+    /// Start a scan reading the mid of sessions from the header extensions of `mid_ext_ids`
+    pub fn new(mid_ext_ids: Vec<u8>) -> Self {
+        Self {
+            detector: CodecDetector::with_default_features(),
+            handshakes: HandshakeSummary::default(),
+            ssrcs: vec![],
+            streams: vec![],
+            mids: HashMap::new(),
+            last_start: None,
+            mid_ext_ids,
+            index: HashMap::new(),
+            sessions: SessionSplitter::new(SESSION_GAP),
+            ssrc_starts: HashMap::new(),
+        }
+    }
+
+    /// Scan a packet captured at `offset` milliseconds, `pos` bytes after the first packet of
+    /// the capture
+    pub fn on_pkt(&mut self, pos: u64, offset: u32, data: &[u8]) {
+        if is_keep_alive(data) {
+            self.detector.on_keep_alive();
+            return;
+        }
+        if let Some(kind) = NotRtpFilter::default().classify(data, &[]) {
+            self.handshakes.on_pkt(kind, data, offset);
+            return;
+        }
+        let Ok(pkt) = parse_rtp(data) else {
+            return;
+        };
+        // RTCP packets parse as RTP with a reserved payload type
+        if matches!(pkt.payload_type(), PayloadType::Reserved(_)) {
+            return;
+        }
+        if !self.ssrcs.contains(&pkt.ssrc()) {
+            self.ssrcs.push(pkt.ssrc());
+        }
+        self.ssrc_starts.entry(pkt.ssrc()).or_insert(offset);
+        let session = (pkt.ssrc(), self.sessions.generation(pkt.ssrc(), offset));
+        let key = (session, pkt.payload_type().to_u8());
+        match self.index.get(&key) {
+            Some(&idx) => {
+                let stream = &mut self.streams[idx].1;
+                let ts_delta = pkt.ts().wrapping_sub(stream.last_ts) as i32 as i64;
+                let elapsed = offset.saturating_sub(stream.last_offset);
+                let duration = stream.last_offset.saturating_sub(stream.first_offset);
+                // the clock rate inferred so far, or the highest one for the shortest jump
+                let clock_rate = infer_clock_rate(stream.ext, duration).unwrap_or(MAX_CLOCK_RATE);
+                stream.ext += match pkt.seq() == stream.last_seq.wrapping_add(1)
+                    && restarts_timeline(ts_delta, elapsed, clock_rate)
+                {
+                    // the stream goes on at the pace it kept so far
+                    true if duration > 0 => stream.ext * elapsed as i64 / duration as i64,
+                    true => 0,
+                    false => ts_delta,
+                };
+                stream.last_ts = pkt.ts();
+                stream.last_seq = pkt.seq();
+                stream.last_offset = offset;
+                stream.index(pos, offset);
+                if stream.head.len() < TRIAL_PACKETS {
+                    stream.head.push(Bytes::copy_from_slice(data));
+                }
+            }
+            None => {
+                self.index.insert(key, self.streams.len());
+                self.streams.push((
+                    key,
+                    StreamScan {
+                        first_offset: offset,
+                        last_offset: offset,
+                        first_ts: pkt.ts(),
+                        last_ts: pkt.ts(),
+                        ext: 0,
+                        last_seq: pkt.seq(),
+                        head: vec![Bytes::copy_from_slice(data)],
+                        entries: vec![IndexEntry {
+                            pos,
+                            offset,
+                            ext: 0,
+                        }],
+                    },
+                ));
+                self.last_start = Some(offset);
+            }
+        }
+        let ids = &self.mid_ext_ids;
+        if let Some(mid) = ids.iter().find_map(|id| pkt.extension_element(*id)) {
+            self.mids
+                .entry(session)
+                .or_insert_with(|| String::from_utf8_lossy(mid).into_owned());
+        }
+        self.detector.on_pkt(&pkt);
+    }
+
+    /// Add the scan of the packets following the scanned ones. Sessions and streams continue
+    /// across the boundary, only the delta time between the last packet of a stream here and
+    /// its first one in `next` is lost to codec detection.
+    pub fn merge(&mut self, next: CaptureScan) {
+        self.detector.merge(next.detector);
+        self.handshakes.merge(next.handshakes);
+        let bases = self.sessions.merge(&next.sessions, &next.ssrc_starts);
+        let session = |(ssrc, generation): SessionId| (ssrc, bases[&ssrc] + generation);
+        for (ssrc, start) in next.ssrc_starts {
+            self.ssrc_starts.entry(ssrc).or_insert(start);
+        }
+        for ssrc in next.ssrcs {
+            if !self.ssrcs.contains(&ssrc) {
+                self.ssrcs.push(ssrc);
+            }
+        }
+        for ((id, pt), stream) in next.streams {
+            let key = (session(id), pt);
+            match self.index.get(&key) {
+                Some(&idx) => {
+                    let prev = &mut self.streams[idx].1;
+                    prev.ext += stream.first_ts.wrapping_sub(prev.last_ts) as i32 as i64;
+                    let mut entries = stream.entries.into_iter().peekable();
+                    // the interval the packets of `next` start in may have an entry already
+                    let interval = |e: &IndexEntry| e.offset / INDEX_INTERVAL_MS;
+                    if entries.peek().map(interval) == prev.entries.last().map(interval) {
+                        entries.next();
+                    }
+                    let base = prev.ext;
+                    prev.entries.extend(entries.map(|e| IndexEntry {
+                        ext: e.ext + base,
+                        ..e
+                    }));
+                    prev.ext += stream.ext;
+                    prev.last_ts = stream.last_ts;
+                    prev.last_seq = stream.last_seq;
+                    prev.last_offset = stream.last_offset;
+                    let missing = TRIAL_PACKETS.saturating_sub(prev.head.len());
+                    prev.head.extend(stream.head.into_iter().take(missing));
+                }
+                None => {
+                    self.index.insert(key, self.streams.len());
+                    self.streams.push((key, stream));
+                }
+            }
+        }
+        for (id, mid) in next.mids {
+            self.mids.entry(session(id)).or_insert(mid);
+        }
+        self.last_start = next.last_start.or(self.last_start);
+    }
+}
+
+/// Scan `data`, the packets following the file header of a capture, split in chunks scanned
+/// by up to `threads` threads. `None` if the capture is malformed, scanning it packet after
+/// packet tells where.
+pub(crate) fn scan_parallel(data: &[u8], threads: usize) -> Option<CaptureScan> {
+    scan_chunks(data, threads.min(data.len() / MIN_CHUNK).max(1))
+}
+
+fn scan_chunks(data: &[u8], chunks: usize) -> Option<CaptureScan> {
+    let mut starts = vec![0];
+    for i in 1..chunks {
+        match sync(data, i * data.len() / chunks) {
+            Some(start) if start > *starts.last().unwrap() => starts.push(start),
+            _ => {}
+        }
+    }
+    let mut ends = starts[1..].to_vec();
+    ends.push(data.len());
+
+    std::thread::scope(|s| {
+        let workers = starts
+            .iter()
+            .zip(&ends)
+            .map(|(&start, &end)| s.spawn(move || scan_chunk(data, start, end)))
+            .collect::<Vec<_>>();
+        let mut scans = workers.into_iter().map(|w| w.join().unwrap());
+        let mut scan = scans.next()??;
+        for next in scans {
+            scan.merge(next?);
+        }
+        Some(scan)
+    })
+}
+
+/// Header of the rtpdump packet at `pos`
+fn packet_header(data: &[u8], pos: usize) -> Option<RDPacket> {
+    let hdr = data.get(pos..pos + std::mem::size_of::<RDPacket>())?;
+    Some(RDPacket {
+        len: u16::from_be_bytes([hdr[0], hdr[1]]),
+        org_len: u16::from_be_bytes([hdr[2], hdr[3]]),
+        offset: u32::from_be_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]),
+    })
+}
+
+/// First position from `from` on where rtpdump packets look to start: the next
+/// [`SYNC_PACKETS`] headers, or those up to the end of the capture, are consistent and followed
+/// by RTP or RTCP packets
+fn sync(data: &[u8], from: usize) -> Option<usize> {
+    let hdr_len = std::mem::size_of::<RDPacket>();
+    (from..data.len().min(from + u16::MAX as usize)).find(|&start| {
+        let (mut pos, mut last) = (start, None);
+        for _ in 0..SYNC_PACKETS {
+            let Some(hdr) = packet_header(data, pos) else {
+                return pos >= data.len();
+            };
+            let len = hdr.len as usize;
+            // the original length is 0 for RTCP, and the whole packet may not be captured
+            let sane_len =
+                len > hdr_len && (hdr.org_len == 0 || hdr.org_len as usize + hdr_len >= len);
+            let sane_offset = last.is_none_or(|last: u32| hdr.offset.abs_diff(last) <= SESSION_GAP);
+            let version = data.get(pos + hdr_len).map(|b| b >> 6);
+            if !sane_len || !sane_offset || version.is_some_and(|v| v != 2) {
+                return false;
+            }
+            last = Some(hdr.offset);
+            pos += len;
+        }
+        true
+    })
+}
+
+/// Scan the packets from `start` to `end`. `None` if a packet is malformed, or if the last one
+/// doesn't end at `end` (some packet data looked like the start of a chunk).
+fn scan_chunk(data: &[u8], start: usize, end: usize) -> Option<CaptureScan> {
+    let hdr_len = std::mem::size_of::<RDPacket>();
+    let mut scan = CaptureScan::new(vec![]);
+    let mut pos = start;
+    while pos < end {
+        // a truncated packet ends the capture
+        let Some(hdr) = packet_header(data, pos) else {
+            break;
+        };
+        if (hdr.len as usize) < hdr_len {
+            return None;
+        }
+        let Some(pkt) = data.get(pos + hdr_len..pos + hdr.len as usize) else {
+            break;
+        };
+        scan.on_pkt(pos as u64, hdr.offset, pkt);
+        pos += hdr.len as usize;
+    }
+    if end < data.len() && pos != end {
+        return None;
+    }
+    Some(scan)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use binrw::BinRead;
+    use codec_detector::rtp::RtpPacketizer;
+
+    use super::*;
+    use crate::FileHeader;
+
+    /// Packets following the file header of a capture of two interleaved streams, the second
+    /// one with its RTP timestamp wrapping around, and of the first SSRC again as a new session
+    fn capture() -> Vec<u8> {
+        let source = std::net::SocketAddrV4::new([127, 0, 0, 1].into(), 5004);
+        let mut writer =
+            crate::RtpdumpWriter::new(vec![], source, std::time::Duration::ZERO).unwrap();
+        let mut a = RtpPacketizer::new(0x1111, 96, 16000, 20);
+        let mut b = RtpPacketizer::new(0x2222, 97, 16000, 20).with_start(0, u32::MAX - 320_000);
+        for i in 0..3000 {
+            writer
+                .write_packet(i * 20, &a.packetize(&[i as u8; 33]))
+                .unwrap();
+            writer
+                .write_packet(i * 20 + 5, &b.packetize(&[0x3c; 62]))
+                .unwrap();
+        }
+        let mut a = RtpPacketizer::new(0x1111, 96, 16000, 20);
+        for i in 0..100 {
+            let offset = 3000 * 20 + SESSION_GAP + i * 20;
+            writer
+                .write_packet(offset, &a.packetize(&[i as u8; 33]))
+                .unwrap();
+        }
+        let capture = writer.into_inner();
+        let mut cursor = Cursor::new(&capture);
+        FileHeader::read(&mut cursor).unwrap();
+        capture[cursor.position() as usize..].to_vec()
+    }
+
+    #[test]
+    fn test_scan_parallel() {
+        let data = capture();
+        let whole = scan_chunks(&data, 1).unwrap();
+        assert_eq!(whole.streams.len(), 3);
+        assert_eq!(whole.ssrcs, vec![0x1111, 0x2222]);
+
+        let chunked = scan_chunks(&data, 7).unwrap();
+        assert_eq!(chunked.ssrcs, whole.ssrcs);
+        assert_eq!(chunked.detector.get_result(), whole.detector.get_result());
+        assert_eq!(chunked.streams.len(), whole.streams.len());
+        for ((key, stream), (whole_key, whole_stream)) in chunked.streams.iter().zip(&whole.streams)
+        {
+            assert_eq!(key, whole_key);
+            assert_eq!(
+                (
+                    stream.first_offset,
+                    stream.last_offset,
+                    stream.first_ts,
+                    stream.last_ts
+                ),
+                (
+                    whole_stream.first_offset,
+                    whole_stream.last_offset,
+                    whole_stream.first_ts,
+                    whole_stream.last_ts
+                )
+            );
+            assert_eq!(stream.ext, whole_stream.ext);
+            assert_eq!(stream.head, whole_stream.head);
+            assert_eq!(stream.entries, whole_stream.entries);
+        }
+        // the second stream wrapped around
+        assert_eq!(whole.streams[1].1.ext, 2999 * 320);
+        // an entry every second of the stream
+        let entries = &whole.streams[1].1.entries;
+        assert_eq!(entries.len(), 60);
+        assert_eq!((entries[1].offset, entries[1].ext), (1005, 50 * 320));
+        assert_eq!(entries[1].pos, 51 * (8 + 12 + 33) + 50 * (8 + 12 + 62));
+    }
+
+    #[test]
+    fn test_sync() {
+        let data = capture();
+        // EVS payloads of the first stream follow 20 bytes of headers
+        let start = sync(&data, 1).unwrap();
+        assert_eq!(start, 8 + 12 + 33);
+        assert_eq!(sync(&data, data.len() - 3), None);
+        // the packets don't end on a wrong boundary
+        assert!(scan_chunk(&data, 0, start + 1).is_none());
+        assert!(scan_chunk(&[0, 4, 0, 0, 0, 0, 0, 0], 0, 8).is_none());
+    }
+}
@@ -0,0 +1,343 @@
+use std::sync::Arc;
+
+use codec_detector::Codec;
+use symphonia_core::errors::{Error, Result};
+
+/// URI of the header extension carrying the media section of a packet (RFC 9143)
+const MID_EXT_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:mid";
+
+/// Payload type mapping of a media section, from its `a=rtpmap` and `a=fmtp` attributes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RtpMap {
+    pub payload_type: u8,
+    /// Encoding name, e.g. "AMR-WB"
+    pub encoding: String,
+    pub clock_rate: u32,
+    pub channels: Option<u8>,
+    pub fmtp: Option<String>,
+}
+
+impl RtpMap {
+    /// Value of a `name=value` parameter of the format parameters
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.fmtp.as_deref()?.split(';').find_map(|p| {
+            let (k, v) = p.split_once('=')?;
+            k.trim().eq_ignore_ascii_case(name).then(|| v.trim())
+        })
+    }
+
+    /// Codec of the payload type, named as in [`RtpCodecRegistry`](crate::RtpCodecRegistry)
+    pub fn codec(&self) -> Codec {
+        let mut name = self.encoding.to_ascii_lowercase().replace('-', "");
+        let mut sample_rate = self.clock_rate;
+        match name.as_str() {
+            // bandwidth-efficient is the default of RFC 4867
+            "amr" if self.param("octet-align") != Some("1") => name = "amrbe".to_string(),
+            "g7221" => name = "G.722.1".to_string(),
+            // the highest audio bandwidth negotiated, the RTP clock is always 16 kHz
+            "evs" => {
+                if let Some(bw) = self.param("bw") {
+                    sample_rate = match bw.rsplit('-').next() {
+                        Some("nb") => 8000,
+                        Some("wb") => 16000,
+                        Some("swb") => 32000,
+                        Some("fb") => 48000,
+                        _ => sample_rate,
+                    };
+                }
+            }
+            _ => {}
+        }
+        Codec {
+            name: Arc::new(name),
+            sample_rate,
+            channels: self.channels,
+            bit_rate: self.param("bitrate").and_then(|br| br.parse().ok()),
+            params: self.fmtp.clone(),
+        }
+    }
+}
+
+/// A `m=` line and its attributes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MediaSection {
+    /// Media type, e.g. "audio"
+    pub media: String,
+    pub mid: Option<String>,
+    /// URL of the section for RTSP requests, relative to the session one unless absolute
+    pub control: Option<String>,
+    pub rtpmaps: Vec<RtpMap>,
+    /// SSRCs announced by `a=ssrc` attributes
+    pub ssrcs: Vec<u32>,
+    /// Id of the header extension carrying the mid of the packets
+    pub mid_ext: Option<u8>,
+}
+
+impl MediaSection {
+    pub fn rtpmap(&self, payload_type: u8) -> Option<&RtpMap> {
+        self.rtpmaps.iter().find(|m| m.payload_type == payload_type)
+    }
+}
+
+/// What a SDP offer or answer tells about the RTP streams of a capture: the media section, and so
+/// the codecs, of every stream, and which sections are bundled on a transport (RFC 9143)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionDescription {
+    /// Mids of every `a=group:BUNDLE` attribute
+    pub bundles: Vec<Vec<String>>,
+    pub media: Vec<MediaSection>,
+}
+
+impl SessionDescription {
+    /// Parse the attributes of interest of a SDP, others and malformed ones are ignored
+    pub fn parse(sdp: &str) -> Result<Self> {
+        let mut desc = Self::default();
+        for line in sdp.lines().map(str::trim) {
+            if let Some(m) = line.strip_prefix("m=") {
+                desc.media.push(MediaSection {
+                    media: m.split(' ').next().unwrap_or_default().to_string(),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let Some(attr) = line.strip_prefix("a=") else {
+                continue;
+            };
+            let (name, value) = attr.split_once(':').unwrap_or((attr, ""));
+            if name == "group" {
+                if let Some(mids) = value.strip_prefix("BUNDLE") {
+                    desc.bundles
+                        .push(mids.split_whitespace().map(str::to_string).collect());
+                }
+                continue;
+            }
+            // other attributes only matter in a media section
+            let Some(section) = desc.media.last_mut() else {
+                continue;
+            };
+            match name {
+                "mid" => section.mid = Some(value.to_string()),
+                "control" => section.control = Some(value.to_string()),
+                "rtpmap" => {
+                    if let Some(map) = parse_rtpmap(value) {
+                        section.rtpmaps.push(map);
+                    }
+                }
+                "fmtp" => {
+                    let Some((pt, params)) = value.split_once(' ') else {
+                        continue;
+                    };
+                    let Some(map) = section
+                        .rtpmaps
+                        .iter_mut()
+                        .find(|m| pt.parse() == Ok(m.payload_type))
+                    else {
+                        continue;
+                    };
+                    map.fmtp = Some(params.trim().to_string());
+                }
+                "ssrc" => {
+                    let ssrc = value.split(' ').next().and_then(|s| s.parse().ok());
+                    if let Some(ssrc) = ssrc.filter(|s| !section.ssrcs.contains(s)) {
+                        section.ssrcs.push(ssrc);
+                    }
+                }
+                "extmap" => {
+                    let mut fields = value.split_whitespace();
+                    // the id may be followed by a direction, e.g. "1/sendonly"
+                    let id = fields
+                        .next()
+                        .and_then(|id| id.split('/').next()?.parse().ok());
+                    if fields.next() == Some(MID_EXT_URI) {
+                        section.mid_ext = id;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if desc.media.is_empty() {
+            return Err(Error::DecodeError("SDP has no media description"));
+        }
+        Ok(desc)
+    }
+
+    /// Ids of the header extension carrying mids, usually the same in every section
+    pub fn mid_ext_ids(&self) -> Vec<u8> {
+        let mut ids = vec![];
+        for id in self.media.iter().filter_map(|m| m.mid_ext) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Media section of a stream: the one announcing its SSRC, else the one of the mid its
+    /// packets carry
+    pub fn section(&self, ssrc: u32, mid: Option<&str>) -> Option<&MediaSection> {
+        self.media
+            .iter()
+            .find(|m| m.ssrcs.contains(&ssrc))
+            .or_else(|| {
+                let mid = mid?;
+                self.media.iter().find(|m| m.mid.as_deref() == Some(mid))
+            })
+    }
+
+    /// BUNDLE group of a mid
+    pub fn bundle(&self, mid: &str) -> Option<&[String]> {
+        self.bundles
+            .iter()
+            .find(|b| b.iter().any(|m| m == mid))
+            .map(Vec::as_slice)
+    }
+}
+
+/// `<payload type> <encoding name>/<clock rate>[/<channels>]`
+fn parse_rtpmap(value: &str) -> Option<RtpMap> {
+    let (pt, encoding) = value.split_once(' ')?;
+    let mut fields = encoding.trim().split('/');
+    Some(RtpMap {
+        payload_type: pt.parse().ok()?,
+        encoding: fields.next()?.to_string(),
+        clock_rate: fields.next()?.parse().ok()?,
+        channels: fields.next().and_then(|c| c.parse().ok()),
+        fmtp: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use symphonia_core::formats::FormatReader;
+    use symphonia_core::io::MediaSourceStream;
+
+    use super::*;
+    use crate::test_util::{evs_packets, rtpdump};
+    use crate::RtpdumpReader;
+
+    const SDP: &str = "v=0\r
+o=- 0 0 IN IP4 127.0.0.1\r
+s=-\r
+t=0 0\r
+a=group:BUNDLE mic1 mic2\r
+m=audio 5000 RTP/AVP 96 101\r
+a=mid:mic1\r
+a=extmap:1 urn:ietf:params:rtp-hdrext:sdes:mid\r
+a=rtpmap:96 AMR-WB/16000\r
+a=rtpmap:101 telephone-event/16000\r
+a=ssrc:4660 cname:mic1\r
+m=audio 5000 RTP/AVP 97\r
+a=mid:mic2\r
+a=control:trackID=2\r
+a=extmap:1/sendonly urn:ietf:params:rtp-hdrext:sdes:mid\r
+a=rtpmap:97 EVS/16000\r
+a=fmtp:97 br=13.2; bw=nb-swb\r
+m=audio 5002 RTP/AVP 98\r
+a=rtpmap:98 AMR/8000\r
+a=fmtp:98 octet-align=1\r
+";
+
+    #[test]
+    fn test_parse_sdp() {
+        let sdp = SessionDescription::parse(SDP).unwrap();
+        assert_eq!(sdp.bundles, vec![vec!["mic1", "mic2"]]);
+        assert_eq!(sdp.media.len(), 3);
+        assert_eq!(sdp.mid_ext_ids(), vec![1]);
+        assert_eq!(sdp.media[0].ssrcs, vec![4660]);
+        assert_eq!(sdp.media[1].control.as_deref(), Some("trackID=2"));
+        assert_eq!(
+            sdp.media[0].rtpmap(101).unwrap().encoding,
+            "telephone-event"
+        );
+
+        // announced SSRC first, then mid
+        assert_eq!(
+            sdp.section(4660, Some("mic2")).unwrap().mid.as_deref(),
+            Some("mic1")
+        );
+        assert_eq!(
+            sdp.section(1, Some("mic2")).unwrap().mid.as_deref(),
+            Some("mic2")
+        );
+        assert!(sdp.section(1, None).is_none());
+        assert_eq!(sdp.bundle("mic2").unwrap(), ["mic1", "mic2"]);
+        assert!(sdp.bundle("mic3").is_none());
+
+        assert!(SessionDescription::parse("v=0\r\n").is_err());
+    }
+
+    #[test]
+    fn test_rtpmap_codec() {
+        let sdp = SessionDescription::parse(SDP).unwrap();
+        let amrwb = sdp.media[0].rtpmap(96).unwrap().codec();
+        assert_eq!((amrwb.name.as_str(), amrwb.sample_rate), ("amrwb", 16000));
+        let evs = sdp.media[1].rtpmap(97).unwrap().codec();
+        assert_eq!((evs.name.as_str(), evs.sample_rate), ("evs", 32000));
+        assert_eq!(
+            sdp.media[2].rtpmap(98).unwrap().codec().name.as_str(),
+            "amr"
+        );
+
+        let g7221 = parse_rtpmap("100 G7221/16000").map(|mut m| {
+            m.fmtp = Some("bitrate=24000".to_string());
+            m.codec()
+        });
+        assert_eq!(g7221.as_ref().unwrap().name.as_str(), "G.722.1");
+        assert_eq!(g7221.unwrap().bit_rate, Some(24000));
+        assert_eq!(
+            parse_rtpmap("98 AMR/8000").unwrap().codec().name.as_str(),
+            "amrbe"
+        );
+    }
+
+    #[test]
+    fn test_sdp_grouping() {
+        // a second microphone on SSRC 0x5678 and payload type 97, tagged with mid "mic2"
+        let capture = rtpdump(evs_packets(20, 0).into_iter().flat_map(|(offset, rtp)| {
+            let mut pkt = vec![0x90, 97];
+            pkt.extend_from_slice(&rtp[2..8]);
+            pkt.extend_from_slice(&0x5678u32.to_be_bytes());
+            pkt.extend_from_slice(&[
+                0xbe, 0xde, 0x00, 0x02, 0x33, b'm', b'i', b'c', b'2', 0, 0, 0,
+            ]);
+            pkt.extend_from_slice(&rtp[12..]);
+            [(offset, rtp), (offset, pkt)]
+        }));
+
+        let sdp = SessionDescription::parse(
+            "v=0\r\na=group:BUNDLE mic1 mic2\r\n\
+             m=audio 5000 RTP/AVP 96\r\na=mid:mic1\r\na=rtpmap:96 AMR-WB/16000\r\n\
+             a=ssrc:4660 cname:a\r\n\
+             m=audio 5000 RTP/AVP 97\r\na=mid:mic2\r\na=rtpmap:97 EVS/16000\r\n\
+             a=extmap:3 urn:ietf:params:rtp-hdrext:sdes:mid\r\n",
+        )
+        .unwrap();
+        let mss = MediaSourceStream::new(Box::new(Cursor::new(capture)), Default::default());
+        let mut reader =
+            RtpdumpReader::try_new_with_sdp(mss, &Default::default(), &Default::default(), &sdp)
+                .unwrap();
+
+        let streams = &reader.detection().streams;
+        assert_eq!(streams.len(), 2);
+        // the SDP wins over detection
+        assert_eq!(streams[0].codec.as_ref().unwrap().name.as_str(), "amrwb");
+        assert_eq!(streams[0].mid.as_deref(), Some("mic1"));
+        assert_eq!(streams[1].codec.as_ref().unwrap().name.as_str(), "evs");
+        assert_eq!(streams[1].mid.as_deref(), Some("mic2"));
+        assert_eq!(reader.tracks().len(), 2);
+
+        let tags = reader
+            .metadata()
+            .current()
+            .unwrap()
+            .tags()
+            .iter()
+            .map(|t| (t.key.clone(), t.value.to_string()))
+            .collect::<Vec<_>>();
+        assert!(tags.contains(&("TRACK1_MID".to_string(), "mic2".to_string())));
+        assert!(tags.contains(&("TRACK0_BUNDLE".to_string(), "mic1 mic2".to_string())));
+    }
+}
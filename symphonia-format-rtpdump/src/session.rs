@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+/// SSRC and generation of a session
+pub(crate) type SessionId = (u32, u32);
+
+/// Splits the packets of a SSRC into sessions.
+///
+/// Endpoints pick SSRCs at random and some reuse them for every call, so a capture may hold
+/// several unrelated streams with the same SSRC. A packet arriving more than `gap`
+/// milliseconds after the previous packet of its SSRC starts a new session of that SSRC.
+#[derive(Clone, Debug)]
+pub(crate) struct SessionSplitter {
+    gap: u32,
+    /// Current generation and capture time of the last packet of every SSRC
+    ssrcs: HashMap<u32, (u32, u32)>,
+}
+
+impl SessionSplitter {
+    pub fn new(gap: u32) -> Self {
+        Self {
+            gap,
+            ssrcs: HashMap::new(),
+        }
+    }
+
+    /// Generation of the session a packet of `ssrc` captured at `offset` milliseconds belongs
+    /// to, the first session of a SSRC is generation 0
+    pub fn generation(&mut self, ssrc: u32, offset: u32) -> u32 {
+        let (generation, last) = self.ssrcs.entry(ssrc).or_insert((0, offset));
+        if offset.saturating_sub(*last) > self.gap {
+            *generation += 1;
+        }
+        *last = (*last).max(offset);
+        *generation
+    }
+
+    /// Continue splitting after a seek, the last packet of `ssrc` read belonged to
+    /// `generation` and was captured at `offset` milliseconds
+    pub fn resume(&mut self, ssrc: u32, generation: u32, offset: u32) {
+        self.ssrcs.insert(ssrc, (generation, offset));
+    }
+
+    /// Continue with the sessions `next` split from the following packets, in which every SSRC
+    /// first appeared at the capture time of `starts`. Returns the generation the first session
+    /// of every SSRC of `next` continues as.
+    pub fn merge(&mut self, next: &Self, starts: &HashMap<u32, u32>) -> HashMap<u32, u32> {
+        let mut bases = HashMap::new();
+        for (&ssrc, &(generation, last)) in &next.ssrcs {
+            let start = starts.get(&ssrc).copied().unwrap_or(last);
+            let (base, last) = match self.ssrcs.get(&ssrc) {
+                Some(&(prev, prev_last)) if start.saturating_sub(prev_last) > self.gap => {
+                    (prev + 1, last.max(prev_last))
+                }
+                Some(&(prev, prev_last)) => (prev, last.max(prev_last)),
+                None => (0, last),
+            };
+            self.ssrcs.insert(ssrc, (base + generation, last));
+            bases.insert(ssrc, base);
+        }
+        bases
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_reused_ssrc() {
+        let mut splitter = SessionSplitter::new(1000);
+        assert_eq!(splitter.generation(1, 0), 0);
+        assert_eq!(splitter.generation(2, 500), 0);
+        assert_eq!(splitter.generation(1, 1000), 0);
+        // out of order capture time doesn't move the session back
+        assert_eq!(splitter.generation(1, 900), 0);
+        assert_eq!(splitter.generation(1, 2001), 1);
+        assert_eq!(splitter.generation(2, 2001), 1);
+        assert_eq!(splitter.generation(1, 2020), 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        let packets = [
+            (1, 0),
+            (2, 500),
+            (1, 1000),
+            (1, 2500),
+            (2, 2600),
+            (3, 2700),
+            (1, 2800),
+        ];
+        let mut whole = SessionSplitter::new(1000);
+        let expected = packets
+            .iter()
+            .map(|&(ssrc, offset)| whole.generation(ssrc, offset))
+            .collect::<Vec<_>>();
+
+        let (mut head, mut tail) = (SessionSplitter::new(1000), SessionSplitter::new(1000));
+        let mut starts = HashMap::new();
+        let mut generations = packets[..3]
+            .iter()
+            .map(|&(ssrc, offset)| (ssrc, head.generation(ssrc, offset)))
+            .collect::<Vec<_>>();
+        let tail_generations = packets[3..]
+            .iter()
+            .map(|&(ssrc, offset)| {
+                starts.entry(ssrc).or_insert(offset);
+                (ssrc, tail.generation(ssrc, offset))
+            })
+            .collect::<Vec<_>>();
+        let bases = head.merge(&tail, &starts);
+        generations.extend(tail_generations.iter().map(|&(s, g)| (s, bases[&s] + g)));
+        let generations = generations.into_iter().map(|(_, g)| g).collect::<Vec<_>>();
+        assert_eq!(generations, expected);
+        assert_eq!(head.ssrcs, whole.ssrcs);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::DecodedTrack;
+
+/// Length in ms of the fades where the audio of one codec gives way to the next
+pub const SPLICE_FADE: u32 = 5;
+
+/// Audio of every track of one SSRC, e.g. before and after the call switched codec, made into
+/// one track by [`splice_tracks`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SplicedTrack {
+    pub ssrc: u32,
+    /// Highest sample rate of the tracks, the others are resampled to it
+    pub sample_rate: u32,
+    /// Position of the first sample of `pcm` on the capture timeline, in samples at
+    /// `sample_rate`
+    pub start_ts: u64,
+    /// Codec of every track spliced, in order
+    pub codecs: Vec<&'static str>,
+    pub pcm: Vec<i16>,
+}
+
+/// Splice the tracks of every SSRC decoded by [`decode_capture`](crate::decode_capture) into
+/// one track, in order of appearance of the SSRCs.
+///
+/// Every track is resampled to the highest sample rate of its SSRC and laid at its place on the
+/// capture timeline, gaps between tracks being filled with silence. Where two tracks overlap,
+/// the earlier one is cut and [`SPLICE_FADE`] ms cross-faded into the later one, elsewhere the
+/// earlier one fades out and the later one fades in, so that no click is heard at a codec
+/// change. Tracks decoded with a speed effect don't line up with the capture timeline.
+pub fn splice_tracks(tracks: &[DecodedTrack]) -> Vec<SplicedTrack> {
+    let mut spliced: Vec<SplicedTrack> = vec![];
+    for ssrc in tracks.iter().map(|t| t.ssrc) {
+        if spliced.iter().any(|s| s.ssrc == ssrc) {
+            continue;
+        }
+        let mut segments = tracks
+            .iter()
+            .filter(|t| t.ssrc == ssrc && t.sample_rate > 0)
+            .collect::<Vec<_>>();
+        let Some(sample_rate) = segments.iter().map(|t| t.sample_rate).max() else {
+            continue;
+        };
+        segments.sort_by_key(|t| t.start_ts as u128 * 1_000_000 / t.sample_rate as u128);
+
+        let fade = (sample_rate * SPLICE_FADE / 1000) as usize;
+        let mut track = SplicedTrack {
+            ssrc,
+            sample_rate,
+            ..Default::default()
+        };
+        for (i, segment) in segments.iter().enumerate() {
+            let pcm = resample(&segment.pcm, segment.sample_rate, sample_rate);
+            let start = segment.start_ts * sample_rate as u64 / segment.sample_rate as u64;
+            track.codecs.push(segment.codec);
+            if i == 0 {
+                track.start_ts = start;
+                track.pcm = pcm;
+                continue;
+            }
+            let at = start.saturating_sub(track.start_ts) as usize;
+            splice(&mut track.pcm, &pcm, at, fade);
+        }
+        spliced.push(track);
+    }
+    spliced
+}
+
+/// Lay `next` at sample `at` of `pcm`, see [`splice_tracks`]
+fn splice(pcm: &mut Vec<i16>, next: &[i16], at: usize, fade: usize) {
+    if at < pcm.len() {
+        // cross-fade over the start of the overlap
+        let overlap = (pcm.len() - at).min(fade).min(next.len());
+        pcm.truncate(at + overlap);
+        for i in 0..overlap {
+            let gain = (i as f32 + 0.5) / overlap as f32;
+            let old = pcm[at + i] as f32 * (1.0 - gain);
+            pcm[at + i] = (old + next[i] as f32 * gain).round() as i16;
+        }
+        pcm.extend_from_slice(&next[overlap..]);
+    } else {
+        let len = pcm.len();
+        let fade_out = fade.min(len);
+        for (i, s) in pcm[len - fade_out..].iter_mut().enumerate() {
+            *s = (*s as f32 * (fade_out - i) as f32 / (fade_out + 1) as f32).round() as i16;
+        }
+        pcm.resize(at, 0);
+        let fade_in = fade.min(next.len());
+        pcm.extend(
+            next[..fade_in]
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (*s as f32 * (i + 1) as f32 / (fade_in + 1) as f32).round() as i16),
+        );
+        pcm.extend_from_slice(&next[fade_in..]);
+    }
+}
+
+/// `pcm` at `to` Hz, interpolating linearly between the samples at `from` Hz
+fn resample(pcm: &[i16], from: u32, to: u32) -> Vec<i16> {
+    if from == to || pcm.is_empty() {
+        return pcm.to_vec();
+    }
+    let n = (pcm.len() as u64 * to as u64 / from as u64) as usize;
+    (0..n)
+        .map(|i| {
+            let pos = i as f64 * from as f64 / to as f64;
+            let j = pos as usize;
+            let frac = pos - j as f64;
+            let a = pcm[j] as f64;
+            let b = pcm[(j + 1).min(pcm.len() - 1)] as f64;
+            (a + (b - a) * frac).round() as i16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tone(n: usize, hz: f64, sample_rate: u32, start: usize) -> Vec<i16> {
+        (start..start + n)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                (8000.0 * (2.0 * std::f64::consts::PI * hz * t).sin()) as i16
+            })
+            .collect()
+    }
+
+    fn track(codec: &'static str, sample_rate: u32, start_ts: u64, pcm: Vec<i16>) -> DecodedTrack {
+        DecodedTrack {
+            ssrc: 0x1234,
+            codec,
+            sample_rate,
+            start_ts,
+            pcm,
+            ..Default::default()
+        }
+    }
+
+    fn max_step(pcm: &[i16]) -> i32 {
+        pcm.windows(2)
+            .map(|w| (w[1] as i32 - w[0] as i32).abs())
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_splice_tracks() {
+        // 1 s of AMR at 8 kHz from 0.5 s, then AMR-WB from 1.5 s, both carrying the same tone
+        let tracks = [
+            track("amrwb", 16000, 24000, tone(16000, 50.0, 16000, 24000)),
+            track("amr", 8000, 4000, tone(8000, 50.0, 8000, 4000)),
+            DecodedTrack {
+                ssrc: 0x5678,
+                ..track("evs", 16000, 0, vec![1; 320])
+            },
+        ];
+        let spliced = splice_tracks(&tracks);
+        assert_eq!(spliced.len(), 2);
+        let call = &spliced[0];
+        assert_eq!(call.codecs, ["amr", "amrwb"]);
+        assert_eq!((call.sample_rate, call.start_ts), (16000, 8000));
+        assert_eq!(call.pcm.len(), 32000);
+        assert!(max_step(&call.pcm) < 400, "step of {}", max_step(&call.pcm));
+        assert_eq!(spliced[1].pcm, [1; 320]);
+
+        // a gap of 0.5 s is silent, the tone fades out before it and in after it
+        let tracks = [
+            track("amr", 8000, 0, tone(8000, 50.0, 8000, 0)),
+            track("amrwb", 16000, 24000, vec![8000; 1600]),
+        ];
+        let call = &splice_tracks(&tracks)[0];
+        assert_eq!(call.pcm.len(), 25600);
+        assert!(call.pcm[16000..24000].iter().all(|s| *s == 0));
+        assert!(max_step(&call.pcm) < 400, "step of {}", max_step(&call.pcm));
+    }
+
+    #[test]
+    fn test_overlap() {
+        // the first track runs 20 samples into the second one
+        let mut pcm = vec![1000; 100];
+        splice(&mut pcm, &[-1000; 100], 80, 10);
+        assert_eq!(pcm.len(), 180);
+        assert_eq!(pcm[79], 1000);
+        assert!(pcm[80..90].windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(pcm[90], -1000);
+    }
+}
@@ -0,0 +1,222 @@
+/// Fastest and slowest speeds a [`TimeStretch`] plays audio at
+pub const SPEED_RANGE: (f64, f64) = (0.25, 4.0);
+
+/// Plays mono audio faster or slower without changing its pitch, with WSOLA (Verhelst and
+/// Roelands, 1993).
+///
+/// The output is made of 30 ms frames overlapping by half. Every frame is cut from the input
+/// `speed` times further than the previous one, give or take half the overlap: the offset
+/// picked is the one whose start looks most like what followed the previous frame, so that
+/// periods of voiced speech line up and the frames cross-fade without artifacts.
+#[derive(Clone, Debug)]
+pub struct TimeStretch {
+    speed: f64,
+    /// Samples of a frame, the second half overlaps the next frame
+    frame: usize,
+    /// Most samples a frame is moved by to line up with the previous one
+    tolerance: usize,
+    /// Rising half of a Hann window, the falling half is its mirror
+    fade: Vec<f32>,
+    input: Vec<f32>,
+    /// Position in the whole input of `input[0]`
+    base: usize,
+    /// Frames laid out so far
+    frames: usize,
+    /// Position in the whole input of the previous frame
+    prev: Option<usize>,
+    /// Faded out second half of the previous frame
+    tail: Vec<f32>,
+    /// Samples output so far
+    written: usize,
+}
+
+impl TimeStretch {
+    /// Stretch audio at `sample_rate` to play `speed` times as fast, clamped to
+    /// [`SPEED_RANGE`]
+    pub fn new(sample_rate: u32, speed: f64) -> Self {
+        Self::new_with((sample_rate as usize * 15 / 1000).max(1), speed)
+    }
+
+    fn new_with(hop: usize, speed: f64) -> Self {
+        let fade = (0..hop)
+            .map(|i| {
+                let x = (i as f64 + 0.5) / hop as f64 * std::f64::consts::FRAC_PI_2;
+                x.sin().powi(2) as f32
+            })
+            .collect();
+        Self {
+            speed: speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1),
+            frame: hop * 2,
+            tolerance: hop / 2,
+            fade,
+            input: vec![],
+            base: 0,
+            frames: 0,
+            prev: None,
+            tail: vec![0.0; hop],
+            written: 0,
+        }
+    }
+
+    /// Stretch `pcm`, appending to `out` the audio of every frame complete so far
+    pub fn process(&mut self, pcm: &[i16], out: &mut Vec<i16>) {
+        self.input.extend(pcm.iter().map(|s| *s as f32));
+        self.lay_frames(usize::MAX, out);
+    }
+
+    /// Append the end of the audio, which no frame follows, then start over
+    pub fn flush(&mut self, out: &mut Vec<i16>) {
+        let len = out.len();
+        let end = self.base + self.input.len();
+        let target = (end as f64 / self.speed).round() as usize;
+        // the last frames are cut from silence past the end
+        let hop = self.frame / 2;
+        self.input
+            .resize(self.input.len() + self.frame + self.tolerance + hop, 0.0);
+        self.lay_frames(end, out);
+        if self.prev.is_some() {
+            out.extend(self.tail.iter().map(|s| to_i16(*s)));
+            self.written += hop;
+        }
+        // down to the length of the input at speed, the padding may have added a frame
+        let excess = self.written.saturating_sub(target).min(out.len() - len);
+        out.truncate(out.len() - excess);
+        *self = Self::new_with(hop, self.speed);
+    }
+
+    /// Lay out the frames the input has room for, up to a frame starting at `end`
+    fn lay_frames(&mut self, end: usize, out: &mut Vec<i16>) {
+        let hop = self.frame / 2;
+        loop {
+            let nominal = (self.frames as f64 * hop as f64 * self.speed).round() as usize;
+            let mut needed = nominal + self.tolerance + self.frame;
+            if let Some(prev) = self.prev {
+                needed = needed.max(prev + hop + self.frame);
+            }
+            if nominal >= end || needed > self.base + self.input.len() {
+                break;
+            }
+
+            let start = match self.prev {
+                None => nominal,
+                Some(prev) => self.best_start(nominal, prev + hop),
+            };
+            let at = start - self.base;
+            let segment = &self.input[at..at + self.frame];
+            if self.prev.is_none() {
+                // nothing to cross-fade with
+                out.extend(segment[..hop].iter().map(|s| to_i16(*s)));
+            } else {
+                out.extend((0..hop).map(|i| to_i16(self.tail[i] + segment[i] * self.fade[i])));
+            }
+            for i in 0..hop {
+                self.tail[i] = segment[hop + i] * self.fade[hop - 1 - i];
+            }
+            self.prev = Some(start);
+            self.frames += 1;
+            self.written += hop;
+
+            // keep what the next frames can still be cut from
+            let next = (self.frames as f64 * hop as f64 * self.speed).round() as usize;
+            let keep_from = (start + hop).min(next.saturating_sub(self.tolerance));
+            if keep_from - self.base >= self.frame * 8 {
+                self.input.drain(..keep_from - self.base);
+                self.base = keep_from;
+            }
+        }
+    }
+
+    /// Start between `nominal - tolerance` and `nominal + tolerance` whose first half frame
+    /// correlates best with the natural continuation of the previous frame at `next`
+    fn best_start(&self, nominal: usize, next: usize) -> usize {
+        let hop = self.frame / 2;
+        let template = &self.input[next - self.base..next - self.base + hop];
+        let first = nominal.saturating_sub(self.tolerance).max(self.base);
+        let mut best = (f32::MIN, nominal);
+        for start in first..=nominal + self.tolerance {
+            let candidate = &self.input[start - self.base..start - self.base + hop];
+            let (dot, energy) = template
+                .iter()
+                .zip(candidate)
+                .fold((0.0, 0.0), |(dot, energy), (t, c)| {
+                    (dot + t * c, energy + c * c)
+                });
+            let score = dot / energy.sqrt().max(1.0);
+            if score > best.0 {
+                best = (score, start);
+            }
+        }
+        best.1
+    }
+}
+
+/// Stretch a whole mono track, see [`TimeStretch`]
+pub fn time_stretch(pcm: &[i16], sample_rate: u32, speed: f64) -> Vec<i16> {
+    let mut stretch = TimeStretch::new(sample_rate, speed);
+    let mut out = Vec::with_capacity((pcm.len() as f64 / stretch.speed) as usize + 1);
+    stretch.process(pcm, &mut out);
+    stretch.flush(&mut out);
+    out
+}
+
+fn to_i16(s: f32) -> i16 {
+    s.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tone(n: usize, hz: f64, sample_rate: u32) -> Vec<i16> {
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                (8000.0 * (2.0 * std::f64::consts::PI * hz * t).sin()) as i16
+            })
+            .collect()
+    }
+
+    /// Zero crossings per sample, twice the frequency of a tone over the sample rate
+    fn crossing_rate(pcm: &[i16]) -> f64 {
+        let crossings = pcm.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count();
+        crossings as f64 / pcm.len() as f64
+    }
+
+    #[test]
+    fn test_time_stretch() {
+        let input = tone(32000, 440.0, 16000);
+        for speed in [0.5, 1.0, 1.5, 2.0] {
+            let out = time_stretch(&input, 16000, speed);
+            let expected = input.len() as f64 / speed;
+            assert!(
+                (out.len() as f64 - expected).abs() <= 1.0,
+                "{speed}x: {} samples",
+                out.len()
+            );
+            // same pitch
+            let rate = crossing_rate(&out) / crossing_rate(&input);
+            assert!((rate - 1.0).abs() < 0.02, "{speed}x: crossing rate {rate}");
+            // frames line up, no clicks
+            let max_step = out
+                .windows(2)
+                .map(|w| (w[1] as i32 - w[0] as i32).abs())
+                .max()
+                .unwrap();
+            assert!(max_step < 2000, "{speed}x: step of {max_step}");
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        let input = tone(16000, 300.0, 8000);
+        let whole = time_stretch(&input, 8000, 1.5);
+        let mut stretch = TimeStretch::new(8000, 1.5);
+        let mut out = vec![];
+        for chunk in input.chunks(160) {
+            stretch.process(chunk, &mut out);
+        }
+        stretch.flush(&mut out);
+        assert_eq!(out, whole);
+        assert!(time_stretch(&[], 8000, 2.0).is_empty());
+    }
+}
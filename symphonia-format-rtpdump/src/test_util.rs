@@ -0,0 +1,37 @@
+//! Captures shared by the tests of the crate
+
+use std::net::SocketAddrV4;
+use std::time::Duration;
+
+use codec_detector::rtp::RtpPacketizer;
+pub use rtp_core::test_util::Lcg;
+
+use crate::RtpdumpWriter;
+
+/// rtpdump capture of RTP packets, each with its offset in ms into the capture
+pub fn rtpdump(pkts: impl IntoIterator<Item = (u32, Vec<u8>)>) -> Vec<u8> {
+    let source = SocketAddrV4::new([127, 0, 0, 1].into(), 5004);
+    let mut writer = RtpdumpWriter::new(vec![], source, Duration::ZERO).unwrap();
+    for (offset, pkt) in pkts {
+        writer.write_packet(offset, &pkt).unwrap();
+    }
+    writer.into_inner()
+}
+
+/// Packets of an EVS 13.2 kbps stream of SSRC 0x1234 and payload type 96 from timestamp 1000,
+/// with pseudo random speech bits, a packet every 20 ms from `start` ms into the capture
+pub fn evs_packets(frames: u16, start: u32) -> Vec<(u32, Vec<u8>)> {
+    let mut packetizer = RtpPacketizer::new(0x1234, 96, 16000, 20).with_start(0, 1000);
+    let mut rng = Lcg(1);
+    (0..frames as u32)
+        .map(|i| {
+            let payload = (0..33).map(|_| rng.byte()).collect::<Vec<_>>();
+            (start + i * 20, packetizer.packetize(&payload))
+        })
+        .collect()
+}
+
+/// rtpdump capture of a single EVS 13.2 kbps stream starting `start` ms into the capture
+pub fn evs_capture(frames: u16, start: u32) -> Vec<u8> {
+    rtpdump(evs_packets(frames, start))
+}
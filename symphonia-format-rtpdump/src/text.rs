@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::io::Write;
+
+use codec_detector::rtp::{parse_rtp, RtpPacket};
+use symphonia_core::errors::Result;
+
+use crate::depacketizer::{PayloadDepacketizer, T140Depacketizer};
+use crate::RtpdumpPackets;
+
+/// Zero width no-break space, sent alone to keep a real-time text session alive
+const KEEP_ALIVE: char = '\u{feff}';
+
+/// T.140 block of a real-time text stream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextCue {
+    /// Milliseconds since the start of recording
+    pub offset: u32,
+    pub text: String,
+}
+
+/// Real-time text of one SSRC, extracted by [`extract_text`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextTranscript {
+    pub ssrc: u32,
+    pub cues: Vec<TextCue>,
+}
+
+impl TextTranscript {
+    /// Text of every cue, in order
+    pub fn text(&self) -> String {
+        self.cues.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    /// Write one line per cue, its offset in seconds then its text with line breaks escaped
+    pub fn write_to(&self, mut w: impl Write) -> std::io::Result<()> {
+        for cue in &self.cues {
+            let text = cue
+                .text
+                .replace("\r\n", "\n")
+                .replace(['\n', '\u{2028}'], "\\n");
+            writeln!(
+                w,
+                "{}.{:03}\t{}",
+                cue.offset / 1000,
+                cue.offset % 1000,
+                text
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Extract the real-time text (RFC 4103) of a rtpdump capture held in memory, carried by
+/// payload type `t140_pt`, and by `red_pt` with redundancy.
+///
+/// The T.140 clock runs at 1 kHz, so a cue is placed by its timestamp relative to the first
+/// packet of its stream. Malformed packets are skipped.
+pub fn extract_text(
+    capture: &[u8],
+    t140_pt: u8,
+    red_pt: Option<u8>,
+) -> Result<Vec<TextTranscript>> {
+    // depacketizer of every stream, with the timestamp and capture time of its first packet
+    let mut streams: Vec<(TextTranscript, T140Depacketizer, u32, u32)> = vec![];
+    let mut frames = VecDeque::new();
+    for (rd, data) in RtpdumpPackets::new(capture)? {
+        let Ok(pkt) = parse_rtp(data) else {
+            continue;
+        };
+        let pt = pkt.payload_type().to_u8();
+        if pt != t140_pt && Some(pt) != red_pt {
+            continue;
+        }
+        let idx = match streams.iter().position(|(t, ..)| t.ssrc == pkt.ssrc()) {
+            Some(idx) => idx,
+            None => {
+                let transcript = TextTranscript {
+                    ssrc: pkt.ssrc(),
+                    cues: vec![],
+                };
+                streams.push((
+                    transcript,
+                    T140Depacketizer::new(red_pt),
+                    pkt.ts(),
+                    rd.offset,
+                ));
+                streams.len() - 1
+            }
+        };
+
+        let (transcript, depack, first_ts, first_offset) = &mut streams[idx];
+        if depack.depacketize(&pkt, &mut frames).is_err() {
+            continue;
+        }
+        for frame in frames.drain(..) {
+            let text = String::from_utf8_lossy(&frame.data).replace(KEEP_ALIVE, "");
+            if text.is_empty() {
+                continue;
+            }
+            transcript.cues.push(TextCue {
+                offset: first_offset.wrapping_add(frame.ts.wrapping_sub(*first_ts)),
+                text,
+            });
+        }
+    }
+    Ok(streams.into_iter().map(|(t, ..)| t).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use codec_detector::rtp::RtpPacketizer;
+
+    use super::*;
+    use crate::test_util::rtpdump;
+
+    #[test]
+    fn test_extract_text() {
+        let pkts: [(u16, u32, &str); 4] = [
+            (0, 5000, "\u{feff}"),
+            (1, 5300, "Help"),
+            (2, 5600, "\r\n"),
+            (3, 6000, "fire"),
+        ];
+        let capture = rtpdump(pkts.map(|(seq, ts, text)| {
+            let mut packetizer = RtpPacketizer::new(0x4321, 98, 1000, 0).with_start(seq, ts);
+            (1000 + ts - 5000 + 7, packetizer.packetize(text.as_bytes()))
+        }));
+
+        let transcripts = extract_text(&capture, 98, Some(100)).unwrap();
+        assert_eq!(transcripts.len(), 1);
+        assert_eq!(transcripts[0].ssrc, 0x4321);
+        assert_eq!(transcripts[0].text(), "Help\r\nfire");
+        assert_eq!(transcripts[0].cues[0].offset, 1307);
+
+        let mut out = vec![];
+        transcripts[0].write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1.307\tHelp\n1.607\t\\n\n2.007\tfire\n"
+        );
+        assert!(extract_text(&capture, 99, None).unwrap().is_empty());
+    }
+}
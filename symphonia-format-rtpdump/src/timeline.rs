@@ -0,0 +1,251 @@
+use std::io::Write;
+
+use codec_detector::rtp::{parse_rtp, RtpPacket};
+use symphonia_core::errors::Result;
+
+use crate::RtpdumpPackets;
+
+/// Longest time in ms between two packets of a talkspurt. Longer silences only carry the SID
+/// frames of comfort noise, if anything.
+pub const TALKSPURT_GAP: u32 = 60;
+
+/// Packets that went missing between two packets of a stream
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Loss {
+    /// Milliseconds since the start of recording of the packet following the gap
+    pub offset: u32,
+    /// Sequence number of the first missing packet
+    pub seq: u16,
+    pub count: u16,
+}
+
+/// Packets arriving at most [`TALKSPURT_GAP`] apart, started by the marker bit or after a
+/// silence, in ms since the start of recording
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Talkspurt {
+    pub start: u32,
+    pub end: u32,
+    pub packets: u32,
+}
+
+/// When the packets of one SSRC arrived, see [`packet_timeline`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct StreamTimeline {
+    pub ssrc: u32,
+    /// Payload type of the first packet
+    pub payload_type: u8,
+    /// Milliseconds since the start of recording of every packet
+    pub arrivals: Vec<u32>,
+    pub losses: Vec<Loss>,
+    pub talkspurts: Vec<Talkspurt>,
+    /// Highest sequence number so far, to tell losses from reordered packets
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_seq: u16,
+}
+
+impl StreamTimeline {
+    fn new(ssrc: u32, payload_type: u8) -> Self {
+        Self {
+            ssrc,
+            payload_type,
+            ..Default::default()
+        }
+    }
+
+    fn on_pkt(&mut self, offset: u32, seq: u16, marked: bool) {
+        if !self.arrivals.is_empty() {
+            let ahead = seq.wrapping_sub(self.last_seq);
+            // older than the highest sequence number, reordered or duplicated
+            if ahead == 0 || ahead >= 0x8000 {
+                self.arrivals.push(offset);
+                return;
+            }
+            if ahead > 1 {
+                self.losses.push(Loss {
+                    offset,
+                    seq: self.last_seq.wrapping_add(1),
+                    count: ahead - 1,
+                });
+            }
+        }
+        self.last_seq = seq;
+
+        match self.talkspurts.last_mut() {
+            Some(spurt) if !marked && offset.saturating_sub(spurt.end) <= TALKSPURT_GAP => {
+                spurt.end = offset;
+                spurt.packets += 1;
+            }
+            _ => {
+                // a lone packet after a silence is a SID frame, it doesn't start a talkspurt
+                if self.talkspurts.last().is_some_and(|s| s.packets == 1) {
+                    self.talkspurts.pop();
+                }
+                self.talkspurts.push(Talkspurt {
+                    start: offset,
+                    end: offset,
+                    packets: 1,
+                });
+            }
+        }
+        self.arrivals.push(offset);
+    }
+}
+
+/// Arrivals, losses and talkspurts of every SSRC of a capture, to draw its packet timeline
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct PacketTimeline {
+    /// Milliseconds since the start of recording of the last packet
+    pub duration: u32,
+    /// Every SSRC in order of appearance
+    pub streams: Vec<StreamTimeline>,
+}
+
+impl PacketTimeline {
+    /// Write the timeline as JSON, for a plot or a canvas to draw it:
+    ///
+    /// ```text
+    /// {"duration":5020,"streams":[{"ssrc":4660,"payload_type":96,"arrivals":[0,20,60],
+    ///   "losses":[{"offset":60,"seq":3,"count":1}],
+    ///   "talkspurts":[{"start":0,"end":60,"packets":3}]}]}
+    /// ```
+    pub fn write_json(&self, mut w: impl Write) -> std::io::Result<()> {
+        write!(w, "{{\"duration\":{},\"streams\":[", self.duration)?;
+        for (i, stream) in self.streams.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "{{\"ssrc\":{},\"payload_type\":{},\"arrivals\":[",
+                stream.ssrc, stream.payload_type
+            )?;
+            for (j, offset) in stream.arrivals.iter().enumerate() {
+                let sep = if j > 0 { "," } else { "" };
+                write!(w, "{sep}{offset}")?;
+            }
+            write!(w, "],\"losses\":[")?;
+            for (j, loss) in stream.losses.iter().enumerate() {
+                let sep = if j > 0 { "," } else { "" };
+                write!(
+                    w,
+                    "{sep}{{\"offset\":{},\"seq\":{},\"count\":{}}}",
+                    loss.offset, loss.seq, loss.count
+                )?;
+            }
+            write!(w, "],\"talkspurts\":[")?;
+            for (j, spurt) in stream.talkspurts.iter().enumerate() {
+                let sep = if j > 0 { "," } else { "" };
+                write!(
+                    w,
+                    "{sep}{{\"start\":{},\"end\":{},\"packets\":{}}}",
+                    spurt.start, spurt.end, spurt.packets
+                )?;
+            }
+            write!(w, "]}}")?;
+        }
+        writeln!(w, "]}}")
+    }
+}
+
+/// Timeline of the RTP packets of a rtpdump capture held in memory, without decoding them.
+/// Packets that aren't RTP are skipped.
+pub fn packet_timeline(capture: &[u8]) -> Result<PacketTimeline> {
+    let mut timeline = PacketTimeline::default();
+    for (rd, data) in RtpdumpPackets::new(capture)? {
+        // RTCP records have no original length
+        if rd.org_len == 0 {
+            continue;
+        }
+        let Ok(pkt) = parse_rtp(data) else {
+            continue;
+        };
+        let idx = match timeline.streams.iter().position(|s| s.ssrc == pkt.ssrc()) {
+            Some(idx) => idx,
+            None => {
+                let stream = StreamTimeline::new(pkt.ssrc(), pkt.payload_type().to_u8());
+                timeline.streams.push(stream);
+                timeline.streams.len() - 1
+            }
+        };
+        timeline.streams[idx].on_pkt(rd.offset, pkt.seq(), pkt.marked());
+        timeline.duration = timeline.duration.max(rd.offset);
+    }
+    for stream in &mut timeline.streams {
+        if stream.talkspurts.last().is_some_and(|s| s.packets == 1) {
+            stream.talkspurts.pop();
+        }
+    }
+    Ok(timeline)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RtpdumpWriter;
+    use codec_detector::rtp::RtpPacketizer;
+
+    #[test]
+    fn test_packet_timeline() {
+        let source = std::net::SocketAddrV4::new([127, 0, 0, 1].into(), 5004);
+        let mut writer = RtpdumpWriter::new(vec![], source, std::time::Duration::ZERO).unwrap();
+        let mut packetizer = RtpPacketizer::new(0x1234, 96, 16000, 20);
+        // a talkspurt, a SID frame, then a talkspurt losing the packets at 440 and 460 ms
+        let offsets = [0, 20, 40, 60, 220, 400, 420, 440, 460, 480, 500];
+        for offset in offsets {
+            let pkt = packetizer.packetize(&[0; 33]);
+            if offset != 440 && offset != 460 {
+                writer.write_packet(offset, &pkt).unwrap();
+            }
+        }
+        let capture = writer.into_inner();
+
+        let timeline = packet_timeline(&capture).unwrap();
+        assert_eq!(timeline.duration, 500);
+        let stream = &timeline.streams[0];
+        assert_eq!((stream.ssrc, stream.payload_type), (0x1234, 96));
+        assert_eq!(stream.arrivals, [0, 20, 40, 60, 220, 400, 420, 480, 500]);
+        assert_eq!(
+            stream.losses,
+            [Loss {
+                offset: 480,
+                seq: 7,
+                count: 2
+            }]
+        );
+        assert_eq!(
+            stream.talkspurts,
+            [
+                Talkspurt {
+                    start: 0,
+                    end: 60,
+                    packets: 4
+                },
+                Talkspurt {
+                    start: 400,
+                    end: 500,
+                    packets: 4
+                }
+            ]
+        );
+
+        let mut json = vec![];
+        timeline.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert_eq!(
+            json,
+            "{\"duration\":500,\"streams\":[{\"ssrc\":4660,\"payload_type\":96,\
+             \"arrivals\":[0,20,40,60,220,400,420,480,500],\
+             \"losses\":[{\"offset\":480,\"seq\":7,\"count\":2}],\
+             \"talkspurts\":[{\"start\":0,\"end\":60,\"packets\":4},\
+             {\"start\":400,\"end\":500,\"packets\":4}]}]}\n"
+        );
+    }
+}
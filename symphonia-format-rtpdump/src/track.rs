@@ -0,0 +1,472 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use codec_detector::rtp::{parse_rtp, RtpPacket};
+use symphonia_core::errors::{Error, Result};
+use symphonia_core::formats::Packet;
+
+use crate::channel::{Channel, ChannelPacket};
+use crate::clock::{restarts_timeline, TrackClock};
+use crate::depacketizer::{Frame, FrameMeta, PayloadDepacketizer};
+use crate::registry::{DepacketizerFactory, RtpCodecDescriptor};
+
+/// A packet of a track dropped as malformed, see
+/// [`RtpdumpReader::issues`](crate::RtpdumpReader::issues)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DecodeIssue {
+    pub track_id: u32,
+    /// RTP sequence number of the packet
+    pub seq: u16,
+    /// RTP timestamp of the packet, `None` if its RTP header couldn't be parsed
+    pub rtp_ts: Option<u32>,
+    /// Milliseconds since the start of recording
+    pub offset: u32,
+    pub reason: String,
+}
+
+/// A frame handed to the decoder of a track as depacketized, see
+/// [`RtpdumpReader::record_frames`](crate::RtpdumpReader::record_frames)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodecFrame {
+    /// RTP sequence number of the packet which released the frame
+    pub seq: u16,
+    /// RTP timestamp of the first sample of the frame
+    pub ts: u32,
+    /// Milliseconds since the start of recording the packet arrived at
+    pub offset: u32,
+    /// Frame type told by the payload format, see [`FrameMeta::frame_type`]
+    pub frame_type: Option<u8>,
+    pub data: Box<[u8]>,
+}
+
+/// The first frame of a track of another frame type than the frames before it, e.g. an EVS
+/// stream switching from 13.2 to 24.4 kbps, to AMR-WB IO or to SID frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ModeChange {
+    /// RTP sequence number of the packet which released the frame
+    pub seq: u16,
+    /// Milliseconds since the start of recording the packet arrived at
+    pub offset: u32,
+    pub frame_type: u8,
+    /// Bitrate of the frame type, see [`FrameMeta::bitrate`]
+    pub bitrate: Option<u32>,
+}
+
+/// Frame types and bitrate of the frames of a track, e.g. to follow the rate adaptation of
+/// AMR or the mode switches of EVS, see
+/// [`RtpdumpReader::frame_stats`](crate::RtpdumpReader::frame_stats)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct FrameStats {
+    /// Frames of every frame type, see [`FrameMeta::frame_type`]
+    pub frame_types: BTreeMap<u8, u64>,
+    /// Bits per second of the frames of every second since the first one, at the bitrate of
+    /// their frame type. Seconds without frames, e.g. lost or not sent, are 0.
+    pub bitrate: Vec<u32>,
+    /// Timeline of the frame types, from the first frame
+    pub modes: Vec<ModeChange>,
+}
+
+impl FrameStats {
+    /// Count a frame of `meta` lasting `dur` units of `clock_rate` from `ext`, released by
+    /// packet `seq` arrived at `offset`
+    fn add(
+        &mut self,
+        (seq, offset): (u16, u32),
+        meta: &FrameMeta,
+        ext: i64,
+        dur: u32,
+        clock_rate: u32,
+    ) {
+        let FrameMeta {
+            frame_type,
+            bitrate,
+            ..
+        } = *meta;
+        *self.frame_types.entry(frame_type).or_default() += 1;
+        if self.modes.last().map(|m| m.frame_type) != Some(frame_type) {
+            self.modes.push(ModeChange {
+                seq,
+                offset,
+                frame_type,
+                bitrate,
+            });
+        }
+        let (Some(bitrate), Ok(second)) = (bitrate, usize::try_from(ext / clock_rate as i64))
+        else {
+            return;
+        };
+        if self.bitrate.len() <= second {
+            self.bitrate.resize(second + 1, 0);
+        }
+        let bits = bitrate as u64 * dur as u64 / clock_rate as u64;
+        self.bitrate[second] = self.bitrate[second].saturating_add(bits as u32);
+    }
+}
+
+/// Everything between the RTP packets of a stream and the packets handed to its decoder:
+/// reordering, depacketization and timestamping.
+pub(crate) struct TrackPipeline {
+    pub channel: Channel,
+    depacketizer: Box<dyn PayloadDepacketizer>,
+    new_depacketizer: DepacketizerFactory,
+    clock: TrackClock,
+    frames: VecDeque<Frame>,
+    /// Place the track on the capture timeline, rather than start it at its first packet
+    pub align_to_capture: bool,
+    /// Append the [`FrameMeta`] of every frame to its packet
+    pub attach_meta: bool,
+    /// Frames depacketized so far, when recorded
+    pub recorded: Option<Vec<CodecFrame>>,
+    pub stats: FrameStats,
+    /// Sequence number and arrival of the last packet depacketized
+    released_by: (u16, u32),
+    /// Sequence number, timestamp and arrival of the last packet depacketized
+    last_pkt: Option<(u16, u32, u32)>,
+}
+
+impl TrackPipeline {
+    pub fn new(desc: &RtpCodecDescriptor, sample_rate: u32, reorder_depth: usize) -> Self {
+        Self {
+            channel: Channel::new(reorder_depth),
+            depacketizer: (desc.depacketizer)(),
+            new_depacketizer: desc.depacketizer,
+            clock: TrackClock::new(desc.clock_rate.unwrap_or(sample_rate), sample_rate),
+            frames: VecDeque::new(),
+            align_to_capture: true,
+            attach_meta: false,
+            recorded: None,
+            stats: FrameStats::default(),
+            released_by: (0, 0),
+            last_pkt: None,
+        }
+    }
+
+    /// Whether the frames of the track carry a [`FrameMeta`] when [`Self::attach_meta`] is set
+    pub fn frame_meta(&self) -> bool {
+        self.depacketizer.frame_meta()
+    }
+
+    /// Turn a packet released by the channel into decoder packets of track `track_id`.
+    ///
+    /// Timestamp gaps up to `max_gap_fill` milliseconds are filled with missing frames. Fails
+    /// if the packet was dropped as malformed, the decoder then conceals its frames.
+    pub fn depacketize(
+        &mut self,
+        track_id: u32,
+        pkt: &ChannelPacket,
+        max_gap_fill: u32,
+        out: &mut VecDeque<Packet>,
+    ) -> Result<()> {
+        let Ok(rtp) = parse_rtp(&pkt.data) else {
+            return Err(Error::DecodeError("Invalid RTP header"));
+        };
+
+        if let Err(e) = self.depacketizer.depacketize(&rtp, &mut self.frames) {
+            self.frames.clear();
+            return Err(e);
+        }
+
+        // a sender restarting its timestamps goes on from where the previous packet ended
+        if let Some((seq, ts, offset)) = self.last_pkt {
+            let ts_delta = rtp.ts().wrapping_sub(ts) as i32 as i64;
+            let elapsed = pkt.offset.saturating_sub(offset);
+            if pkt.seq.0 == seq.wrapping_add(1)
+                && restarts_timeline(ts_delta, elapsed, self.clock.clock_rate())
+            {
+                self.clock.restart(rtp.ts());
+            }
+        }
+        self.last_pkt = Some((pkt.seq.0, rtp.ts(), pkt.offset));
+
+        self.released_by = (pkt.seq.0, pkt.offset);
+        self.record(pkt.seq.0, pkt.offset);
+        self.clock
+            .start(if self.align_to_capture { pkt.offset } else { 0 });
+        self.timestamp(track_id, max_gap_fill, out);
+        Ok(())
+    }
+
+    /// Hand the frames the depacketizer still holds back to the decoder once the stream ended
+    pub fn flush(&mut self, track_id: u32, max_gap_fill: u32, out: &mut VecDeque<Packet>) {
+        self.depacketizer.flush(&mut self.frames);
+        // released by the end of the stream, after the last packet
+        let last = self.recorded.as_ref().and_then(|r| r.last());
+        let (seq, offset) = last.map_or((0, 0), |f| (f.seq, f.offset));
+        self.record(seq, offset);
+        self.timestamp(track_id, max_gap_fill, out);
+    }
+
+    /// Data of the stand-in frames handed to the decoder in place of the frames lost or not sent
+    pub fn missing_frame(&self) -> Box<[u8]> {
+        self.depacketizer.missing_frame()
+    }
+
+    /// Unwrapped offset from `origin` of position `ts` on the track timeline, see
+    /// [`TrackClock::ext_at`]
+    pub fn ext_at(&self, origin: u32, start: u64, ts: u64) -> i64 {
+        self.clock.ext_at(origin, start, ts)
+    }
+
+    /// Drop every buffered packet and frame before reading from another position of the
+    /// capture. With an `anchor` (origin, start, ext), the next packet read is the one `ext`
+    /// units after `origin`, whose position on the track timeline is returned. Without, the
+    /// track is read from its start again.
+    pub fn seek(&mut self, anchor: Option<(u32, u64, i64)>) -> Option<u64> {
+        self.channel.clear();
+        self.depacketizer = (self.new_depacketizer)();
+        self.frames.clear();
+        self.last_pkt = None;
+        match anchor {
+            Some((origin, start, ext)) => Some(self.clock.resume(origin, start, ext)),
+            None => {
+                self.clock.reset();
+                None
+            }
+        }
+    }
+
+    /// Record the depacketized frames, released by packet `seq` arrived at `offset`
+    fn record(&mut self, seq: u16, offset: u32) {
+        let Some(recorded) = self.recorded.as_mut() else {
+            return;
+        };
+        recorded.extend(self.frames.iter().map(|frame| CodecFrame {
+            seq,
+            ts: frame.ts,
+            offset,
+            frame_type: frame.meta.map(|m| m.frame_type),
+            data: frame.data.clone(),
+        }));
+    }
+
+    /// Turn the depacketized frames into decoder packets on the track timeline
+    fn timestamp(&mut self, track_id: u32, max_gap_fill: u32, out: &mut VecDeque<Packet>) {
+        let clock = &mut self.clock;
+        let max_gap = max_gap_fill as i64 * clock.clock_rate() as i64 / 1000;
+        for frame in self.frames.drain(..) {
+            let ext = clock.extend(frame.ts);
+            // payload formats not telling the frame duration use 20 ms frames
+            let dur = frame.dur.unwrap_or(clock.clock_rate() / 50).max(1);
+            if let Some(meta) = &frame.meta {
+                let clock_rate = clock.clock_rate();
+                self.stats.add(self.released_by, meta, ext, dur, clock_rate);
+            }
+
+            // stand-in frames for every frame lost or not sent (e.g. DTX) before this one
+            let gap = clock.gap(ext);
+            if gap >= dur as i64 && gap <= max_gap {
+                let mut missing = ext - gap;
+                while missing + dur as i64 <= ext {
+                    let ts = clock.samples_at(missing);
+                    out.push_back(Packet::new_from_boxed_slice(
+                        track_id,
+                        ts,
+                        clock.samples_at(missing + dur as i64) - ts,
+                        self.depacketizer.missing_frame(),
+                    ));
+                    missing += dur as i64;
+                }
+            }
+
+            let data = match frame.meta {
+                Some(meta) if self.attach_meta => {
+                    let mut data = frame.data.into_vec();
+                    meta.append(&mut data);
+                    data.into_boxed_slice()
+                }
+                _ => frame.data,
+            };
+            let ts = clock.samples_at(ext);
+            out.push_back(Packet::new_from_boxed_slice(
+                track_id,
+                ts,
+                clock.samples_at(ext + dur as i64) - ts,
+                data,
+            ));
+            clock.advance(ext, dur);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use codec_detector::rtp::SeqNum;
+    use symphonia_core::codecs::CODEC_TYPE_NULL;
+
+    use super::*;
+    use crate::depacketizer::{AmrDepacketizer, AmrPacking, Passthrough};
+    use crate::test_util::Lcg;
+
+    /// Packet `seq` of a stream starting at `first` and `ts_base`, carrying a 20 ms frame, its
+    /// payload being `seq`
+    fn rtp_pkt(seq: u16, first: u16, ts_base: u32) -> ChannelPacket {
+        let ts = ts_base.wrapping_add(seq.wrapping_sub(first) as u32 * 320);
+        let mut data = vec![0x80, 96];
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&ts.to_be_bytes());
+        data.extend_from_slice(&0x1234u32.to_be_bytes());
+        data.extend_from_slice(&seq.to_be_bytes());
+        ChannelPacket {
+            seq: SeqNum(seq),
+            offset: 0,
+            data: data.into(),
+        }
+    }
+
+    /// Packets `first..first + len` after random loss, duplication and reordering
+    fn arrivals(rng: &mut Lcg, first: u16, len: u16, depth: usize) -> Vec<u16> {
+        let mut seqs = vec![];
+        for i in 0..len {
+            let seq = first.wrapping_add(i);
+            if rng.chance(10) {
+                continue;
+            }
+            seqs.push(seq);
+            if rng.chance(5) {
+                seqs.push(seq);
+            }
+        }
+        // move some packets later, mostly within the reorder depth
+        for i in (0..seqs.len()).rev() {
+            if rng.chance(15) {
+                let shift = rng.next_u32() as usize % (depth * 2 + 1);
+                let to = (i + shift).min(seqs.len() - 1);
+                let seq = seqs.remove(i);
+                seqs.insert(to, seq);
+            }
+        }
+        seqs
+    }
+
+    #[test]
+    fn test_demux_invariants() {
+        let desc = RtpCodecDescriptor {
+            name: "test",
+            codec: CODEC_TYPE_NULL,
+            clock_rate: None,
+            depacketizer: || Box::new(Passthrough),
+        };
+        let mut rng = Lcg(0x5eed);
+        for case in 0..200 {
+            let depth = 1 + rng.next_u32() as usize % 8;
+            // some streams wrap their sequence numbers and timestamps
+            let first = rng.next_u32() as u16;
+            let ts_base = rng.next_u32() << 8;
+            let seqs = arrivals(&mut rng, first, 300, depth);
+
+            let mut pipeline = TrackPipeline::new(&desc, 16000, depth);
+            let mut out = VecDeque::new();
+            for seq in &seqs {
+                pipeline.channel.add_pkt(rtp_pkt(*seq, first, ts_base));
+                while let Some(pkt) = pipeline.channel.pop_pkt() {
+                    pipeline.depacketize(0, &pkt, 60_000, &mut out).unwrap();
+                }
+            }
+            while let Some(pkt) = pipeline.channel.drain_pkt() {
+                pipeline.depacketize(0, &pkt, 60_000, &mut out).unwrap();
+            }
+            pipeline.flush(0, 60_000, &mut out);
+
+            let mut delivered = HashSet::new();
+            let mut fillers = 0;
+            for (i, packet) in out.iter().enumerate() {
+                if i > 0 {
+                    assert!(
+                        packet.ts() > out[i - 1].ts(),
+                        "case {case}: ts not increasing"
+                    );
+                }
+                if packet.data.is_empty() {
+                    fillers += 1;
+                    continue;
+                }
+                let seq = u16::from_be_bytes([packet.data[0], packet.data[1]]);
+                assert!(delivered.insert(seq), "case {case}: {seq} delivered twice");
+            }
+            // stand-in frames only fill the sequence numbers never delivered between the
+            // first and last delivered ones
+            let offsets = delivered.iter().map(|s| s.wrapping_sub(first));
+            let span = offsets.clone().max().unwrap() - offsets.min().unwrap() + 1;
+            assert!(
+                fillers <= span as usize - delivered.len(),
+                "case {case}: {fillers} stand-in frames"
+            );
+        }
+    }
+
+    #[test]
+    fn test_frame_stats() {
+        let desc = RtpCodecDescriptor {
+            name: "amr",
+            codec: CODEC_TYPE_NULL,
+            clock_rate: Some(8000),
+            depacketizer: || Box::new(AmrDepacketizer::new(false, AmrPacking::OctetAligned)),
+        };
+        let mut pipeline = TrackPipeline::new(&desc, 8000, 0);
+        let mut out = VecDeque::new();
+        // 1 s at 12.2 kbps, 1 s at 5.9 kbps then 1 s of SID frames every 160 ms
+        let frames = (0..50)
+            .map(|_| 7)
+            .chain((0..50).map(|_| 2))
+            .chain((0..50).filter(|i| i % 8 == 0).map(|_| 8));
+        for (i, ft) in frames.enumerate() {
+            let seq = i as u16;
+            let ts = match ft {
+                8 => 100 * 160 + (i as u32 - 100) * 8 * 160,
+                _ => seq as u32 * 160,
+            };
+            let mut data = vec![0x80, 97];
+            data.extend_from_slice(&seq.to_be_bytes());
+            data.extend_from_slice(&ts.to_be_bytes());
+            data.extend_from_slice(&0x1234u32.to_be_bytes());
+            // CMR, then the ToC of a single good frame
+            data.extend_from_slice(&[0xf0, ft << 3 | 0x04]);
+            let bits = [95, 103, 118, 134, 148, 159, 204, 244, 39][ft as usize];
+            data.resize(data.len() + usize::div_ceil(bits, 8), 0x55);
+            let pkt = ChannelPacket {
+                seq: SeqNum(seq),
+                offset: i as u32 * 20,
+                data: data.into(),
+            };
+            pipeline.depacketize(0, &pkt, 0, &mut out).unwrap();
+        }
+
+        let stats = &pipeline.stats;
+        assert_eq!(
+            stats.frame_types,
+            BTreeMap::from([(2, 50), (7, 50), (8, 7)])
+        );
+        // SID frames last 20 ms, though they stand for 160
+        assert_eq!(stats.bitrate, [12200, 5900, 7 * 39]);
+        assert_eq!(
+            stats.modes,
+            [
+                ModeChange {
+                    seq: 0,
+                    offset: 0,
+                    frame_type: 7,
+                    bitrate: Some(12200)
+                },
+                ModeChange {
+                    seq: 50,
+                    offset: 1000,
+                    frame_type: 2,
+                    bitrate: Some(5900)
+                },
+                ModeChange {
+                    seq: 100,
+                    offset: 2000,
+                    frame_type: 8,
+                    bitrate: Some(1950)
+                }
+            ]
+        );
+    }
+}
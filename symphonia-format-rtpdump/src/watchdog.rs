@@ -0,0 +1,184 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use symphonia_core::audio::AudioBufferRef;
+use symphonia_core::codecs::Decoder;
+use symphonia_core::errors::{Error, Result};
+use symphonia_core::formats::Packet;
+
+use crate::Metrics;
+
+/// Time a decoder may spend on one packet before the packet counts as an overrun.
+///
+/// Decoding isn't interrupted, the time is measured afterwards: once a track had
+/// `max_overruns` packets over budget its decoder is given up and the track marked degraded,
+/// so that a stream sending a decoder into very long loops doesn't hold up a whole batch. The
+/// default budget is unlimited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeBudget {
+    pub per_packet: Duration,
+    pub max_overruns: u64,
+}
+
+impl Default for DecodeBudget {
+    fn default() -> Self {
+        Self {
+            per_packet: Duration::MAX,
+            max_overruns: u64::MAX,
+        }
+    }
+}
+
+/// Time spent decoding the packets of a track
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DecodeStats {
+    pub packets: u64,
+    /// Packets over the per packet budget
+    pub overruns: u64,
+    pub total: Duration,
+    pub slowest: Duration,
+}
+
+/// Decoder measuring the time spent on every packet against a [`DecodeBudget`]
+pub struct BudgetedDecoder {
+    inner: Box<dyn Decoder>,
+    budget: DecodeBudget,
+    stats: DecodeStats,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl BudgetedDecoder {
+    pub fn new(inner: Box<dyn Decoder>, budget: DecodeBudget) -> Self {
+        Self {
+            inner,
+            budget,
+            stats: DecodeStats::default(),
+            metrics: None,
+        }
+    }
+
+    /// Count the packets the decoder rejects in `metrics`
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Decode a packet, fails without calling the decoder once the track is degraded
+    pub fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        if self.is_degraded() {
+            return Err(Error::DecodeError("Decoder over its time budget"));
+        }
+
+        let start = Instant::now();
+        let decoded = self.inner.decode(packet);
+        let elapsed = start.elapsed();
+        self.stats.packets += 1;
+        self.stats.total += elapsed;
+        self.stats.slowest = self.stats.slowest.max(elapsed);
+        if elapsed > self.budget.per_packet {
+            self.stats.overruns += 1;
+            warn!(
+                "decoding packet at ts {} took {:?}, over its {:?} budget",
+                packet.ts(),
+                elapsed,
+                self.budget.per_packet
+            );
+        }
+        if let (Err(Error::DecodeError(_)), Some(metrics)) = (&decoded, &self.metrics) {
+            metrics.add_decode_error();
+        }
+        decoded
+    }
+
+    /// Whether too many packets went over budget for the track to be decoded any further
+    pub fn is_degraded(&self) -> bool {
+        self.stats.overruns >= self.budget.max_overruns
+    }
+
+    pub fn stats(&self) -> DecodeStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use symphonia_core::audio::{AsAudioBufferRef, AudioBuffer, Channels, Signal, SignalSpec};
+    use symphonia_core::codecs::{
+        CodecDescriptor, CodecParameters, DecoderOptions, FinalizeResult,
+    };
+
+    use super::*;
+
+    /// Decoder taking as many milliseconds as the first byte of a packet says
+    struct SlowDecoder {
+        params: CodecParameters,
+        buf: AudioBuffer<i16>,
+    }
+
+    impl Decoder for SlowDecoder {
+        fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+            let mut buf = AudioBuffer::new(160, SignalSpec::new(8000, Channels::FRONT_CENTRE));
+            buf.render_reserved(Some(160));
+            Ok(Self {
+                params: params.clone(),
+                buf,
+            })
+        }
+
+        fn supported_codecs() -> &'static [CodecDescriptor] {
+            &[]
+        }
+
+        fn reset(&mut self) {}
+
+        fn codec_params(&self) -> &CodecParameters {
+            &self.params
+        }
+
+        fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+            std::thread::sleep(Duration::from_millis(packet.data[0] as u64));
+            Ok(self.buf.as_audio_buffer_ref())
+        }
+
+        fn finalize(&mut self) -> FinalizeResult {
+            Default::default()
+        }
+
+        fn last_decoded(&self) -> AudioBufferRef<'_> {
+            self.buf.as_audio_buffer_ref()
+        }
+    }
+
+    #[test]
+    fn test_decode_budget() {
+        let inner = SlowDecoder::try_new(&CodecParameters::new(), &Default::default()).unwrap();
+        let budget = DecodeBudget {
+            per_packet: Duration::from_millis(20),
+            max_overruns: 2,
+        };
+        let mut decoder = BudgetedDecoder::new(Box::new(inner), budget);
+        let mut decode = |ms: u8| {
+            let packet = Packet::new_from_slice(0, 0, 160, &[ms]);
+            decoder.decode(&packet).map(|_| ())
+        };
+
+        assert!(decode(0).is_ok());
+        assert!(decode(40).is_ok());
+        assert!(decode(0).is_ok());
+        assert!(decode(40).is_ok());
+        // given up after the second overrun
+        assert!(decode(0).is_err());
+
+        let stats = decoder.stats();
+        assert!(decoder.is_degraded());
+        assert_eq!((stats.packets, stats.overruns), (4, 2));
+        assert!(stats.slowest >= Duration::from_millis(40));
+        assert!(!BudgetedDecoder::new(
+            Box::new(SlowDecoder::try_new(&CodecParameters::new(), &Default::default()).unwrap()),
+            DecodeBudget::default()
+        )
+        .is_degraded());
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::Write;
+use std::net::SocketAddrV4;
+use std::time::Duration;
+
+use codec_detector::rtp::RtpPacketizer;
+use symphonia_core::errors::{Error, Result};
+
+use crate::{G711Law, MAGIC};
+
+/// Writes captures in the rtpdump format of [`RtpdumpReader`](crate::RtpdumpReader), e.g. to
+/// synthesize test streams
+pub struct RtpdumpWriter<W: Write> {
+    w: W,
+}
+
+impl<W: Write> RtpdumpWriter<W> {
+    /// Write the file header, `source` being the address the packets were sent from and `start`
+    /// the time since the Unix epoch the recording started at
+    pub fn new(mut w: W, source: SocketAddrV4, start: Duration) -> Result<Self> {
+        w.write_all(MAGIC)?;
+        writeln!(w, "{}/{}", source.ip(), source.port())?;
+        w.write_all(&(start.as_secs() as u32).to_be_bytes())?;
+        w.write_all(&start.subsec_micros().to_be_bytes())?;
+        w.write_all(&u32::from(*source.ip()).to_be_bytes())?;
+        w.write_all(&source.port().to_be_bytes())?;
+        w.write_all(&[0; 2])?;
+        Ok(Self { w })
+    }
+
+    /// Write a RTP packet received `offset` milliseconds after the start of the recording
+    pub fn write_packet(&mut self, offset: u32, rtp: &[u8]) -> Result<()> {
+        let len = u16::try_from(rtp.len() + 8)
+            .map_err(|_| Error::DecodeError("RTP packet too long for rtpdump"))?;
+        self.w.write_all(&len.to_be_bytes())?;
+        self.w.write_all(&(len - 8).to_be_bytes())?;
+        self.w.write_all(&offset.to_be_bytes())?;
+        self.w.write_all(rtp)?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// Capture of a G.711 stream carrying 8 kHz audio, a packet every `ptime` milliseconds. The
+/// last packet is padded with silence.
+pub fn g711_capture(pcm: &[i16], law: G711Law, ssrc: u32, ptime: u32) -> Result<Vec<u8>> {
+    let source = SocketAddrV4::new([127, 0, 0, 1].into(), 5004);
+    let mut writer = RtpdumpWriter::new(vec![], source, Duration::ZERO)?;
+    let mut packetizer = RtpPacketizer::new(ssrc, law.payload_type(), 8000, ptime);
+    let len = packetizer.samples_per_packet() as usize;
+    if len == 0 {
+        return Err(Error::DecodeError("Invalid packet time"));
+    }
+
+    for (i, chunk) in pcm.chunks(len).enumerate() {
+        let mut payload = chunk.iter().map(|s| law.encode(*s)).collect::<Vec<_>>();
+        payload.resize(len, law.encode(0));
+        writer.write_packet(i as u32 * ptime, &packetizer.packetize(&payload))?;
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use codec_detector::rtp::{parse_rtp, RtpPacket};
+
+    use super::*;
+    use crate::RtpdumpPackets;
+
+    #[test]
+    fn test_g711_capture() {
+        let pcm = (0..400).map(|i| (i * 40) as i16).collect::<Vec<_>>();
+        let capture = g711_capture(&pcm, G711Law::MuLaw, 0x4242, 20).unwrap();
+        assert!(capture.starts_with(b"#!rtpplay1.0 127.0.0.1/5004\n"));
+
+        let pkts = RtpdumpPackets::new(&capture)
+            .unwrap()
+            .map(|(rd, data)| (rd, parse_rtp(data).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(pkts.len(), 3);
+        for (i, (rd, pkt)) in pkts.iter().enumerate() {
+            assert_eq!(rd.offset, i as u32 * 20);
+            assert_eq!(rd.org_len as usize, 12 + 160);
+            assert_eq!(pkt.ssrc(), 0x4242);
+            assert_eq!(pkt.payload_type().to_u8(), 0);
+            assert_eq!(pkt.seq(), i as u16);
+            assert_eq!(pkt.ts(), i as u32 * 160);
+        }
+        let payload = pkts[1].1.payload();
+        assert_eq!(
+            G711Law::MuLaw.decode(payload[0]),
+            G711Law::MuLaw.decode(G711Law::MuLaw.encode(6400))
+        );
+        // silence padding
+        assert_eq!(pkts[2].1.payload()[80..], [0xff; 80]);
+
+        assert!(g711_capture(&pcm, G711Law::ALaw, 1, 0).is_err());
+    }
+}
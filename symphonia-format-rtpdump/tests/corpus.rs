@@ -0,0 +1,146 @@
+//! End-to-end regression check of every codec path: the synthetic captures of `tests/assets`
+//! are demuxed and decoded, and the results compared against `tests/assets/golden.txt`.
+//!
+//! After an intended change of the output, regenerate the captures and the golden file with
+//! `RTPDUMP_UPDATE_GOLDEN=1 cargo test -p symphonia-format-rtpdump --test corpus`.
+
+use std::io::{Cursor, ErrorKind};
+use std::net::SocketAddrV4;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use codec_detector::rtp::RtpPacketizer;
+use rtp_core::test_util::Lcg;
+use symphonia_core::errors::Error;
+use symphonia_core::formats::FormatReader;
+use symphonia_core::io::MediaSourceStream;
+use symphonia_format_rtpdump::{decode_capture, RtpdumpReader, RtpdumpWriter};
+
+/// Capture name, payload type, RTP clock rate, and the first bytes of every payload, followed
+/// by pseudo-random bytes up to the payload size
+const CORPUS: [(&str, u8, u32, &[u8], usize); 4] = [
+    // EVS 13.2 kbps compact format
+    ("evs.rtpdump", 96, 16000, &[], 33),
+    // AMR 12.2 kbps octet aligned: CMR 15, F0 FT7 Q1
+    ("amr.rtpdump", 97, 8000, &[0xf0, 0x3c], 33),
+    // AMR-WB 23.85 kbps octet aligned: CMR 15, F0 FT8 Q1
+    ("amrwb.rtpdump", 98, 16000, &[0xf0, 0x44], 62),
+    // G.722.1 24 kbps
+    ("g7221.rtpdump", 99, 16000, &[], 60),
+];
+
+/// Packets of every capture, a second of 20 ms frames
+const PACKETS: u32 = 50;
+
+fn assets() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets")
+}
+
+fn capture(pt: u8, clock_rate: u32, head: &[u8], size: usize) -> Vec<u8> {
+    let source = SocketAddrV4::new([127, 0, 0, 1].into(), 5004);
+    let mut writer = RtpdumpWriter::new(vec![], source, Duration::ZERO).unwrap();
+    let mut packetizer = RtpPacketizer::new(0x1000 + pt as u32, pt, clock_rate, 20);
+    let mut rng = Lcg(pt as u32);
+    for i in 0..PACKETS {
+        let mut payload = head.to_vec();
+        payload.resize_with(size, || rng.byte());
+        writer
+            .write_packet(i * 20, &packetizer.packetize(&payload))
+            .unwrap();
+    }
+    writer.into_inner()
+}
+
+/// 64-bit FNV-1a
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Packets the reader hands to the decoders, and the hash of their tracks, timestamps and data
+fn demux(data: &[u8]) -> (u64, u64) {
+    let mss = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+    let mut reader = RtpdumpReader::try_new(mss, &Default::default()).unwrap();
+    let (mut packets, mut hash) = (0, FNV_OFFSET);
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => panic!("{}", e),
+        };
+        packets += 1;
+        hash = fnv1a(hash, &packet.track_id().to_le_bytes());
+        hash = fnv1a(hash, &packet.ts().to_le_bytes());
+        hash = fnv1a(hash, &packet.dur().to_le_bytes());
+        hash = fnv1a(hash, &packet.data);
+    }
+    (packets, hash)
+}
+
+/// One line of the golden file: capture, codec, packets and hash of the demuxed packets,
+/// samples and hash of the decoded audio. The audio columns hold `-` when not recorded.
+fn golden_line(name: &str, data: &[u8]) -> String {
+    let tracks = decode_capture(data).unwrap();
+    assert_eq!(tracks.len(), 1, "{}", name);
+    let track = &tracks[0];
+    let (packets, demux_hash) = demux(data);
+    let pcm = track
+        .pcm
+        .iter()
+        .flat_map(|s| s.to_le_bytes())
+        .collect::<Vec<_>>();
+    format!(
+        "{} {} {} {:016x} {} {:016x}",
+        name,
+        track.codec,
+        packets,
+        demux_hash,
+        track.pcm.len(),
+        fnv1a(FNV_OFFSET, &pcm)
+    )
+}
+
+#[test]
+fn test_corpus() {
+    let update = std::env::var_os("RTPDUMP_UPDATE_GOLDEN").is_some();
+    let golden_path = assets().join("golden.txt");
+    let golden = std::fs::read_to_string(&golden_path).unwrap_or_default();
+    let mut lines = vec![];
+    for (name, pt, clock_rate, head, size) in CORPUS {
+        let path = assets().join(name);
+        let data = capture(pt, clock_rate, head, size);
+        if update {
+            std::fs::write(&path, &data).unwrap();
+        } else {
+            assert_eq!(std::fs::read(&path).unwrap(), data, "{} is outdated", name);
+        }
+
+        let line = golden_line(name, &data);
+        if update {
+            lines.push(line);
+            continue;
+        }
+        let expected = golden
+            .lines()
+            .find(|l| l.split(' ').next() == Some(name))
+            .unwrap_or_else(|| panic!("no golden output for {}", name));
+        let expected = expected.split(' ').collect::<Vec<_>>();
+        let actual = line.split(' ').collect::<Vec<_>>();
+        assert_eq!(actual.len(), expected.len(), "{}", name);
+        for (column, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+            if *expected != "-" {
+                assert_eq!(actual, expected, "{} column {}", name, column);
+            }
+        }
+    }
+    if update {
+        lines.insert(
+            0,
+            "# capture codec packets demux-hash samples pcm-hash".to_string(),
+        );
+        std::fs::write(golden_path, lines.join("\n") + "\n").unwrap();
+    }
+}
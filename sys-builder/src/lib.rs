@@ -19,7 +19,7 @@ impl ParseCallbacks for IgnoreMacros {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum LinkType {
     #[default]
     Dynamic,
@@ -64,55 +64,196 @@ impl Library {
     }
 }
 
-/// Find library header/library/pkgconfig location
+/// Find library header/library/pkgconfig location, and link to it.
+///
+/// `$<root_env>` is looked at first, then pkg-config, then vcpkg when targeting Windows. A
+/// library none of them knows is still linked, from the default search paths of the linker.
 pub fn find_lib(library: &mut Library) -> Result<()> {
     cargo_emit::rerun_if_env_changed!(library.root_env);
 
     if let Ok(prefix) = env::var(&library.root_env) {
-        let prefix = Path::new(&prefix);
-        library
-            .inc_paths
-            .push(PathBuf::from(&prefix).join("include"));
-        let mut link_paths = vec![];
-        for sub_dir in ["lib", "lib64"] {
-            let link_path = prefix.join(sub_dir);
-            link_paths.push(link_path);
+        return find_in_prefix(library, Path::new(&prefix));
+    }
+    if find_with_pkg_config(library)? {
+        return Ok(());
+    }
+    if env::var("CARGO_CFG_TARGET_OS").is_ok_and(|os| os == "windows") && find_with_vcpkg(library) {
+        return Ok(());
+    }
+
+    cargo_emit::rustc_link_lib!(library.name => library.link_type.to_string());
+    Ok(())
+}
+
+/// Whether the crate is built for a MSVC target, whose tools take options and name libraries
+/// differently (e.g. `/W4`, `g722_1.lib` rather than `-Wall`, `libg722_1.a`)
+pub fn is_msvc() -> bool {
+    env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|env| env == "msvc")
+}
+
+/// Use the library installed in `prefix`, given by `$<root_env>`
+fn find_in_prefix(library: &mut Library, prefix: &Path) -> Result<()> {
+    library
+        .inc_paths
+        .push(PathBuf::from(&prefix).join("include"));
+    let mut link_paths = vec![];
+    for sub_dir in ["lib", "lib64"] {
+        let link_path = prefix.join(sub_dir);
+        link_paths.push(link_path);
+    }
+
+    if !prefix.exists() || !prefix.is_dir() {
+        bail!(
+            "{} should point to a directory that exists.",
+            library.root_env
+        );
+    }
+
+    if link_paths.iter().all(|p| !p.exists()) {
+        bail!("no sub directory found in `${}`.", library.root_env);
+    }
+    if link_paths.iter().all(|p| !p.is_dir()) {
+        bail!("no sub directory found in `${}`.", library.root_env);
+    }
+
+    for p in link_paths {
+        if p.exists() && p.is_dir() {
+            cargo_emit::rustc_link_search!(p.to_string_lossy() => "native");
         }
+    }
 
-        if !prefix.exists() || !prefix.is_dir() {
+    // MSVC links its C++ runtime on its own, matching the CRT picked by crt-static
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let std_link = if is_msvc() {
+        None
+    } else if target_os == "macos" {
+        Some("c++")
+    } else {
+        Some("stdc++")
+    };
+    match std_link {
+        Some(std_link) if library.static_link_std_cpp => {
+            cargo_emit::rustc_link_lib!(std_link => "static:-bundle")
+        }
+        Some(std_link) => cargo_emit::rustc_link_lib!(std_link),
+        None => {}
+    }
+
+    cargo_emit::rustc_link_lib!(library.name => library.link_type.to_string());
+    Ok(())
+}
+
+/// Look the library up with pkg-config, returns false if pkg-config doesn't know it. Fails if
+/// the installed version is older than `library.version`.
+fn find_with_pkg_config(library: &mut Library) -> Result<bool> {
+    let mut config = pkg_config::Config::new();
+    config
+        .statik(matches!(library.link_type, LinkType::Static))
+        // the version is checked below, for a better error than "not found"
+        .cargo_metadata(false);
+    let Ok(found) = config.probe(&library.name) else {
+        return Ok(false);
+    };
+
+    if let Some(min) = &library.version {
+        if !version_at_least(&found.version, min) {
             bail!(
-                "{} should point to a directory that exists.",
-                library.root_env
+                "{} {} found by pkg-config, at least {} is required.",
+                library.name,
+                found.version,
+                min
             );
         }
+    }
 
-        if link_paths.iter().all(|p| !p.exists()) {
-            bail!("no sub directory found in `${}`.", library.root_env);
-        }
-        if link_paths.iter().all(|p| !p.is_dir()) {
-            bail!("no sub directory found in `${}`.", library.root_env);
+    for p in &found.link_paths {
+        cargo_emit::rustc_link_search!(p.to_string_lossy() => "native");
+    }
+    for lib in &found.libs {
+        match link_kind(library, lib) {
+            Some(kind) => cargo_emit::rustc_link_lib!(lib => kind.to_string()),
+            None => cargo_emit::rustc_link_lib!(lib),
         }
+    }
+    library.inc_paths.extend(found.include_paths);
+    library.link_paths.extend(found.link_paths);
+    Ok(true)
+}
 
-        for p in link_paths {
-            if p.exists() && p.is_dir() {
-                cargo_emit::rustc_link_search!(p.to_string_lossy() => "native");
-            }
-        }
+/// Kind of a library pkg-config lists for `library`: the requested one for the library itself,
+/// the linker's default for the libraries it depends on (e.g. libm or libstdc++), which a static
+/// build must not force to static
+fn link_kind(library: &Library, lib: &str) -> Option<LinkType> {
+    (lib == library.name).then_some(library.link_type)
+}
 
-        let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap();
-        let std_link = if target_os == "macos" {
-            "c++"
-        } else {
-            "stdc++"
-        };
-        if library.static_link_std_cpp {
-            cargo_emit::rustc_link_lib!(std_link => "static:-bundle");
-        } else {
-            cargo_emit::rustc_link_lib!(std_link);
-        }
+/// Look the library up in the vcpkg tree `$VCPKG_ROOT`, for the triplet in `$VCPKGRS_TRIPLET`
+/// or the default x64 one. Returns false if it isn't installed there.
+fn find_with_vcpkg(library: &mut Library) -> bool {
+    cargo_emit::rerun_if_env_changed!("VCPKG_ROOT");
+    cargo_emit::rerun_if_env_changed!("VCPKGRS_TRIPLET");
+    let Ok(root) = env::var("VCPKG_ROOT") else {
+        return false;
+    };
+    let triplet = env::var("VCPKGRS_TRIPLET").unwrap_or_else(|_| match library.link_type {
+        LinkType::Dynamic => "x64-windows".to_string(),
+        LinkType::Static => "x64-windows-static".to_string(),
+    });
 
-        cargo_emit::rustc_link_lib!(library.name => library.link_type.to_string());
+    let installed = Path::new(&root).join("installed").join(triplet);
+    let lib_dir = installed.join("lib");
+    if !lib_dir.join(format!("{}.lib", library.name)).is_file() {
+        return false;
     }
 
-    Ok(())
+    cargo_emit::rustc_link_search!(lib_dir.to_string_lossy() => "native");
+    cargo_emit::rustc_link_lib!(library.name => library.link_type.to_string());
+    library.inc_paths.push(installed.join("include"));
+    library.link_paths.push(lib_dir);
+    true
+}
+
+/// Compare dotted versions numerically, e.g. "0.1.10" is at least "0.1.9". Non numeric parts
+/// (e.g. a "-rc1" suffix) are ignored.
+fn version_at_least(version: &str, min: &str) -> bool {
+    let parse = |v: &str| {
+        v.split('.')
+            .map(|part| {
+                part.chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+                    .parse::<u64>()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (mut version, mut min) = (parse(version), parse(min));
+    let len = version.len().max(min.len());
+    version.resize(len, 0);
+    min.resize(len, 0);
+    version >= min
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_version_at_least() {
+        assert!(version_at_least("0.1.10", "0.1.9"));
+        assert!(version_at_least("0.1.6", "0.1.6"));
+        assert!(version_at_least("2", "1.9.9"));
+        assert!(version_at_least("1.0.0-rc1", "1"));
+        assert!(!version_at_least("0.1.5", "0.1.6"));
+        assert!(!version_at_least("0.1", "0.1.1"));
+    }
+
+    #[test]
+    fn test_link_kind() {
+        let mut library = Library::new("g722_1".to_string(), "LIBG7221_ROOT".to_string());
+        library.link_type = LinkType::Static;
+        assert_eq!(link_kind(&library, "g722_1"), Some(LinkType::Static));
+        assert_eq!(link_kind(&library, "m"), None);
+        assert_eq!(link_kind(&library, "stdc++"), None);
+    }
 }
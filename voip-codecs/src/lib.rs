@@ -0,0 +1,139 @@
+//! Codec types of the voice codecs decoded by this workspace, declared in one place with their
+//! names, media types and RTP clock rates.
+//!
+//! The decoder crates re-export their codec type from here, and the rtpdump reader maps the
+//! codecs it detects to them.
+
+use symphonia_core::codecs::{decl_codec_type, CodecType};
+
+mod validator;
+
+pub use validator::{hex, Validator};
+
+/// A voice codec decoded by a crate of this workspace
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoipCodec {
+    /// Short name of the decoder, e.g. "evs"
+    pub name: &'static str,
+    /// Name of the codec in its standard, for front-ends to show, e.g. "AMR-WB"
+    pub display_name: &'static str,
+    /// IANA media subtype of the RTP payload format, e.g. "AMR-WB" for audio/AMR-WB
+    pub media_subtype: &'static str,
+    /// Bytes the codec type is declared from, unique among [`CODECS`]
+    pub id: &'static [u8],
+    pub codec: CodecType,
+    /// Default RTP clock rate in Hz
+    pub clock_rate: u32,
+}
+
+impl VoipCodec {
+    const fn new(
+        name: &'static str,
+        display_name: &'static str,
+        media_subtype: &'static str,
+        id: &'static [u8],
+        clock_rate: u32,
+    ) -> Self {
+        Self {
+            name,
+            display_name,
+            media_subtype,
+            id,
+            codec: decl_codec_type(id),
+            clock_rate,
+        }
+    }
+
+    /// Media type of the RTP payload format, e.g. "audio/EVS"
+    pub fn mime_type(&self) -> String {
+        format!("audio/{}", self.media_subtype)
+    }
+}
+
+pub const AMR: VoipCodec = VoipCodec::new("amr", "AMR", "AMR", b"amr", 8000);
+pub const AMRWB: VoipCodec = VoipCodec::new("amrwb", "AMR-WB", "AMR-WB", b"amrwb", 16000);
+/// EVS uses a 16 kHz RTP clock whatever the audio bandwidth
+pub const EVS: VoipCodec = VoipCodec::new("evs", "EVS", "EVS", b"evs", 16000);
+/// 32 kHz for the 14 kHz audio of G.722.1 Annex C, see RFC 5577
+pub const G722_1: VoipCodec = VoipCodec::new("g722.1", "G.722.1", "G7221", b"g7221", 16000);
+
+pub const CODEC_TYPE_AMR: CodecType = AMR.codec;
+pub const CODEC_TYPE_AMRWB: CodecType = AMRWB.codec;
+pub const CODEC_TYPE_EVS: CodecType = EVS.codec;
+pub const CODEC_TYPE_G722_1: CodecType = G722_1.codec;
+
+/// Every codec of the workspace
+pub const CODECS: &[VoipCodec] = &[AMR, AMRWB, EVS, G722_1];
+
+// two codecs declared from the same bytes would share their codec type
+const _: () = assert!(unique_ids(CODECS), "codec types must be unique");
+
+const fn unique_ids(codecs: &[VoipCodec]) -> bool {
+    let mut i = 0;
+    while i < codecs.len() {
+        let mut j = i + 1;
+        while j < codecs.len() {
+            if same_bytes(codecs[i].id, codecs[j].id) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn same_bytes(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Codec of a decoder short name, matched case-insensitively
+pub fn by_name(name: &str) -> Option<&'static VoipCodec> {
+    CODECS.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+/// Codec of an IANA media subtype, e.g. the encoding name of a SDP rtpmap, matched
+/// case-insensitively
+pub fn by_media_subtype(subtype: &str) -> Option<&'static VoipCodec> {
+    CODECS
+        .iter()
+        .find(|c| c.media_subtype.eq_ignore_ascii_case(subtype))
+}
+
+/// Codec of a codec type
+pub fn by_codec_type(codec: CodecType) -> Option<&'static VoipCodec> {
+    CODECS.iter().find(|c| c.codec == codec)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(by_name("EVS"), Some(&EVS));
+        assert_eq!(by_name("g722.1").map(|c| c.codec), Some(CODEC_TYPE_G722_1));
+        assert_eq!(by_name("opus"), None);
+        assert_eq!(
+            by_codec_type(CODEC_TYPE_AMRWB).map(|c| c.clock_rate),
+            Some(16000)
+        );
+        assert_eq!(by_media_subtype("amr-wb"), Some(&AMRWB));
+        assert_eq!(by_media_subtype("AMRWB"), None);
+        assert_eq!(G722_1.mime_type(), "audio/G7221");
+        assert!(!unique_ids(&[AMR, EVS, AMR]));
+        for codec in CODECS {
+            assert_eq!(by_codec_type(codec.codec), Some(codec));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use log::{debug, warn};
+use symphonia_core::checksum::Md5;
+use symphonia_core::codecs::{FinalizeResult, VerificationCheck};
+use symphonia_core::io::Monitor;
+
+/// Hashes the decoded PCM so that `finalize` can check it against the MD5 provided in
+/// `CodecParameters::verification_check` when `DecoderOptions::verify` is set.
+#[derive(Default)]
+pub struct Validator {
+    enabled: bool,
+    md5: Md5,
+}
+
+impl Validator {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            md5: Md5::default(),
+        }
+    }
+
+    /// Hash decoded samples as little endian 16 bits PCM
+    pub fn update(&mut self, pcm: &[i16]) {
+        if self.enabled {
+            for sample in pcm {
+                self.md5.process_buf_bytes(&sample.to_le_bytes());
+            }
+        }
+    }
+
+    pub fn finalize(&self, check: Option<VerificationCheck>) -> FinalizeResult {
+        let mut result = FinalizeResult::default();
+        if !self.enabled {
+            return result;
+        }
+
+        let decoded = self.md5.md5();
+        debug!("verification: decoded md5 = {}", hex(&decoded));
+        match check {
+            Some(VerificationCheck::Md5(expected)) => {
+                debug!("verification: expected md5 = {}", hex(&expected));
+                result.verify_ok = Some(decoded == expected);
+            }
+            _ => warn!("verification requested but the expected md5 checksum was not provided"),
+        }
+        result
+    }
+}
+
+/// Lower case hexadecimal form of `bytes`
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validator() {
+        let pcm = [0i16, 1, -1, i16::MAX];
+        let mut expected = Md5::default();
+        expected.process_buf_bytes(&[0, 0, 1, 0, 0xff, 0xff, 0xff, 0x7f]);
+        let expected = expected.md5();
+
+        let mut validator = Validator::new(true);
+        validator.update(&pcm);
+        let check = Some(VerificationCheck::Md5(expected));
+        assert_eq!(validator.finalize(check).verify_ok, Some(true));
+        validator.update(&pcm);
+        assert_eq!(validator.finalize(check).verify_ok, Some(false));
+        assert_eq!(validator.finalize(None).verify_ok, None);
+
+        let mut validator = Validator::new(false);
+        validator.update(&pcm);
+        assert_eq!(validator.finalize(check).verify_ok, None);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex(&[]), "");
+        assert_eq!(hex(&[0x00, 0x0f, 0xa5, 0xff]), "000fa5ff");
+    }
+}
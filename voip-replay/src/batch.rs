@@ -0,0 +1,269 @@
+// Symphonia
+// Copyright (c) 2019-2022 The Project Symphonia Developers.
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Decoding of many rtpdump captures, summarized as JSON
+
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use log::{info, warn};
+use symphonia::core::errors::{Error, Result};
+use symphonia_format_rtpdump::{
+    decode_capture_with_options, AudioFingerprint, CaptureDecodeOptions, DecodedTrack,
+};
+
+use crate::exit;
+use crate::progress::ProgressBar;
+
+/// Outcome of one capture
+struct Job {
+    path: PathBuf,
+    result: Result<Vec<DecodedTrack>>,
+    /// Audio fingerprint of every track
+    fingerprints: Vec<AudioFingerprint>,
+}
+
+/// Captures of a directory, or the files whose name matches a pattern with `*` and `?`
+/// wildcards, sorted by path
+pub fn list_captures(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    let (dir, pattern) = if path.is_dir() {
+        (path, "*")
+    } else {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+        (dir.unwrap_or(Path::new(".")), name)
+    };
+
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let matched = name.to_str().is_some_and(|n| wildcard_match(pattern, n));
+        if matched && entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    // position after the last `*` in the pattern, and in the name when it was met
+    let (mut p, mut n, mut star) = (0, 0, None);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the `*` eat one more byte
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Decode every capture with `jobs` threads and `options`, then write the summary to `out`.
+/// Returns the exit code: partial success if some captures couldn't be decoded, the code of
+/// the error of the first one if none could.
+///
+/// With `progress`, a bar shows the bytes of the captures decoded so far.
+pub fn decode_batch(
+    paths: &[PathBuf],
+    jobs: usize,
+    options: &CaptureDecodeOptions,
+    progress: bool,
+    out: impl Write,
+) -> Result<i32> {
+    let next = AtomicUsize::new(0);
+    let done = Mutex::new(Vec::with_capacity(paths.len()));
+    let sizes = paths
+        .iter()
+        .map(|p| std::fs::metadata(p).map_or(0, |m| m.len()))
+        .collect::<Vec<_>>();
+    let bar = progress.then(|| {
+        let bar = ProgressBar::new("batch", Some(sizes.iter().sum()));
+        // files and bytes done
+        Mutex::new((bar, 0, 0))
+    });
+    std::thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, paths.len().max(1)) {
+            s.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(idx) else {
+                    break;
+                };
+                let result = std::fs::read(path)
+                    .map_err(Error::from)
+                    .and_then(|bytes| decode_capture_with_options(&bytes, options));
+                match &result {
+                    Ok(tracks) => info!("{}: {} tracks", path.display(), tracks.len()),
+                    Err(err) => warn!("{}: {}", path.display(), err),
+                }
+                let fingerprints = result
+                    .as_deref()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|t| AudioFingerprint::new(&t.pcm, t.sample_rate))
+                    .collect();
+                done.lock().unwrap().push((
+                    idx,
+                    Job {
+                        path: path.clone(),
+                        result,
+                        fingerprints,
+                    },
+                ));
+                if let Some(bar) = &bar {
+                    let (bar, files, bytes) = &mut *bar.lock().unwrap();
+                    *files += 1;
+                    *bytes += sizes[idx];
+                    bar.update(*bytes, &format!(" {}/{} files", files, paths.len()));
+                }
+            });
+        }
+    });
+    if let Some(bar) = bar {
+        let (mut bar, files, bytes) = bar.into_inner().unwrap();
+        bar.finish(bytes, &format!(" {}/{} files", files, paths.len()));
+    }
+
+    let mut done = done.into_inner().unwrap();
+    done.sort_by_key(|(idx, _)| *idx);
+    let jobs = done.into_iter().map(|(_, job)| job).collect::<Vec<_>>();
+    write_summary(&jobs, out)?;
+
+    let mut errors = jobs.iter().filter_map(|j| j.result.as_ref().err());
+    let failed = errors.clone().count();
+    if failed > 0 {
+        warn!("{} of {} captures failed", failed, jobs.len());
+    }
+    Ok(match errors.next() {
+        None => exit::SUCCESS,
+        Some(_) if failed < jobs.len() => exit::PARTIAL_SUCCESS,
+        Some(err) => exit::error_code(err),
+    })
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// One object per capture, in the order of the paths, followed by the totals. The
+/// fingerprint of a track is its [`AudioFingerprint`] in hexadecimal, to look for the same
+/// audio in other captures. Its frames of every frame type, their bitrate every second and
+/// the changes of frame type come from its [`FrameStats`](symphonia_format_rtpdump::FrameStats):
+///
+/// ```text
+/// {"files":[{"path":"a.rtp","tracks":[{"ssrc":4660,"codec":"evs","sample_rate":16000,
+///   "duration":12.34,"issues":0,"degraded":false,"fingerprint":"9a3c01f2...",
+///   "frame_types":{"4":617},"bitrate":[13200,13200,...],
+///   "modes":[{"offset":0,"frame_type":4,"bitrate":13200}]}],
+///   "error":null}],
+///  "total":1,"failed":0}
+/// ```
+fn write_summary(jobs: &[Job], mut out: impl Write) -> Result<()> {
+    let mut json = String::from("{\"files\":[");
+    for (i, job) in jobs.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let path = job.path.to_string_lossy();
+        let _ = write!(json, "\n{{\"path\":{},\"tracks\":[", json_string(&path));
+        let tracks = job.result.as_deref().unwrap_or_default();
+        for (j, (track, fingerprint)) in tracks.iter().zip(&job.fingerprints).enumerate() {
+            if j > 0 {
+                json.push(',');
+            }
+            let duration = track.pcm.len() as f64 / track.sample_rate.max(1) as f64;
+            let frame_types = track
+                .frame_stats
+                .frame_types
+                .iter()
+                .map(|(ft, count)| format!("\"{ft}\":{count}"))
+                .collect::<Vec<_>>();
+            let bitrate = track
+                .frame_stats
+                .bitrate
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>();
+            let modes = track
+                .frame_stats
+                .modes
+                .iter()
+                .map(|m| {
+                    let bitrate = m.bitrate.map_or("null".to_string(), |b| b.to_string());
+                    format!(
+                        "{{\"offset\":{},\"frame_type\":{},\"bitrate\":{}}}",
+                        m.offset, m.frame_type, bitrate
+                    )
+                })
+                .collect::<Vec<_>>();
+            let _ = write!(
+                json,
+                "{{\"ssrc\":{},\"codec\":{},\"sample_rate\":{},\"duration\":{:.3},\
+                 \"issues\":{},\"degraded\":{},\"fingerprint\":\"{}\",\
+                 \"frame_types\":{{{}}},\"bitrate\":[{}],\"modes\":[{}]}}",
+                track.ssrc,
+                json_string(track.codec),
+                track.sample_rate,
+                duration,
+                track.issues.len(),
+                track.degraded,
+                fingerprint,
+                frame_types.join(","),
+                bitrate.join(","),
+                modes.join(",")
+            );
+        }
+        let error = match &job.result {
+            Ok(_) => "null".to_string(),
+            Err(err) => json_string(&err.to_string()),
+        };
+        let _ = write!(json, "],\"error\":{error}}}");
+    }
+    let failed = jobs.iter().filter(|j| j.result.is_err()).count();
+    let _ = writeln!(json, "\n],\"total\":{},\"failed\":{}}}", jobs.len(), failed);
+    out.write_all(json.as_bytes())?;
+    Ok(())
+}
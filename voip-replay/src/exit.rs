@@ -0,0 +1,67 @@
+// Symphonia
+// Copyright (c) 2019-2022 The Project Symphonia Developers.
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Exit codes, so that scripts can tell why voip-replay failed
+
+use std::io::ErrorKind;
+
+use symphonia::core::errors::Error;
+use symphonia_format_rtpdump::NO_RTP_STREAM;
+
+use crate::batch::json_string;
+
+pub const SUCCESS: i32 = 0;
+/// Verification, conformance or comparison failed
+pub const CHECK_FAILED: i32 = 1;
+/// Some packets or captures couldn't be decoded, the others were
+pub const PARTIAL_SUCCESS: i32 = 2;
+pub const NO_RTP_FOUND: i32 = 3;
+/// Format or codec without a reader or decoder
+pub const UNSUPPORTED: i32 = 4;
+/// The input is malformed
+pub const INVALID_INPUT: i32 = 5;
+/// The input or output couldn't be read or written
+pub const IO_ERROR: i32 = 6;
+/// Any other error
+pub const FAILURE: i32 = 255;
+
+/// Exit code of an error
+pub fn error_code(err: &Error) -> i32 {
+    match err {
+        Error::Unsupported(NO_RTP_STREAM) => NO_RTP_FOUND,
+        Error::Unsupported(_) => UNSUPPORTED,
+        Error::DecodeError(_) => INVALID_INPUT,
+        // a truncated input
+        Error::IoError(err) if err.kind() == ErrorKind::UnexpectedEof => INVALID_INPUT,
+        Error::IoError(_) => IO_ERROR,
+        _ => FAILURE,
+    }
+}
+
+/// Name of an exit code, stable for scripts to match on
+pub fn reason(code: i32) -> &'static str {
+    match code {
+        SUCCESS => "success",
+        CHECK_FAILED => "check_failed",
+        PARTIAL_SUCCESS => "partial_success",
+        NO_RTP_FOUND => "no_rtp_found",
+        UNSUPPORTED => "unsupported",
+        INVALID_INPUT => "invalid_input",
+        IO_ERROR => "io_error",
+        _ => "failure",
+    }
+}
+
+/// One line JSON object describing a failure, for --json-errors
+pub fn json_failure(code: i32, message: &str) -> String {
+    format!(
+        "{{\"code\":{},\"reason\":\"{}\",\"message\":{}}}",
+        code,
+        reason(code),
+        json_string(message)
+    )
+}
@@ -13,11 +13,16 @@
 #![allow(clippy::needless_update)]
 
 use std::fs::File;
-use std::io::Write;
+use std::io::{Cursor, Read, Write};
 use std::path::Path;
+use std::time::Instant;
 
 use lazy_static::lazy_static;
-use symphonia::core::codecs::{CodecRegistry, DecoderOptions, FinalizeResult, CODEC_TYPE_NULL};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, CodecRegistry, Decoder, DecoderOptions, FinalizeResult,
+    VerificationCheck, CODEC_TYPE_NULL,
+};
 use symphonia::core::errors::{Error, Result};
 use symphonia::core::formats::{Cue, FormatOptions, FormatReader, SeekMode, SeekTo, Track};
 use symphonia::core::io::{MediaSource, MediaSourceStream, ReadOnlySource};
@@ -28,8 +33,20 @@ use symphonia::core::units::{Time, TimeBase};
 use clap::{Arg, ArgAction, ArgMatches};
 use log::{error, info, warn};
 use symphonia::default::{register_enabled_codecs, register_enabled_formats};
-
+use symphonia_bundle_evs::conformance::{read_pcm, PcmDiff};
+use symphonia_format_rtpdump::{
+    calibrate, export_frames, extract_text, g711_capture, packet_timeline, retimestamp,
+    serve_decoder, CaptureDecodeOptions, DecodedFrame, Effect, EffectChain, G711Law,
+    IsolatedDecoder, LiveDecoder, PcmComparison, Psnr, QualityScorer, RetimestampMode,
+    RetimestampOptions, RtpCodecRegistry, RtspClient, SegSnr, SPEED_RANGE,
+};
+
+mod batch;
+mod exit;
 mod output;
+mod progress;
+
+use progress::{ProgressSource, ReadProgress};
 
 #[cfg(not(target_os = "linux"))]
 mod resampler;
@@ -41,6 +58,11 @@ fn main() {
         .version("1.0")
         .author("Philip Deljanov <philip.deljanov@gmail.com>")
         .about("Play audio with Symphonia")
+        .after_help(
+            "Exit codes: 0 success, 1 verification or comparison failed, 2 partial success, \
+             3 no RTP found, 4 unsupported format or codec, 5 invalid input, 6 I/O error, \
+             255 other errors",
+        )
         .arg(
             Arg::new("seek")
                 .long("seek")
@@ -63,6 +85,54 @@ fn main() {
                 .help("Decode, but do not play the audio")
                 .conflicts_with_all(&["probe-only", "verify-only", "verify"]),
         )
+        .arg(
+            Arg::new("raw")
+                .long("raw")
+                .action(ArgAction::SetTrue)
+                .help("Decode the selected track and write it to stdout as little endian PCM, 16 bits unless --sample-format says otherwise")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "seek"]),
+        )
+        .arg(
+            Arg::new("pad-start")
+                .long("pad-start")
+                .action(ArgAction::SetTrue)
+                .requires("raw")
+                .help("Precede the audio written by --raw with silence since the start of the capture"),
+        )
+        .arg(
+            Arg::new("sample-format")
+                .long("sample-format")
+                .value_name("FORMAT")
+                .value_parser(["s16", "s24", "s32", "f32"])
+                .default_value("s16")
+                .requires("raw")
+                .help("Samples written by --raw: 16, 24 or 32 bits integers, or 32 bits IEEE floats from -1 to 1"),
+        )
+        .arg(
+            Arg::new("speed")
+                .long("speed")
+                .value_name("X")
+                .value_parser(parse_speed)
+                .requires("raw")
+                .help("Write the audio of --raw X times as fast (e.g. 1.5 or 2) without changing its pitch, from 0.25 to 4"),
+        )
+        .arg(
+            Arg::new("effects")
+                .long("effects")
+                .value_name("EFFECTS|FILE")
+                .value_parser(parse_effects)
+                .requires("raw")
+                .help("Post-process the audio of --raw with the effects applied in order, separated by commas, or read one per line from FILE: highpass[=HZ] against hum, deesser[=HZ], agc[=DBFS] and speed=X, e.g. 'highpass=120,agc'"),
+        )
+        .arg(
+            Arg::new("priming-frames")
+                .long("priming-frames")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0")
+                .help("Drop the first N decoded frames of every new decoder, which may carry start-up artifacts")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only"]),
+        )
         .arg(
             Arg::new("probe-only")
                 .long("probe-only")
@@ -77,6 +147,84 @@ fn main() {
                 .help("Verify the decoded audio is valid, but do not play the audio")
                 .conflicts_with_all(&["verify"]),
         )
+        .arg(
+            Arg::new("md5")
+                .long("md5")
+                .value_name("HEX")
+                .value_parser(parse_md5)
+                .requires("verify-only")
+                .help("Expected MD5 of the decoded 16 bits little endian PCM, checked by --verify-only"),
+        )
+        .arg(
+            Arg::new("conformance")
+                .long("conformance")
+                .value_name("REFERENCE")
+                .help("Decode the selected track and compare it with the 16 bits little endian PCM of REFERENCE, e.g. the output of a 3GPP conformance test vector")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek"]),
+        )
+        .arg(
+            Arg::new("compare")
+                .long("compare")
+                .value_name("OTHER")
+                .help("Decode the selected track of the input and of OTHER, e.g. the same capture re-encoded or with another codec mapping, and report where their audio differs")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance"]),
+        )
+        .arg(
+            Arg::new("score")
+                .long("score")
+                .value_name("SCORER")
+                .value_parser(["segsnr", "psnr"])
+                .default_value("segsnr")
+                .requires("compare")
+                .help("Objective quality score of the differing segments reported by --compare, OTHER being scored against the input"),
+        )
+        .arg(
+            Arg::new("tolerance")
+                .long("tolerance")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u16))
+                .default_value("0")
+                .requires("conformance")
+                .help("Largest difference between decoded and reference samples accepted by --conformance, 0 for bit-exact"),
+        )
+        .arg(
+            Arg::new("text")
+                .long("text")
+                .value_name("PT")
+                .value_parser(clap::value_parser!(u8).range(0..128))
+                .help("Write the real-time text (RFC 4103) of the rtpdump input carried by payload type PT to stdout, one line per block with its offset in seconds")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance", "compare"]),
+        )
+        .arg(
+            Arg::new("calibrate")
+                .long("calibrate")
+                .value_name("CODEC/RATE")
+                .value_parser(parse_codec_rate)
+                .help("Write the codec detection features of the rtpdump input, known to carry CODEC at RATE Hz (e.g. evs/16000), to stdout as YAML rows of a feature file")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance", "compare", "text", "batch", "packetize"]),
+        )
+        .arg(
+            Arg::new("export-frames")
+                .long("export-frames")
+                .value_name("DIR")
+                .help("Write the frames every track of the rtpdump input hands to its decoder into DIR, for replay into a reference decoder: the frames of a track in a file (AMR and EVS in their storage format) and their sequence number, RTP timestamp, arrival, frame type and size in a CSV file")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance", "compare", "text", "calibrate", "batch", "packetize"]),
+        )
+        .arg(
+            Arg::new("timeline")
+                .long("timeline")
+                .action(ArgAction::SetTrue)
+                .help("Write when the packets of every SSRC of the rtpdump input arrived, the packets lost and the talkspurts to stdout as JSON, without decoding them")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance", "compare", "text", "calibrate", "export-frames", "batch", "packetize"]),
+        )
+        .arg(
+            Arg::new("red")
+                .long("red")
+                .value_name("PT")
+                .value_parser(clap::value_parser!(u8).range(0..128))
+                .requires("text")
+                .help("Payload type of the real-time text sent with redundancy"),
+        )
         .arg(
             Arg::new("verify")
                 .long("verify")
@@ -88,7 +236,7 @@ fn main() {
             Arg::new("no-progress")
                 .long("no-progress")
                 .action(ArgAction::SetTrue)
-                .help("Do not display playback progress"),
+                .help("Do not display playback progress, nor the progress bars of long decodes drawn on stderr when it is a terminal"),
         )
         .arg(
             Arg::new("no-gapless")
@@ -96,10 +244,93 @@ fn main() {
                 .action(ArgAction::SetTrue)
                 .help("Disable gapless decoding and playback"),
         )
+        .arg(
+            Arg::new("packetize")
+                .long("packetize")
+                .value_name("LAW")
+                .value_parser(["pcma", "pcmu"])
+                .help("Encode the 8 kHz mono INPUT, e.g. a WAV file, to G.711 and write it to stdout as a rtpdump capture of a RTP stream")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance", "compare", "text", "batch"]),
+        )
+        .arg(
+            Arg::new("ptime")
+                .long("ptime")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u32).range(1..=200))
+                .default_value("20")
+                .help("Milliseconds of audio per packet of --packetize and --retimestamp"),
+        )
+        .arg(
+            Arg::new("retimestamp")
+                .long("retimestamp")
+                .value_name("FROM")
+                .value_parser(["seq", "arrival"])
+                .help("Regenerate the RTP timestamps of the input before reading it, from the sequence numbers or the arrival times, for senders with constant or wrong timestamps"),
+        )
+        .arg(
+            Arg::new("clock-rate")
+                .long("clock-rate")
+                .value_name("HZ")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .requires("retimestamp")
+                .help("RTP clock rate of the timestamps regenerated by --retimestamp, inferred from the input by default"),
+        )
+        .arg(
+            Arg::new("isolate")
+                .long("isolate")
+                .action(ArgAction::SetTrue)
+                .help("Run the EVS, AMR and G.722.1 decoders in worker processes, so that a crash of their C code only fails the packets being decoded"),
+        )
+        .arg(
+            Arg::new("decoder-worker")
+                .long("decoder-worker")
+                .action(ArgAction::SetTrue)
+                .hide(true)
+                .help("Serve a decoder over stdin and stdout, the worker of --isolate"),
+        )
+        .arg(
+            Arg::new("batch")
+                .long("batch")
+                .action(ArgAction::SetTrue)
+                .help("Decode every rtpdump capture of the INPUT directory, or matching the INPUT pattern (e.g. 'calls/*.rtp'), and write a JSON summary to stdout")
+                .conflicts_with_all(["probe-only", "decode-only", "verify-only", "verify", "raw", "seek", "conformance", "compare", "text"]),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("batch")
+                .help("Captures decoded at once by --batch, the number of CPUs by default"),
+        )
+        .arg(
+            Arg::new("silence-chunk")
+                .long("silence-chunk")
+                .value_name("FRAMES")
+                .value_parser(clap::value_parser!(usize))
+                .requires("batch")
+                .help("With --batch, render long runs of lost or DTX frames as silence FRAMES frames at a time instead of decoding every frame"),
+        )
+        .arg(
+            Arg::new("json-errors")
+                .long("json-errors")
+                .action(ArgAction::SetTrue)
+                .help("On failure, also write a JSON object with the exit code, its reason and a message as the last line of stderr"),
+        )
+        .arg(
+            Arg::new("list-codecs")
+                .long("list-codecs")
+                .action(ArgAction::SetTrue)
+                .help("List the codecs of RTP captures and whether they can be decoded, then exit"),
+        )
         .arg(
             Arg::new("INPUT")
-                .help("The input file path, or - to use standard input")
-                .required(true)
+                .help(
+                    "The input file path, - to use standard input, or a rtsp:// URL to decode \
+                     a live stream to stdout",
+                )
+                .required_unless_present_any(["list-codecs", "decoder-worker"])
                 .index(1),
         )
         .get_matches();
@@ -111,6 +342,30 @@ fn main() {
     registry.register_all::<symphonia_bundle_amr::AmrwbDecoder>();
     registry.register_all::<symphonia_codec_g7221::Decoder>();
 
+    if args.get_flag("decoder-worker") {
+        let code = match serve_decoder(&c_decoders(), std::io::stdin(), std::io::stdout()) {
+            Ok(()) => 0,
+            Err(err) => {
+                error!("decoder worker: {}", err.to_string().to_lowercase());
+                exit::error_code(&err)
+            }
+        };
+        std::process::exit(code)
+    }
+    if args.get_flag("isolate") {
+        for desc in c_decoders() {
+            registry.register(&CodecDescriptor {
+                inst_func: make_isolated,
+                ..desc
+            });
+        }
+    }
+
+    if args.get_flag("list-codecs") {
+        print_codecs(&registry);
+        return;
+    }
+
     let mut probe = Probe::default();
     register_enabled_formats(&mut probe);
     probe.register_all::<symphonia_bundle_evs::format::EvsReader>();
@@ -118,26 +373,99 @@ fn main() {
     probe.register_all::<symphonia_bundle_amr::AmrwbReader>();
     probe.register_all::<symphonia_format_rtpdump::RtpdumpReader>();
 
-    // For any error, return an exit code -1. Otherwise return the exit code provided.
-    let code = match run(&args, registry, probe) {
-        Ok(code) => code,
+    // For any error, return the exit code of its kind. Otherwise return the exit code provided.
+    let (code, message) = match run(&args, registry, probe) {
+        Ok(code) => (code, exit::reason(code).replace('_', " ")),
         Err(err) => {
             error!("{}", err.to_string().to_lowercase());
-            -1
+            (exit::error_code(&err), err.to_string())
         }
     };
+    if code != exit::SUCCESS && args.get_flag("json-errors") {
+        eprintln!("{}", exit::json_failure(code, &message));
+    }
 
     std::process::exit(code)
 }
 
+/// Decoders wrapping C libraries, run by worker processes with --isolate
+fn c_decoders() -> Vec<CodecDescriptor> {
+    [
+        symphonia_bundle_evs::dec::Decoder::supported_codecs(),
+        symphonia_bundle_amr::AmrDecoder::supported_codecs(),
+        symphonia_bundle_amr::AmrwbDecoder::supported_codecs(),
+        symphonia_codec_g7221::Decoder::supported_codecs(),
+    ]
+    .concat()
+}
+
+fn make_isolated(params: &CodecParameters, _options: &DecoderOptions) -> Result<Box<dyn Decoder>> {
+    let desc = c_decoders()
+        .into_iter()
+        .find(|d| d.codec == params.codec)
+        .ok_or(Error::Unsupported("Not a C-backed decoder"))?;
+    let exe = std::env::current_exe()?;
+    let decoder = IsolatedDecoder::spawn(exe, &["--decoder-worker"], desc.short_name, params)?;
+    Ok(Box::new(decoder))
+}
+
 fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32> {
     let path_str: &String = args.get_one("INPUT").unwrap();
 
+    if path_str.starts_with("rtsp://") {
+        return decode_rtsp(path_str);
+    }
+
+    if args.get_flag("batch") {
+        let paths = batch::list_captures(path_str)?;
+        let jobs = match args.get_one::<usize>("jobs") {
+            Some(&jobs) => jobs,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        let options = CaptureDecodeOptions {
+            silence_chunk: args.get_one::<usize>("silence-chunk").copied().unwrap_or(0),
+            ..Default::default()
+        };
+        return batch::decode_batch(
+            &paths,
+            jobs,
+            &options,
+            progress::enabled(args.get_flag("no-progress")),
+            std::io::stdout().lock(),
+        );
+    }
+
+    if let Some(&pt) = args.get_one::<u8>("text") {
+        let capture = read_input(path_str)?;
+        return write_text(&capture, pt, args.get_one::<u8>("red").copied());
+    }
+
+    if let Some((codec, rate)) = args.get_one::<(String, u32)>("calibrate") {
+        let yaml = calibrate(&read_input(path_str)?)?.to_yaml(codec, *rate);
+        std::io::stdout().write_all(yaml.as_bytes())?;
+        return Ok(exit::SUCCESS);
+    }
+
+    if let Some(dir) = args.get_one::<String>("export-frames") {
+        return write_frames(&read_input(path_str)?, Path::new(dir));
+    }
+
+    if args.get_flag("timeline") {
+        let mut stdout = std::io::stdout().lock();
+        packet_timeline(&read_input(path_str)?)?.write_json(&mut stdout)?;
+        stdout.flush()?;
+        return Ok(exit::SUCCESS);
+    }
+
     // Create a hint to help the format registry guess what format reader is appropriate.
     let mut hint = Hint::new();
 
-    // If the path string is '-' then read from standard input.
-    let source = if path_str == "-" {
+    let source = if let Some(options) = retimestamp_options(args) {
+        // the whole capture is repaired before the demuxer reads it
+        let capture = retimestamp(&read_input(path_str)?, &options)?;
+        Box::new(Cursor::new(capture)) as Box<dyn MediaSource>
+    } else if path_str == "-" {
+        // If the path string is '-' then read from standard input.
         Box::new(ReadOnlySource::new(std::io::stdin())) as Box<dyn MediaSource>
     } else {
         // Othwerise, get a Path from the path string.
@@ -150,7 +478,23 @@ fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32>
             }
         }
 
-        Box::new(File::open(path)?)
+        open_file(path)?
+    };
+
+    // Long decodes not played show how far the input was read.
+    let no_progress = args.get_flag("no-progress");
+    let long_decode = ["decode-only", "verify-only", "raw"]
+        .iter()
+        .any(|mode| args.get_flag(mode));
+    let (source, mut read_progress) = if long_decode && progress::enabled(no_progress) {
+        let source = ProgressSource::new(source);
+        let name = Path::new(path_str)
+            .file_name()
+            .map_or("-".into(), |n| n.to_string_lossy());
+        let progress = ReadProgress::new(name, &source);
+        (Box::new(source) as Box<dyn MediaSource>, Some(progress))
+    } else {
+        (source, None)
     };
 
     // Create the media source stream using the boxed media source from above.
@@ -166,12 +510,12 @@ fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32>
     let metadata_opts: MetadataOptions = Default::default();
 
     // Get the value of the track option, if provided.
-    let track = match args.get_one::<&str>("track") {
+    let track = match args.get_one::<String>("track") {
         Some(track_str) => track_str.parse::<usize>().ok(),
         _ => None,
     };
 
-    let no_progress = args.get_flag("no-progress");
+    let priming_frames = *args.get_one::<usize>("priming-frames").unwrap();
 
     // Probe the media source stream for metadata and get the format reader.
     match probe.format(&hint, mss, &format_opts, &metadata_opts) {
@@ -185,6 +529,8 @@ fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32>
                         verify: true,
                         ..Default::default()
                     },
+                    args.get_one::<[u8; 16]>("md5").copied(),
+                    read_progress.as_mut(),
                 )
             } else if args.get_flag("decode-only") {
                 // Decode-only mode decodes the audio, but does not play or verify it.
@@ -195,6 +541,58 @@ fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32>
                         verify: false,
                         ..Default::default()
                     },
+                    None,
+                    read_progress.as_mut(),
+                )
+            } else if let Some(reference) = args.get_one::<String>("conformance") {
+                // Conformance mode compares the decoded audio with a reference output.
+                check_conformance(
+                    &registry,
+                    probed.format,
+                    track,
+                    Path::new(reference),
+                    *args.get_one::<u16>("tolerance").unwrap(),
+                )
+            } else if let Some(other) = args.get_one::<String>("compare") {
+                // Compare mode decodes a second input and reports where both differ.
+                let mut other_hint = Hint::new();
+                if let Some(extension) = Path::new(other).extension().and_then(|e| e.to_str()) {
+                    other_hint.with_extension(extension);
+                }
+                let mss = MediaSourceStream::new(open_file(Path::new(other))?, Default::default());
+                let other = probe.format(&other_hint, mss, &format_opts, &metadata_opts)?;
+                let scorer: &dyn QualityScorer =
+                    match args.get_one::<String>("score").unwrap().as_str() {
+                        "psnr" => &Psnr,
+                        _ => &SegSnr,
+                    };
+                compare_decodes(&registry, probed.format, other.format, track, scorer)
+            } else if args.get_flag("raw") {
+                // Raw mode writes the decoded audio to stdout, e.g. to pipe it into sox.
+                decode_to_stdout(
+                    &registry,
+                    probed.format,
+                    track,
+                    RawOptions {
+                        pad_start: args.get_flag("pad-start"),
+                        priming_frames,
+                        effects: effects(args),
+                        format: sample_format(args),
+                    },
+                    read_progress.as_mut(),
+                )
+            } else if let Some(law) = args.get_one::<String>("packetize") {
+                // Packetize mode synthesizes a G.711 RTP stream of the audio.
+                let law = match law.as_str() {
+                    "pcma" => G711Law::ALaw,
+                    _ => G711Law::MuLaw,
+                };
+                packetize(
+                    &registry,
+                    probed.format,
+                    track,
+                    law,
+                    *args.get_one::<u32>("ptime").unwrap(),
                 )
             } else if args.get_flag("probe-only") {
                 // Probe-only mode only prints information about the format, tracks, metadata, etc.
@@ -223,6 +621,7 @@ fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32>
                     seek_time,
                     &decode_opts,
                     no_progress,
+                    priming_frames,
                 )
             }
         }
@@ -234,25 +633,172 @@ fn run(args: &ArgMatches, registry: CodecRegistry, probe: Probe) -> Result<i32>
     }
 }
 
+#[cfg(all(feature = "mmap", unix))]
+fn open_file(path: &Path) -> Result<Box<dyn MediaSource>> {
+    let file = File::open(path)?;
+    Ok(Box::new(symphonia_format_rtpdump::MmapSource::open(&file)?))
+}
+
+#[cfg(not(all(feature = "mmap", unix)))]
+fn open_file(path: &Path) -> Result<Box<dyn MediaSource>> {
+    Ok(Box::new(File::open(path)?))
+}
+
+fn parse_codec_rate(s: &str) -> std::result::Result<(String, u32), String> {
+    let (codec, rate) = s.split_once('/').ok_or("expected CODEC/RATE")?;
+    let rate = rate
+        .parse()
+        .map_err(|e: std::num::ParseIntError| e.to_string())?;
+    if codec.is_empty() || rate == 0 {
+        return Err("expected a codec name and a sample rate".to_string());
+    }
+    Ok((codec.to_string(), rate))
+}
+
+/// Effects of a file, or of the argument itself
+fn parse_effects(s: &str) -> std::result::Result<EffectChain, String> {
+    let spec = match Path::new(s).is_file() {
+        true => std::fs::read_to_string(s).map_err(|e| e.to_string())?,
+        false => s.to_string(),
+    };
+    EffectChain::parse(&spec).map_err(|e| e.to_string())
+}
+
+/// Post-processing of --effects followed by the one of --speed
+fn effects(args: &ArgMatches) -> EffectChain {
+    let mut effects = args
+        .get_one::<EffectChain>("effects")
+        .cloned()
+        .unwrap_or_default();
+    if let Some(&speed) = args.get_one::<f64>("speed") {
+        effects.0.push(Effect::Speed(speed));
+    }
+    effects
+}
+
+/// Little endian samples raw mode writes
+#[derive(Clone, Copy, Debug)]
+enum SampleFormat {
+    S16,
+    S24,
+    S32,
+    F32,
+}
+
+impl SampleFormat {
+    fn bytes(self) -> usize {
+        match self {
+            SampleFormat::S16 => 2,
+            SampleFormat::S24 => 3,
+            SampleFormat::S32 | SampleFormat::F32 => 4,
+        }
+    }
+
+    /// Append `samples` in this format to `bytes`
+    fn extend(self, samples: &[i16], bytes: &mut Vec<u8>) {
+        bytes.reserve(samples.len() * self.bytes());
+        for &s in samples {
+            match self {
+                SampleFormat::S16 => bytes.extend(s.to_le_bytes()),
+                SampleFormat::S24 => bytes.extend(&((s as i32) << 8).to_le_bytes()[..3]),
+                SampleFormat::S32 => bytes.extend(((s as i32) << 16).to_le_bytes()),
+                SampleFormat::F32 => bytes.extend((s as f32 / 32768.0).to_le_bytes()),
+            }
+        }
+    }
+}
+
+fn sample_format(args: &ArgMatches) -> SampleFormat {
+    match args.get_one::<String>("sample-format").map(String::as_str) {
+        Some("s24") => SampleFormat::S24,
+        Some("s32") => SampleFormat::S32,
+        Some("f32") => SampleFormat::F32,
+        _ => SampleFormat::S16,
+    }
+}
+
+/// How --retimestamp repairs the input, `None` to read it as is
+fn retimestamp_options(args: &ArgMatches) -> Option<RetimestampOptions> {
+    let mode = match args.get_one::<String>("retimestamp")?.as_str() {
+        "arrival" => RetimestampMode::Arrival,
+        _ => RetimestampMode::Sequence,
+    };
+    Some(RetimestampOptions {
+        mode,
+        ptime: *args.get_one::<u32>("ptime").unwrap(),
+        clock_rate: args.get_one::<u32>("clock-rate").copied(),
+    })
+}
+
+fn parse_speed(s: &str) -> std::result::Result<f64, String> {
+    let speed = s
+        .parse::<f64>()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(SPEED_RANGE.0..=SPEED_RANGE.1).contains(&speed) {
+        return Err(format!(
+            "expected a speed from {} to {}",
+            SPEED_RANGE.0, SPEED_RANGE.1
+        ));
+    }
+    Ok(speed)
+}
+
+/// The whole input, `-` being standard input
+fn read_input(path_str: &str) -> Result<Vec<u8>> {
+    if path_str == "-" {
+        let mut capture = vec![];
+        std::io::stdin().read_to_end(&mut capture)?;
+        Ok(capture)
+    } else {
+        Ok(std::fs::read(path_str)?)
+    }
+}
+
+fn parse_md5(s: &str) -> std::result::Result<[u8; 16], String> {
+    if s.len() != 32 || !s.is_ascii() {
+        return Err("expected 32 hexadecimal digits".to_string());
+    }
+    let mut md5 = [0; 16];
+    for (i, b) in md5.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|e| e.to_string())?;
+    }
+    Ok(md5)
+}
+
 fn decode_only(
     registry: &CodecRegistry,
     mut reader: Box<dyn FormatReader>,
     decode_opts: &DecoderOptions,
+    expected_md5: Option<[u8; 16]>,
+    mut progress: Option<&mut ReadProgress>,
 ) -> Result<i32> {
     // Get the default track.
     // TODO: Allow track selection.
     let track = reader.default_track().unwrap();
     let track_id = track.id;
 
+    // Containers of voice codecs carry no checksum, the expected one comes from the user.
+    let mut codec_params = track.codec_params.clone();
+    if let Some(md5) = expected_md5 {
+        codec_params.with_verification_code(VerificationCheck::Md5(md5));
+    }
+
     // Create a decoder for the track.
-    let mut decoder = registry.make(&track.codec_params, decode_opts)?;
+    let mut decoder = registry.make(&codec_params, decode_opts)?;
+    if let Some(progress) = progress.as_mut() {
+        progress.set_track(track_id);
+    }
 
     // Decode all packets, ignoring all decode errors.
+    let mut decode_errors = 0;
     let result = loop {
         let packet = match reader.next_packet() {
             Ok(packet) => packet,
             Err(err) => break Err(err),
         };
+        if let Some(progress) = progress.as_mut() {
+            progress.update();
+        }
 
         // If the packet does not belong to the selected track, skip over it.
         if packet.track_id() != track_id {
@@ -262,22 +808,422 @@ fn decode_only(
         // Decode the packet into audio samples.
         match decoder.decode(&packet) {
             Ok(_decoded) => continue,
-            Err(Error::DecodeError(err)) => warn!("decode error: {}", err),
+            Err(Error::DecodeError(err)) => {
+                warn!("decode error: {}", err);
+                decode_errors += 1;
+            }
             Err(err) => break Err(err),
         }
     };
 
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+
     // Return if a fatal error occured.
     ignore_end_of_stream_error(result)?;
 
     // Finalize the decoder and return the verification result if it's been enabled.
-    do_verification(decoder.finalize())
+    match do_verification(decoder.finalize())? {
+        exit::SUCCESS if decode_errors > 0 => Ok(exit::PARTIAL_SUCCESS),
+        code => Ok(code),
+    }
+}
+
+struct RawOptions {
+    /// Precede the audio with silence since the start of the capture
+    pad_start: bool,
+    /// Decoded frames dropped after the decoder is created
+    priming_frames: usize,
+    effects: EffectChain,
+    format: SampleFormat,
+}
+
+fn decode_to_stdout(
+    registry: &CodecRegistry,
+    mut reader: Box<dyn FormatReader>,
+    track_num: Option<usize>,
+    options: RawOptions,
+    mut progress: Option<&mut ReadProgress>,
+) -> Result<i32> {
+    let RawOptions {
+        mut pad_start,
+        mut priming_frames,
+        effects,
+        format,
+    } = options;
+    let track = track_num
+        .and_then(|t| reader.tracks().get(t))
+        .or_else(|| first_supported_track(reader.tracks()));
+    let Some(track) = track else {
+        return Ok(0);
+    };
+    let track_id = track.id;
+    let channels = track.codec_params.channels.map_or(1, |c| c.count());
+    let mut effects = match effects.is_empty() {
+        true => None,
+        false if channels != 1 => return Err(Error::Unsupported("effects take mono audio")),
+        false => {
+            let sample_rate = track.codec_params.sample_rate.unwrap_or(8000);
+            Some((effects.speed(), effects.processor(sample_rate)))
+        }
+    };
+    let mut processed = vec![];
+
+    let mut decoder = registry.make(&track.codec_params, &Default::default())?;
+    let mut stdout = std::io::stdout().lock();
+    let mut sample_buf = None;
+    let mut bytes = vec![];
+    let mut decode_errors = 0;
+    if let Some(progress) = progress.as_mut() {
+        progress.set_track(track_id);
+    }
+
+    let result = loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(err) => break Err(err),
+        };
+        if let Some(progress) = progress.as_mut() {
+            progress.update();
+        }
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        // silence from the capture start up to the first packet of the track
+        if std::mem::take(&mut pad_start) {
+            let speed = effects.as_ref().map_or(1.0, |(speed, _)| *speed);
+            let samples = (packet.ts() as f64 / speed) as usize;
+            let silence = vec![0; samples * channels * format.bytes()];
+            if let Err(err) = stdout.write_all(&silence) {
+                if err.kind() == std::io::ErrorKind::BrokenPipe {
+                    return Ok(0);
+                }
+                break Err(err.into());
+            }
+        }
+
+        match decoder.decode(&packet) {
+            Ok(_) if priming_frames > 0 => priming_frames -= 1,
+            Ok(decoded) => {
+                let buf = sample_buf.get_or_insert_with(|| {
+                    SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec())
+                });
+                // decoders may return more samples than the first buffer could hold
+                if buf.capacity() < decoded.capacity() * decoded.spec().channels.count() {
+                    *buf = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+                }
+                buf.copy_interleaved_ref(decoded);
+                let samples = match effects.as_mut() {
+                    Some((_, effects)) => {
+                        processed.clear();
+                        effects.process(buf.samples(), &mut processed);
+                        &processed
+                    }
+                    None => buf.samples(),
+                };
+
+                bytes.clear();
+                format.extend(samples, &mut bytes);
+                if let Err(err) = stdout.write_all(&bytes) {
+                    // the reading end of the pipe went away, nothing left to do
+                    if err.kind() == std::io::ErrorKind::BrokenPipe {
+                        return Ok(0);
+                    }
+                    break Err(err.into());
+                }
+            }
+            Err(Error::DecodeError(err)) => {
+                warn!("decode error: {}", err);
+                decode_errors += 1;
+            }
+            Err(err) => break Err(err),
+        }
+    };
+
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+
+    ignore_end_of_stream_error(result)?;
+    if let Some((_, effects)) = effects.as_mut() {
+        processed.clear();
+        effects.flush(&mut processed);
+        bytes.clear();
+        format.extend(&processed, &mut bytes);
+        match stdout.write_all(&bytes) {
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => return Ok(0),
+            result => result?,
+        }
+    }
+    stdout.flush()?;
+    Ok(match decode_errors {
+        0 => exit::SUCCESS,
+        _ => exit::PARTIAL_SUCCESS,
+    })
+}
+
+/// Write a rtpdump capture of a G.711 stream carrying the audio of a track to stdout
+fn packetize(
+    registry: &CodecRegistry,
+    reader: Box<dyn FormatReader>,
+    track_num: Option<usize>,
+    law: G711Law,
+    ptime: u32,
+) -> Result<i32> {
+    let track = track_num
+        .and_then(|t| reader.tracks().get(t))
+        .or_else(|| first_supported_track(reader.tracks()));
+    if track
+        .and_then(|t| t.codec_params.channels)
+        .map_or(1, |c| c.count())
+        != 1
+    {
+        return Err(Error::Unsupported("packetizing takes mono audio"));
+    }
+    let Some((pcm, sample_rate)) = decode_track(registry, reader, track_num, true)? else {
+        return Ok(exit::SUCCESS);
+    };
+    if sample_rate != 8000 {
+        return Err(Error::Unsupported("G.711 takes 8 kHz audio"));
+    }
+
+    let capture = g711_capture(&pcm, law, 0x5eed_0711, ptime)?;
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&capture)?;
+    stdout.flush()?;
+    Ok(exit::SUCCESS)
+}
+
+/// Write the real-time text of every stream of a capture to stdout
+fn write_text(capture: &[u8], t140_pt: u8, red_pt: Option<u8>) -> Result<i32> {
+    let mut stdout = std::io::stdout().lock();
+    for transcript in extract_text(capture, t140_pt, red_pt)? {
+        writeln!(stdout, "# ssrc {:#010x}", transcript.ssrc)?;
+        transcript.write_to(&mut stdout)?;
+    }
+    stdout.flush()?;
+    Ok(0)
+}
+
+/// Write the frames and a CSV listing them of every track of a capture into `dir`, named after
+/// the track and its SSRC
+fn write_frames(capture: &[u8], dir: &Path) -> Result<i32> {
+    std::fs::create_dir_all(dir)?;
+    for (id, track) in export_frames(capture)?.iter().enumerate() {
+        // storage formats of RFC 4867 section 5 and 3GPP TS 26.445 Annex A.2.6
+        let (ext, header): (_, &[u8]) = match track.codec {
+            "amr" => ("amr", b"#!AMR\n"),
+            "amrwb" => ("awb", b"#!AMR-WB\n"),
+            "evs" => ("evs", b"#!EVS_MC1.0\n\0\0\0\x01"),
+            _ => ("bin", b""),
+        };
+        let name = format!("track{}_{:08x}", id, track.ssrc);
+        let mut frames = std::io::BufWriter::new(File::create(dir.join(format!("{name}.{ext}")))?);
+        let mut csv = std::io::BufWriter::new(File::create(dir.join(format!("{name}.csv")))?);
+        frames.write_all(header)?;
+        writeln!(csv, "seq,ts,arrival_ms,frame_type,size")?;
+        for frame in &track.frames {
+            frames.write_all(&frame.data)?;
+            let frame_type = frame.frame_type.map(|t| t.to_string()).unwrap_or_default();
+            writeln!(
+                csv,
+                "{},{},{},{},{}",
+                frame.seq,
+                frame.ts,
+                frame.offset,
+                frame_type,
+                frame.data.len()
+            )?;
+        }
+        frames.flush()?;
+        csv.flush()?;
+        info!("{}: {} {} frames", name, track.frames.len(), track.codec);
+    }
+    Ok(exit::SUCCESS)
+}
+
+/// Decode a RTSP stream as it arrives, writing the audio of its first RTP stream to stdout like
+/// raw mode
+fn decode_rtsp(url: &str) -> Result<i32> {
+    let mut client = RtspClient::connect(url)?;
+    client.describe()?;
+    if client.setup()? == 0 {
+        info!("the stream has no audio");
+        return Ok(0);
+    }
+    client.play()?;
+
+    let start = Instant::now();
+    let mut live = LiveDecoder::new();
+    let mut stdout = std::io::stdout().lock();
+    let mut frames: Vec<DecodedFrame> = vec![];
+    let mut ssrc = None;
+    loop {
+        let pkt = client.next_rtp()?;
+        match &pkt {
+            Some(pkt) => live.push(pkt, start.elapsed().as_millis() as u32, |f| frames.push(f)),
+            None => live.flush(|f| frames.push(f)),
+        }
+
+        for frame in frames.drain(..) {
+            if *ssrc.get_or_insert(frame.ssrc) != frame.ssrc {
+                continue;
+            }
+            let bytes = frame
+                .pcm
+                .iter()
+                .flat_map(|s| s.to_le_bytes())
+                .collect::<Vec<_>>();
+            if let Err(err) = stdout.write_all(&bytes) {
+                // the reading end of the pipe went away, nothing left to do
+                if err.kind() == std::io::ErrorKind::BrokenPipe {
+                    let _ = client.teardown();
+                    return Ok(0);
+                }
+                return Err(err.into());
+            }
+        }
+        if pkt.is_none() {
+            break;
+        }
+    }
+
+    stdout.flush()?;
+    Ok(0)
+}
+
+fn check_conformance(
+    registry: &CodecRegistry,
+    reader: Box<dyn FormatReader>,
+    track_num: Option<usize>,
+    reference: &Path,
+    tolerance: u16,
+) -> Result<i32> {
+    // every decode error is a deviation from the reference, so none is skipped
+    let Some((decoded, _)) = decode_track(registry, reader, track_num, false)? else {
+        return Ok(0);
+    };
+
+    let reference = read_pcm(&std::fs::read(reference)?);
+    let diff = PcmDiff::compare(&decoded, &reference, tolerance);
+    info!(
+        "compared {} samples: {} beyond tolerance, largest difference {}, length differs by {}",
+        diff.samples, diff.mismatches, diff.max_diff, diff.len_diff
+    );
+    let is_ok = diff.is_ok();
+    println!("conformance: {}", if is_ok { "passed" } else { "failed" });
+    Ok(if is_ok {
+        exit::SUCCESS
+    } else {
+        exit::CHECK_FAILED
+    })
+}
+
+fn compare_decodes(
+    registry: &CodecRegistry,
+    reader: Box<dyn FormatReader>,
+    other: Box<dyn FormatReader>,
+    track_num: Option<usize>,
+    scorer: &dyn QualityScorer,
+) -> Result<i32> {
+    let Some((a, sample_rate)) = decode_track(registry, reader, track_num, true)? else {
+        return Ok(0);
+    };
+    let Some((b, _)) = decode_track(registry, other, track_num, true)? else {
+        return Ok(0);
+    };
+
+    // one second segments
+    let segment_len = sample_rate.max(1) as usize;
+    let cmp = PcmComparison::new(&a, &b, segment_len);
+    let scores = cmp.scores(&a, &b, sample_rate, scorer);
+    for (segment, score) in cmp.segments.iter().zip(scores) {
+        if segment.mismatches == 0 {
+            continue;
+        }
+        println!(
+            "{:>8.1}s: {} of {} samples differ, largest difference {}, {} {:.2} dB",
+            segment.start as f64 / segment_len as f64,
+            segment.mismatches,
+            segment.samples,
+            segment.max_delta,
+            scorer.name(),
+            score,
+        );
+    }
+    if cmp.len_diff != 0 {
+        println!("length differs by {} samples", cmp.len_diff);
+    }
+    let identical = cmp.is_identical();
+    println!(
+        "compare: {} (largest difference {})",
+        if identical { "identical" } else { "different" },
+        cmp.max_delta()
+    );
+    Ok(if identical {
+        exit::SUCCESS
+    } else {
+        exit::CHECK_FAILED
+    })
+}
+
+/// Decode a track to 16 bits PCM, returned with its sample rate. With `skip_errors`, packets
+/// the decoder rejects are skipped like during playback.
+fn decode_track(
+    registry: &CodecRegistry,
+    mut reader: Box<dyn FormatReader>,
+    track_num: Option<usize>,
+    skip_errors: bool,
+) -> Result<Option<(Vec<i16>, u32)>> {
+    let track = track_num
+        .and_then(|t| reader.tracks().get(t))
+        .or_else(|| first_supported_track(reader.tracks()));
+    let Some(track) = track else {
+        return Ok(None);
+    };
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.unwrap_or_default();
+
+    let mut decoder = registry.make(&track.codec_params, &Default::default())?;
+    let mut sample_buf = None;
+    let mut decoded = vec![];
+
+    let result = loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(err) => break Err(err),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(audio) => {
+                let buf = sample_buf.get_or_insert_with(|| {
+                    SampleBuffer::<i16>::new(audio.capacity() as u64, *audio.spec())
+                });
+                if buf.capacity() < audio.capacity() * audio.spec().channels.count() {
+                    *buf = SampleBuffer::<i16>::new(audio.capacity() as u64, *audio.spec());
+                }
+                buf.copy_interleaved_ref(audio);
+                decoded.extend_from_slice(buf.samples());
+            }
+            Err(Error::DecodeError(err)) if skip_errors => warn!("decode error: {}", err),
+            Err(err) => break Err(err),
+        }
+    };
+    ignore_end_of_stream_error(result)?;
+    Ok(Some((decoded, sample_rate)))
 }
 
 #[derive(Copy, Clone)]
 struct PlayTrackOptions {
     track_id: u32,
     seek_ts: u64,
+    /// Decoded frames dropped after the decoder is created
+    priming_frames: usize,
 }
 
 fn play(
@@ -287,6 +1233,7 @@ fn play(
     seek_time: Option<f64>,
     decode_opts: &DecoderOptions,
     no_progress: bool,
+    priming_frames: usize,
 ) -> Result<i32> {
     // If the user provided a track number, select that track if it exists, otherwise, select the
     // first track with a known codec.
@@ -334,7 +1281,11 @@ fn play(
     // The audio output device.
     let mut audio_output = None;
 
-    let mut track_info = PlayTrackOptions { track_id, seek_ts };
+    let mut track_info = PlayTrackOptions {
+        track_id,
+        seek_ts,
+        priming_frames,
+    };
 
     let result = loop {
         match play_track(
@@ -358,6 +1309,7 @@ fn play(
                 track_info = PlayTrackOptions {
                     track_id,
                     seek_ts: 0,
+                    priming_frames,
                 };
             }
             res => break res,
@@ -392,6 +1344,7 @@ fn play_track(
 
     // Create a decoder for the track.
     let mut decoder = registry.make(&track.codec_params, decode_opts)?;
+    let mut priming_frames = play_opts.priming_frames;
 
     // Get the selected track's timebase and duration.
     let tb = track.codec_params.time_base;
@@ -424,6 +1377,8 @@ fn play_track(
 
         // Decode the packet into audio samples.
         match decoder.decode(&packet) {
+            // The first frames of a new decoder may carry start-up artifacts, e.g. after a reset.
+            Ok(_) if priming_frames > 0 => priming_frames -= 1,
             Ok(decoded) => {
                 // If the audio output is not open, try to open it.
                 if audio_output.is_none() {
@@ -500,13 +1455,40 @@ fn do_verification(finalization: FinalizeResult) -> Result<i32> {
             // Got a verification result.
             println!("verification: {}", if is_ok { "passed" } else { "failed" });
 
-            Ok(i32::from(!is_ok))
+            Ok(if is_ok {
+                exit::SUCCESS
+            } else {
+                exit::CHECK_FAILED
+            })
         }
         // Verification not enabled by user, or unsupported by the codec.
         _ => Ok(0),
     }
 }
 
+fn print_codecs(registry: &CodecRegistry) {
+    println!("+ Codecs of RTP captures:");
+    for cap in RtpCodecRegistry::default().capabilities(registry) {
+        let mime = cap.info.map_or(String::new(), |info| info.mime_type());
+        match cap.decoder {
+            Some(decoder) => println!(
+                "|     {:<12} {:<8} {:<14} decoded by {}",
+                cap.name,
+                cap.display_name(),
+                mime,
+                decoder
+            ),
+            None => println!(
+                "|     {:<12} {:<8} {:<14} not built, tracks can't be decoded",
+                cap.name,
+                cap.display_name(),
+                mime
+            ),
+        }
+    }
+    println!("-");
+}
+
 fn print_format(registry: &CodecRegistry, path: &str, probed: &mut ProbeResult) {
     println!("+ {}", path);
     print_tracks(registry, probed.format.tracks());
@@ -0,0 +1,189 @@
+// Symphonia
+// Copyright (c) 2019-2022 The Project Symphonia Developers.
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Progress of long decodes, drawn on stderr
+
+use std::io::{IsTerminal, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use symphonia::core::io::MediaSource;
+
+/// Time between two redraws of a bar
+const REDRAW: Duration = Duration::from_millis(200);
+
+/// Whether to draw progress bars: stdout may carry the decoded audio, so they go to stderr, and
+/// only when it is a terminal
+pub fn enabled(no_progress: bool) -> bool {
+    !no_progress && std::io::stderr().is_terminal()
+}
+
+/// Media source sharing how far it has been read
+pub struct ProgressSource {
+    inner: Box<dyn MediaSource>,
+    pos: Arc<AtomicU64>,
+}
+
+impl ProgressSource {
+    pub fn new(inner: Box<dyn MediaSource>) -> Self {
+        Self {
+            inner,
+            pos: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Offset of the next byte read, updated as the source is read and seeked
+    pub fn position(&self) -> Arc<AtomicU64> {
+        self.pos.clone()
+    }
+}
+
+impl Read for ProgressSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.pos.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+}
+
+impl Seek for ProgressSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = self.inner.seek(pos)?;
+        self.pos.store(pos, Ordering::Relaxed);
+        Ok(pos)
+    }
+}
+
+impl MediaSource for ProgressSource {
+    fn is_seekable(&self) -> bool {
+        self.inner.is_seekable()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.inner.byte_len()
+    }
+}
+
+/// Bar following how far a [`ProgressSource`] was read
+pub struct ReadProgress {
+    bar: ProgressBar,
+    pos: Arc<AtomicU64>,
+}
+
+impl ReadProgress {
+    pub fn new(label: impl Into<String>, source: &ProgressSource) -> Self {
+        Self {
+            bar: ProgressBar::new(label, source.byte_len()),
+            pos: source.position(),
+        }
+    }
+
+    /// Name the track being decoded in the label
+    pub fn set_track(&mut self, track_id: u32) {
+        self.bar.label += &format!(" track {}", track_id);
+    }
+
+    pub fn update(&mut self) {
+        self.bar.update(self.pos.load(Ordering::Relaxed), "");
+    }
+
+    pub fn finish(&mut self) {
+        self.bar.finish(self.pos.load(Ordering::Relaxed), "");
+    }
+}
+
+/// Bar of the bytes done out of a total, with the rate and the time remaining
+pub struct ProgressBar {
+    label: String,
+    total: Option<u64>,
+    start: Instant,
+    drawn: Option<Instant>,
+}
+
+impl ProgressBar {
+    pub fn new(label: impl Into<String>, total: Option<u64>) -> Self {
+        Self {
+            label: label.into(),
+            total,
+            start: Instant::now(),
+            drawn: None,
+        }
+    }
+
+    /// Redraw the bar if it wasn't for a while, `extra` being appended to it
+    pub fn update(&mut self, done: u64, extra: &str) {
+        let now = Instant::now();
+        if self.drawn.is_some_and(|t| now - t < REDRAW) {
+            return;
+        }
+        self.drawn = Some(now);
+        self.draw(done, extra);
+    }
+
+    /// Draw the bar a last time and move to the next line
+    pub fn finish(&mut self, done: u64, extra: &str) {
+        self.draw(done, extra);
+        eprintln!();
+    }
+
+    fn draw(&self, done: u64, extra: &str) {
+        const WIDTH: u64 = 30;
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let mut line = format!("\r{} ", self.label);
+        match self.total.filter(|t| *t > 0) {
+            Some(total) => {
+                let done = done.min(total);
+                let filled = (WIDTH * done / total) as usize;
+                line += &format!(
+                    "[{:<width$}] {:>3}% ",
+                    "=".repeat(filled),
+                    100 * done / total,
+                    width = WIDTH as usize
+                );
+                line += &format!("{} ", fmt_rate(rate));
+                if rate > 0.0 {
+                    line += &format!("ETA {}", fmt_secs((total - done) as f64 / rate));
+                }
+            }
+            None => line += &format!("{} {}", fmt_bytes(done as f64), fmt_rate(rate)),
+        }
+        line += extra;
+        // trailing spaces erase the end of a longer previous line
+        line += "    ";
+
+        let mut stderr = std::io::stderr().lock();
+        let _ = stderr.write_all(line.as_bytes());
+        let _ = stderr.flush();
+    }
+}
+
+fn fmt_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn fmt_rate(rate: f64) -> String {
+    format!("{}/s", fmt_bytes(rate))
+}
+
+fn fmt_secs(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    format!("{}:{:0>2}:{:0>2}", secs / 3600, secs / 60 % 60, secs % 60)
+}